@@ -0,0 +1,78 @@
+//! Measures file API requests from many concurrent readers while one client writes.
+//!
+//! Run with `cargo bench --bench concurrency`.
+
+use axum::{
+    body::Body,
+    http::{header, Method, Request, StatusCode},
+    Router,
+};
+use criterion::{criterion_group, criterion_main, Criterion};
+use mdserve::{new_router, scan_markdown_files};
+use std::fs;
+use tower::ServiceExt;
+
+const FILE_COUNT: usize = 100;
+const READERS: usize = 100;
+
+fn write_docs(dir: &std::path::Path) {
+    let body = "## Section\n\nSome *markdown* with a [link](other.md) and `code`.\n\n".repeat(50);
+    for i in 0..FILE_COUNT {
+        fs::write(
+            dir.join(format!("page-{i}.md")),
+            format!("# Page {i}\n\n{body}"),
+        )
+        .expect("Failed to write file");
+    }
+}
+
+/// Sends `READERS` file reads and one frontmatter update at the same time.
+async fn readers_and_writer(router: &Router, round: usize) {
+    let mut requests = Vec::with_capacity(READERS + 1);
+    for i in 0..READERS {
+        let request = Request::get(format!("/api/files/page-{}.md", i % FILE_COUNT))
+            .body(Body::empty())
+            .expect("Failed to build request");
+        requests.push(tokio::spawn(router.clone().oneshot(request)));
+    }
+
+    let update = Request::builder()
+        .method(Method::PUT)
+        .uri("/api/files/page-0.md/frontmatter")
+        .header(header::CONTENT_TYPE, "application/json")
+        .body(Body::from(format!(
+            r#"{{"frontmatter": {{"round": {round}}}}}"#
+        )))
+        .expect("Failed to build request");
+    requests.push(tokio::spawn(router.clone().oneshot(update)));
+
+    for request in requests {
+        let response = request.await.expect("Task failed").expect("Request failed");
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+}
+
+fn concurrent_requests(c: &mut Criterion) {
+    let temp_dir = tempfile::tempdir().expect("Failed to create temp dir");
+    write_docs(temp_dir.path());
+
+    let runtime = tokio::runtime::Runtime::new().expect("Failed to create runtime");
+    let router = runtime.block_on(async {
+        let tracked_files = scan_markdown_files(temp_dir.path())
+            .expect("Failed to scan")
+            .files;
+        new_router(temp_dir.path().to_path_buf(), tracked_files, true)
+            .expect("Failed to create router")
+    });
+
+    let mut round = 0;
+    c.bench_function("100 readers and 1 writer", |b| {
+        b.iter(|| {
+            round += 1;
+            runtime.block_on(readers_and_writer(&router, round));
+        })
+    });
+}
+
+criterion_group!(benches, concurrent_requests);
+criterion_main!(benches);
@@ -0,0 +1,58 @@
+//! Compares router startup time with eager and lazy file loading.
+//!
+//! Run with `cargo bench --bench startup`.
+
+use mdserve::{new_router_with_config, scan_markdown_files, RouterConfig};
+use std::fs;
+use std::time::{Duration, Instant};
+
+const FILE_COUNT: usize = 2000;
+const ITERATIONS: u32 = 5;
+
+fn write_docs_tree(dir: &std::path::Path) {
+    let body = "## Section\n\nSome *markdown* with a [link](other.md) and `code`.\n\n".repeat(50);
+    for i in 0..FILE_COUNT {
+        let folder = dir.join(format!("section-{}", i % 50));
+        fs::create_dir_all(&folder).expect("Failed to create folder");
+        fs::write(
+            folder.join(format!("page-{i}.md")),
+            format!("# Page {i}\n\n{body}"),
+        )
+        .expect("Failed to write file");
+    }
+}
+
+fn time_startup(dir: &std::path::Path, lazy: bool) -> Duration {
+    let runtime = tokio::runtime::Runtime::new().expect("Failed to create runtime");
+    let mut total = Duration::ZERO;
+
+    for _ in 0..ITERATIONS {
+        let tracked_files = scan_markdown_files(dir).expect("Failed to scan").files;
+        let config = RouterConfig {
+            lazy,
+            ..RouterConfig::default()
+        };
+
+        let start = Instant::now();
+        let router = runtime.block_on(async {
+            new_router_with_config(dir.to_path_buf(), tracked_files, true, config)
+                .expect("Failed to create router")
+        });
+        drop(router);
+        total += start.elapsed();
+    }
+
+    total / ITERATIONS
+}
+
+fn main() {
+    let temp_dir = tempfile::tempdir().expect("Failed to create temp dir");
+    write_docs_tree(temp_dir.path());
+
+    let eager = time_startup(temp_dir.path(), false);
+    let lazy = time_startup(temp_dir.path(), true);
+
+    println!("startup with {FILE_COUNT} files (mean of {ITERATIONS} runs)");
+    println!("  eager: {eager:?}");
+    println!("  lazy:  {lazy:?}");
+}
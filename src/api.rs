@@ -0,0 +1,2076 @@
+//! JSON API for tools and scripts that work with the tracked markdown files.
+
+use axum::{
+    body::Body,
+    extract::{DefaultBodyLimit, Multipart, OriginalUri, Query, State},
+    http::{header, HeaderMap, HeaderValue, StatusCode, Uri},
+    response::{IntoResponse, Response},
+    routing::{get, post},
+    Json, Router,
+};
+use futures_util::{stream, StreamExt};
+use percent_encoding::{percent_decode_str, utf8_percent_encode, AsciiSet, NON_ALPHANUMERIC};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap},
+    convert::Infallible,
+    path::{Path, PathBuf},
+    sync::{atomic::Ordering, Arc, Mutex, MutexGuard, PoisonError},
+    time::SystemTime,
+};
+use tower_http::compression::CompressionLayer;
+
+use crate::app::{
+    is_markdown_file, normalize_key, unix_secs, FileCreateError, FileMoveError,
+    FrontmatterPatchError, GlobalTask, MarkdownState, SearchIndexEntry, ServerMessage,
+    SharedMarkdownState,
+};
+use crate::coverage::file_coverage;
+use crate::duplicates::{self, duplicate_titles, find_duplicates};
+use crate::export;
+use crate::graph::build_graph;
+use crate::history::{
+    file_at_commit, file_blame, file_contributors, file_history, HistoryError,
+    DEFAULT_CONTRIBUTORS_LIMIT, DEFAULT_HISTORY_LIMIT,
+};
+use crate::idempotency::{fingerprint, IdempotencyStore, Reservation};
+use crate::import::{read_archive, ImportError};
+use crate::lint::{lint_markdown, LintConfig, Severity};
+use crate::parser;
+use crate::timeline::build_timeline;
+use crate::validate::{
+    internal_links_valid, missing_frontmatter_fields, validate_markdown, ValidationError,
+};
+
+/// The API routes. Imports may upload up to `max_import_size` bytes.
+pub(crate) fn routes(max_import_size: usize) -> Router<SharedMarkdownState> {
+    Router::new()
+        .route("/api/files", get(api_list_files).delete(api_bulk_delete))
+        .route("/api/directories", get(api_list_directories))
+        .route("/api/workspace", get(api_workspace))
+        .route(
+            "/api/workspace/search-index",
+            get(api_search_index).layer(CompressionLayer::new()),
+        )
+        .route("/api/workspace/graph", get(api_workspace_graph))
+        .route(
+            "/api/workspace/broken-links",
+            get(api_workspace_broken_links),
+        )
+        .route("/api/workspace/orphaned", get(api_workspace_orphaned))
+        .route(
+            "/api/workspace/duplicate-titles",
+            get(api_workspace_duplicate_titles),
+        )
+        .route("/api/workspace/diagrams", get(api_workspace_diagrams))
+        .route("/api/workspace/tag-cloud", get(api_workspace_tag_cloud))
+        .route("/api/workspace/stats/timeline", get(api_workspace_timeline))
+        .route("/api/workspace/sitemap.xml", get(api_workspace_sitemap))
+        .route("/api/workspace/export", get(api_workspace_export))
+        .route(
+            "/api/workspace/import",
+            post(api_workspace_import).layer(DefaultBodyLimit::max(max_import_size)),
+        )
+        .route("/api/workspace/verify", post(api_workspace_verify))
+        .route("/api/stats", get(api_stats))
+        .route("/api/files/bulk", post(api_bulk_files))
+        .route(
+            "/api/files/*path",
+            get(api_get_file_resource)
+                .post(api_post_file_resource)
+                .put(api_put_file_resource)
+                .patch(api_patch_file),
+        )
+        .route("/api/tasks", get(api_get_tasks).patch(api_update_task))
+}
+
+/// Builds a `{"error": "..."}` response.
+fn api_error(status: StatusCode, message: impl Into<String>) -> Response {
+    (status, Json(json!({ "error": message.into() }))).into_response()
+}
+
+#[derive(Debug, Serialize)]
+struct ApiFile {
+    path: String,
+    /// Parent directory (`""` for root-level files); omitted in single-file mode
+    #[serde(skip_serializing_if = "Option::is_none")]
+    directory: Option<String>,
+    /// Only listed with `--title-from-frontmatter`, and only for files that have one
+    #[serde(skip_serializing_if = "Option::is_none")]
+    title: Option<String>,
+    /// Only listed with `?include_content=true`, and only for files that can be read
+    #[serde(skip_serializing_if = "Option::is_none")]
+    markdown: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+struct FilesResponse {
+    files: Vec<ApiFile>,
+}
+
+#[derive(Debug, Serialize)]
+struct DirectoriesResponse {
+    directories: Vec<String>,
+}
+
+/// Returns the parent directory of a tracked-file key, `""` at the root.
+fn parent_directory(relative_path: &str) -> &str {
+    relative_path
+        .rsplit_once('/')
+        .map_or("", |(directory, _)| directory)
+}
+
+/// Returns the last segment of a tracked-file key.
+fn file_name(relative_path: &str) -> &str {
+    relative_path
+        .rsplit_once('/')
+        .map_or(relative_path, |(_, name)| name)
+}
+
+/// The file name of `relative_path` without its extension, e.g. `intro` for
+/// `guide/intro.md`.
+fn file_stem(relative_path: &str) -> &str {
+    let name = file_name(relative_path);
+    name.rsplit_once('.').map_or(name, |(stem, _)| stem)
+}
+
+#[derive(Debug, Deserialize)]
+struct FilesQuery {
+    /// Only list files whose path contains this, ignoring case
+    q: Option<String>,
+    /// Only list files under this directory, e.g. `guide` for `guide/intro.md`
+    dir: Option<String>,
+    /// `false` leaves out files in subdirectories of `dir` (or of the root)
+    recursive: Option<bool>,
+    /// Only list files modified after this Unix timestamp, in seconds
+    modified_after: Option<u64>,
+    /// Only list files modified before this Unix timestamp, in seconds
+    modified_before: Option<u64>,
+    /// Number of matching files to skip
+    #[serde(default)]
+    offset: usize,
+    /// Most files to list, after `offset`
+    limit: Option<usize>,
+    /// Include each file's markdown, for at most `max_inline_content` files
+    #[serde(default)]
+    include_content: bool,
+    /// `name` (default), `depth` or `directory`, see [`crate::app::SortCriterion`]
+    sort: Option<String>,
+}
+
+/// Lists tracked files. `X-Total-Count` has the number of tracked files and
+/// `X-Filtered-Count` the number matching the filters, before `offset` and `limit`.
+async fn api_list_files(
+    Query(query): Query<FilesQuery>,
+    State(state): State<SharedMarkdownState>,
+) -> Response {
+    let sort = match query.sort.as_deref().map(str::parse).transpose() {
+        Ok(sort) => sort.unwrap_or_default(),
+        Err(message) => return api_error(StatusCode::BAD_REQUEST, message),
+    };
+    let state = state.read().await;
+    let needle = query.q.map(|q| q.to_lowercase());
+    let dir = query
+        .dir
+        .map(|dir| normalize_key(&dir).trim_matches('/').to_string())
+        .unwrap_or_default();
+    let recursive = query.recursive.unwrap_or(true);
+    let with_titles = state.config.title_from_frontmatter;
+    if with_titles {
+        state.load_all_files();
+    }
+
+    let matching = state
+        .sorted_filenames_by(sort)
+        .into_iter()
+        .filter(|path| {
+            let relative_to_dir = if dir.is_empty() {
+                Some(path.as_str())
+            } else {
+                path.strip_prefix(dir.as_str())
+                    .and_then(|rest| rest.strip_prefix('/'))
+            };
+            relative_to_dir.is_some_and(|rest| recursive || !rest.contains('/'))
+        })
+        .filter(|path| {
+            needle
+                .as_ref()
+                .is_none_or(|needle| path.to_lowercase().contains(needle.as_str()))
+        })
+        .filter(|path| {
+            if query.modified_after.is_none() && query.modified_before.is_none() {
+                return true;
+            }
+            let modified = state.tracked_files[path].read().modified_secs();
+            query.modified_after.is_none_or(|after| modified > after)
+                && query.modified_before.is_none_or(|before| modified < before)
+        })
+        .collect::<Vec<_>>();
+    let filtered_count = matching.len();
+    let page = matching
+        .into_iter()
+        .skip(query.offset)
+        .take(query.limit.unwrap_or(usize::MAX))
+        .collect::<Vec<_>>();
+
+    let max_inline_content = state.config.max_inline_content;
+    if query.include_content && page.len() > max_inline_content {
+        return api_error(
+            StatusCode::BAD_REQUEST,
+            format!(
+                "include_content returns at most {max_inline_content} files, {} match; use limit and offset to page through them",
+                page.len()
+            ),
+        );
+    }
+
+    let files = page
+        .into_iter()
+        .map(|path| ApiFile {
+            directory: state
+                .is_directory_mode
+                .then(|| parent_directory(&path).to_string()),
+            title: with_titles.then(|| state.file_title(&path)).flatten(),
+            markdown: query
+                .include_content
+                .then(|| current_markdown(&state, &path).ok())
+                .flatten()
+                .map(|markdown| markdown.to_string()),
+            path,
+        })
+        .collect::<Vec<_>>();
+
+    let headers = [
+        (TOTAL_COUNT, state.tracked_files.len().to_string()),
+        (FILTERED_COUNT, filtered_count.to_string()),
+    ];
+    (headers, Json(FilesResponse { files })).into_response()
+}
+
+const TOTAL_COUNT: &str = "x-total-count";
+const FILTERED_COUNT: &str = "x-filtered-count";
+
+/// Lists the distinct parent directories of tracked files, so clients can group the
+/// file list without building a full tree.
+async fn api_list_directories(
+    State(state): State<SharedMarkdownState>,
+) -> Json<DirectoriesResponse> {
+    let state = state.read().await;
+
+    let directories: BTreeSet<&str> = state
+        .tracked_files
+        .keys()
+        .map(|path| parent_directory(path))
+        .filter(|directory| !directory.is_empty())
+        .collect();
+
+    Json(DirectoriesResponse {
+        directories: directories.into_iter().map(String::from).collect(),
+    })
+}
+
+/// Optional behaviour enabled by the server's configuration.
+#[derive(Debug, Serialize)]
+struct WorkspaceFeatures {
+    lazy_load: bool,
+    include_hidden: bool,
+    snapshot: bool,
+}
+
+#[derive(Debug, Serialize)]
+struct WorkspaceResponse {
+    base_dir: String,
+    is_directory_mode: bool,
+    file_count: usize,
+    server_version: &'static str,
+    features: WorkspaceFeatures,
+}
+
+/// Describes the running server so clients can set themselves up in one request.
+async fn api_workspace(State(state): State<SharedMarkdownState>) -> Json<WorkspaceResponse> {
+    let state = state.read().await;
+
+    Json(WorkspaceResponse {
+        base_dir: state.base_dir.display().to_string(),
+        is_directory_mode: state.is_directory_mode,
+        file_count: state.tracked_files.len(),
+        server_version: env!("CARGO_PKG_VERSION"),
+        features: WorkspaceFeatures {
+            lazy_load: state.config.lazy,
+            include_hidden: state.config.include_hidden,
+            snapshot: state.is_directory_mode && state.config.snapshot_file.is_some(),
+        },
+    })
+}
+
+/// Links between the tracked files with per-file metadata. Rebuilt at most every few
+/// seconds, so changes can take that long to show up.
+async fn api_workspace_graph(State(state): State<SharedMarkdownState>) -> Response {
+    let state = state.read().await;
+    let cache = state.graph_cache.clone();
+    let cached = cache.lock().unwrap_or_else(PoisonError::into_inner).get();
+    let graph = match cached {
+        Some(graph) => graph,
+        None => {
+            state.load_all_files();
+            let graph = build_graph(&state);
+            cache
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .insert(graph)
+        }
+    };
+    Json(&*graph).into_response()
+}
+
+/// Links from any tracked file to markdown files that aren't tracked.
+async fn api_workspace_broken_links(State(state): State<SharedMarkdownState>) -> Response {
+    let state = state.read().await;
+    Json(&*state.broken_links()).into_response()
+}
+
+/// Tracked files no other file links to, except root files and index pages.
+async fn api_workspace_orphaned(State(state): State<SharedMarkdownState>) -> Response {
+    let state = state.read().await;
+    Json(&*state.orphans()).into_response()
+}
+
+/// First `# Heading`s shared by more than one tracked file.
+async fn api_workspace_duplicate_titles(State(state): State<SharedMarkdownState>) -> Response {
+    let state = state.read().await;
+    Json(duplicate_titles(&state)).into_response()
+}
+
+#[derive(Debug, Deserialize)]
+struct DiagramsQuery {
+    /// Only list diagrams of this type, `mermaid` or `plantuml`
+    #[serde(rename = "type")]
+    kind: Option<String>,
+}
+
+/// The diagrams of every tracked file, by file and then line.
+async fn api_workspace_diagrams(
+    Query(query): Query<DiagramsQuery>,
+    State(state): State<SharedMarkdownState>,
+) -> Response {
+    let kind = match query.kind.as_deref().map(str::parse).transpose() {
+        Ok(kind) => kind,
+        Err(message) => return api_error(StatusCode::BAD_REQUEST, message),
+    };
+    let state = state.read().await;
+    state.load_all_files();
+
+    let mut diagrams = state.all_diagrams();
+    diagrams.retain(|diagram| kind.is_none_or(|kind| diagram.kind == kind));
+    Json(diagrams).into_response()
+}
+
+#[derive(Debug, Deserialize)]
+struct TagCloudQuery {
+    /// Leave out tags used by fewer files
+    min_count: Option<usize>,
+    /// `false` leaves out each tag's files
+    include_files: Option<bool>,
+}
+
+/// Every frontmatter tag with how many files use it, most used first.
+async fn api_workspace_tag_cloud(
+    Query(query): Query<TagCloudQuery>,
+    State(state): State<SharedMarkdownState>,
+) -> Response {
+    let state = state.read().await;
+    Json(state.tag_cloud(
+        query.min_count.unwrap_or(1),
+        query.include_files.unwrap_or(true),
+    ))
+    .into_response()
+}
+
+#[derive(Debug, Deserialize)]
+struct TimelineQuery {
+    /// `day` (default), `week` or `month`
+    granularity: Option<String>,
+    /// Unix timestamp in seconds, default the oldest modification time
+    start: Option<u64>,
+    /// Unix timestamp in seconds, default now
+    end: Option<u64>,
+}
+
+/// How many tracked files were last modified in each period, from the modification times
+/// in memory.
+async fn api_workspace_timeline(
+    Query(query): Query<TimelineQuery>,
+    State(state): State<SharedMarkdownState>,
+) -> Response {
+    let granularity = match query.granularity.as_deref().map(str::parse).transpose() {
+        Ok(granularity) => granularity.unwrap_or_default(),
+        Err(message) => return api_error(StatusCode::BAD_REQUEST, message),
+    };
+    let state = state.read().await;
+    let modified: Vec<u64> = state
+        .tracked_files
+        .values()
+        .map(|entry| entry.read().modified_secs())
+        .collect();
+    drop(state);
+
+    let end = query.end.unwrap_or_else(|| unix_secs(SystemTime::now()));
+    let start = query
+        .start
+        .or_else(|| modified.iter().copied().min())
+        .unwrap_or(end);
+    match build_timeline(&modified, granularity, start, end) {
+        Ok(timeline) => Json(timeline).into_response(),
+        Err(message) => api_error(StatusCode::BAD_REQUEST, message),
+    }
+}
+
+/// A `sitemap.xml` of the tracked files as a static export would publish them at
+/// `--public-url`.
+async fn api_workspace_sitemap(State(state): State<SharedMarkdownState>) -> Response {
+    let state = state.read().await;
+    let Some(public_url) = &state.config.public_url else {
+        return api_error(
+            StatusCode::NOT_IMPLEMENTED,
+            "Set --public-url to serve a sitemap",
+        );
+    };
+    let files: Vec<(String, u64)> = state
+        .get_sorted_filenames()
+        .into_iter()
+        .map(|path| {
+            let modified = state.tracked_files[&path].read().modified_secs();
+            (path, modified)
+        })
+        .collect();
+
+    (
+        [(
+            header::CONTENT_TYPE,
+            HeaderValue::from_static("application/xml"),
+        )],
+        export::build_sitemap(public_url, &files),
+    )
+        .into_response()
+}
+
+#[derive(Debug, Deserialize)]
+struct WorkspaceExportQuery {
+    /// `false` leaves out the local images the files reference
+    include_images: Option<bool>,
+}
+
+/// Downloads the tracked files, and the local images they reference, as a ZIP archive
+/// laid out like the served directory. `413` if it would hold more than
+/// `max_export_size` bytes before compression.
+async fn api_workspace_export(
+    Query(query): Query<WorkspaceExportQuery>,
+    State(state): State<SharedMarkdownState>,
+) -> Response {
+    let state = state.read().await;
+    let mut files = Vec::new();
+    let mut images = BTreeMap::new();
+    for path in state.get_sorted_filenames() {
+        let markdown = match current_markdown(&state, &path) {
+            Ok(markdown) => markdown,
+            Err((status, message)) => return api_error(status, format!("{path}: {message}")),
+        };
+        if query.include_images.unwrap_or(true) {
+            for image in export::referenced_images(&markdown, &state.base_dir, &path) {
+                if let Ok(name) = image.strip_prefix(&state.base_dir) {
+                    images.insert(normalize_key(&name.to_string_lossy()), image);
+                }
+            }
+        }
+        files.push((path, markdown.to_string()));
+    }
+
+    let max_export_size = state.config.max_export_size as u64;
+    let size = files
+        .iter()
+        .map(|(_, markdown)| markdown.len() as u64)
+        .chain(
+            images
+                .values()
+                .map(|image| std::fs::metadata(image).map_or(0, |metadata| metadata.len())),
+        )
+        .sum::<u64>();
+    if size > max_export_size {
+        return api_error(
+            StatusCode::PAYLOAD_TOO_LARGE,
+            format!("Export would be {size} bytes, more than the limit of {max_export_size}"),
+        );
+    }
+
+    match export::build_workspace_zip(&files, &images) {
+        Ok(archive) => {
+            let directory = state
+                .base_dir
+                .file_name()
+                .map_or("workspace".into(), |name| name.to_string_lossy());
+            (
+                [
+                    (
+                        header::CONTENT_TYPE,
+                        HeaderValue::from_static("application/zip"),
+                    ),
+                    (
+                        header::CONTENT_DISPOSITION,
+                        content_disposition("attachment", &format!("{directory}-export.zip")),
+                    ),
+                ],
+                archive,
+            )
+                .into_response()
+        }
+        Err(e) => api_error(StatusCode::INTERNAL_SERVER_ERROR, format!("{e:#}")),
+    }
+}
+
+#[derive(Debug, Default, Serialize)]
+struct ImportResponse {
+    imported: usize,
+    /// Markdown files that weren't imported: not UTF-8, already existing, or hidden or
+    /// excluded from tracking
+    skipped: Vec<String>,
+    /// Markdown files that couldn't be imported, with the reason
+    errors: Vec<String>,
+}
+
+/// Creates the markdown files of the ZIP archive in the `file` field of a multipart
+/// upload, with their paths in the archive relative to the served directory. Existing
+/// files are never overwritten. Clients get one `BulkReload` for all new files.
+async fn api_workspace_import(
+    State(state): State<SharedMarkdownState>,
+    mut multipart: Multipart,
+) -> Response {
+    let mut archive = None;
+    loop {
+        match multipart.next_field().await {
+            Ok(Some(field)) if field.name() == Some("file") => match field.bytes().await {
+                Ok(bytes) => archive = Some(bytes),
+                Err(e) => return api_error(e.status(), e.body_text()),
+            },
+            Ok(Some(_)) => {}
+            Ok(None) => break,
+            Err(e) => return api_error(e.status(), e.body_text()),
+        }
+    }
+    let Some(archive) = archive else {
+        return api_error(
+            StatusCode::BAD_REQUEST,
+            "Expected a ZIP archive in the 'file' field",
+        );
+    };
+
+    let mut state = state.write().await;
+    if !state.is_directory_mode {
+        return api_error(
+            StatusCode::BAD_REQUEST,
+            "Importing needs a directory to be served",
+        );
+    }
+    let contents = match read_archive(&archive, state.config.max_import_size as u64) {
+        Ok(contents) => contents,
+        Err(e @ ImportError::TooLarge(_)) => {
+            return api_error(StatusCode::PAYLOAD_TOO_LARGE, e.to_string())
+        }
+        Err(e) => return api_error(StatusCode::BAD_REQUEST, e.to_string()),
+    };
+
+    let mut response = ImportResponse {
+        skipped: contents.skipped,
+        errors: contents
+            .errors
+            .into_iter()
+            .map(|path| format!("{path}: path leaves the directory"))
+            .collect(),
+        ..ImportResponse::default()
+    };
+    let mut added = Vec::new();
+    for (path, markdown) in contents.files {
+        match state.create_file(&path, &markdown) {
+            Ok(()) => added.push(path),
+            Err(FileCreateError::Excluded | FileCreateError::AlreadyExists) => {
+                response.skipped.push(path)
+            }
+            Err(e) => response.errors.push(format!("{path}: {e}")),
+        }
+    }
+
+    response.imported = added.len();
+    if !added.is_empty() {
+        state.change_tx.send(ServerMessage::BulkReload {
+            added,
+            removed: Vec::new(),
+            modified: Vec::new(),
+        });
+    }
+    Json(response).into_response()
+}
+
+/// Checks the tracked files against the disk. A `POST` so nothing caches the result.
+async fn api_workspace_verify(State(state): State<SharedMarkdownState>) -> Response {
+    let state = state.read().await;
+    Json(state.check_consistency()).into_response()
+}
+
+/// Format version of the search index, bumped on incompatible changes.
+const SEARCH_INDEX_VERSION: u32 = 1;
+
+#[derive(Debug, Serialize)]
+struct SearchIndexResponse {
+    version: u32,
+    files: Vec<SearchIndexEntry>,
+}
+
+/// Every file's plain text, for clients that search on their own. The payload can be
+/// large, so the route gzips it and lets clients reuse it for a minute.
+async fn api_search_index(State(state): State<SharedMarkdownState>) -> Response {
+    let state = state.read().await;
+    state.load_all_files();
+
+    (
+        [(header::CACHE_CONTROL, "max-age=60")],
+        Json(SearchIndexResponse {
+            version: SEARCH_INDEX_VERSION,
+            files: state.search_index(),
+        }),
+    )
+        .into_response()
+}
+
+#[derive(Debug, Serialize)]
+struct StatsResponse {
+    tracked_files: usize,
+    websocket_connections: usize,
+    max_websocket_connections: usize,
+    /// See `GET /api/workspace/broken-links`
+    broken_link_count: usize,
+    /// See `GET /api/workspace/orphaned`
+    orphaned_count: usize,
+    /// Titles shared by several files, see `GET /api/workspace/duplicate-titles`
+    title_duplicates: usize,
+}
+
+/// Live counters, available without enabling `/__metrics`.
+async fn api_stats(State(state): State<SharedMarkdownState>) -> Json<StatsResponse> {
+    let state = state.read().await;
+
+    Json(StatsResponse {
+        tracked_files: state.tracked_files.len(),
+        websocket_connections: state.ws_connections.load(Ordering::SeqCst),
+        max_websocket_connections: state.config.max_ws_connections,
+        broken_link_count: state.broken_links().len(),
+        orphaned_count: state.orphans().len(),
+        title_duplicates: duplicate_titles(&state).len(),
+    })
+}
+
+/// Most paths `POST /api/files/bulk` accepts in one request.
+const MAX_BULK_PATHS: usize = 50;
+
+#[derive(Debug, Deserialize)]
+struct BulkFilesRequest {
+    paths: Vec<String>,
+}
+
+/// One entry of a bulk response: the content and hash, or why it couldn't be read.
+#[derive(Debug, Serialize)]
+#[serde(untagged)]
+enum BulkFile {
+    Found {
+        path: String,
+        markdown: String,
+        hash: String,
+    },
+    Error {
+        path: String,
+        error: String,
+    },
+}
+
+#[derive(Debug, Serialize)]
+struct BulkFilesResponse {
+    files: Vec<BulkFile>,
+}
+
+/// Returns several files in one round trip, in request order. Unknown paths get an
+/// `error` entry instead of failing the whole request.
+async fn api_bulk_files(
+    State(state): State<SharedMarkdownState>,
+    Json(request): Json<BulkFilesRequest>,
+) -> Response {
+    if request.paths.len() > MAX_BULK_PATHS {
+        return api_error(
+            StatusCode::BAD_REQUEST,
+            format!("At most {MAX_BULK_PATHS} paths per request"),
+        );
+    }
+
+    let state = state.read().await;
+    let files = request
+        .paths
+        .into_iter()
+        .map(|path| {
+            let requested = normalize_key(&path);
+            let key = state.lookup_by_path(&requested).unwrap_or(&requested);
+            match current_markdown(&state, key) {
+                Ok(markdown) => BulkFile::Found {
+                    hash: format!("{:x}", state.tracked_files[key].read().content_hash),
+                    markdown: markdown.to_string(),
+                    path,
+                },
+                Err((StatusCode::NOT_FOUND, _)) => BulkFile::Error {
+                    path,
+                    error: "not found".to_string(),
+                },
+                Err((_, error)) => BulkFile::Error { path, error },
+            }
+        })
+        .collect();
+
+    Json(BulkFilesResponse { files }).into_response()
+}
+
+#[derive(Debug, Serialize)]
+struct BulkDeleteResult {
+    path: String,
+    /// `204` when deleted, `404` when not tracked, `500` when deleting failed
+    status: u16,
+}
+
+/// Deletes several files, reporting each outcome in a `207 Multi-Status` response.
+/// Clients get one reload once all deletions are done.
+async fn api_bulk_delete(
+    State(state): State<SharedMarkdownState>,
+    Json(request): Json<BulkFilesRequest>,
+) -> Response {
+    if request.paths.len() > MAX_BULK_PATHS {
+        return api_error(
+            StatusCode::BAD_REQUEST,
+            format!("At most {MAX_BULK_PATHS} paths per request"),
+        );
+    }
+
+    let mut state = state.write().await;
+    if !state.config.allow_bulk_delete {
+        return api_error(
+            StatusCode::FORBIDDEN,
+            "Bulk delete is disabled; start mdserve with --allow-bulk-delete",
+        );
+    }
+
+    let results: Vec<BulkDeleteResult> = request
+        .paths
+        .into_iter()
+        .map(|path| {
+            let key = normalize_key(&path);
+            let status = if !state.tracked_files.contains_key(&key) {
+                StatusCode::NOT_FOUND
+            } else if let Err(e) = state.delete_file(&key) {
+                tracing::warn!("Failed to delete {key}: {e}");
+                StatusCode::INTERNAL_SERVER_ERROR
+            } else {
+                StatusCode::NO_CONTENT
+            };
+            BulkDeleteResult {
+                path,
+                status: status.as_u16(),
+            }
+        })
+        .collect();
+
+    if results
+        .iter()
+        .any(|result| result.status == StatusCode::NO_CONTENT.as_u16())
+    {
+        // Directories may have lost their last file, and with it their ordering
+        state.reload_orderings();
+        state.change_tx.send(ServerMessage::Reload);
+    }
+    (StatusCode::MULTI_STATUS, Json(results)).into_response()
+}
+
+/// Extracts and percent-decodes the path after `/api/files/` from the raw request URI.
+///
+/// Each segment is decoded on its own so `gu%C3%ADa.md` and `%C3%BCber%20docs.md` match
+/// their tracked names, while an encoded slash or backslash (`%2F`, `%5C`) can't
+/// introduce a path separator. Returns `None` for such segments and for invalid UTF-8.
+/// A literal backslash is a separator, as in tracked file keys.
+fn decode_file_path(uri: &Uri) -> Option<String> {
+    let raw = normalize_key(uri.path().strip_prefix("/api/files/")?);
+    let segments = raw
+        .split('/')
+        .map(|segment| {
+            let decoded = percent_decode_str(segment).decode_utf8().ok()?;
+            (!decoded.contains(['/', '\\'])).then_some(decoded)
+        })
+        .collect::<Option<Vec<_>>>()?;
+    Some(segments.join("/"))
+}
+
+/// Splits a `/api/files/*path` capture into the file path and an optional sub-resource.
+///
+/// axum only allows a wildcard as the last segment, so `guide/intro.md/anchors` arrives
+/// as one path; anything after a markdown file name is treated as the sub-resource, which
+/// may itself have several segments (`export/html`).
+fn split_file_resource(path: &str) -> (&str, Option<&str>) {
+    let path = path.strip_prefix('/').unwrap_or(path);
+    path.rmatch_indices('/')
+        .map(|(i, _)| (&path[..i], &path[i + 1..]))
+        .find(|(file, _)| is_markdown_file(Path::new(file)))
+        .map_or((path, None), |(file, resource)| (file, Some(resource)))
+}
+
+/// The directory prefix of a `/api/files/*path` capture asking for a directory's
+/// `children`: `""` for `children` itself and `guide/` for `guide/children`.
+fn children_prefix(path: &str) -> Option<String> {
+    let path = path.strip_prefix('/').unwrap_or(path);
+    if path == "children" {
+        return Some(String::new());
+    }
+    let directory = path.strip_suffix("/children")?.trim_end_matches('/');
+    (!directory.is_empty() && !is_markdown_file(Path::new(directory)))
+        .then(|| format!("{directory}/"))
+}
+
+/// Returns the current markdown of a tracked file, loading it if needed.
+fn current_markdown(
+    state: &MarkdownState,
+    relative_path: &str,
+) -> Result<Arc<str>, (StatusCode, String)> {
+    if !state.tracked_files.contains_key(relative_path) {
+        return Err((StatusCode::NOT_FOUND, "File not found".to_string()));
+    }
+
+    state
+        .refresh_file(relative_path)
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    Ok(state.tracked_files[relative_path].read().markdown.clone())
+}
+
+#[derive(Debug, Deserialize)]
+struct FileQuery {
+    /// `json` (default) or `raw`
+    format: Option<String>,
+    /// Send the raw markdown as an attachment, so browsers save it instead of showing it
+    #[serde(default)]
+    download: bool,
+    /// Maximum number of results for list resources such as `related`
+    limit: Option<usize>,
+    /// Comma-separated [`PROPERTY_FIELDS`] to include in `properties`, default all
+    fields: Option<String>,
+    /// Deepest heading level, 1 to 6, included in `toc`
+    depth: Option<u8>,
+    /// Return `toc` as a flat list instead of a tree
+    #[serde(default)]
+    flat: bool,
+    /// Only list `codeblocks` in this language, ignoring case
+    lang: Option<String>,
+    /// Only list `codeblocks` marked as runnable
+    #[serde(default)]
+    runnable: bool,
+    /// Only list `diagrams` of this type, `mermaid` or `plantuml`
+    #[serde(rename = "type")]
+    kind: Option<String>,
+}
+
+const DEFAULT_RELATED_LIMIT: usize = 5;
+
+async fn api_get_file_resource(
+    OriginalUri(uri): OriginalUri,
+    Query(query): Query<FileQuery>,
+    State(state): State<SharedMarkdownState>,
+) -> Response {
+    let Some(path) = decode_file_path(&uri) else {
+        return api_error(StatusCode::BAD_REQUEST, "Invalid file path");
+    };
+    if let Some(dir_prefix) = children_prefix(&path) {
+        return match state.read().await.children(&dir_prefix) {
+            Some(children) => Json(children).into_response(),
+            None => api_error(StatusCode::NOT_FOUND, "Directory not found"),
+        };
+    }
+
+    let (requested_path, resource) = split_file_resource(&path);
+    if resource == Some("outline") {
+        return outline_redirect(&uri);
+    }
+    let state = state.read().await;
+    let relative_path = &state
+        .lookup_by_path(requested_path)
+        .unwrap_or(requested_path)
+        .to_string();
+
+    if resource == Some("status") {
+        return match state.file_status(relative_path) {
+            Some(status) => Json(status).into_response(),
+            None => api_error(StatusCode::NOT_FOUND, "File not found"),
+        };
+    }
+
+    if let Some(resource @ ("hash-chain" | "hash-chain/latest")) = resource {
+        let Some(mut chain) = state.hash_chain(relative_path) else {
+            return api_error(StatusCode::NOT_FOUND, "File not found");
+        };
+        if resource == "hash-chain" {
+            return Json(chain).into_response();
+        }
+        return match chain.pop() {
+            Some(latest) => Json(latest).into_response(),
+            None => api_error(StatusCode::NOT_FOUND, "No changes recorded"),
+        };
+    }
+
+    let restore_hash = resource.and_then(|resource| resource.strip_prefix("restore/"));
+    if matches!(resource, Some("history" | "blame" | "contributors")) || restore_hash.is_some() {
+        let path = match git_file_path(&state, relative_path, resource.unwrap_or_default()) {
+            Ok(path) => path,
+            Err((status, message)) => return api_error(status, message),
+        };
+        drop(state);
+        return match (resource, restore_hash) {
+            (_, Some(hash)) => {
+                let hash = hash.to_string();
+                git_response(move || {
+                    let markdown = file_at_commit(&path, &hash)?;
+                    Ok(RestoredFile { markdown, hash })
+                })
+                .await
+            }
+            (Some("history"), _) => {
+                let limit = query.limit.unwrap_or(DEFAULT_HISTORY_LIMIT);
+                git_response(move || file_history(&path, limit)).await
+            }
+            (Some("contributors"), _) => {
+                let limit = query.limit.unwrap_or(DEFAULT_CONTRIBUTORS_LIMIT);
+                git_response(move || file_contributors(&path, limit)).await
+            }
+            _ => git_response(move || file_blame(&path)).await,
+        };
+    }
+
+    // Resources that relate the file to the other tracked files
+    if let Some(resource @ ("ancestors" | "neighbors" | "related" | "mentions" | "similar")) =
+        resource
+    {
+        if !state.tracked_files.contains_key(relative_path) {
+            return api_error(StatusCode::NOT_FOUND, "File not found");
+        }
+        if resource == "ancestors" {
+            return Json(ancestors(relative_path)).into_response();
+        }
+        if resource == "neighbors" {
+            let (previous, next) = state.neighbors(relative_path);
+            return Json(NeighborsResponse { previous, next }).into_response();
+        }
+        state.load_all_files();
+        if resource == "mentions" {
+            return Json(state.find_mentions(relative_path)).into_response();
+        }
+        if resource == "similar" {
+            return Json(state.structurally_similar_files(relative_path)).into_response();
+        }
+        let limit = query.limit.unwrap_or(DEFAULT_RELATED_LIMIT);
+        return Json(state.related_files(relative_path, limit)).into_response();
+    }
+
+    let markdown = match current_markdown(&state, relative_path) {
+        Ok(markdown) => markdown,
+        Err((status, message)) => return api_error(status, message),
+    };
+    let streaming_threshold = state.config.streaming_threshold;
+    if resource == Some("export/html") {
+        let base_dir = state.base_dir.clone();
+        let export_css = state.config.export_css.clone();
+        drop(state);
+        return export_html_response(&markdown, &base_dir, relative_path, export_css.as_deref());
+    }
+    if resource == Some("export/zip") {
+        let base_dir = state.base_dir.clone();
+        drop(state);
+        return export_zip_response(&markdown, &base_dir, relative_path);
+    }
+    if resource == Some("export/pdf") {
+        let source = state.base_dir.join(relative_path);
+        let working_dir = source.parent().unwrap_or(&state.base_dir).to_path_buf();
+        let pandoc_path = state.config.pandoc_path.clone();
+        drop(state);
+        return export_pdf_response(&markdown, &working_dir, relative_path, pandoc_path).await;
+    }
+    if resource == Some("metadata") {
+        return match state.file_metadata(relative_path) {
+            Some(metadata) => Json(metadata).into_response(),
+            None => api_error(StatusCode::NOT_FOUND, "File not found"),
+        };
+    }
+    if resource == Some("coverage") {
+        return Json(file_coverage(relative_path, &markdown, &state)).into_response();
+    }
+    if resource == Some("internal-links-valid") {
+        let valid = internal_links_valid(&state, relative_path);
+        return Json(json!({ "valid": valid })).into_response();
+    }
+    let summary = match resource {
+        Some("summary" | "properties") => state.file_summary(relative_path),
+        _ => None,
+    };
+    drop(state);
+
+    match resource {
+        None if query.download => {
+            raw_markdown_response(relative_path, markdown, Disposition::Attachment)
+        }
+        None => match query.format.as_deref() {
+            None | Some("json") if markdown.len() > streaming_threshold => {
+                streamed_file_content_response(markdown)
+            }
+            None | Some("json") => Json(FileContentResponse {
+                markdown: &markdown,
+            })
+            .into_response(),
+            Some("raw") => raw_markdown_response(relative_path, markdown, Disposition::Inline),
+            Some(other) => api_error(
+                StatusCode::BAD_REQUEST,
+                format!("Unknown format '{other}', expected 'json' or 'raw'"),
+            ),
+        },
+        Some("anchors") => Json(parser::generate_anchors(&markdown)).into_response(),
+        Some("toc") => match query.depth.unwrap_or(6) {
+            depth @ 1..=6 if query.flat => {
+                let mut anchors = parser::generate_anchors(&markdown);
+                anchors.retain(|anchor| anchor.level <= depth);
+                Json(anchors).into_response()
+            }
+            depth @ 1..=6 => Json(parser::build_outline(
+                parser::generate_anchors(&markdown),
+                depth,
+            ))
+            .into_response(),
+            _ => api_error(StatusCode::BAD_REQUEST, "depth must be between 1 and 6"),
+        },
+        Some("reading-time") => Json(parser::estimate_reading_time(&markdown)).into_response(),
+        Some("tables") => Json(parser::extract_tables(&markdown)).into_response(),
+        Some("codeblocks") => {
+            let mut blocks = parser::extract_code_blocks(&markdown);
+            blocks.retain(|block| {
+                (!query.runnable || block.runnable)
+                    && query.lang.as_ref().is_none_or(|lang| {
+                        block
+                            .lang
+                            .as_ref()
+                            .is_some_and(|block_lang| block_lang.eq_ignore_ascii_case(lang))
+                    })
+            });
+            Json(blocks).into_response()
+        }
+        Some("diagrams") => {
+            let kind: Option<parser::DiagramKind> =
+                match query.kind.as_deref().map(str::parse).transpose() {
+                    Ok(kind) => kind,
+                    Err(message) => return api_error(StatusCode::BAD_REQUEST, message),
+                };
+            let mut diagrams = parser::extract_diagrams(&markdown);
+            diagrams.retain(|diagram| kind.is_none_or(|kind| diagram.kind == kind));
+            Json(diagrams).into_response()
+        }
+        Some("references") => Json(parser::parse_references(&markdown)).into_response(),
+        Some("definitions") => Json(parser::extract_definitions(&markdown)).into_response(),
+        Some("footnotes") => Json(parser::extract_footnotes(&markdown)).into_response(),
+        Some("glossary") => Json(parser::extract_glossary_terms(&markdown)).into_response(),
+        Some("todos") => Json(parser::extract_all_todos(&markdown)).into_response(),
+        Some("admonitions") => Json(parser::extract_admonitions(&markdown)).into_response(),
+        Some("badges") => Json(parser::extract_badges(&markdown)).into_response(),
+        Some("math") => {
+            let formulas = parser::extract_math(&markdown);
+            Json(json!({ "count": formulas.len(), "formulas": formulas })).into_response()
+        }
+        Some("summary") => {
+            Json(summary_response(relative_path, &markdown, summary)).into_response()
+        }
+        Some("properties") => {
+            properties_response(relative_path, &markdown, summary, query.fields.as_deref())
+        }
+        Some(_) => api_error(StatusCode::NOT_FOUND, "Unknown file resource"),
+    }
+}
+
+/// `outline` is the old name of the nested `toc`, kept for existing links.
+fn outline_redirect(uri: &Uri) -> Response {
+    let file_path = uri.path().strip_suffix("/outline").unwrap_or(uri.path());
+    let mut location = format!("{file_path}/toc");
+    if let Some(query) = uri.query() {
+        location.push('?');
+        location.push_str(query);
+    }
+    (
+        StatusCode::MOVED_PERMANENTLY,
+        [(header::LOCATION, location)],
+    )
+        .into_response()
+}
+
+/// A file's content as of a git commit, from the `restore/<hash>` resources.
+#[derive(Debug, Serialize)]
+struct RestoredFile {
+    markdown: String,
+    hash: String,
+}
+
+/// The path on disk of a tracked file for the resources that run git, or the status and
+/// message to respond with if it isn't tracked or git is disabled. Every resource but
+/// `history` also needs `--enable-git`.
+fn git_file_path(
+    state: &MarkdownState,
+    relative_path: &str,
+    resource: &str,
+) -> Result<PathBuf, (StatusCode, &'static str)> {
+    let Some(entry) = state.tracked_files.get(relative_path) else {
+        return Err((StatusCode::NOT_FOUND, "File not found"));
+    };
+    if !state.config.git_history {
+        return Err((StatusCode::NOT_IMPLEMENTED, "git history is disabled"));
+    }
+    if resource != "history" && !state.config.enable_git {
+        return Err((
+            StatusCode::FORBIDDEN,
+            "git is disabled; start mdserve with --enable-git",
+        ));
+    }
+    Ok(entry.read().path.clone())
+}
+
+/// Runs git on a blocking thread. Errors become responses: `501` when git or a
+/// repository is missing, `404` for unknown commits.
+async fn run_git_blocking<T: Send + 'static>(
+    run: impl FnOnce() -> Result<T, HistoryError> + Send + 'static,
+) -> Result<T, Response> {
+    match tokio::task::spawn_blocking(run).await {
+        Ok(Ok(result)) => Ok(result),
+        Ok(Err(e)) => {
+            let status = match e {
+                HistoryError::Unavailable(_) => StatusCode::NOT_IMPLEMENTED,
+                HistoryError::NotFound(_) => StatusCode::NOT_FOUND,
+                HistoryError::Failed(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            };
+            Err(api_error(status, e.to_string()))
+        }
+        Err(e) => Err(api_error(StatusCode::INTERNAL_SERVER_ERROR, e.to_string())),
+    }
+}
+
+/// Builds a resource from git, see [`run_git_blocking`].
+async fn git_response<T: Serialize + Send + 'static>(
+    run: impl FnOnce() -> Result<T, HistoryError> + Send + 'static,
+) -> Response {
+    match run_git_blocking(run).await {
+        Ok(result) => Json(result).into_response(),
+        Err(response) => response,
+    }
+}
+
+/// Builds the `export/html` resource: the file as a standalone HTML document.
+fn export_html_response(
+    markdown: &str,
+    base_dir: &Path,
+    relative_path: &str,
+    export_css: Option<&Path>,
+) -> Response {
+    let stylesheet = match export_css {
+        Some(path) => match std::fs::read_to_string(path) {
+            Ok(stylesheet) => stylesheet,
+            Err(e) => {
+                return api_error(
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    format!("Failed to read {}: {e}", path.display()),
+                )
+            }
+        },
+        None => export::DEFAULT_CSS.to_string(),
+    };
+
+    match export::render_standalone_html(markdown, base_dir, relative_path, &stylesheet) {
+        Ok(html) => ([(header::CONTENT_TYPE, "text/html; charset=utf-8")], html).into_response(),
+        Err(e) => api_error(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()),
+    }
+}
+
+/// Builds the `export/zip` resource: the file and its local images as an attachment.
+fn export_zip_response(markdown: &str, base_dir: &Path, relative_path: &str) -> Response {
+    match export::build_zip(markdown, base_dir, relative_path) {
+        Ok(archive) => {
+            let name = format!("{}.zip", file_stem(relative_path));
+            (
+                [
+                    (
+                        header::CONTENT_TYPE,
+                        HeaderValue::from_static("application/zip"),
+                    ),
+                    (
+                        header::CONTENT_DISPOSITION,
+                        content_disposition("attachment", &name),
+                    ),
+                ],
+                archive,
+            )
+                .into_response()
+        }
+        Err(e) => api_error(StatusCode::INTERNAL_SERVER_ERROR, format!("{e:#}")),
+    }
+}
+
+/// Builds the `export/pdf` resource by running pandoc, `501` if it isn't installed.
+async fn export_pdf_response(
+    markdown: &str,
+    working_dir: &Path,
+    relative_path: &str,
+    pandoc_path: Option<PathBuf>,
+) -> Response {
+    // `which` also checks that an explicitly configured path is executable
+    let Ok(pandoc) = which::which(pandoc_path.unwrap_or_else(|| PathBuf::from("pandoc"))) else {
+        return api_error(StatusCode::NOT_IMPLEMENTED, "pandoc not installed");
+    };
+
+    match export::render_pdf(&pandoc, markdown, working_dir).await {
+        Ok(pdf) => {
+            let name = format!("{}.pdf", file_stem(relative_path));
+            (
+                [
+                    (
+                        header::CONTENT_TYPE,
+                        HeaderValue::from_static("application/pdf"),
+                    ),
+                    (
+                        header::CONTENT_DISPOSITION,
+                        content_disposition("inline", &name),
+                    ),
+                ],
+                pdf,
+            )
+                .into_response()
+        }
+        Err(e) => {
+            tracing::warn!("PDF export of {relative_path} failed: {e:#}");
+            api_error(StatusCode::INTERNAL_SERVER_ERROR, format!("{e:#}"))
+        }
+    }
+}
+
+/// Builds the `summary` resource, preferring the summary cached in the state.
+fn summary_response(
+    relative_path: &str,
+    markdown: &str,
+    cached: Option<parser::DocumentSummary>,
+) -> SummaryResponse {
+    let summary = cached.unwrap_or_else(|| parser::extract_summary(markdown));
+    SummaryResponse {
+        title: summary
+            .title
+            .unwrap_or_else(|| file_name(relative_path).to_string()),
+        summary: summary.summary,
+        word_count: summary.word_count,
+    }
+}
+
+/// Fields of the `properties` resource, each the same as the file resource of that name.
+const PROPERTY_FIELDS: &[&str] = &[
+    "anchors",
+    "reading_time",
+    "tables",
+    "summary",
+    "frontmatter",
+    "tasks",
+];
+
+/// Several file resources in one response, for dashboards that show them all.
+/// Fields that weren't requested are left out.
+#[derive(Debug, Default, Serialize)]
+struct PropertiesResponse {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    anchors: Option<Vec<parser::AnchorEntry>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    reading_time: Option<parser::ReadingTimeEstimate>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    tables: Option<Vec<parser::MarkdownTable>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    summary: Option<SummaryResponse>,
+    /// `null` when the file has no (valid) frontmatter
+    #[serde(skip_serializing_if = "Option::is_none")]
+    frontmatter: Option<serde_yaml::Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    tasks: Option<Vec<parser::Task>>,
+}
+
+fn properties_response(
+    relative_path: &str,
+    markdown: &str,
+    cached_summary: Option<parser::DocumentSummary>,
+    fields: Option<&str>,
+) -> Response {
+    let requested: Vec<&str> = fields
+        .map(|fields| {
+            fields
+                .split(',')
+                .map(str::trim)
+                .filter(|field| !field.is_empty())
+                .collect()
+        })
+        .unwrap_or_default();
+    if let Some(unknown) = requested.iter().find(|f| !PROPERTY_FIELDS.contains(f)) {
+        return api_error(
+            StatusCode::BAD_REQUEST,
+            format!(
+                "Unknown property '{unknown}', expected one of {}",
+                PROPERTY_FIELDS.join(", ")
+            ),
+        );
+    }
+    let wants = |field: &str| requested.is_empty() || requested.contains(&field);
+
+    let mut properties = PropertiesResponse::default();
+    if wants("anchors") {
+        properties.anchors = Some(parser::generate_anchors(markdown));
+    }
+    if wants("reading_time") {
+        properties.reading_time = Some(parser::estimate_reading_time(markdown));
+    }
+    if wants("tables") {
+        properties.tables = Some(parser::extract_tables(markdown));
+    }
+    if wants("summary") {
+        properties.summary = Some(summary_response(relative_path, markdown, cached_summary));
+    }
+    if wants("frontmatter") {
+        properties.frontmatter =
+            Some(parser::parse_frontmatter(markdown).unwrap_or(serde_yaml::Value::Null));
+    }
+    if wants("tasks") {
+        properties.tasks = Some(parser::extract_tasks(markdown));
+    }
+    Json(properties).into_response()
+}
+
+#[derive(Debug, Serialize)]
+struct NeighborsResponse {
+    previous: Option<String>,
+    next: Option<String>,
+}
+
+/// A step of the breadcrumb trail to a file.
+#[derive(Debug, Serialize)]
+struct Ancestor {
+    name: String,
+    /// Directories end in `/`
+    path: String,
+    /// Only set on the file itself, the last step
+    #[serde(skip_serializing_if = "Option::is_none")]
+    is_file: Option<bool>,
+}
+
+/// The directories containing a tracked file, outermost first, followed by the file.
+/// Every directory has a tracked file in it, if only this one.
+fn ancestors(relative_path: &str) -> Vec<Ancestor> {
+    let mut ancestors = Vec::new();
+    let mut path = String::new();
+    let mut components = relative_path.split('/').peekable();
+    while let Some(name) = components.next() {
+        path.push_str(name);
+        let is_file = components.peek().is_none();
+        if !is_file {
+            path.push('/');
+        }
+        ancestors.push(Ancestor {
+            name: name.to_string(),
+            path: path.clone(),
+            is_file: is_file.then_some(true),
+        });
+    }
+    ancestors
+}
+
+/// [`parser::DocumentSummary`] with the file name standing in for a missing title.
+#[derive(Debug, Serialize)]
+struct SummaryResponse {
+    title: String,
+    summary: String,
+    word_count: usize,
+}
+
+#[derive(Debug, Serialize)]
+struct FileContentResponse<'a> {
+    markdown: &'a str,
+}
+
+const STREAM_CHUNK_BYTES: usize = 64 * 1024;
+
+/// Streams the same `{"markdown": "..."}` body as [`FileContentResponse`], escaping the
+/// content chunk by chunk so large files are never serialized in one piece.
+fn streamed_file_content_response(markdown: Arc<str>) -> Response {
+    let mut chunk_ranges = Vec::new();
+    let mut start = 0;
+    while start < markdown.len() {
+        let mut end = (start + STREAM_CHUNK_BYTES).min(markdown.len());
+        while !markdown.is_char_boundary(end) {
+            end += 1;
+        }
+        chunk_ranges.push(start..end);
+        start = end;
+    }
+
+    let content = stream::iter(chunk_ranges).map(move |range| {
+        let quoted = serde_json::Value::from(&markdown[range]).to_string();
+        // Drop the surrounding quotes; the envelope supplies them
+        Ok::<_, Infallible>(quoted[1..quoted.len() - 1].to_string())
+    });
+    let body = stream::once(async { Ok(r#"{"markdown":""#.to_string()) })
+        .chain(content)
+        .chain(stream::once(async { Ok(r#""}"#.to_string()) }));
+
+    (
+        [(header::CONTENT_TYPE, "application/json")],
+        Body::from_stream(body),
+    )
+        .into_response()
+}
+
+/// How a browser should treat a raw markdown response.
+#[derive(Debug, Clone, Copy)]
+enum Disposition {
+    /// Shown in the browser
+    Inline,
+    /// Saved as a file
+    Attachment,
+}
+
+/// Characters percent-encoded in an RFC 6266 `filename*` value: all but `attr-char`.
+const FILENAME_STAR: &AsciiSet = &NON_ALPHANUMERIC
+    .remove(b'!')
+    .remove(b'#')
+    .remove(b'$')
+    .remove(b'&')
+    .remove(b'+')
+    .remove(b'-')
+    .remove(b'.')
+    .remove(b'^')
+    .remove(b'_')
+    .remove(b'`')
+    .remove(b'|')
+    .remove(b'~');
+
+/// A `Content-Disposition` value naming `filename`. Names that aren't printable ASCII
+/// get an ASCII `filename` with `_` in place of the other characters, for old clients,
+/// and the exact name in an RFC 6266 `filename*`.
+fn content_disposition(disposition_type: &'static str, filename: &str) -> HeaderValue {
+    let ascii: String = filename
+        .chars()
+        .map(|c| {
+            if c.is_ascii() && !c.is_ascii_control() {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    let mut disposition = format!(
+        "{disposition_type}; filename=\"{}\"",
+        ascii.replace('\\', "\\\\").replace('"', "\\\"")
+    );
+    if ascii != filename {
+        disposition.push_str(&format!(
+            "; filename*=UTF-8''{}",
+            utf8_percent_encode(filename, FILENAME_STAR)
+        ));
+    }
+    // Only visible ASCII is left, so this always succeeds
+    HeaderValue::from_str(&disposition).unwrap_or(HeaderValue::from_static(disposition_type))
+}
+
+/// Returns the markdown as plain text, for `curl` and shell pipelines.
+fn raw_markdown_response(
+    relative_path: &str,
+    markdown: Arc<str>,
+    disposition: Disposition,
+) -> Response {
+    let disposition_type = match disposition {
+        Disposition::Inline => "inline",
+        Disposition::Attachment => "attachment",
+    };
+    (
+        [
+            (
+                header::CONTENT_TYPE,
+                HeaderValue::from_static("text/plain; charset=utf-8"),
+            ),
+            (
+                header::CONTENT_DISPOSITION,
+                content_disposition(disposition_type, file_name(relative_path)),
+            ),
+        ],
+        markdown.to_string(),
+    )
+        .into_response()
+}
+
+/// Body of the endpoints that take prospective file content.
+#[derive(Debug, Deserialize)]
+struct ContentRequest {
+    markdown: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct LintRequest {
+    markdown: String,
+    /// Severities replacing the configured ones, by rule name
+    #[serde(default)]
+    rules: BTreeMap<String, Severity>,
+}
+
+#[derive(Debug, Serialize)]
+struct ValidateResponse {
+    valid: bool,
+    errors: Vec<ValidationError>,
+    /// Fields from `--require-frontmatter` that the content's frontmatter lacks
+    missing_required_fields: Vec<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct RenameRequest {
+    name: String,
+}
+
+/// Dispatches `POST /api/files/<path>/<resource>`; each resource has its own body.
+async fn api_post_file_resource(
+    OriginalUri(uri): OriginalUri,
+    State(state): State<SharedMarkdownState>,
+    body: axum::body::Bytes,
+) -> Response {
+    let Some(path) = decode_file_path(&uri) else {
+        return api_error(StatusCode::BAD_REQUEST, "Invalid file path");
+    };
+    let (relative_path, resource) = split_file_resource(&path);
+    match resource {
+        Some("validate") => match serde_json::from_slice(&body) {
+            Ok(request) => api_validate_file(relative_path, request, &state).await,
+            Err(e) => api_error(StatusCode::BAD_REQUEST, e.to_string()),
+        },
+        Some("diff") => match serde_json::from_slice(&body) {
+            Ok(request) => api_diff_file(relative_path, request, &state).await,
+            Err(e) => api_error(StatusCode::BAD_REQUEST, e.to_string()),
+        },
+        Some("lint") => match serde_json::from_slice(&body) {
+            Ok(request) => api_lint_file(request, &state).await,
+            Err(e) => api_error(StatusCode::BAD_REQUEST, e.to_string()),
+        },
+        Some("rename") => match serde_json::from_slice(&body) {
+            Ok(request) => api_rename_file(relative_path, request, &state).await,
+            Err(e) => api_error(StatusCode::BAD_REQUEST, e.to_string()),
+        },
+        Some("duplicate-check") if body.is_empty() => {
+            api_duplicate_check(relative_path, DuplicateCheckRequest::default(), &state).await
+        }
+        Some("duplicate-check") => match serde_json::from_slice(&body) {
+            Ok(request) => api_duplicate_check(relative_path, request, &state).await,
+            Err(e) => api_error(StatusCode::BAD_REQUEST, e.to_string()),
+        },
+        Some("preview") if body.is_empty() => {
+            api_preview_file(relative_path, PreviewRequest::default(), &state).await
+        }
+        Some("preview") => match serde_json::from_slice(&body) {
+            Ok(request) => api_preview_file(relative_path, request, &state).await,
+            Err(e) => api_error(StatusCode::BAD_REQUEST, e.to_string()),
+        },
+        Some(resource) if resource.starts_with("restore/") => {
+            api_restore_file(relative_path, &resource["restore/".len()..], &state).await
+        }
+        _ => api_error(StatusCode::NOT_FOUND, "Unknown file resource"),
+    }
+}
+
+async fn api_lint_file(request: LintRequest, state: &SharedMarkdownState) -> Response {
+    let mut rules = state.read().await.config.lint_rules.rules.clone();
+    rules.extend(request.rules);
+    match LintConfig::new(rules) {
+        Ok(config) => Json(lint_markdown(&request.markdown, &config)).into_response(),
+        Err(e) => api_error(StatusCode::BAD_REQUEST, e),
+    }
+}
+
+/// Overwrites a file with its content as of git commit `hash` and returns that content.
+async fn api_restore_file(
+    relative_path: &str,
+    hash: &str,
+    state: &SharedMarkdownState,
+) -> Response {
+    let path = {
+        let state = state.read().await;
+        let relative_path = state.lookup_by_path(relative_path).unwrap_or(relative_path);
+        match git_file_path(&state, relative_path, "restore") {
+            Ok(path) => path,
+            Err((status, message)) => return api_error(status, message),
+        }
+    };
+    let hash = hash.to_string();
+    let markdown = {
+        let hash = hash.clone();
+        match run_git_blocking(move || file_at_commit(&path, &hash)).await {
+            Ok(markdown) => markdown,
+            Err(response) => return response,
+        }
+    };
+
+    let state = state.read().await;
+    let Some(relative_path) = state.lookup_by_path(relative_path) else {
+        return api_error(StatusCode::NOT_FOUND, "File not found");
+    };
+    if let Err(e) = state.update_file(relative_path, &markdown) {
+        return api_error(StatusCode::INTERNAL_SERVER_ERROR, e.to_string());
+    }
+    Json(RestoredFile { markdown, hash }).into_response()
+}
+
+/// Lints posted content as if it were saved at `relative_path`. The file doesn't need
+/// to exist yet.
+async fn api_validate_file(
+    relative_path: &str,
+    request: ContentRequest,
+    state: &SharedMarkdownState,
+) -> Response {
+    let state = state.read().await;
+    let relative_path = state.lookup_by_path(relative_path).unwrap_or(relative_path);
+    let errors = validate_markdown(relative_path, &request.markdown, &state);
+    let missing_required_fields =
+        missing_frontmatter_fields(&request.markdown, &state.config.required_frontmatter);
+    Json(ValidateResponse {
+        valid: errors.is_empty() && missing_required_fields.is_empty(),
+        errors,
+        missing_required_fields,
+    })
+    .into_response()
+}
+
+#[derive(Debug, Serialize)]
+struct DiffResponse {
+    diff: Vec<parser::DiffHunk>,
+}
+
+/// Line diff from the stored content to the posted content. Nothing is written.
+async fn api_diff_file(
+    relative_path: &str,
+    request: ContentRequest,
+    state: &SharedMarkdownState,
+) -> Response {
+    let state = state.read().await;
+    let relative_path = state.lookup_by_path(relative_path).unwrap_or(relative_path);
+    let markdown = match current_markdown(&state, relative_path) {
+        Ok(markdown) => markdown,
+        Err((status, message)) => return api_error(status, message),
+    };
+    drop(state);
+
+    Json(DiffResponse {
+        diff: parser::diff_lines(&markdown, &request.markdown),
+    })
+    .into_response()
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct PreviewRequest {
+    /// Values for the `{{key}}` placeholders in the file
+    #[serde(default)]
+    variables: HashMap<String, String>,
+}
+
+#[derive(Debug, Serialize)]
+struct PreviewResponse {
+    html: String,
+}
+
+/// Renders a file with its `{{key}}` placeholders filled in. Nothing is written.
+async fn api_preview_file(
+    relative_path: &str,
+    request: PreviewRequest,
+    state: &SharedMarkdownState,
+) -> Response {
+    let markdown = {
+        let state = state.read().await;
+        let relative_path = state.lookup_by_path(relative_path).unwrap_or(relative_path);
+        match current_markdown(&state, relative_path) {
+            Ok(markdown) => markdown,
+            Err((status, message)) => return api_error(status, message),
+        }
+    };
+
+    let markdown = parser::substitute_variables(&markdown, &request.variables);
+    match MarkdownState::markdown_to_html(&markdown) {
+        Ok(html) => Json(PreviewResponse { html }).into_response(),
+        Err(e) => api_error(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()),
+    }
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct DuplicateCheckRequest {
+    /// Paragraph similarity, from 0 to 1, at which content counts as duplicated
+    threshold: Option<f64>,
+}
+
+/// Finds other files with paragraphs copied from this one. Nothing is written.
+async fn api_duplicate_check(
+    relative_path: &str,
+    request: DuplicateCheckRequest,
+    state: &SharedMarkdownState,
+) -> Response {
+    let threshold = request.threshold.unwrap_or(duplicates::DEFAULT_THRESHOLD);
+    if !(0.0..=1.0).contains(&threshold) {
+        return api_error(StatusCode::BAD_REQUEST, "threshold must be between 0 and 1");
+    }
+
+    let state = state.read().await;
+    let relative_path = state
+        .lookup_by_path(relative_path)
+        .unwrap_or(relative_path)
+        .to_string();
+    let markdown = match current_markdown(&state, &relative_path) {
+        Ok(markdown) => markdown,
+        Err((status, message)) => return api_error(status, message),
+    };
+    state.load_all_files();
+    let others: Vec<(String, Arc<str>)> = state
+        .get_sorted_filenames()
+        .into_iter()
+        .filter(|file| *file != relative_path)
+        .map(|file| {
+            let markdown = state.tracked_files[&file].read().markdown.clone();
+            (file, markdown)
+        })
+        .collect();
+    drop(state);
+
+    Json(find_duplicates(
+        &markdown,
+        others
+            .iter()
+            .map(|(path, markdown)| (path.as_str(), &**markdown)),
+        threshold,
+    ))
+    .into_response()
+}
+
+/// Renames a file within its directory. `name` is the new file name only.
+async fn api_rename_file(
+    relative_path: &str,
+    request: RenameRequest,
+    state: &SharedMarkdownState,
+) -> Response {
+    let mut state = state.write().await;
+    let relative_path = state
+        .lookup_by_path(relative_path)
+        .unwrap_or(relative_path)
+        .to_string();
+    let directory = parent_directory(&relative_path);
+    let new_path = if directory.is_empty() {
+        normalize_key(&request.name)
+    } else {
+        normalize_key(&format!("{directory}/{}", request.name))
+    };
+
+    match state.rename_file(&relative_path, &new_path) {
+        Ok(()) => Json(ApiFile {
+            directory: state
+                .is_directory_mode
+                .then(|| parent_directory(&new_path).to_string()),
+            title: None,
+            markdown: None,
+            path: new_path,
+        })
+        .into_response(),
+        Err(e) => {
+            let status = match e {
+                FileMoveError::NotFound => StatusCode::NOT_FOUND,
+                FileMoveError::DifferentDirectory => StatusCode::UNPROCESSABLE_ENTITY,
+                FileMoveError::InvalidName => StatusCode::BAD_REQUEST,
+                FileMoveError::AlreadyExists => StatusCode::CONFLICT,
+                FileMoveError::Io(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            };
+            api_error(status, e.to_string())
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct FrontmatterRequest {
+    frontmatter: serde_yaml::Value,
+}
+
+/// Dispatches `PUT /api/files/<path>/<resource>`.
+async fn api_put_file_resource(
+    OriginalUri(uri): OriginalUri,
+    State(state): State<SharedMarkdownState>,
+    headers: HeaderMap,
+    body: axum::body::Bytes,
+) -> Response {
+    let Some(path) = decode_file_path(&uri) else {
+        return api_error(StatusCode::BAD_REQUEST, "Invalid file path");
+    };
+    let (relative_path, resource) = split_file_resource(&path);
+
+    let Some(key) = headers
+        .get(IDEMPOTENCY_KEY)
+        .and_then(|key| key.to_str().ok())
+        .map(str::to_string)
+    else {
+        return put_file_resource(relative_path, resource, &body, &state).await;
+    };
+
+    let store = state.read().await.idempotency.clone();
+    let reservation = lock_idempotency(&store).reserve(&key, fingerprint(&path, &body));
+    match reservation {
+        Reservation::Reserved => {}
+        Reservation::Replay(status, body) => {
+            return (status, [(IDEMPOTENT_REPLAYED, "true")], Json(body)).into_response();
+        }
+        Reservation::InFlight => {
+            return api_error(
+                StatusCode::CONFLICT,
+                "A request with this Idempotency-Key is in progress",
+            );
+        }
+        Reservation::Mismatch => {
+            return api_error(
+                StatusCode::UNPROCESSABLE_ENTITY,
+                "Idempotency-Key was used for a different request",
+            );
+        }
+    }
+    // Frees the key again unless a response gets stored, also if the client goes away
+    // mid-request
+    let reserved = ReservedKey {
+        store: &store,
+        key: &key,
+    };
+
+    let response = put_file_resource(relative_path, resource, &body, &state).await;
+    // Server errors may be transient, so a retry runs again
+    if response.status().is_server_error() {
+        return response;
+    }
+    let (parts, body) = response.into_parts();
+    let Ok(bytes) = axum::body::to_bytes(body, usize::MAX).await else {
+        return api_error(StatusCode::INTERNAL_SERVER_ERROR, "Failed to read response");
+    };
+    if let Ok(value) = serde_json::from_slice(&bytes) {
+        lock_idempotency(&store).complete(&key, parts.status, value);
+    }
+    drop(reserved);
+    Response::from_parts(parts, Body::from(bytes))
+}
+
+/// An `Idempotency-Key` reserved for a request, released when dropped if the request
+/// didn't complete it.
+struct ReservedKey<'a> {
+    store: &'a Mutex<IdempotencyStore>,
+    key: &'a str,
+}
+
+impl Drop for ReservedKey<'_> {
+    fn drop(&mut self) {
+        lock_idempotency(self.store).release(self.key);
+    }
+}
+
+/// Header a client sets to make retrying a `PUT` safe.
+const IDEMPOTENCY_KEY: &str = "idempotency-key";
+/// Set on responses replayed for an `Idempotency-Key` that was seen before.
+const IDEMPOTENT_REPLAYED: &str = "idempotent-replayed";
+
+fn lock_idempotency(store: &Mutex<IdempotencyStore>) -> MutexGuard<'_, IdempotencyStore> {
+    store.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+async fn put_file_resource(
+    relative_path: &str,
+    resource: Option<&str>,
+    body: &[u8],
+    state: &SharedMarkdownState,
+) -> Response {
+    match resource {
+        None => match serde_json::from_slice(body) {
+            Ok(request) => api_update_file(relative_path, request, state).await,
+            Err(e) => api_error(StatusCode::BAD_REQUEST, e.to_string()),
+        },
+        Some("frontmatter") => match serde_json::from_slice(body) {
+            Ok(request) => api_update_frontmatter(relative_path, request, state).await,
+            Err(e) => api_error(StatusCode::BAD_REQUEST, e.to_string()),
+        },
+        _ => api_error(StatusCode::NOT_FOUND, "Unknown file resource"),
+    }
+}
+
+/// Replaces a file's content and returns it.
+async fn api_update_file(
+    relative_path: &str,
+    request: ContentRequest,
+    state: &SharedMarkdownState,
+) -> Response {
+    let state = state.read().await;
+    let Some(relative_path) = state.lookup_by_path(relative_path) else {
+        return api_error(StatusCode::NOT_FOUND, "File not found");
+    };
+    if let Err(e) = state.update_file(relative_path, &request.markdown) {
+        return api_error(StatusCode::INTERNAL_SERVER_ERROR, e.to_string());
+    }
+
+    Json(FileContentResponse {
+        markdown: &request.markdown,
+    })
+    .into_response()
+}
+
+/// Replaces a file's frontmatter and returns the new file content.
+async fn api_update_frontmatter(
+    relative_path: &str,
+    request: FrontmatterRequest,
+    state: &SharedMarkdownState,
+) -> Response {
+    let state = state.read().await;
+    let Some(relative_path) = state.lookup_by_path(relative_path) else {
+        return api_error(StatusCode::NOT_FOUND, "File not found");
+    };
+    if let Err(e) = state.update_frontmatter(relative_path, request.frontmatter) {
+        return api_error(StatusCode::INTERNAL_SERVER_ERROR, e.to_string());
+    }
+
+    let markdown = state.tracked_files[relative_path].read().markdown.clone();
+    Json(FileContentResponse {
+        markdown: &markdown,
+    })
+    .into_response()
+}
+
+/// Media type of the JSON Patch documents `PATCH /api/files/<path>` accepts.
+const JSON_PATCH_CONTENT_TYPE: &str = "application/json-patch+json";
+
+/// Applies a JSON Patch to a file's frontmatter and returns the patched frontmatter.
+async fn api_patch_file(
+    OriginalUri(uri): OriginalUri,
+    State(state): State<SharedMarkdownState>,
+    headers: HeaderMap,
+    body: axum::body::Bytes,
+) -> Response {
+    let Some(path) = decode_file_path(&uri) else {
+        return api_error(StatusCode::BAD_REQUEST, "Invalid file path");
+    };
+    let (relative_path, resource) = split_file_resource(&path);
+    if resource.is_some() {
+        return api_error(StatusCode::NOT_FOUND, "Unknown file resource");
+    }
+
+    let content_type = headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.split(';').next())
+        .map(str::trim);
+    if !content_type.is_some_and(|value| value.eq_ignore_ascii_case(JSON_PATCH_CONTENT_TYPE)) {
+        return api_error(
+            StatusCode::UNSUPPORTED_MEDIA_TYPE,
+            format!("Expected Content-Type: {JSON_PATCH_CONTENT_TYPE}"),
+        );
+    }
+    let patch: json_patch::Patch = match serde_json::from_slice(&body) {
+        Ok(patch) => patch,
+        Err(e) => return api_error(StatusCode::BAD_REQUEST, e.to_string()),
+    };
+
+    let state = state.read().await;
+    let Some(relative_path) = state.lookup_by_path(relative_path) else {
+        return api_error(StatusCode::NOT_FOUND, "File not found");
+    };
+    match state.patch_frontmatter(relative_path, &patch) {
+        Ok(frontmatter) => Json(frontmatter).into_response(),
+        Err(e) => {
+            let status = match e {
+                FrontmatterPatchError::NotFound => StatusCode::NOT_FOUND,
+                FrontmatterPatchError::Invalid(_) => StatusCode::UNPROCESSABLE_ENTITY,
+                FrontmatterPatchError::Io(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            };
+            api_error(status, e.to_string())
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct TasksQuery {
+    checked: Option<bool>,
+    file: Option<String>,
+}
+
+async fn api_get_tasks(
+    Query(query): Query<TasksQuery>,
+    State(state): State<SharedMarkdownState>,
+) -> Json<Vec<GlobalTask>> {
+    let state = state.read().await;
+    state.load_all_files();
+
+    let tasks = state
+        .all_tasks()
+        .into_iter()
+        .filter(|task| query.checked.is_none_or(|checked| task.checked == checked))
+        .filter(|task| {
+            query
+                .file
+                .as_ref()
+                .is_none_or(|file| task.file == normalize_key(file))
+        })
+        .collect();
+
+    Json(tasks)
+}
+
+#[derive(Debug, Deserialize)]
+struct UpdateTaskRequest {
+    file: String,
+    line: usize,
+    checked: bool,
+}
+
+/// Checks or unchecks a single task, writing the change back to the file.
+async fn api_update_task(
+    State(state): State<SharedMarkdownState>,
+    Json(mut request): Json<UpdateTaskRequest>,
+) -> Response {
+    let state = state.read().await;
+    let requested = normalize_key(&request.file);
+    let Some(file) = state.lookup_by_path(&requested) else {
+        return api_error(StatusCode::NOT_FOUND, "File not found");
+    };
+    request.file = file.to_string();
+
+    if state.refresh_file(&request.file).is_err() {
+        return api_error(StatusCode::INTERNAL_SERVER_ERROR, "Failed to read file");
+    }
+    let Some(entry) = state.tracked_files.get(&request.file) else {
+        return api_error(StatusCode::NOT_FOUND, "File not found");
+    };
+    let markdown = entry.read().markdown.clone();
+
+    let Some(updated) = parser::set_task_checked(&markdown, request.line, request.checked) else {
+        return api_error(StatusCode::NOT_FOUND, "No task on that line");
+    };
+
+    if let Err(e) = state.update_file(&request.file, &updated) {
+        return api_error(StatusCode::INTERNAL_SERVER_ERROR, e.to_string());
+    }
+
+    let task = parser::extract_tasks(&updated)
+        .into_iter()
+        .find(|task| task.line == request.line)
+        .map(|task| GlobalTask {
+            file: request.file,
+            line: task.line,
+            text: task.text,
+            checked: task.checked,
+        });
+    Json(task).into_response()
+}
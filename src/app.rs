@@ -7,32 +7,72 @@ use axum::{
     http::{header, HeaderMap, StatusCode},
     response::{Html, IntoResponse},
     routing::get,
-    Router,
+    Json, Router,
 };
 use futures_util::{SinkExt, StreamExt};
 use minijinja::{context, value::Value, Environment};
-use notify::{Config, Event, RecommendedWatcher, RecursiveMode, Watcher};
+use notify::{Config, Event, PollWatcher, RecommendedWatcher, RecursiveMode, Watcher};
 use serde::{Deserialize, Serialize};
 use std::{
     fs,
+    io::Write,
     net::Ipv6Addr,
     path::{Path, PathBuf},
-    sync::{Arc, OnceLock},
-    time::SystemTime,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, OnceLock,
+    },
+    time::{Duration, Instant, SystemTime},
 };
 use tokio::{
     net::TcpListener,
-    sync::{broadcast, mpsc, Mutex},
+    sync::{mpsc, RwLock},
+};
+use tower_http::{
+    classify::{ServerErrorsAsFailures, SharedClassifier},
+    cors::{self, CorsLayer},
+    trace::{MakeSpan, OnRequest, OnResponse, TraceLayer},
 };
-use tower_http::cors::CorsLayer;
+use uuid::Uuid;
+
+use crate::file_index::FileIndex;
+use crate::graph::{self, GraphCache, OrphanedFile};
+use crate::idempotency::IdempotencyStore;
+use crate::lint::LintConfig;
+use crate::parser::{DiagramKind, DocumentSummary};
+use crate::session::{ConnectionGuard, EventBroadcaster, SessionRegistry};
+use crate::validate::{self, missing_frontmatter_fields, BrokenLink, MarkdownLink};
+use crate::{api, parser, telemetry};
 
 const TEMPLATE_NAME: &str = "main.html";
 const RESCAN_DELAY_MS: u64 = 200;
+const STREAMING_THRESHOLD_BYTES: usize = 1024 * 1024;
+const MAX_WS_CONNECTIONS: usize = 100;
+const MAX_INLINE_CONTENT: usize = 50;
+const MAX_IMPORT_SIZE_BYTES: usize = 50 * 1024 * 1024;
+const MAX_EXPORT_SIZE_BYTES: usize = 200 * 1024 * 1024;
+/// Files `GET /api/workspace/orphaned` never reports unless `no_orphan_check` is set
+const DEFAULT_NO_ORPHAN_CHECK: [&str; 2] = ["**/index.md", "**/README.md"];
+const REQUEST_TIMEOUT_SECS: u64 = 30;
+/// Header carrying the ID that ties a request to its log lines
+const REQUEST_ID_HEADER: &str = "x-request-id";
+/// Longest client-supplied request ID kept, in bytes
+const MAX_REQUEST_ID_LEN: usize = 128;
+/// How long to wait before trying again when the watcher can't be re-registered
+const WATCH_RECOVERY_RETRY: Duration = Duration::from_secs(5);
+/// How many content changes each tracked file remembers for `hash-chain`
+const HASH_CHAIN_CAPACITY: usize = 20;
 static TEMPLATE_ENV: OnceLock<Environment<'static>> = OnceLock::new();
 const MERMAID_JS: &str = include_str!("../static/js/mermaid.min.js");
 const MERMAID_ETAG: &str = concat!("\"", env!("CARGO_PKG_VERSION"), "\"");
 
-type SharedMarkdownState = Arc<Mutex<MarkdownState>>;
+pub(crate) type SharedMarkdownState = Arc<RwLock<MarkdownState>>;
+
+/// `time` in seconds since the Unix epoch, 0 for earlier times.
+pub(crate) fn unix_secs(time: SystemTime) -> u64 {
+    time.duration_since(SystemTime::UNIX_EPOCH)
+        .map_or(0, |since| since.as_secs())
+}
 
 fn template_env() -> &'static Environment<'static> {
     TEMPLATE_ENV.get_or_init(|| {
@@ -47,6 +87,13 @@ fn template_env() -> &'static Environment<'static> {
 pub enum ClientMessage {
     Ping,
     RequestRefresh,
+    RequestFileList,
+    /// Sent after reconnecting, with the ID and last event `seq` of the previous
+    /// connection, to have the events it missed replayed
+    Reconnect {
+        client_id: Uuid,
+        last_seq: u64,
+    },
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
@@ -56,34 +103,210 @@ pub enum ServerMessage {
     Pong,
     FileRenamed { old_name: String, new_name: String },
     FileRemoved { name: String },
+    FileAdded { name: String },
+    FileList { files: Vec<String> },
+    /// Sent instead of individual events when a rescan finds more than
+    /// [`BULK_CHANGE_THRESHOLD`] changes at once, e.g. after `git checkout`
+    BulkReload {
+        added: Vec<String>,
+        removed: Vec<String>,
+        modified: Vec<String>,
+    },
+    /// First message on every connection. `server_time` is in milliseconds since the
+    /// Unix epoch and `last_seq` is the number of the last event sent before connecting.
+    Connected {
+        client_id: Uuid,
+        server_time: u64,
+        last_seq: u64,
+    },
+}
+
+use std::collections::{BTreeSet, HashMap, VecDeque};
+
+/// Tunables for [`new_router_with_config`]. [`new_router`] uses the defaults.
+#[derive(Debug, Clone)]
+pub struct RouterConfig {
+    /// How long to wait after a rename or removal before rescanning the directory
+    pub rescan_delay: Duration,
+    /// Glob patterns, relative to the base directory, of files that are never tracked
+    /// in directory mode (e.g. `drafts/**`)
+    pub exclude_patterns: Vec<String>,
+    /// Defer reading each file until it is first requested, keeping startup fast and
+    /// memory low for very large documentation trees
+    pub lazy: bool,
+    /// Poll interval handed to `notify`, in milliseconds. Only affects watcher backends
+    /// that poll; event-driven backends (inotify, FSEvents, ...) ignore it. This is
+    /// separate from `rescan_delay`, which is applied afterwards by mdserve itself.
+    pub watcher_debounce_ms: Option<u64>,
+    /// Watch by polling the tree at this interval instead of relying on the platform's
+    /// file system events, for network mounts and containers where those never arrive.
+    /// Takes precedence over `watcher_debounce_ms`.
+    pub poll_interval: Option<Duration>,
+    /// Track files inside hidden directories and hidden files (names starting with `.`)
+    /// in directory mode
+    pub include_hidden: bool,
+    /// Files larger than this many bytes are streamed by `GET /api/files/<path>` instead
+    /// of being serialized in one piece
+    pub streaming_threshold: usize,
+    /// Directory-mode state is restored from this file at startup, if present, and
+    /// written back on graceful shutdown by [`serve_markdown`]
+    pub snapshot_file: Option<PathBuf>,
+    /// Frontmatter fields every file should set; missing ones are logged at startup
+    /// and reported by the validate endpoint
+    pub required_frontmatter: Vec<String>,
+    /// Serve Prometheus metrics at `GET /__metrics`. Off by default since they reveal
+    /// which files and routes are being requested.
+    pub metrics: bool,
+    /// WebSocket connections allowed at once; further upgrade requests get a 503
+    pub max_ws_connections: usize,
+    /// Requests still unanswered after this long get a 504. WebSocket upgrades are
+    /// exempt.
+    pub request_timeout: Duration,
+    /// Accept `DELETE /api/files`, which deletes many files from disk in one request
+    pub allow_bulk_delete: bool,
+    /// Include each file's title (frontmatter `title`, else the first `# Heading`) in
+    /// `GET /api/files`. Reads every file, even with `lazy`.
+    pub title_from_frontmatter: bool,
+    /// Stylesheet inlined into `export/html` documents instead of the built-in one. Read
+    /// on every export, so edits apply without a restart.
+    pub export_css: Option<PathBuf>,
+    /// pandoc executable for `export/pdf`, instead of looking up `pandoc` in `PATH`
+    pub pandoc_path: Option<PathBuf>,
+    /// Serve the `history` resource of `/api/files/<path>` by running git. When off, the
+    /// other git resources are off too, even with `enable_git`.
+    pub git_history: bool,
+    /// Also serve the `contributors`, `blame` and `restore/<hash>` resources of
+    /// `/api/files/<path>`, which show who wrote each line and can overwrite files with
+    /// an old commit's version
+    pub enable_git: bool,
+    /// Re-create the file watcher when it loses the base directory, e.g. after a
+    /// remount replaced it
+    pub watch_recovery: bool,
+    /// Log a warning for every inconsistency [`MarkdownState::check_consistency`] finds
+    /// once the files are tracked, e.g. files a snapshot lists that are gone
+    pub verify_on_startup: bool,
+    /// Severities of the `lint` rules, unless a request sets its own
+    pub lint_rules: LintConfig,
+    /// Most files `GET /api/files?include_content=true` returns the content of; longer
+    /// lists get a 400 asking for a `limit`
+    pub max_inline_content: usize,
+    /// Largest upload `POST /api/workspace/import` accepts, and most bytes of markdown
+    /// it extracts from one archive
+    pub max_import_size: usize,
+    /// Most bytes of files and images `GET /api/workspace/export` puts in one archive
+    pub max_export_size: usize,
+    /// Glob patterns of entry points, such as a landing page, that are never reported as
+    /// orphaned
+    pub root_files: Vec<String>,
+    /// Glob patterns of files left out of the orphan check, by default index pages and
+    /// READMEs
+    pub no_orphan_check: Vec<String>,
+    /// URL a static export of the files is published at, for the locations in
+    /// `GET /api/workspace/sitemap.xml`. Without it the sitemap isn't served.
+    pub public_url: Option<String>,
+}
+
+impl Default for RouterConfig {
+    fn default() -> Self {
+        Self {
+            rescan_delay: Duration::from_millis(RESCAN_DELAY_MS),
+            exclude_patterns: Vec::new(),
+            lazy: false,
+            watcher_debounce_ms: None,
+            poll_interval: None,
+            include_hidden: false,
+            streaming_threshold: STREAMING_THRESHOLD_BYTES,
+            snapshot_file: None,
+            required_frontmatter: Vec::new(),
+            metrics: false,
+            max_ws_connections: MAX_WS_CONNECTIONS,
+            request_timeout: Duration::from_secs(REQUEST_TIMEOUT_SECS),
+            allow_bulk_delete: false,
+            title_from_frontmatter: false,
+            export_css: None,
+            pandoc_path: None,
+            git_history: true,
+            enable_git: false,
+            watch_recovery: true,
+            verify_on_startup: false,
+            lint_rules: LintConfig::default(),
+            max_inline_content: MAX_INLINE_CONTENT,
+            max_import_size: MAX_IMPORT_SIZE_BYTES,
+            max_export_size: MAX_EXPORT_SIZE_BYTES,
+            root_files: Vec::new(),
+            no_orphan_check: DEFAULT_NO_ORPHAN_CHECK.map(String::from).to_vec(),
+            public_url: None,
+        }
+    }
 }
 
-use std::collections::HashMap;
+/// Markdown files found by [`scan_markdown_files`], plus entries that couldn't be read.
+#[derive(Debug, Default)]
+pub struct ScanResult {
+    /// Markdown files, sorted
+    pub files: Vec<PathBuf>,
+    /// Files or directories that were skipped, with the reason
+    pub skipped: Vec<(PathBuf, String)>,
+}
 
-pub fn scan_markdown_files(dir: &Path) -> Result<Vec<PathBuf>> {
-    let mut md_files = Vec::new();
-    scan_markdown_files_recursive(dir, &mut md_files)?;
-    md_files.sort();
-    Ok(md_files)
+/// Recursively finds markdown files under `dir`, skipping hidden files and directories
+/// (`.github`, `.obsidian`, ...).
+///
+/// Unreadable subdirectories and entries are collected in [`ScanResult::skipped`] instead
+/// of failing the whole scan; only an unreadable `dir` itself is an error.
+///
+/// # Migration
+///
+/// This used to return `Result<Vec<PathBuf>>` and stop at the first I/O error. Use
+/// `scan_markdown_files(dir)?.files` for the previous list of files, and report
+/// `skipped` if partial results matter to the caller.
+pub fn scan_markdown_files(dir: &Path) -> Result<ScanResult> {
+    scan_markdown_files_with_options(dir, false)
 }
 
-fn scan_markdown_files_recursive(dir: &Path, md_files: &mut Vec<PathBuf>) -> Result<()> {
-    for entry in fs::read_dir(dir)? {
-        let entry = entry?;
+/// Like [`scan_markdown_files`], optionally descending into hidden files and directories.
+pub fn scan_markdown_files_with_options(dir: &Path, include_hidden: bool) -> Result<ScanResult> {
+    let entries = fs::read_dir(dir)?;
+    let mut result = ScanResult::default();
+    scan_entries(dir, entries, include_hidden, &mut result);
+    result.files.sort();
+    Ok(result)
+}
+
+fn scan_entries(dir: &Path, entries: fs::ReadDir, include_hidden: bool, result: &mut ScanResult) {
+    for entry in entries {
+        let entry = match entry {
+            Ok(entry) => entry,
+            Err(e) => {
+                result.skipped.push((dir.to_path_buf(), e.to_string()));
+                continue;
+            }
+        };
+        if !include_hidden && entry.file_name().to_string_lossy().starts_with('.') {
+            continue;
+        }
         let path = entry.path();
 
         if path.is_file() && is_markdown_file(&path) {
-            md_files.push(path);
+            result.files.push(path);
         } else if path.is_dir() {
             // Recursively scan subdirectories
-            scan_markdown_files_recursive(&path, md_files)?;
+            match fs::read_dir(&path) {
+                Ok(entries) => scan_entries(&path, entries, include_hidden, result),
+                Err(e) => result.skipped.push((path, e.to_string())),
+            }
         }
     }
+}
 
-    Ok(())
+/// Whether any component of a relative path is hidden (starts with `.`).
+fn is_hidden_path(relative_path: &str) -> bool {
+    Path::new(relative_path).components().any(|component| {
+        matches!(component, std::path::Component::Normal(name) if name.to_string_lossy().starts_with('.'))
+    })
 }
 
-fn is_markdown_file(path: &Path) -> bool {
+pub(crate) fn is_markdown_file(path: &Path) -> bool {
     path.extension()
         .and_then(|ext| ext.to_str())
         .map(|ext| ext.eq_ignore_ascii_case("md") || ext.eq_ignore_ascii_case("markdown"))
@@ -96,29 +319,507 @@ fn calculate_relative_path(file_path: &Path, base_dir: &Path) -> Result<String>
     let relative_path = canonical_path
         .strip_prefix(base_dir)
         .map_err(|_| anyhow::anyhow!("File path is not within base directory"))?
-        .to_string_lossy()
-        .to_string();
-    Ok(relative_path)
+        .to_string_lossy();
+    Ok(normalize_key(&relative_path))
+}
+
+/// Converts a relative path into a `tracked_files` key.
+///
+/// Keys always use `/`, like URL paths, so paths built with Windows separators must go
+/// through here both when files are tracked and when requests look them up.
+pub(crate) fn normalize_key(s: &str) -> String {
+    s.replace('\\', "/")
 }
 
-/// Compare two FileTreeNode items for sorting: folders first, then files, both alphabetically
+/// Compare two FileTreeNode items for sorting: folders first, alphabetically, then files.
+/// Files compare equal so a stable sort keeps them in the order they were added, which
+/// is [`MarkdownState::get_sorted_filenames`] order.
 fn compare_tree_nodes(a: &FileTreeNode, b: &FileTreeNode) -> std::cmp::Ordering {
     match (a.is_folder, b.is_folder) {
         (true, false) => std::cmp::Ordering::Less,
         (false, true) => std::cmp::Ordering::Greater,
-        _ => a.name.cmp(&b.name),
+        (true, true) => a.name.cmp(&b.name),
+        (false, false) => std::cmp::Ordering::Equal,
     }
 }
 
-struct TrackedFile {
-    path: PathBuf,
+/// File listing the order of the markdown files in its directory, one name per line
+const ORDERING_FILE: &str = ".mdindex";
+
+/// Reads the names listed in `dir`'s [`ORDERING_FILE`], skipping blank lines.
+///
+/// Returns `None` if the directory has no ordering file.
+pub(crate) fn load_directory_ordering(dir: &Path) -> Option<Vec<String>> {
+    let content = fs::read_to_string(dir.join(ORDERING_FILE)).ok()?;
+    Some(
+        content
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty())
+            .map(String::from)
+            .collect(),
+    )
+}
+
+/// Orders two tracked-file keys by the ordering of the directory where their paths
+/// part (`""` for the base directory): listed names first, in the listed order, then the
+/// rest in plain string order.
+fn compare_by_ordering(
+    a: &str,
+    b: &str,
+    orderings: &HashMap<String, Vec<String>>,
+) -> std::cmp::Ordering {
+    use std::cmp::Ordering;
+
+    let mut shared_len: usize = 0;
+    for (a_part, b_part) in a.split('/').zip(b.split('/')) {
+        if a_part != b_part {
+            let directory = &a[..shared_len.saturating_sub(1)];
+            let rank = |name: &str| {
+                orderings
+                    .get(directory)
+                    .and_then(|listed| listed.iter().position(|entry| entry == name))
+            };
+            return match (rank(a_part), rank(b_part)) {
+                (Some(a_rank), Some(b_rank)) => a_rank.cmp(&b_rank),
+                (Some(_), None) => Ordering::Less,
+                (None, Some(_)) => Ordering::Greater,
+                (None, None) => a.cmp(b),
+            };
+        }
+        shared_len += a_part.len() + 1;
+    }
+    a.cmp(b)
+}
+
+pub(crate) struct TrackedFile {
+    pub(crate) path: PathBuf,
     #[allow(dead_code)]  // Will be used for folder removal and root route handling
     relative_path: String,  // Path relative to base_dir (e.g., "folder/file.md")
     last_modified: SystemTime,
+    /// Shared so API responses can hold on to the content without copying it
+    pub(crate) markdown: Arc<str>,
     html: String,
-    content_hash: md5::Digest,
+    pub(crate) content_hash: md5::Digest,
+    loaded: bool, // false until first access when lazy loading is enabled
+    /// Computed on first request and cleared whenever the content changes
+    summary: Option<DocumentSummary>,
+    /// Title from [`MarkdownState::file_title`], cached and cleared like `summary`
+    title: Option<Option<String>>,
+    /// [`parser::heading_terms`] of the content, cached and cleared like `summary`
+    heading_terms: Option<Arc<HashMap<String, usize>>>,
+    /// [`validate::markdown_links`] of the content, cached and cleared like `summary`
+    links: Option<Arc<Vec<MarkdownLink>>>,
+    /// [`parser::frontmatter_tags`] of the content, cached and cleared like `summary`
+    tags: Option<Arc<Vec<String>>>,
+    /// When the content changed and its new hash, oldest first, at most
+    /// [`HASH_CHAIN_CAPACITY`] of them. The initial load isn't recorded.
+    hash_chain: VecDeque<(SystemTime, md5::Digest)>,
 }
 
+impl TrackedFile {
+    /// Reads and renders a markdown file from disk.
+    fn load(path: PathBuf, relative_path: String) -> Result<Self> {
+        let mut tracked = Self::unloaded(path, relative_path)?;
+        tracked.load_content()?;
+        Ok(tracked)
+    }
+
+    /// Tracks a file by its metadata only; content is read on first access.
+    fn unloaded(path: PathBuf, relative_path: String) -> Result<Self> {
+        let last_modified = fs::metadata(&path)?.modified()?;
+
+        Ok(TrackedFile {
+            path,
+            relative_path,
+            last_modified,
+            markdown: Arc::from(""),
+            html: String::new(),
+            content_hash: md5::compute(""),
+            loaded: false,
+            summary: None,
+            title: None,
+            heading_terms: None,
+            links: None,
+            tags: None,
+            hash_chain: VecDeque::new(),
+        })
+    }
+
+    fn load_content(&mut self) -> Result<()> {
+        let metadata = fs::metadata(&self.path)?;
+        let markdown = fs::read_to_string(&self.path)?;
+        self.html = MarkdownState::markdown_to_html(&markdown)?;
+        self.content_hash = md5::compute(&markdown);
+        self.markdown = markdown.into();
+        self.last_modified = metadata.modified()?;
+        self.loaded = true;
+        self.clear_derived();
+        Ok(())
+    }
+
+    /// Forgets everything cached from the content, for when the content changes.
+    fn clear_derived(&mut self) {
+        self.summary = None;
+        self.title = None;
+        self.heading_terms = None;
+        self.links = None;
+        self.tags = None;
+    }
+
+    /// Modification time, in Unix seconds, of the file when it was last read.
+    pub(crate) fn modified_secs(&self) -> u64 {
+        unix_secs(self.last_modified)
+    }
+
+    /// Appends the current content hash to the hash chain, forgetting the oldest entry
+    /// when it is full.
+    fn record_hash(&mut self) {
+        if self.hash_chain.len() == HASH_CHAIN_CAPACITY {
+            self.hash_chain.pop_front();
+        }
+        self.hash_chain
+            .push_back((SystemTime::now(), self.content_hash));
+    }
+}
+
+/// A tracked file behind its own lock, so reading or updating one file doesn't wait on
+/// requests for other files. The outer [`SharedMarkdownState`] lock only has to be
+/// taken for writing to add, remove or rename files.
+#[derive(Clone)]
+pub(crate) struct FileEntry {
+    content: Arc<std::sync::RwLock<TrackedFile>>,
+}
+
+impl FileEntry {
+    fn new(tracked: TrackedFile) -> Self {
+        FileEntry {
+            content: Arc::new(std::sync::RwLock::new(tracked)),
+        }
+    }
+
+    /// Never hold this across a call that takes the write lock of the same file.
+    // A panic while the lock is held leaves at worst a stale copy of the file, so
+    // poisoning is ignored
+    pub(crate) fn read(&self) -> std::sync::RwLockReadGuard<'_, TrackedFile> {
+        self.content
+            .read()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+    }
+
+    pub(crate) fn write(&self) -> std::sync::RwLockWriteGuard<'_, TrackedFile> {
+        self.content
+            .write()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+    }
+}
+
+const SNAPSHOT_VERSION: u32 = 1;
+
+/// On-disk format written by `--snapshot-file`.
+#[derive(Debug, Serialize, Deserialize)]
+struct StateSnapshot {
+    version: u32,
+    base_dir: PathBuf,
+    files: Vec<FileSnapshot>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct FileSnapshot {
+    relative_path: String,
+    last_modified: SystemTime,
+    /// Hex MD5 of the content if it had been loaded; informational, restore goes by mtime
+    content_hash: Option<String>,
+}
+
+/// A task list item together with the tracked file it belongs to.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub(crate) struct GlobalTask {
+    pub(crate) file: String,
+    pub(crate) line: usize,
+    pub(crate) text: String,
+    pub(crate) checked: bool,
+}
+
+/// A diagram together with the tracked file it belongs to.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub(crate) struct WorkspaceDiagram {
+    pub(crate) file: String,
+    #[serde(rename = "type")]
+    pub(crate) kind: DiagramKind,
+    pub(crate) definition: String,
+    pub(crate) line_start: usize,
+    pub(crate) line_end: usize,
+}
+
+/// A tracked file as listed in the client-side search index.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub(crate) struct SearchIndexEntry {
+    pub(crate) path: String,
+    pub(crate) title: Option<String>,
+    /// The file's text without markdown syntax, see [`parser::plain_text`]
+    pub(crate) content: String,
+    pub(crate) tags: Vec<String>,
+}
+
+/// Another tracked file with frontmatter tags in common with the one asked about.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub(crate) struct RelatedFile {
+    pub(crate) path: String,
+    pub(crate) shared_tags: Vec<String>,
+}
+
+/// A frontmatter tag and the tracked files using it.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub(crate) struct TagCount {
+    pub(crate) tag: String,
+    /// Number of files using the tag
+    pub(crate) count: usize,
+    /// The files, sorted by path, unless left out of the response
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) files: Option<Vec<String>>,
+}
+
+/// Another tracked file whose headings use similar words to the one asked about.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub(crate) struct SimilarFile {
+    pub(crate) path: String,
+    /// Cosine similarity of the heading term frequencies, rounded to two decimals
+    pub(crate) score: f64,
+}
+
+/// How many files [`MarkdownState::structurally_similar_files`] returns at most.
+const MAX_SIMILAR_FILES: usize = 5;
+
+/// Cosine similarity of two term-frequency vectors, 0 if either is empty.
+fn cosine_similarity(a: &HashMap<String, usize>, b: &HashMap<String, usize>) -> f64 {
+    let dot: usize = a
+        .iter()
+        .filter_map(|(term, count)| Some(count * b.get(term)?))
+        .sum();
+    let norm = |terms: &HashMap<String, usize>| {
+        (terms.values().map(|count| count * count).sum::<usize>() as f64).sqrt()
+    };
+    let norms = norm(a) * norm(b);
+    if norms == 0.0 {
+        0.0
+    } else {
+        dot as f64 / norms
+    }
+}
+
+/// The direct children of a directory in the tracked file tree, by name.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub(crate) struct ChildrenResult {
+    pub(crate) files: Vec<String>,
+    pub(crate) directories: Vec<String>,
+}
+
+/// Tracked files that no longer match the disk, found by
+/// [`MarkdownState::check_consistency`]. Each list is sorted.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub(crate) struct ConsistencyReport {
+    /// Files that don't exist anymore
+    pub(crate) missing: Vec<String>,
+    /// Loaded files whose content on disk no longer has the hash held in memory, or that
+    /// can't be read
+    pub(crate) diverged: Vec<String>,
+    /// Files whose path, with symlinks resolved, is outside the base directory
+    pub(crate) out_of_tree: Vec<String>,
+}
+
+impl ConsistencyReport {
+    pub(crate) fn is_consistent(&self) -> bool {
+        self.missing.is_empty() && self.diverged.is_empty() && self.out_of_tree.is_empty()
+    }
+}
+
+/// Everything about a tracked file that is cheap to compute, for enriching file lists.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub(crate) struct FileMetadata {
+    pub(crate) path: String,
+    /// Size of the content in bytes
+    pub(crate) size_bytes: usize,
+    /// Modification time, in Unix seconds, of the file when it was last read
+    pub(crate) modified: u64,
+    /// MD5 of the content
+    pub(crate) hash: String,
+    /// See [`MarkdownState::file_title`]
+    pub(crate) title: Option<String>,
+    pub(crate) word_count: usize,
+    pub(crate) reading_time_secs: usize,
+    pub(crate) has_frontmatter: bool,
+    /// Top-level keys of the frontmatter, in order; empty if it doesn't parse
+    pub(crate) frontmatter_keys: Vec<String>,
+    pub(crate) heading_count: usize,
+    pub(crate) link_count: usize,
+    pub(crate) image_count: usize,
+    pub(crate) task_count: usize,
+    /// Whether the content is only whitespace
+    pub(crate) is_empty: bool,
+}
+
+/// Whether a tracked file's in-memory copy still matches the file on disk.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub(crate) struct FileStatus {
+    pub(crate) path: String,
+    /// MD5 of the content held in memory, `None` if it hasn't been loaded yet
+    pub(crate) in_memory_hash: Option<String>,
+    /// MD5 of the file as it is on disk now, `None` if it can't be read
+    pub(crate) on_disk_hash: Option<String>,
+    pub(crate) in_sync: bool,
+    /// Modification time, in Unix seconds, of the file when it was last read
+    pub(crate) last_refresh: u64,
+}
+
+/// A change to a tracked file's content, as listed by [`MarkdownState::hash_chain`].
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub(crate) struct HashChainEntry {
+    /// When the change was saved or noticed, in Unix seconds
+    pub(crate) timestamp: u64,
+    /// MD5 of the new content
+    pub(crate) hash: String,
+}
+
+/// A place where another tracked file mentions the title of the one asked about.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub(crate) struct MentionResult {
+    pub(crate) path: String,
+    /// The mention with up to [`MENTION_CONTEXT_CHARS`] characters on either side,
+    /// whitespace collapsed
+    pub(crate) context: String,
+}
+
+/// Characters of surrounding text kept on each side of a mention.
+const MENTION_CONTEXT_CHARS: usize = 50;
+
+/// The text around `markdown[start..end]` for a [`MentionResult`], with `...` where it
+/// was cut.
+fn mention_context(markdown: &str, start: usize, end: usize) -> String {
+    let context_start = markdown[..start]
+        .char_indices()
+        .rev()
+        .nth(MENTION_CONTEXT_CHARS - 1)
+        .map_or(0, |(i, _)| i);
+    let context_end = markdown[end..]
+        .char_indices()
+        .nth(MENTION_CONTEXT_CHARS)
+        .map_or(markdown.len(), |(i, _)| end + i);
+
+    let mut context = markdown[context_start..context_end]
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ");
+    if context_start > 0 {
+        context.insert_str(0, "...");
+    }
+    if context_end < markdown.len() {
+        context.push_str("...");
+    }
+    context
+}
+
+/// Orders [`MarkdownState::sorted_filenames_by`] lists the tracked files in.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub(crate) enum SortCriterion {
+    /// [`MarkdownState::get_sorted_filenames`] order
+    #[default]
+    Name,
+    /// Shallow files first, alphabetically within each depth
+    Depth,
+    /// Grouped by parent directory, directories and the files in each alphabetically
+    Directory,
+}
+
+impl std::str::FromStr for SortCriterion {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "name" => Ok(Self::Name),
+            "depth" => Ok(Self::Depth),
+            "directory" => Ok(Self::Directory),
+            _ => Err(format!(
+                "Unknown sort '{s}', expected 'name', 'depth' or 'directory'"
+            )),
+        }
+    }
+}
+
+/// Why a tracked file could not be renamed or moved.
+#[derive(Debug)]
+pub(crate) enum FileMoveError {
+    NotFound,
+    /// A rename may only change the file name, not its directory
+    DifferentDirectory,
+    /// The new name is not a markdown file name
+    InvalidName,
+    AlreadyExists,
+    Io(std::io::Error),
+}
+
+impl std::fmt::Display for FileMoveError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FileMoveError::NotFound => write!(f, "File not found"),
+            FileMoveError::DifferentDirectory => {
+                write!(f, "Rename must keep the file in the same directory")
+            }
+            FileMoveError::InvalidName => write!(f, "New name must be a markdown file"),
+            FileMoveError::AlreadyExists => write!(f, "A file with that name already exists"),
+            FileMoveError::Io(e) => write!(f, "{e}"),
+        }
+    }
+}
+
+impl std::error::Error for FileMoveError {}
+
+/// Why a file could not be created.
+#[derive(Debug)]
+pub(crate) enum FileCreateError {
+    /// Hidden or matching an exclude pattern, so it wouldn't be tracked
+    Excluded,
+    AlreadyExists,
+    /// A symlinked directory on the way leads out of the base directory
+    OutsideBaseDir,
+    Io(std::io::Error),
+}
+
+impl std::fmt::Display for FileCreateError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FileCreateError::Excluded => write!(f, "File is hidden or excluded"),
+            FileCreateError::AlreadyExists => write!(f, "A file with that name already exists"),
+            FileCreateError::OutsideBaseDir => write!(f, "File would be outside the directory"),
+            FileCreateError::Io(e) => write!(f, "{e}"),
+        }
+    }
+}
+
+impl std::error::Error for FileCreateError {}
+
+/// Why a JSON Patch could not be applied to a file's frontmatter.
+#[derive(Debug)]
+pub(crate) enum FrontmatterPatchError {
+    NotFound,
+    /// The patch fails (e.g. a `test` operation), or the frontmatter isn't a mapping
+    /// before or after it
+    Invalid(String),
+    Io(anyhow::Error),
+}
+
+impl std::fmt::Display for FrontmatterPatchError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FrontmatterPatchError::NotFound => write!(f, "File not found"),
+            FrontmatterPatchError::Invalid(message) => write!(f, "{message}"),
+            FrontmatterPatchError::Io(e) => write!(f, "{e}"),
+        }
+    }
+}
+
+impl std::error::Error for FrontmatterPatchError {}
+
 #[derive(Debug, Clone, serde::Serialize)]
 struct FileTreeNode {
     name: String,           // Display name (e.g., "intro.md" or "docs")
@@ -128,56 +829,511 @@ struct FileTreeNode {
     children: Vec<FileTreeNode>,  // Child nodes (files and subfolders)
 }
 
-struct MarkdownState {
-    base_dir: PathBuf,
-    tracked_files: HashMap<String, TrackedFile>,
-    is_directory_mode: bool,
-    change_tx: broadcast::Sender<ServerMessage>,
+/// Link checks over every tracked file, computed on first request and dropped
+/// whenever a file's content changes or files are added or removed.
+#[derive(Default)]
+pub(crate) struct WorkspaceAggregates {
+    /// Bumped on every invalidation, so results computed from older content are dropped
+    generation: u64,
+    broken_links: Option<Arc<Vec<BrokenLink>>>,
+    orphans: Option<Arc<Vec<OrphanedFile>>>,
+}
+
+pub(crate) struct MarkdownState {
+    pub(crate) base_dir: PathBuf,
+    pub(crate) tracked_files: HashMap<String, FileEntry>,
+    pub(crate) is_directory_mode: bool,
+    pub(crate) change_tx: EventBroadcaster,
+    /// WebSocket sessions, behind their own lock so connections never wait on the state
+    pub(crate) sessions: Arc<std::sync::Mutex<SessionRegistry>>,
+    /// Open WebSocket connections, limited to `config.max_ws_connections`
+    pub(crate) ws_connections: Arc<AtomicUsize>,
+    /// Responses to recent `PUT` requests that carried an `Idempotency-Key`
+    pub(crate) idempotency: Arc<std::sync::Mutex<IdempotencyStore>>,
+    /// The last `/api/workspace/graph` built, reused for a few seconds
+    pub(crate) graph_cache: Arc<std::sync::Mutex<GraphCache>>,
+    /// Broken links and orphans, see [`MarkdownState::broken_links`]
+    aggregates: Arc<std::sync::Mutex<WorkspaceAggregates>>,
+    /// Files by frontmatter tag, see [`MarkdownState::tag_cloud`]
+    tag_index: Arc<std::sync::Mutex<FileIndex>>,
+    /// Files by first `# Heading`, see [`MarkdownState::files_by_title`]
+    title_index: Arc<std::sync::Mutex<FileIndex>>,
+    /// When the server started, for the uptime reported by `/__health`
+    pub(crate) started_at: Instant,
+    pub(crate) config: RouterConfig,
+    exclude_patterns: Vec<glob::Pattern>,
+    /// `root_files` and `no_orphan_check` of the config
+    orphan_check_exempt: Vec<glob::Pattern>,
+    /// [`load_directory_ordering`] of the directories holding tracked files that have
+    /// one, by directory (`""` for the base directory)
+    orderings: HashMap<String, Vec<String>>,
 }
 
 impl MarkdownState {
-    fn new(base_dir: PathBuf, file_paths: Vec<PathBuf>, is_directory_mode: bool) -> Result<Self> {
-        let (change_tx, _) = broadcast::channel::<ServerMessage>(16);
+    fn new(
+        base_dir: PathBuf,
+        file_paths: Vec<PathBuf>,
+        is_directory_mode: bool,
+        config: RouterConfig,
+    ) -> Result<Self> {
+        let mut state = Self::empty(base_dir, is_directory_mode, config)?;
 
-        let mut tracked_files = HashMap::new();
         for file_path in file_paths {
-            let metadata = fs::metadata(&file_path)?;
-            let last_modified = metadata.modified()?;
-            let content = fs::read_to_string(&file_path)?;
-            let html = Self::markdown_to_html(&content)?;
-            let content_hash = md5::compute(&content);
-            let relative_path = calculate_relative_path(&file_path, &base_dir)?;
-
-            tracked_files.insert(
-                relative_path.clone(),
-                TrackedFile {
-                    path: file_path,
-                    relative_path,
-                    last_modified,
-                    html,
-                    content_hash,
-                },
-            );
+            let relative_path = calculate_relative_path(&file_path, &state.base_dir)?;
+            if state.is_excluded(&relative_path) {
+                continue;
+            }
+
+            let tracked = state.track_file(file_path, relative_path.clone())?;
+            state.insert_tracked(&relative_path, tracked);
         }
 
+        state.reload_orderings();
+        state.warn_missing_frontmatter();
+        Ok(state)
+    }
+
+    /// Logs a warning for each loaded file that lacks a required frontmatter field.
+    /// Unloaded files are checked by the validate endpoint only.
+    fn warn_missing_frontmatter(&self) {
+        if self.config.required_frontmatter.is_empty() {
+            return;
+        }
+
+        for relative_path in self.get_sorted_filenames() {
+            let tracked = self.tracked_files[&relative_path].read();
+            if !tracked.loaded {
+                continue;
+            }
+            let missing =
+                missing_frontmatter_fields(&tracked.markdown, &self.config.required_frontmatter);
+            if !missing.is_empty() {
+                tracing::warn!(
+                    "{relative_path} is missing required frontmatter: {}",
+                    missing.join(", ")
+                );
+            }
+        }
+    }
+
+    /// State with no tracked files yet.
+    fn empty(base_dir: PathBuf, is_directory_mode: bool, config: RouterConfig) -> Result<Self> {
+        // Static file requests are checked with `starts_with(base_dir)` against canonical
+        // paths, so the base directory must be canonical too
+        let base_dir = base_dir.canonicalize()?;
+
+        let exclude_patterns = config
+            .exclude_patterns
+            .iter()
+            .map(|pattern| glob::Pattern::new(pattern))
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+        let orphan_check_exempt = config
+            .root_files
+            .iter()
+            .chain(&config.no_orphan_check)
+            .map(|pattern| glob::Pattern::new(pattern))
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+
         Ok(MarkdownState {
             base_dir,
-            tracked_files,
+            tracked_files: HashMap::new(),
             is_directory_mode,
-            change_tx,
+            change_tx: EventBroadcaster::new(),
+            sessions: Arc::default(),
+            ws_connections: Arc::default(),
+            idempotency: Arc::default(),
+            graph_cache: Arc::default(),
+            aggregates: Arc::default(),
+            tag_index: Arc::default(),
+            title_index: Arc::default(),
+            started_at: Instant::now(),
+            config,
+            exclude_patterns,
+            orphan_check_exempt,
+            orderings: HashMap::new(),
         })
     }
 
+    /// Records which files are tracked and their modification times, without content.
+    ///
+    /// Only directory mode is snapshotted; see [`MarkdownState::restore_from_snapshot`].
+    pub(crate) fn serialize_snapshot(&self) -> Vec<u8> {
+        let files = self
+            .get_sorted_filenames()
+            .into_iter()
+            .map(|relative_path| {
+                let tracked = self.tracked_files[&relative_path].read();
+                FileSnapshot {
+                    last_modified: tracked.last_modified,
+                    content_hash: tracked
+                        .loaded
+                        .then(|| format!("{:x}", tracked.content_hash)),
+                    relative_path,
+                }
+            })
+            .collect();
+        let snapshot = StateSnapshot {
+            version: SNAPSHOT_VERSION,
+            base_dir: self.base_dir.clone(),
+            files,
+        };
+        // Paths, strings and timestamps always serialize
+        serde_json::to_vec(&snapshot).unwrap_or_default()
+    }
+
+    /// Rebuilds directory-mode state from [`MarkdownState::serialize_snapshot`] output.
+    ///
+    /// Files whose modification time matches the snapshot are tracked without being read
+    /// (as with lazy loading); changed files are read now. Files that disappeared are
+    /// dropped and files added since the snapshot are picked up by a directory scan.
+    pub(crate) fn restore_from_snapshot(
+        data: &[u8],
+        base_dir: PathBuf,
+        config: RouterConfig,
+    ) -> Result<Self> {
+        let snapshot: StateSnapshot = serde_json::from_slice(data)?;
+        if snapshot.version != SNAPSHOT_VERSION {
+            anyhow::bail!("Unsupported snapshot version {}", snapshot.version);
+        }
+
+        let mut state = Self::empty(base_dir, true, config)?;
+        if snapshot.base_dir != state.base_dir {
+            anyhow::bail!(
+                "Snapshot is for {}, not {}",
+                snapshot.base_dir.display(),
+                state.base_dir.display()
+            );
+        }
+
+        for file in snapshot.files {
+            let path = state.base_dir.join(&file.relative_path);
+            let Ok(modified) = fs::metadata(&path).and_then(|m| m.modified()) else {
+                continue;
+            };
+            if state.is_excluded(&file.relative_path) {
+                continue;
+            }
+
+            let tracked = if modified == file.last_modified {
+                TrackedFile::unloaded(path, file.relative_path.clone())?
+            } else {
+                TrackedFile::load(path, file.relative_path.clone())?
+            };
+            state.insert_tracked(&file.relative_path, tracked);
+        }
+
+        let scan = scan_markdown_files_with_options(&state.base_dir, state.config.include_hidden)?;
+        for file_path in scan.files {
+            let relative_path = calculate_relative_path(&file_path, &state.base_dir)?;
+            if state.tracked_files.contains_key(&relative_path) || state.is_excluded(&relative_path)
+            {
+                continue;
+            }
+            let tracked = state.track_file(file_path, relative_path.clone())?;
+            state.insert_tracked(&relative_path, tracked);
+        }
+
+        state.reload_orderings();
+        state.warn_missing_frontmatter();
+        Ok(state)
+    }
+
     fn show_navigation(&self) -> bool {
         self.is_directory_mode
     }
 
-    fn get_sorted_filenames(&self) -> Vec<String> {
+    /// Adds a file to `tracked_files` under its normalized key.
+    fn insert_tracked(&mut self, relative_path: &str, tracked: TrackedFile) {
+        let relative_path = normalize_key(relative_path);
+        self.file_changed(&relative_path);
+        self.tracked_files
+            .insert(relative_path, FileEntry::new(tracked));
+    }
+
+    /// Starts tracking a file, deferring the read when lazy loading is enabled.
+    fn track_file(&self, path: PathBuf, relative_path: String) -> Result<TrackedFile> {
+        if self.config.lazy {
+            TrackedFile::unloaded(path, relative_path)
+        } else {
+            TrackedFile::load(path, relative_path)
+        }
+    }
+
+    /// Reads every tracked file that has not been loaded yet.
+    pub(crate) fn load_all_files(&self) {
+        for entry in self.tracked_files.values() {
+            let mut tracked = entry.write();
+            if !tracked.loaded {
+                let _ = tracked.load_content();
+            }
+        }
+    }
+
+    /// [`validate::broken_links`], reused until a file changes, is added or is removed.
+    pub(crate) fn broken_links(&self) -> Arc<Vec<BrokenLink>> {
+        self.cached_aggregate(
+            |aggregates| &mut aggregates.broken_links,
+            || validate::broken_links(self),
+        )
+    }
+
+    /// [`graph::find_orphans`], reused like [`MarkdownState::broken_links`].
+    pub(crate) fn orphans(&self) -> Arc<Vec<OrphanedFile>> {
+        self.cached_aggregate(
+            |aggregates| &mut aggregates.orphans,
+            || graph::find_orphans(self),
+        )
+    }
+
+    /// The aggregate in `slot`, computed with `compute` unless cached. A result is only
+    /// kept if no file changed while it was being computed.
+    fn cached_aggregate<T>(
+        &self,
+        slot: fn(&mut WorkspaceAggregates) -> &mut Option<Arc<T>>,
+        compute: impl FnOnce() -> T,
+    ) -> Arc<T> {
+        let generation = {
+            let mut aggregates = self.lock_aggregates();
+            if let Some(cached) = slot(&mut aggregates) {
+                return cached.clone();
+            }
+            aggregates.generation
+        };
+
+        let result = Arc::new(compute());
+        let mut aggregates = self.lock_aggregates();
+        if aggregates.generation == generation {
+            *slot(&mut aggregates) = Some(result.clone());
+        }
+        result
+    }
+
+    /// Drops what was derived from the content of `relative_path` after it changed, was
+    /// added or was removed: the cached aggregates and its entries in the tag and title
+    /// indexes. Never call while holding a file's lock, as rebuilding them takes the
+    /// files' locks.
+    fn file_changed(&self, relative_path: &str) {
+        {
+            let mut aggregates = self.lock_aggregates();
+            aggregates.generation += 1;
+            aggregates.broken_links = None;
+            aggregates.orphans = None;
+        }
+        lock_index(&self.tag_index).remove(relative_path);
+        lock_index(&self.title_index).remove(relative_path);
+    }
+
+    fn lock_aggregates(&self) -> std::sync::MutexGuard<'_, WorkspaceAggregates> {
+        self.aggregates
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+    }
+
+    /// Adds the tracked files missing from `index` under the keys `keys` finds in them,
+    /// loading files that weren't yet. The index stays locked meanwhile, so a file that
+    /// changes in between is removed again only after it was added.
+    fn update_index<'a, K: IntoIterator<Item = String>>(
+        &self,
+        index: &'a std::sync::Mutex<FileIndex>,
+        keys: impl Fn(&str) -> K,
+    ) -> std::sync::MutexGuard<'a, FileIndex> {
+        let mut index = lock_index(index);
+        for (path, entry) in &self.tracked_files {
+            if index.contains(path) {
+                continue;
+            }
+            {
+                let mut tracked = entry.write();
+                if !tracked.loaded {
+                    let _ = tracked.load_content();
+                }
+            }
+            index.insert(path, keys(path));
+        }
+        index
+    }
+
+    /// Whether a file matches one of the configured exclude patterns (directory mode only).
+    fn is_excluded(&self, relative_path: &str) -> bool {
+        self.is_directory_mode
+            && ((!self.config.include_hidden && is_hidden_path(relative_path))
+                || self
+                    .exclude_patterns
+                    .iter()
+                    .any(|pattern| pattern.matches(relative_path)))
+    }
+
+    /// Whether `relative_path` is a root file or left out of the orphan check.
+    pub(crate) fn is_orphan_check_exempt(&self, relative_path: &str) -> bool {
+        self.orphan_check_exempt
+            .iter()
+            .any(|pattern| pattern.matches(relative_path))
+    }
+
+    /// The tracked key for `path`, treating `.md` and `.markdown` as aliases: if
+    /// `guide.md` isn't tracked but `guide.markdown` is, that is returned, and vice versa.
+    pub(crate) fn lookup_by_path(&self, path: &str) -> Option<&str> {
+        let alias = if let Some(stem) = path.strip_suffix(".md") {
+            Some(format!("{stem}.markdown"))
+        } else {
+            path.strip_suffix(".markdown")
+                .map(|stem| format!("{stem}.md"))
+        };
+
+        std::iter::once(path.to_string())
+            .chain(alias)
+            .find_map(|candidate| self.tracked_files.get_key_value(&candidate))
+            .map(|(key, _)| key.as_str())
+    }
+
+    /// Tracked files in alphabetical order, except where a directory has an `.mdindex`
+    /// listing its files (see [`load_directory_ordering`]): there the listed files come
+    /// first in the listed order, followed by the others alphabetically.
+    pub(crate) fn get_sorted_filenames(&self) -> Vec<String> {
         let mut filenames: Vec<_> = self.tracked_files.keys().cloned().collect();
         filenames.sort();
+        if !self.orderings.is_empty() {
+            filenames.sort_by(|a, b| compare_by_ordering(a, b, &self.orderings));
+        }
+        filenames
+    }
+
+    /// Re-reads the `.mdindex` of every directory holding tracked files, directly or
+    /// further down.
+    pub(crate) fn reload_orderings(&mut self) {
+        let directories: BTreeSet<&str> = self
+            .tracked_files
+            .keys()
+            .flat_map(|file| {
+                file.match_indices('/')
+                    .map(|(end, _)| &file[..end])
+                    .chain(std::iter::once(""))
+            })
+            .collect();
+        self.orderings = directories
+            .into_iter()
+            .filter_map(|directory| {
+                load_directory_ordering(&self.base_dir.join(directory))
+                    .map(|ordering| (directory.to_string(), ordering))
+            })
+            .collect();
+    }
+
+    /// Re-reads the `.mdindex` of one directory, e.g. after the watcher saw it change.
+    pub(crate) fn reload_ordering(&mut self, directory: &str) {
+        match load_directory_ordering(&self.base_dir.join(directory)) {
+            Some(ordering) => self.orderings.insert(directory.to_string(), ordering),
+            None => self.orderings.remove(directory),
+        };
+    }
+
+    /// Reads the `.mdindex` of the directories of a newly tracked file that weren't known
+    /// to have one yet.
+    fn load_orderings_for(&mut self, relative_path: &str) {
+        let directories = relative_path
+            .match_indices('/')
+            .map(|(end, _)| &relative_path[..end])
+            .chain(std::iter::once(""));
+        for directory in directories {
+            if !self.orderings.contains_key(directory) {
+                if let Some(ordering) = load_directory_ordering(&self.base_dir.join(directory)) {
+                    self.orderings.insert(directory.to_string(), ordering);
+                }
+            }
+        }
+    }
+
+    /// Replaces the old name of a file renamed within its directory in that directory's
+    /// ordering, in memory and in its [`ORDERING_FILE`], so the file keeps its place.
+    fn rename_in_ordering(&mut self, old_rel: &str, new_rel: &str) {
+        let (directory, old_name) = old_rel.rsplit_once('/').unwrap_or(("", old_rel));
+        let new_name = new_rel.rsplit_once('/').map_or(new_rel, |(_, name)| name);
+        let Some(entry) = self
+            .orderings
+            .get_mut(directory)
+            .and_then(|ordering| ordering.iter_mut().find(|entry| *entry == old_name))
+        else {
+            return;
+        };
+        *entry = new_name.to_string();
+
+        let path = self.base_dir.join(directory).join(ORDERING_FILE);
+        let result = fs::read_to_string(&path).and_then(|content| {
+            let content: String = content
+                .lines()
+                .map(|line| {
+                    if line.trim() == old_name {
+                        new_name
+                    } else {
+                        line
+                    }
+                })
+                .flat_map(|line| [line, "\n"])
+                .collect();
+            fs::write(&path, content)
+        });
+        if let Err(e) = result {
+            tracing::warn!("Failed to update {}: {e}", path.display());
+        }
+    }
+
+    /// Tracked files in the order `criterion` asks for. A file's depth is the number of
+    /// `/` in its path.
+    pub(crate) fn sorted_filenames_by(&self, criterion: SortCriterion) -> Vec<String> {
+        let mut filenames: Vec<String> = self.tracked_files.keys().cloned().collect();
+        match criterion {
+            SortCriterion::Name => return self.get_sorted_filenames(),
+            SortCriterion::Depth => {
+                filenames.sort_by_cached_key(|path| (path.matches('/').count(), path.clone()))
+            }
+            SortCriterion::Directory => filenames.sort_by(|a, b| {
+                let a = a.rsplit_once('/').unwrap_or(("", a));
+                a.cmp(&b.rsplit_once('/').unwrap_or(("", b)))
+            }),
+        }
         filenames
     }
 
+    /// The files before and after `rel_path` in [`Self::get_sorted_filenames`] order, for
+    /// previous/next page navigation.
+    pub(crate) fn neighbors(&self, rel_path: &str) -> (Option<String>, Option<String>) {
+        let filenames = self.get_sorted_filenames();
+        let Some(index) = filenames.iter().position(|name| name == rel_path) else {
+            return (None, None);
+        };
+
+        let previous = index.checked_sub(1).map(|i| filenames[i].clone());
+        let next = filenames.get(index + 1).cloned();
+        (previous, next)
+    }
+
+    /// The files and subdirectories directly inside `dir_prefix`, which is either empty
+    /// for the root or a directory ending in `/`, e.g. `guide/` for `guide/intro.md`.
+    ///
+    /// Returns `None` if no tracked file is under `dir_prefix`.
+    pub(crate) fn children(&self, dir_prefix: &str) -> Option<ChildrenResult> {
+        let mut files = BTreeSet::new();
+        let mut directories = BTreeSet::new();
+        for rest in self
+            .tracked_files
+            .keys()
+            .filter_map(|key| key.strip_prefix(dir_prefix))
+        {
+            match rest.split_once('/') {
+                Some((directory, _)) => directories.insert(directory.to_string()),
+                None => files.insert(rest.to_string()),
+            };
+        }
+
+        if files.is_empty() && directories.is_empty() && !dir_prefix.is_empty() {
+            return None;
+        }
+        Some(ChildrenResult {
+            files: files.into_iter().collect(),
+            directories: directories.into_iter().collect(),
+        })
+    }
+
     fn get_file_tree(&self) -> Vec<FileTreeNode> {
         use std::collections::BTreeMap;
 
@@ -220,83 +1376,714 @@ impl MarkdownState {
                     children: Vec::new(),
                 };
 
-                folders
-                    .get_mut(&parent_folder)
-                    .unwrap()
-                    .push(file_node);
-            }
+                folders
+                    .get_mut(&parent_folder)
+                    .unwrap()
+                    .push(file_node);
+            }
+        }
+
+        // Build folder tree recursively
+        fn build_folder_nodes(
+            folder_path: &str,
+            folders: &BTreeMap<String, Vec<FileTreeNode>>,
+        ) -> FileTreeNode {
+            let name = folder_path.split('/').next_back().unwrap_or(folder_path).to_string();
+            let mut children = Vec::new();
+
+            // Add files in this folder
+            if let Some(files) = folders.get(folder_path) {
+                children.extend(files.clone());
+            }
+
+            // Add subfolders
+            let folder_prefix = format!("{}/", folder_path);
+            for (sub_folder_path, _) in folders.iter() {
+                if sub_folder_path.starts_with(&folder_prefix) {
+                    // Check if this is a direct child (not a deeper descendant)
+                    let remaining = &sub_folder_path[folder_prefix.len()..];
+                    if !remaining.contains('/') {
+                        let subfolder_node = build_folder_nodes(sub_folder_path, folders);
+                        children.push(subfolder_node);
+                    }
+                }
+            }
+
+            // Sort children: folders first, then files, both alphabetically
+            children.sort_by(compare_tree_nodes);
+
+            FileTreeNode {
+                name,
+                path: folder_path.to_string(),
+                is_folder: true,
+                children,
+            }
+        }
+
+        // Build root-level folder nodes
+        let mut result = Vec::new();
+
+        // Add root-level folders
+        for (folder_path, _) in folders.iter() {
+            // Only process top-level folders (no '/' in path)
+            if !folder_path.contains('/') {
+                result.push(build_folder_nodes(folder_path, &folders));
+            }
+        }
+
+        // Add root-level files
+        result.extend(root_files);
+
+        // Sort result: folders first, then files, both alphabetically
+        result.sort_by(compare_tree_nodes);
+
+        result
+    }
+
+    /// Brings a tracked file up to date with disk, loading it on first access.
+    ///
+    /// Only takes the file's write lock when it actually has to be (re)read.
+    pub(crate) fn refresh_file(&self, relative_path: &str) -> Result<()> {
+        let Some(entry) = self.tracked_files.get(relative_path) else {
+            return Ok(());
+        };
+
+        {
+            let tracked = entry.read();
+            if tracked.loaded && fs::metadata(&tracked.path)?.modified()? <= tracked.last_modified {
+                return Ok(());
+            }
+        }
+
+        // Checked again: another request may have refreshed the file in the meantime
+        let mut tracked = entry.write();
+        if !tracked.loaded {
+            return tracked.load_content();
+        }
+
+        let metadata = fs::metadata(&tracked.path)?;
+        let current_modified = metadata.modified()?;
+
+        if current_modified > tracked.last_modified {
+            let content = fs::read_to_string(&tracked.path)?;
+            tracked.html = Self::markdown_to_html(&content)?;
+            tracked.content_hash = md5::compute(&content);
+            tracked.markdown = content.into();
+            tracked.last_modified = current_modified;
+            tracked.clear_derived();
+            tracked.record_hash();
+            drop(tracked);
+            self.file_changed(relative_path);
+        }
+
+        Ok(())
+    }
+
+    /// Returns the cached [`DocumentSummary`] of a loaded file, computing it if needed.
+    pub(crate) fn file_summary(&self, relative_path: &str) -> Option<DocumentSummary> {
+        let entry = self.tracked_files.get(relative_path)?;
+        if let Some(summary) = &entry.read().summary {
+            return Some(summary.clone());
+        }
+
+        let mut tracked = entry.write();
+        let summary = parser::extract_summary(&tracked.markdown);
+        tracked.summary = Some(summary.clone());
+        Some(summary)
+    }
+
+    /// The title of a loaded file: its frontmatter `title`, or else its first `# Heading`.
+    pub(crate) fn file_title(&self, relative_path: &str) -> Option<String> {
+        let entry = self.tracked_files.get(relative_path)?;
+        if let Some(title) = &entry.read().title {
+            return title.clone();
+        }
+
+        // Computed and stored under one lock, so a concurrent update can't leave the
+        // title of the old content cached
+        let mut guard = entry.write();
+        let tracked = &mut *guard;
+        let title = parser::frontmatter_title(&tracked.markdown).or_else(|| {
+            tracked
+                .summary
+                .get_or_insert_with(|| parser::extract_summary(&tracked.markdown))
+                .title
+                .clone()
+        });
+        tracked.title = Some(title.clone());
+        title
+    }
+
+    /// The cached [`parser::heading_terms`] of a loaded file, computing them if needed.
+    fn file_heading_terms(&self, relative_path: &str) -> Option<Arc<HashMap<String, usize>>> {
+        let entry = self.tracked_files.get(relative_path)?;
+        if let Some(terms) = &entry.read().heading_terms {
+            return Some(terms.clone());
+        }
+
+        let mut tracked = entry.write();
+        let terms = Arc::new(parser::heading_terms(&tracked.markdown));
+        tracked.heading_terms = Some(terms.clone());
+        Some(terms)
+    }
+
+    /// The cached [`validate::markdown_links`] of a loaded file, computing them if needed.
+    pub(crate) fn file_links(&self, relative_path: &str) -> Option<Arc<Vec<MarkdownLink>>> {
+        let entry = self.tracked_files.get(relative_path)?;
+        if let Some(links) = &entry.read().links {
+            return Some(links.clone());
+        }
+
+        let mut tracked = entry.write();
+        let links = Arc::new(validate::markdown_links(&tracked.markdown));
+        tracked.links = Some(links.clone());
+        Some(links)
+    }
+
+    /// The cached [`parser::frontmatter_tags`] of a loaded file, computing them if needed.
+    fn file_tags(&self, relative_path: &str) -> Option<Arc<Vec<String>>> {
+        let entry = self.tracked_files.get(relative_path)?;
+        if let Some(tags) = &entry.read().tags {
+            return Some(tags.clone());
+        }
+
+        let mut tracked = entry.write();
+        let tags = Arc::new(parser::frontmatter_tags(&tracked.markdown));
+        tracked.tags = Some(tags.clone());
+        Some(tags)
+    }
+
+    /// Writes new content for a tracked file to disk, refreshes the in-memory copy
+    /// and notifies connected clients.
+    pub(crate) fn update_file(&self, relative_path: &str, content: &str) -> Result<()> {
+        let mut tracked = self
+            .tracked_files
+            .get(relative_path)
+            .ok_or_else(|| anyhow::anyhow!("File is not tracked: {relative_path}"))?
+            .write();
+
+        fs::write(&tracked.path, content)?;
+        tracked.html = Self::markdown_to_html(content)?;
+        tracked.content_hash = md5::compute(content);
+        tracked.markdown = content.into();
+        tracked.last_modified = fs::metadata(&tracked.path)?.modified()?;
+        tracked.loaded = true;
+        tracked.clear_derived();
+        tracked.record_hash();
+        drop(tracked);
+        self.file_changed(relative_path);
+
+        self.change_tx.send(ServerMessage::Reload);
+        Ok(())
+    }
+
+    /// Replaces the YAML frontmatter of a tracked file, keeping its body as is, and
+    /// saves it through [`MarkdownState::update_file`].
+    pub(crate) fn update_frontmatter(
+        &self,
+        rel_path: &str,
+        new_fm: serde_yaml::Value,
+    ) -> Result<()> {
+        self.refresh_file(rel_path)?;
+        let markdown = self
+            .tracked_files
+            .get(rel_path)
+            .ok_or_else(|| anyhow::anyhow!("File is not tracked: {rel_path}"))?
+            .read()
+            .markdown
+            .clone();
+
+        let content = parser::replace_frontmatter(&markdown, &new_fm)?;
+        self.update_file(rel_path, &content)
+    }
+
+    /// Applies a JSON Patch (RFC 6902) to a file's frontmatter, seen as JSON, saves the
+    /// file and returns the patched frontmatter.
+    ///
+    /// A file without frontmatter starts from `{}`, and a patch that removes every field
+    /// removes the block. Nothing is saved if any operation fails.
+    pub(crate) fn patch_frontmatter(
+        &self,
+        rel_path: &str,
+        patch: &json_patch::Patch,
+    ) -> Result<serde_json::Value, FrontmatterPatchError> {
+        let entry = self
+            .tracked_files
+            .get(rel_path)
+            .ok_or(FrontmatterPatchError::NotFound)?;
+        self.refresh_file(rel_path)
+            .map_err(FrontmatterPatchError::Io)?;
+        let markdown = entry.read().markdown.clone();
+
+        let invalid = |message: &str| FrontmatterPatchError::Invalid(message.to_string());
+        let mut frontmatter = match parser::split_frontmatter(&markdown) {
+            (None, _) => serde_json::Value::Object(serde_json::Map::new()),
+            (Some(_), _) => match parser::parse_frontmatter(&markdown) {
+                Some(serde_yaml::Value::Null) => serde_json::Value::Object(serde_json::Map::new()),
+                Some(yaml) => serde_json::to_value(yaml)
+                    .map_err(|_| invalid("Frontmatter can't be represented as JSON"))?,
+                None => return Err(invalid("Frontmatter is not valid")),
+            },
+        };
+        if !frontmatter.is_object() {
+            return Err(invalid("Frontmatter is not a mapping"));
+        }
+
+        json_patch::patch(&mut frontmatter, patch)
+            .map_err(|e| FrontmatterPatchError::Invalid(e.to_string()))?;
+        let yaml = match &frontmatter {
+            serde_json::Value::Object(fields) if fields.is_empty() => serde_yaml::Value::Null,
+            serde_json::Value::Object(_) => serde_yaml::to_value(&frontmatter)
+                .map_err(|e| FrontmatterPatchError::Io(e.into()))?,
+            _ => return Err(invalid("Patched frontmatter must be a mapping")),
+        };
+
+        let content = parser::replace_frontmatter(&markdown, &yaml)
+            .map_err(|e| FrontmatterPatchError::Io(e.into()))?;
+        self.update_file(rel_path, &content)
+            .map_err(FrontmatterPatchError::Io)?;
+        Ok(frontmatter)
+    }
+
+    /// Deletes a tracked file from disk and stops tracking it.
+    ///
+    /// Clients are not notified, so deleting several files can end in a single reload.
+    pub(crate) fn delete_file(&mut self, relative_path: &str) -> Result<()> {
+        let path = self
+            .tracked_files
+            .get(relative_path)
+            .ok_or_else(|| anyhow::anyhow!("File is not tracked: {relative_path}"))?
+            .read()
+            .path
+            .clone();
+
+        match fs::remove_file(&path) {
+            // Already gone from disk, just not rescanned yet
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => return Err(e.into()),
+            _ => {}
+        }
+        self.tracked_files.remove(relative_path);
+        self.file_changed(relative_path);
+        Ok(())
+    }
+
+    /// Writes a new markdown file, creating its directories, and starts tracking it.
+    ///
+    /// Clients are not notified, so creating several files can end in a single reload.
+    pub(crate) fn create_file(
+        &mut self,
+        relative_path: &str,
+        content: &str,
+    ) -> std::result::Result<(), FileCreateError> {
+        let relative_path = normalize_key(relative_path);
+        if self.is_excluded(&relative_path) {
+            return Err(FileCreateError::Excluded);
+        }
+        let path = self.base_dir.join(&relative_path);
+        if self.tracked_files.contains_key(&relative_path) || path.exists() {
+            return Err(FileCreateError::AlreadyExists);
+        }
+
+        // Symlinked directories could lead anywhere, so check where the part that exists
+        // resolves to before creating the rest, and where the parent ended up after
+        let parent = path.parent().unwrap_or(&self.base_dir);
+        let is_inside = |dir: &Path| -> std::result::Result<bool, FileCreateError> {
+            Ok(dir
+                .canonicalize()
+                .map_err(FileCreateError::Io)?
+                .starts_with(&self.base_dir))
+        };
+        let existing = parent
+            .ancestors()
+            .find(|ancestor| ancestor.exists())
+            .unwrap_or(&self.base_dir);
+        if !is_inside(existing)? {
+            return Err(FileCreateError::OutsideBaseDir);
+        }
+        fs::create_dir_all(parent).map_err(FileCreateError::Io)?;
+        if !is_inside(parent)? {
+            return Err(FileCreateError::OutsideBaseDir);
+        }
+        let mut file = match fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&path)
+        {
+            Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => {
+                return Err(FileCreateError::AlreadyExists)
+            }
+            result => result.map_err(FileCreateError::Io)?,
+        };
+        file.write_all(content.as_bytes())
+            .map_err(FileCreateError::Io)?;
+
+        let tracked = self
+            .track_file(path, relative_path.clone())
+            .map_err(|e| FileCreateError::Io(std::io::Error::other(e)))?;
+        self.insert_tracked(&relative_path, tracked);
+        self.load_orderings_for(&relative_path);
+        Ok(())
+    }
+
+    /// Renames a tracked file within its directory, on disk and in the tracked set.
+    pub(crate) fn rename_file(
+        &mut self,
+        old_rel: &str,
+        new_rel: &str,
+    ) -> std::result::Result<(), FileMoveError> {
+        if !self.tracked_files.contains_key(old_rel) {
+            return Err(FileMoveError::NotFound);
+        }
+        if Path::new(old_rel).parent() != Path::new(new_rel).parent() {
+            return Err(FileMoveError::DifferentDirectory);
+        }
+        let new_name_is_valid = Path::new(new_rel)
+            .file_name()
+            .is_some_and(|name| !name.to_string_lossy().starts_with('.'));
+        if !new_name_is_valid || !is_markdown_file(Path::new(new_rel)) {
+            return Err(FileMoveError::InvalidName);
+        }
+
+        let new_path = self.base_dir.join(new_rel);
+        if self.tracked_files.contains_key(new_rel) || new_path.exists() {
+            return Err(FileMoveError::AlreadyExists);
+        }
+
+        let entry = self
+            .tracked_files
+            .remove(old_rel)
+            .ok_or(FileMoveError::NotFound)?;
+        let old_path = entry.read().path.clone();
+        if let Err(e) = fs::rename(&old_path, &new_path) {
+            self.tracked_files.insert(old_rel.to_string(), entry);
+            return Err(FileMoveError::Io(e));
+        }
+
+        {
+            let mut tracked = entry.write();
+            tracked.path = new_path;
+            tracked.relative_path = new_rel.to_string();
+        }
+        self.tracked_files.insert(new_rel.to_string(), entry);
+        self.file_changed(old_rel);
+        self.file_changed(new_rel);
+        self.rename_in_ordering(old_rel, new_rel);
+
+        self.change_tx.send(ServerMessage::FileRenamed {
+            old_name: old_rel.to_string(),
+            new_name: new_rel.to_string(),
+        });
+        Ok(())
+    }
+
+    /// Collects the task list items of every tracked file, ordered by file then line.
+    pub(crate) fn all_tasks(&self) -> Vec<GlobalTask> {
+        self.get_sorted_filenames()
+            .into_iter()
+            .flat_map(|file| {
+                let markdown = self.tracked_files[&file].read().markdown.clone();
+                let tasks = parser::extract_tasks(&markdown);
+                tasks.into_iter().map(move |task| GlobalTask {
+                    file: file.clone(),
+                    line: task.line,
+                    text: task.text,
+                    checked: task.checked,
+                })
+            })
+            .collect()
+    }
+
+    /// Collects the diagrams of every tracked file, ordered by file then line.
+    pub(crate) fn all_diagrams(&self) -> Vec<WorkspaceDiagram> {
+        self.get_sorted_filenames()
+            .into_iter()
+            .flat_map(|file| {
+                let markdown = self.tracked_files[&file].read().markdown.clone();
+                let diagrams = parser::extract_diagrams(&markdown);
+                diagrams.into_iter().map(move |diagram| WorkspaceDiagram {
+                    file: file.clone(),
+                    kind: diagram.kind,
+                    definition: diagram.definition,
+                    line_start: diagram.line_start,
+                    line_end: diagram.line_end,
+                })
+            })
+            .collect()
+    }
+
+    /// Every loaded file with its title, tags and plain text, sorted by path.
+    pub(crate) fn search_index(&self) -> Vec<SearchIndexEntry> {
+        self.get_sorted_filenames()
+            .into_iter()
+            .map(|path| {
+                let markdown = self.tracked_files[&path].read().markdown.clone();
+                SearchIndexEntry {
+                    title: self.file_title(&path),
+                    content: parser::plain_text(&markdown),
+                    tags: self
+                        .file_tags(&path)
+                        .map(|tags| tags.to_vec())
+                        .unwrap_or_default(),
+                    path,
+                }
+            })
+            .collect()
+    }
+
+    /// Files sharing frontmatter tags with `rel_path`, most shared tags first and then by
+    /// path, keeping at most `limit`. Shared tags are listed in `rel_path`'s order.
+    pub(crate) fn related_files(&self, rel_path: &str, limit: usize) -> Vec<RelatedFile> {
+        let Some(tags) = self.file_tags(rel_path).filter(|tags| !tags.is_empty()) else {
+            return Vec::new();
+        };
+
+        let mut related: Vec<RelatedFile> = self
+            .get_sorted_filenames()
+            .into_iter()
+            .filter(|file| file != rel_path)
+            .filter_map(|file| {
+                let other_tags = self.file_tags(&file)?;
+                let shared_tags: Vec<String> = tags
+                    .iter()
+                    .filter(|tag| other_tags.contains(tag))
+                    .cloned()
+                    .collect();
+                (!shared_tags.is_empty()).then_some(RelatedFile {
+                    path: file,
+                    shared_tags,
+                })
+            })
+            .collect();
+
+        // Stable, so files with the same count stay in path order
+        related.sort_by_key(|file| std::cmp::Reverse(file.shared_tags.len()));
+        related.truncate(limit);
+        related
+    }
+
+    /// Tracked files by the text of their first `# Heading`, from their cached
+    /// [`MarkdownState::file_summary`]. Only files that changed since the last call are
+    /// added again; files without a heading aren't listed.
+    pub(crate) fn files_by_title(&self) -> std::sync::MutexGuard<'_, FileIndex> {
+        self.update_index(&self.title_index, |path| {
+            self.file_summary(path)
+                .and_then(|summary| summary.title)
+                .filter(|title| !title.is_empty())
+        })
+    }
+
+    /// Every frontmatter tag used by at least `min_count` files, most used first and
+    /// then by tag. Tags are case-sensitive and count once per file.
+    ///
+    /// Served from the tag index, where only files that changed since the last call are
+    /// added again, from their cached [`MarkdownState::file_tags`].
+    pub(crate) fn tag_cloud(&self, min_count: usize, include_files: bool) -> Vec<TagCount> {
+        let index = self.update_index(&self.tag_index, |path| {
+            self.file_tags(path)
+                .map(|tags| tags.to_vec())
+                .unwrap_or_default()
+        });
+
+        let mut cloud: Vec<TagCount> = index
+            .iter()
+            .filter(|(_, files)| files.len() >= min_count)
+            .map(|(tag, files)| TagCount {
+                tag: tag.clone(),
+                count: files.len(),
+                files: include_files.then(|| files.iter().cloned().collect()),
+            })
+            .collect();
+        // Stable, so tags with the same count stay in alphabetical order
+        cloud.sort_by_key(|tag| std::cmp::Reverse(tag.count));
+        cloud
+    }
+
+    /// Files whose headings are most like those of `rel_path`, by the cosine similarity of
+    /// their heading term frequencies: best first, then by path, at most
+    /// [`MAX_SIMILAR_FILES`]. Files sharing no heading words are left out.
+    pub(crate) fn structurally_similar_files(&self, rel_path: &str) -> Vec<SimilarFile> {
+        let Some(terms) = self.file_heading_terms(rel_path) else {
+            return Vec::new();
+        };
+        if terms.is_empty() {
+            return Vec::new();
         }
 
-        // Build folder tree recursively
-        fn build_folder_nodes(
-            folder_path: &str,
-            folders: &BTreeMap<String, Vec<FileTreeNode>>,
-        ) -> FileTreeNode {
-            let name = folder_path.split('/').next_back().unwrap_or(folder_path).to_string();
-            let mut children = Vec::new();
+        let mut similar: Vec<SimilarFile> = self
+            .get_sorted_filenames()
+            .into_iter()
+            .filter(|file| file != rel_path)
+            .filter_map(|file| {
+                let score = cosine_similarity(&terms, &*self.file_heading_terms(&file)?);
+                (score > 0.0).then(|| SimilarFile {
+                    path: file,
+                    score: (score * 100.0).round() / 100.0,
+                })
+            })
+            .collect();
 
-            // Add files in this folder
-            if let Some(files) = folders.get(folder_path) {
-                children.extend(files.clone());
-            }
+        // Stable, so files with the same score stay in path order
+        similar.sort_by(|a, b| b.score.total_cmp(&a.score));
+        similar.truncate(MAX_SIMILAR_FILES);
+        similar
+    }
 
-            // Add subfolders
-            let folder_prefix = format!("{}/", folder_path);
-            for (sub_folder_path, _) in folders.iter() {
-                if sub_folder_path.starts_with(&folder_prefix) {
-                    // Check if this is a direct child (not a deeper descendant)
-                    let remaining = &sub_folder_path[folder_prefix.len()..];
-                    if !remaining.contains('/') {
-                        let subfolder_node = build_folder_nodes(sub_folder_path, folders);
-                        children.push(subfolder_node);
-                    }
-                }
+    /// Compares every tracked file with the disk without changing anything, for finding
+    /// files the watcher missed changes to. Files that were never loaded have no hash
+    /// to compare, so only their existence and location are checked.
+    pub(crate) fn check_consistency(&self) -> ConsistencyReport {
+        let mut report = ConsistencyReport::default();
+        for relative_path in self.get_sorted_filenames() {
+            let tracked = self.tracked_files[&relative_path].read();
+            let resolved = tracked
+                .path
+                .canonicalize()
+                .unwrap_or_else(|_| tracked.path.clone());
+            if !resolved.starts_with(&self.base_dir) {
+                report.out_of_tree.push(relative_path.clone());
             }
 
-            // Sort children: folders first, then files, both alphabetically
-            children.sort_by(compare_tree_nodes);
-
-            FileTreeNode {
-                name,
-                path: folder_path.to_string(),
-                is_folder: true,
-                children,
+            match fs::read(&tracked.path) {
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                    report.missing.push(relative_path);
+                }
+                Err(_) => report.diverged.push(relative_path),
+                Ok(content) if tracked.loaded && md5::compute(&content) != tracked.content_hash => {
+                    report.diverged.push(relative_path);
+                }
+                Ok(_) => {}
             }
         }
+        report
+    }
 
-        // Build root-level folder nodes
-        let mut result = Vec::new();
-
-        // Add root-level folders
-        for (folder_path, _) in folders.iter() {
-            // Only process top-level folders (no '/' in path)
-            if !folder_path.contains('/') {
-                result.push(build_folder_nodes(folder_path, &folders));
-            }
+    /// Logs what [`Self::check_consistency`] finds.
+    fn warn_inconsistencies(&self) {
+        let report = self.check_consistency();
+        for path in &report.missing {
+            tracing::warn!("{path} is tracked but doesn't exist anymore");
+        }
+        for path in &report.diverged {
+            tracing::warn!("{path} changed on disk since it was read");
+        }
+        for path in &report.out_of_tree {
+            tracing::warn!("{path} is outside {}", self.base_dir.display());
+        }
+        if report.is_consistent() {
+            tracing::info!(
+                "All {} tracked files are consistent",
+                self.tracked_files.len()
+            );
         }
+    }
 
-        // Add root-level files
-        result.extend(root_files);
+    /// Metadata of a loaded file, using the cached summary and title.
+    pub(crate) fn file_metadata(&self, rel_path: &str) -> Option<FileMetadata> {
+        let (markdown, modified, hash) = {
+            let tracked = self.tracked_files.get(rel_path)?.read();
+            (
+                tracked.markdown.clone(),
+                tracked.modified_secs(),
+                tracked.content_hash,
+            )
+        };
+        let counts = parser::element_counts(&markdown);
+        let frontmatter_keys = match parser::parse_frontmatter(&markdown) {
+            Some(serde_yaml::Value::Mapping(mapping)) => mapping
+                .keys()
+                .filter_map(|key| key.as_str().map(str::to_string))
+                .collect(),
+            _ => Vec::new(),
+        };
 
-        // Sort result: folders first, then files, both alphabetically
-        result.sort_by(compare_tree_nodes);
+        Some(FileMetadata {
+            path: rel_path.to_string(),
+            size_bytes: markdown.len(),
+            modified,
+            hash: format!("{hash:x}"),
+            title: self.file_title(rel_path),
+            word_count: self.file_summary(rel_path)?.word_count,
+            reading_time_secs: parser::estimate_reading_time(&markdown).seconds,
+            has_frontmatter: parser::split_frontmatter(&markdown).0.is_some(),
+            frontmatter_keys,
+            heading_count: counts.headings,
+            link_count: counts.links,
+            image_count: counts.images,
+            task_count: parser::extract_tasks(&markdown).len(),
+            is_empty: markdown.trim().is_empty(),
+        })
+    }
 
-        result
+    /// Compares the in-memory copy of a tracked file with the file on disk, without
+    /// updating it.
+    pub(crate) fn file_status(&self, rel_path: &str) -> Option<FileStatus> {
+        let tracked = self.tracked_files.get(rel_path)?.read();
+        let in_memory_hash = tracked
+            .loaded
+            .then(|| format!("{:x}", tracked.content_hash));
+        let on_disk_hash = fs::read(&tracked.path)
+            .ok()
+            .map(|content| format!("{:x}", md5::compute(content)));
+
+        Some(FileStatus {
+            path: rel_path.to_string(),
+            in_sync: in_memory_hash.is_some() && in_memory_hash == on_disk_hash,
+            in_memory_hash,
+            on_disk_hash,
+            last_refresh: tracked.modified_secs(),
+        })
     }
 
-    fn refresh_file(&mut self, relative_path: &str) -> Result<()> {
-        if let Some(tracked) = self.tracked_files.get_mut(relative_path) {
-            let metadata = fs::metadata(&tracked.path)?;
-            let current_modified = metadata.modified()?;
+    /// The recorded content changes of a tracked file, oldest first, after bringing it
+    /// up to date with disk.
+    pub(crate) fn hash_chain(&self, rel_path: &str) -> Option<Vec<HashChainEntry>> {
+        // A file that can't be read still has its recorded changes
+        let _ = self.refresh_file(rel_path);
+        let tracked = self.tracked_files.get(rel_path)?.read();
+        Some(
+            tracked
+                .hash_chain
+                .iter()
+                .map(|(changed_at, hash)| HashChainEntry {
+                    timestamp: unix_secs(*changed_at),
+                    hash: format!("{hash:x}"),
+                })
+                .collect(),
+        )
+    }
 
-            if current_modified > tracked.last_modified {
-                let content = fs::read_to_string(&tracked.path)?;
-                tracked.html = Self::markdown_to_html(&content)?;
-                tracked.last_modified = current_modified;
-            }
+    /// Every place another loaded file mentions the title (first `# Heading`) of
+    /// `rel_path`, ignoring ASCII case, in path order. Empty if the file has no title.
+    ///
+    /// Titles come from the cached [`DocumentSummary`], which [`Self::update_file`]
+    /// resets, so a changed title is picked up on the next call.
+    pub(crate) fn find_mentions(&self, rel_path: &str) -> Vec<MentionResult> {
+        let Some(title) = self
+            .file_summary(rel_path)
+            .and_then(|summary| summary.title)
+        else {
+            return Vec::new();
+        };
+        if title.is_empty() {
+            return Vec::new();
         }
+        // ASCII lowercasing keeps byte offsets, so matches can be sliced from the original
+        let needle = title.to_ascii_lowercase();
 
-        Ok(())
+        let mut mentions = Vec::new();
+        for file in self.get_sorted_filenames() {
+            if file == rel_path {
+                continue;
+            }
+            let markdown = self.tracked_files[&file].read().markdown.clone();
+            for (start, _) in markdown.to_ascii_lowercase().match_indices(&needle) {
+                mentions.push(MentionResult {
+                    path: file.clone(),
+                    context: mention_context(&markdown, start, start + needle.len()),
+                });
+            }
+        }
+        mentions
     }
 
     fn add_tracked_file(&mut self, file_path: PathBuf) -> Result<()> {
@@ -306,33 +2093,23 @@ impl MarkdownState {
             return Ok(());
         }
 
-        let metadata = fs::metadata(&file_path)?;
-        let content = fs::read_to_string(&file_path)?;
-        let content_hash = md5::compute(&content);
-
-        self.tracked_files.insert(
-            relative_path.clone(),
-            TrackedFile {
-                path: file_path,
-                relative_path,
-                last_modified: metadata.modified()?,
-                html: Self::markdown_to_html(&content)?,
-                content_hash,
-            },
-        );
+        let tracked = self.track_file(file_path, relative_path.clone())?;
+        self.insert_tracked(&relative_path, tracked);
+        self.load_orderings_for(&relative_path);
 
         Ok(())
     }
 
     /// Rescans the base directory and synchronizes tracked_files with the current file system state.
-    /// Returns true if the file list changed (files added or removed).
+    /// Returns true if anything changed (files added, removed, or modified on disk).
     fn rescan_directory(&mut self) -> Result<bool> {
         if !self.is_directory_mode {
             return Ok(false);
         }
 
         // Get current files in directory
-        let current_files = scan_markdown_files(&self.base_dir)?;
+        let current_files =
+            scan_markdown_files_with_options(&self.base_dir, self.config.include_hidden)?.files;
         let current_relative_paths: std::collections::HashSet<String> = current_files
             .iter()
             .filter_map(|p| {
@@ -340,23 +2117,45 @@ impl MarkdownState {
                     canonical
                         .strip_prefix(&self.base_dir)
                         .ok()
-                        .map(|rel| rel.to_string_lossy().to_string())
+                        .map(|rel| normalize_key(&rel.to_string_lossy()))
                 })
             })
+            .filter(|relative_path| !self.is_excluded(relative_path))
             .collect();
 
         // Track relative paths that are currently tracked
         let tracked_relative_paths: std::collections::HashSet<String> =
             self.tracked_files.keys().cloned().collect();
 
+        // Files replaced in place (e.g. a temp file renamed over the original) keep
+        // their path, so pick up their new content as well
+        let mut content_changed = false;
+        for relative_path in current_relative_paths.intersection(&tracked_relative_paths) {
+            let (loaded, previous_hash) = {
+                let tracked = self.tracked_files[relative_path].read();
+                (tracked.loaded, tracked.content_hash)
+            };
+            if !loaded {
+                continue;
+            }
+            if self.refresh_file(relative_path).is_ok()
+                && self.tracked_files[relative_path].read().content_hash != previous_hash
+            {
+                content_changed = true;
+            }
+        }
+
         // Check if there are any differences
         if current_relative_paths == tracked_relative_paths {
-            return Ok(false);
+            self.reload_orderings();
+            return Ok(content_changed);
         }
 
         // Remove files that no longer exist
-        self.tracked_files
-            .retain(|relative_path, _| current_relative_paths.contains(relative_path));
+        for relative_path in tracked_relative_paths.difference(&current_relative_paths) {
+            self.tracked_files.remove(relative_path);
+            self.file_changed(relative_path);
+        }
 
         // Add new files
         for file_path in current_files {
@@ -366,43 +2165,24 @@ impl MarkdownState {
             let Ok(rel_path) = canonical_path.strip_prefix(&self.base_dir) else {
                 continue;
             };
-            let relative_path = rel_path.to_string_lossy().to_string();
+            let relative_path = normalize_key(&rel_path.to_string_lossy());
 
-            if self.tracked_files.contains_key(&relative_path) {
+            if self.tracked_files.contains_key(&relative_path) || self.is_excluded(&relative_path) {
                 continue;
             }
 
             // Try to add new file, ignore errors for individual files
-            let Ok(metadata) = fs::metadata(&file_path) else {
-                continue;
-            };
-            let Ok(content) = fs::read_to_string(&file_path) else {
-                continue;
-            };
-            let Ok(html) = Self::markdown_to_html(&content) else {
-                continue;
-            };
-            let Ok(last_modified) = metadata.modified() else {
+            let Ok(tracked) = self.track_file(file_path, relative_path.clone()) else {
                 continue;
             };
-            let content_hash = md5::compute(&content);
-
-            self.tracked_files.insert(
-                relative_path.clone(),
-                TrackedFile {
-                    path: file_path,
-                    relative_path,
-                    last_modified,
-                    html,
-                    content_hash,
-                },
-            );
+            self.insert_tracked(&relative_path, tracked);
         }
 
+        self.reload_orderings();
         Ok(true)
     }
 
-    fn markdown_to_html(content: &str) -> Result<String> {
+    pub(crate) fn markdown_to_html(content: &str) -> Result<String> {
         let mut options = markdown::Options::gfm();
         options.compile.allow_dangerous_html = true;
         options.parse.constructs.frontmatter = true;
@@ -430,108 +2210,171 @@ async fn handle_markdown_file_change(path: &Path, state: &SharedMarkdownState) {
         return;
     }
 
-    let mut state_guard = state.lock().await;
+    let state_guard = state.read().await;
 
     let Ok(relative_path) = calculate_relative_path(path, &state_guard.base_dir) else {
         return;
     };
 
     // If file is already tracked, refresh its content
-    if state_guard.tracked_files.contains_key(&relative_path) {
-        if state_guard.refresh_file(&relative_path).is_ok() {
-            let _ = state_guard.change_tx.send(ServerMessage::Reload);
+    if let Some(entry) = state_guard.tracked_files.get(&relative_path) {
+        // Files that were never loaded are read fresh on first access anyway
+        let loaded = entry.read().loaded;
+        if !loaded || state_guard.refresh_file(&relative_path).is_ok() {
+            telemetry::record_file_reload();
+            state_guard.change_tx.send(ServerMessage::Reload);
         }
-    } else if state_guard.is_directory_mode {
+    } else if state_guard.is_directory_mode && !state_guard.is_excluded(&relative_path) {
         // New file in directory mode - add and reload
+        drop(state_guard);
+        let mut state_guard = state.write().await;
         if state_guard.add_tracked_file(path.to_path_buf()).is_ok() {
-            let _ = state_guard.change_tx.send(ServerMessage::Reload);
+            telemetry::record_file_reload();
+            state_guard.change_tx.send(ServerMessage::Reload);
         }
     }
 }
 
+/// Most changes a single rescan reports one by one; beyond this they are sent as one
+/// [`ServerMessage::BulkReload`].
+const BULK_CHANGE_THRESHOLD: usize = 2;
+
 enum FileChangeType {
     Renamed { old_name: String, new_name: String },
     Removed { name: String },
+    Added { name: String },
+    Bulk {
+        added: Vec<String>,
+        removed: Vec<String>,
+        modified: Vec<String>,
+    },
     Other,
 }
 
-fn detect_file_change(
+/// Content hashes of the loaded files; files not yet read have no known hash.
+fn content_hashes(tracked_files: &HashMap<String, FileEntry>) -> HashMap<String, md5::Digest> {
+    tracked_files
+        .iter()
+        .filter_map(|(k, v)| {
+            let tracked = v.read();
+            tracked.loaded.then(|| (k.clone(), tracked.content_hash))
+        })
+        .collect()
+}
+
+/// Classifies the file list changes found by a rescan.
+/// A removed and an added file are only paired up as a rename when their content hashes
+/// match; anything left over is reported as separate removals and additions, so an
+/// unrelated deletion and creation in the same rescan never look like a rename.
+fn detect_file_changes(
     old_files: &std::collections::HashSet<String>,
     new_files: &std::collections::HashSet<String>,
-    old_tracked_files: &HashMap<String, md5::Digest>,
-    new_tracked_files: &HashMap<String, TrackedFile>,
-) -> FileChangeType {
-    let added: Vec<_> = new_files.difference(old_files).collect();
-    let removed: Vec<_> = old_files.difference(new_files).collect();
-
-    // Detect rename: exactly one file added and one removed with matching content hashes
-    if let ([new_name], [old_name]) = (added.as_slice(), removed.as_slice()) {
-        // Verify content is the same by comparing hashes
-        if let (Some(old_hash), Some(new_file)) = (
-            old_tracked_files.get(*old_name),
-            new_tracked_files.get(*new_name),
-        ) {
-            if *old_hash == new_file.content_hash {
-                return FileChangeType::Renamed {
-                    old_name: (*old_name).clone(),
-                    new_name: (*new_name).clone(),
-                };
-            }
+    old_hashes: &HashMap<String, md5::Digest>,
+    new_hashes: &HashMap<String, md5::Digest>,
+) -> Vec<FileChangeType> {
+    let mut added: Vec<_> = new_files.difference(old_files).cloned().collect();
+    let mut removed: Vec<_> = old_files.difference(new_files).cloned().collect();
+    let mut modified: Vec<_> = old_files
+        .intersection(new_files)
+        .filter(|name| old_hashes.get(*name) != new_hashes.get(*name))
+        .cloned()
+        .collect();
+    added.sort();
+    removed.sort();
+    modified.sort();
+
+    let mut unmatched_added: Vec<_> = added.iter().collect();
+    let mut changes = Vec::new();
+    for old_name in &removed {
+        let old_hash = old_hashes.get(old_name);
+        let renamed_to = unmatched_added
+            .iter()
+            .position(|new_name| old_hash.is_some() && new_hashes.get(*new_name) == old_hash);
+
+        match renamed_to {
+            Some(index) => changes.push(FileChangeType::Renamed {
+                old_name: old_name.clone(),
+                new_name: unmatched_added.remove(index).clone(),
+            }),
+            None => changes.push(FileChangeType::Removed {
+                name: old_name.clone(),
+            }),
         }
     }
 
-    // Detect removal: at least one file removed
-    if let Some(&first_removed) = removed.first() {
-        return FileChangeType::Removed {
-            name: first_removed.clone(),
-        };
+    changes.extend(
+        unmatched_added
+            .into_iter()
+            .map(|name| FileChangeType::Added { name: name.clone() }),
+    );
+
+    // Content changes only count here; on their own they are reported as `Other`
+    if changes.len() + modified.len() > BULK_CHANGE_THRESHOLD {
+        return vec![FileChangeType::Bulk {
+            added,
+            removed,
+            modified,
+        }];
+    }
+
+    if changes.is_empty() {
+        changes.push(FileChangeType::Other);
     }
 
-    FileChangeType::Other
+    changes
 }
 
-fn send_change_message(
-    change_type: FileChangeType,
-    tx: &broadcast::Sender<ServerMessage>,
-) {
+fn send_change_message(change_type: FileChangeType, tx: &EventBroadcaster) {
     let message = match change_type {
         FileChangeType::Renamed { old_name, new_name } => {
             ServerMessage::FileRenamed { old_name, new_name }
         }
         FileChangeType::Removed { name } => ServerMessage::FileRemoved { name },
+        FileChangeType::Added { name } => ServerMessage::FileAdded { name },
+        FileChangeType::Bulk {
+            added,
+            removed,
+            modified,
+        } => ServerMessage::BulkReload {
+            added,
+            removed,
+            modified,
+        },
         FileChangeType::Other => ServerMessage::Reload,
     };
 
-    let _ = tx.send(message);
+    tx.send(message);
 }
 
 async fn rescan_and_detect_changes(state: &SharedMarkdownState) {
     let (old_files, old_hashes) = {
-        let guard = state.lock().await;
+        let guard = state.read().await;
         let files = guard.tracked_files.keys().cloned().collect();
-        let hashes: HashMap<String, md5::Digest> = guard
-            .tracked_files
-            .iter()
-            .map(|(k, v)| (k.clone(), v.content_hash))
-            .collect();
-        (files, hashes)
+        (files, content_hashes(&guard.tracked_files))
     };
 
-    let mut guard = state.lock().await;
+    let mut guard = state.write().await;
 
-    let Ok(changed) = guard.rescan_directory() else {
-        return;
+    let changed = match guard.rescan_directory() {
+        Ok(changed) => changed,
+        Err(e) => {
+            tracing::trace!("rescan failed: {e}");
+            return;
+        }
     };
 
     if !changed {
+        tracing::trace!("rescan detected no changes");
         return;
     }
 
     let new_files: std::collections::HashSet<String> =
         guard.tracked_files.keys().cloned().collect();
+    let new_hashes = content_hashes(&guard.tracked_files);
 
-    let change_type = detect_file_change(&old_files, &new_files, &old_hashes, &guard.tracked_files);
-    send_change_message(change_type, &guard.change_tx);
+    for change_type in detect_file_changes(&old_files, &new_files, &old_hashes, &new_hashes) {
+        send_change_message(change_type, &guard.change_tx);
+    }
 }
 
 /// Schedules a delayed rescan for directory mode to handle editor save sequences.
@@ -539,7 +2382,9 @@ async fn rescan_and_detect_changes(state: &SharedMarkdownState) {
 fn schedule_delayed_rescan(state: &SharedMarkdownState) {
     let state_clone = state.clone();
     tokio::spawn(async move {
-        tokio::time::sleep(tokio::time::Duration::from_millis(RESCAN_DELAY_MS)).await;
+        let delay = state_clone.read().await.config.rescan_delay;
+        tracing::trace!("rescan scheduled in {delay:?}");
+        tokio::time::sleep(delay).await;
         rescan_and_detect_changes(&state_clone).await;
     });
 }
@@ -551,7 +2396,7 @@ async fn handle_rename_event(
 ) {
     use notify::event::RenameMode;
 
-    let is_dir_mode = state.lock().await.is_directory_mode;
+    let is_dir_mode = state.read().await.is_directory_mode;
     if is_dir_mode {
         schedule_delayed_rescan(state);
         return;
@@ -582,7 +2427,7 @@ async fn handle_md_create_or_modify(path: &Path, state: &SharedMarkdownState) {
 }
 
 async fn handle_md_remove(_path: &Path, state: &SharedMarkdownState) {
-    let is_dir_mode = state.lock().await.is_directory_mode;
+    let is_dir_mode = state.read().await.is_directory_mode;
     if !is_dir_mode {
         return;
     }
@@ -590,44 +2435,203 @@ async fn handle_md_remove(_path: &Path, state: &SharedMarkdownState) {
     schedule_delayed_rescan(state);
 }
 
+/// Picks up a changed, added or removed [`ORDERING_FILE`] and reloads clients, since
+/// the navigation order may have changed.
+async fn handle_ordering_change(path: &Path, state: &SharedMarkdownState) {
+    let mut guard = state.write().await;
+    let Some(directory) = path
+        .parent()
+        .and_then(|dir| calculate_relative_path(dir, &guard.base_dir).ok())
+    else {
+        return;
+    };
+    guard.reload_ordering(&directory);
+    guard.change_tx.send(ServerMessage::Reload);
+}
+
 async fn handle_image_change(state: &SharedMarkdownState) {
-    let guard = state.lock().await;
-    let _ = guard.change_tx.send(ServerMessage::Reload);
+    let guard = state.read().await;
+    telemetry::record_file_reload();
+    guard.change_tx.send(ServerMessage::Reload);
 }
 
 async fn handle_file_event(event: Event, state: &SharedMarkdownState) {
     use notify::EventKind::{Create, Modify, Remove};
-    use notify::event::ModifyKind;
+    use notify::event::{CreateKind, MetadataKind, ModifyKind};
 
     match event.kind {
         Modify(ModifyKind::Name(rename_mode)) => {
             handle_rename_event(rename_mode, &event.paths, state).await;
         }
+        Create(CreateKind::Folder) => {
+            // Files written right after their directory was created can land before the
+            // watcher has picked up the directory, so look for them with a rescan
+            let is_dir_mode = state.read().await.is_directory_mode;
+            if is_dir_mode {
+                schedule_delayed_rescan(state);
+            }
+        }
         _ => {
             for path in &event.paths {
-                if is_markdown_file(path) {
+                if path.file_name() == Some(std::ffi::OsStr::new(ORDERING_FILE)) {
+                    match event.kind {
+                        Create(_) | Modify(_) | Remove(_) => {
+                            handle_ordering_change(path, state).await;
+                        }
+                        _ => {
+                            tracing::trace!(
+                                "ignored {:?} event for {}",
+                                event.kind,
+                                path.display()
+                            );
+                        }
+                    }
+                } else if is_markdown_file(path) {
                     match event.kind {
-                        Create(_) | Modify(ModifyKind::Data(_)) => {
+                        // The poll watcher reports writes as a modification time change
+                        Create(_)
+                        | Modify(ModifyKind::Data(_))
+                        | Modify(ModifyKind::Metadata(MetadataKind::WriteTime)) => {
                             handle_md_create_or_modify(path, state).await;
                         }
                         Remove(_) => {
                             handle_md_remove(path, state).await;
                         }
-                        _ => {}
+                        _ => {
+                            tracing::trace!(
+                                "ignored {:?} event for {}",
+                                event.kind,
+                                path.display()
+                            );
+                        }
                     }
                 } else if path.is_file() && is_image_file(path.to_str().unwrap_or("")) {
                     match event.kind {
                         Modify(_) | Create(_) | Remove(_) => {
                             handle_image_change(state).await;
                         }
-                        _ => {}
+                        _ => {
+                            tracing::trace!(
+                                "ignored {:?} event for {}",
+                                event.kind,
+                                path.display()
+                            );
+                        }
                     }
+                } else {
+                    tracing::trace!(
+                        "ignored event for non-markdown, non-image path {}",
+                        path.display()
+                    );
                 }
             }
         }
     }
 }
 
+/// What the file watcher passes on to the event loop in [`build_router`].
+enum WatcherMessage {
+    Event(Event),
+    /// The watcher lost the base directory and has to be re-created
+    RewatchNeeded,
+}
+
+/// The directory a watcher was registered on, as it was at the time.
+struct WatchedDir {
+    path: PathBuf,
+    identity: Option<(u64, u64)>,
+}
+
+impl WatchedDir {
+    fn new(path: &Path) -> Self {
+        WatchedDir {
+            path: path.to_path_buf(),
+            identity: dir_identity(path),
+        }
+    }
+
+    /// Whether the directory is gone or another one now sits at its path.
+    fn is_lost(&self) -> bool {
+        let identity = dir_identity(&self.path);
+        identity.is_none() || identity != self.identity
+    }
+}
+
+/// Identifies the directory at `path`, to tell when it was replaced; `None` when it's
+/// missing.
+fn dir_identity(path: &Path) -> Option<(u64, u64)> {
+    let metadata = fs::metadata(path).ok().filter(fs::Metadata::is_dir)?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::MetadataExt;
+        Some((metadata.dev(), metadata.ino()))
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = metadata;
+        Some((0, 0))
+    }
+}
+
+/// Forwards a file watcher result to the event loop.
+///
+/// With `recovery`, errors meaning the watch on that directory is gone (it was
+/// unmounted, moved or replaced) ask for the watcher to be re-created, since it would
+/// otherwise silently stop delivering events. Other IO errors, like one file failing to
+/// be read, are only logged.
+fn forward_watcher_result(
+    res: std::result::Result<Event, notify::Error>,
+    recovery: Option<&WatchedDir>,
+    tx: &mpsc::Sender<WatcherMessage>,
+) {
+    match res {
+        Ok(event) => {
+            let _ = tx.blocking_send(WatcherMessage::Event(event));
+        }
+        Err(e) => {
+            tracing::error!("file watcher error: {e}");
+            let lost = recovery.is_some_and(|watched| match e.kind {
+                notify::ErrorKind::WatchNotFound => true,
+                notify::ErrorKind::Io(_) => watched.is_lost(),
+                _ => false,
+            });
+            if lost {
+                let _ = tx.blocking_send(WatcherMessage::RewatchNeeded);
+            }
+        }
+    }
+}
+
+/// Creates the file watcher and starts watching `base_dir` recursively.
+fn create_watcher(
+    base_dir: &Path,
+    poll_interval: Option<Duration>,
+    watcher_config: Config,
+    recovery: bool,
+    tx: mpsc::Sender<WatcherMessage>,
+) -> Result<Box<dyn Watcher + Send>> {
+    let watched = recovery.then(|| WatchedDir::new(base_dir));
+    let event_handler = move |res| forward_watcher_result(res, watched.as_ref(), &tx);
+    let mut watcher: Box<dyn Watcher + Send> = match poll_interval {
+        Some(interval) => {
+            tracing::info!("polling for file changes every {}ms", interval.as_millis());
+            // Modification times are only compared to the second, so without hashing a
+            // save in the same second as the previous poll would be missed
+            Box::new(PollWatcher::new(
+                event_handler,
+                Config::default()
+                    .with_poll_interval(interval)
+                    .with_compare_contents(true),
+            )?)
+        }
+        None => Box::new(RecommendedWatcher::new(event_handler, watcher_config)?),
+    };
+
+    // Watch recursively to detect file changes in subdirectories
+    watcher.watch(base_dir, RecursiveMode::Recursive)?;
+    Ok(watcher)
+}
+
 /// Creates a new Router for serving markdown files.
 ///
 /// # Errors
@@ -642,46 +2646,360 @@ pub fn new_router(
     tracked_files: Vec<PathBuf>,
     is_directory_mode: bool,
 ) -> Result<Router> {
+    new_router_with_config(
+        base_dir,
+        tracked_files,
+        is_directory_mode,
+        RouterConfig::default(),
+    )
+}
+
+/// Creates a new Router for serving markdown files with custom [`RouterConfig`] settings.
+///
+/// # Errors
+///
+/// Same as [`new_router`], and additionally if an exclude pattern is not a valid glob.
+pub fn new_router_with_config(
+    base_dir: PathBuf,
+    tracked_files: Vec<PathBuf>,
+    is_directory_mode: bool,
+    config: RouterConfig,
+) -> Result<Router> {
+    let (router, _state) = build_router(base_dir, tracked_files, is_directory_mode, config)?;
+    Ok(router)
+}
+
+/// Loads the state from the configured snapshot when possible, otherwise from scratch.
+fn initial_state(
+    base_dir: PathBuf,
+    tracked_files: Vec<PathBuf>,
+    is_directory_mode: bool,
+    config: RouterConfig,
+) -> Result<MarkdownState> {
+    let verify = config.verify_on_startup;
+    let state = restored_state(&base_dir, is_directory_mode, &config).map_or_else(
+        || MarkdownState::new(base_dir, tracked_files, is_directory_mode, config),
+        Ok,
+    )?;
+    if verify {
+        state.warn_inconsistencies();
+    }
+    Ok(state)
+}
+
+/// The state saved in the configured snapshot, if there is one that can be restored.
+fn restored_state(
+    base_dir: &Path,
+    is_directory_mode: bool,
+    config: &RouterConfig,
+) -> Option<MarkdownState> {
+    let snapshot_file = config
+        .snapshot_file
+        .as_ref()
+        .filter(|_| is_directory_mode)?;
+    let data = fs::read(snapshot_file).ok()?;
+    match MarkdownState::restore_from_snapshot(&data, base_dir.to_path_buf(), config.clone()) {
+        Ok(state) => Some(state),
+        Err(e) => {
+            tracing::warn!("Ignoring snapshot {}: {e}", snapshot_file.display());
+            None
+        }
+    }
+}
+
+fn build_router(
+    base_dir: PathBuf,
+    tracked_files: Vec<PathBuf>,
+    is_directory_mode: bool,
+    config: RouterConfig,
+) -> Result<(Router, SharedMarkdownState)> {
     let base_dir = base_dir.canonicalize()?;
 
-    let state = Arc::new(Mutex::new(MarkdownState::new(
+    let watcher_config = match config.watcher_debounce_ms {
+        Some(ms) => Config::default()
+            .with_compare_contents(false)
+            .with_poll_interval(Duration::from_millis(ms)),
+        None => Config::default(),
+    };
+
+    let poll_interval = config.poll_interval;
+    let metrics = config.metrics;
+    let watch_recovery = config.watch_recovery;
+    let timeout = config.request_timeout;
+    let max_import_size = config.max_import_size;
+
+    let state = Arc::new(RwLock::new(initial_state(
         base_dir.clone(),
         tracked_files,
         is_directory_mode,
+        config,
     )?));
 
     let watcher_state = state.clone();
     let (tx, mut rx) = mpsc::channel(100);
-
-    let mut watcher = RecommendedWatcher::new(
-        move |res: std::result::Result<Event, notify::Error>| {
-            if let Ok(event) = res {
-                let _ = tx.blocking_send(event);
-            }
-        },
-        Config::default(),
-    )?;
-
-    // Watch recursively to detect file changes in subdirectories
-    watcher.watch(&base_dir, RecursiveMode::Recursive)?;
+    let watch = move || {
+        create_watcher(
+            &base_dir,
+            poll_interval,
+            watcher_config,
+            watch_recovery,
+            tx.clone(),
+        )
+    };
+    let mut watcher = watch()?;
 
     tokio::spawn(async move {
-        let _watcher = watcher;
-        while let Some(event) = rx.recv().await {
-            handle_file_event(event, &watcher_state).await;
+        while let Some(message) = rx.recv().await {
+            match message {
+                WatcherMessage::Event(event) => {
+                    tracing::trace!("fs event: kind={:?} paths={:?}", event.kind, event.paths);
+                    handle_file_event(event, &watcher_state).await;
+                }
+                WatcherMessage::RewatchNeeded => {
+                    drop(watcher);
+                    watcher = loop {
+                        match watch() {
+                            Ok(watcher) => break watcher,
+                            Err(e) => {
+                                tracing::error!(
+                                    "failed to re-register file watcher, retrying in {WATCH_RECOVERY_RETRY:?}: {e}"
+                                );
+                                tokio::time::sleep(WATCH_RECOVERY_RETRY).await;
+                            }
+                        }
+                    };
+                    tracing::info!("file watcher re-registered");
+                    // Changes made while nothing was watching never produced events
+                    if watcher_state.read().await.is_directory_mode {
+                        schedule_delayed_rescan(&watcher_state);
+                    }
+                }
+            }
         }
     });
 
-    let router = Router::new()
+    let mut router = Router::new()
         .route("/", get(serve_html_root))
         .route("/ws", get(websocket_handler))
         .route("/__health", get(server_health))
         .route("/mermaid.min.js", get(serve_mermaid_js))
-        .route("/*path", get(serve_file))
-        .layer(CorsLayer::permissive())
-        .with_state(state);
+        .merge(api::routes(max_import_size))
+        .route("/*path", get(serve_file));
+    if metrics {
+        let prometheus = telemetry::prometheus();
+        tokio::spawn(async move {
+            // Drains recorded histogram samples even when nobody scrapes the endpoint
+            let mut interval = tokio::time::interval(Duration::from_secs(5));
+            loop {
+                interval.tick().await;
+                prometheus.run_upkeep();
+            }
+        });
+        router = router
+            .route("/__metrics", get(serve_metrics))
+            .route_layer(axum::middleware::from_fn(telemetry::track_requests));
+    }
 
-    Ok(router)
+    let router = router
+        .layer(axum::middleware::from_fn_with_state(
+            timeout,
+            request_timeout,
+        ))
+        .layer(axum::middleware::from_fn(cors))
+        .layer(request_trace_layer())
+        .layer(axum::middleware::from_fn(request_id))
+        .with_state(state.clone());
+    // Route layers run before the router adds the `Allow` header to `405` responses, so
+    // that header can only be read from out here
+    let router = Router::new()
+        .fallback_service(router)
+        .layer(axum::middleware::from_fn(allowed_methods));
+
+    Ok((router, state))
+}
+
+/// Allows cross-origin requests from anywhere for every method the routes support.
+///
+/// `CorsLayer` answers every `OPTIONS` request as a pre-flight, so a plain `OPTIONS`
+/// (without `Access-Control-Request-Method`) bypasses it, to be answered by
+/// [`allowed_methods`] instead.
+async fn cors(
+    request: axum::extract::Request,
+    next: axum::middleware::Next,
+) -> axum::response::Response {
+    use axum::http::Method;
+    use tower::{Layer, ServiceExt};
+
+    let is_plain_options = request.method() == Method::OPTIONS
+        && !request
+            .headers()
+            .contains_key(header::ACCESS_CONTROL_REQUEST_METHOD);
+    if is_plain_options {
+        return next.run(request).await;
+    }
+
+    let layer = CorsLayer::new()
+        .allow_origin(cors::Any)
+        .allow_headers(cors::Any)
+        .expose_headers(cors::Any)
+        .allow_methods([
+            Method::GET,
+            Method::HEAD,
+            Method::POST,
+            Method::PUT,
+            Method::PATCH,
+            Method::DELETE,
+            Method::OPTIONS,
+        ]);
+    match layer.layer(next).oneshot(request).await {
+        Ok(response) => response.map(axum::body::Body::new),
+        Err(infallible) => match infallible {},
+    }
+}
+
+/// Answers a plain `OPTIONS` request (see [`cors`]) with the methods its route accepts,
+/// plus `OPTIONS`, in the `Allow` header. They are taken from the `405 Method Not
+/// Allowed` the router answers it with, which lists the route's handlers, so they can't
+/// drift apart. Other responses, like `404` for unknown paths, are passed on.
+async fn allowed_methods(
+    request: axum::extract::Request,
+    next: axum::middleware::Next,
+) -> axum::response::Response {
+    let is_plain_options = request.method() == axum::http::Method::OPTIONS
+        && !request
+            .headers()
+            .contains_key(header::ACCESS_CONTROL_REQUEST_METHOD);
+    let mut response = next.run(request).await;
+    if !is_plain_options || response.status() != StatusCode::METHOD_NOT_ALLOWED {
+        return response;
+    }
+    let Some(allow) = response
+        .headers()
+        .get(header::ALLOW)
+        .and_then(|allow| allow.to_str().ok())
+    else {
+        return response;
+    };
+
+    let methods: Vec<&str> = allow
+        .split(',')
+        .map(str::trim)
+        .filter(|method| !method.is_empty())
+        .chain(["OPTIONS"])
+        .collect();
+    if let Ok(allow) = header::HeaderValue::from_str(&methods.join(", ")) {
+        *response.status_mut() = StatusCode::NO_CONTENT;
+        response.headers_mut().insert(header::ALLOW, allow);
+    }
+    response
+}
+
+/// Answers `504 Gateway Timeout` when a request takes longer than `timeout`, e.g. on a
+/// stalled network file system.
+///
+/// WebSocket upgrades are let through: the handshake is quick and the connection that
+/// follows is meant to stay open.
+async fn request_timeout(
+    State(timeout): State<Duration>,
+    request: axum::extract::Request,
+    next: axum::middleware::Next,
+) -> axum::response::Response {
+    if request.headers().contains_key(header::UPGRADE) {
+        return next.run(request).await;
+    }
+
+    match tokio::time::timeout(timeout, next.run(request)).await {
+        Ok(response) => response,
+        Err(_) => (
+            StatusCode::GATEWAY_TIMEOUT,
+            Json(serde_json::json!({ "error": "request timeout" })),
+        )
+            .into_response(),
+    }
+}
+
+/// Makes sure every request has an `X-Request-ID` and echoes it in the response.
+///
+/// A client-supplied ID is kept, cut to [`MAX_REQUEST_ID_LEN`] bytes; otherwise a UUID
+/// is generated. Runs before the trace layer, which records the ID in the request span.
+async fn request_id(
+    mut request: axum::extract::Request,
+    next: axum::middleware::Next,
+) -> axum::response::Response {
+    // `to_str` only accepts visible ASCII, so the ID can't break up a log line
+    let id = request
+        .headers()
+        .get(REQUEST_ID_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .map(|id| id[..id.len().min(MAX_REQUEST_ID_LEN)].to_string())
+        .filter(|id| !id.is_empty())
+        .unwrap_or_else(|| uuid::Uuid::new_v4().to_string());
+    let value = header::HeaderValue::from_str(&id).expect("request ID is visible ASCII");
+    request
+        .headers_mut()
+        .insert(REQUEST_ID_HEADER, value.clone());
+
+    let mut response = next.run(request).await;
+    response.headers_mut().insert(REQUEST_ID_HEADER, value);
+    response
+}
+
+/// Logs every request in a span with its ID, method, path, status and duration.
+/// Successful responses are logged at DEBUG, client errors at WARN and server errors
+/// at ERROR.
+fn request_trace_layer() -> TraceLayer<
+    SharedClassifier<ServerErrorsAsFailures>,
+    impl MakeSpan<axum::body::Body> + Clone,
+    impl OnRequest<axum::body::Body> + Clone,
+    impl OnResponse<axum::body::Body> + Clone,
+    (),
+    (),
+    (),
+> {
+    TraceLayer::new_for_http()
+        .make_span_with(|request: &axum::http::Request<axum::body::Body>| {
+            // WARN so the span, and with it the method and path, is enabled wherever
+            // the warnings and errors logged inside it are
+            let request_id = request
+                .headers()
+                .get(REQUEST_ID_HEADER)
+                .and_then(|value| value.to_str().ok())
+                .unwrap_or_default();
+            tracing::warn_span!(
+                "request",
+                request_id = %request_id,
+                http.method = %request.method(),
+                http.path = %request.uri().path(),
+                http.status_code = tracing::field::Empty,
+                duration_ms = tracing::field::Empty,
+            )
+        })
+        .on_request(
+            |_: &axum::http::Request<axum::body::Body>, _: &tracing::Span| {
+                tracing::trace!("started processing request");
+            },
+        )
+        .on_response(
+            |response: &axum::http::Response<axum::body::Body>,
+             latency: Duration,
+             span: &tracing::Span| {
+                let status = response.status();
+                span.record("http.status_code", status.as_u16());
+                span.record("duration_ms", latency.as_millis() as u64);
+
+                if status.is_server_error() {
+                    tracing::error!("request failed");
+                } else if status.is_client_error() {
+                    tracing::warn!("request rejected");
+                } else {
+                    tracing::debug!("request finished");
+                }
+            },
+        )
+        // Server errors are already logged by `on_response`
+        .on_body_chunk(())
+        .on_eos(())
+        .on_failure(())
 }
 
 /// Serves markdown files with live reload support.
@@ -699,11 +3017,13 @@ pub async fn serve_markdown(
     is_directory_mode: bool,
     hostname: impl AsRef<str>,
     port: u16,
+    config: RouterConfig,
 ) -> Result<()> {
     let hostname = hostname.as_ref();
 
     let first_file = tracked_files.first().cloned();
-    let router = new_router(base_dir.clone(), tracked_files, is_directory_mode)?;
+    let snapshot_file = config.snapshot_file.clone().filter(|_| is_directory_mode);
+    let (router, state) = build_router(base_dir.clone(), tracked_files, is_directory_mode, config)?;
 
     let listener = TcpListener::bind((hostname, port)).await?;
 
@@ -719,11 +3039,47 @@ pub async fn serve_markdown(
     println!("⚡ Live reload enabled");
     println!("\nPress Ctrl+C to stop the server");
 
-    axum::serve(listener, router).await?;
+    axum::serve(listener, router)
+        .with_graceful_shutdown(shutdown_signal())
+        .await?;
+
+    if let Some(snapshot_file) = snapshot_file {
+        let snapshot = state.read().await.serialize_snapshot();
+        if let Err(e) = fs::write(&snapshot_file, snapshot) {
+            eprintln!(
+                "⚠️  Failed to write snapshot {}: {e}",
+                snapshot_file.display()
+            );
+        }
+    }
 
     Ok(())
 }
 
+/// Resolves on Ctrl+C, or SIGTERM on Unix.
+async fn shutdown_signal() {
+    let ctrl_c = async {
+        let _ = tokio::signal::ctrl_c().await;
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate()) {
+            Ok(mut signal) => {
+                signal.recv().await;
+            }
+            Err(_) => std::future::pending::<()>().await,
+        }
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => {},
+        _ = terminate => {},
+    }
+}
+
 /// Format the host address (hostname + port) for printing.
 fn format_host(hostname: &str, port: u16) -> String {
     if hostname.parse::<Ipv6Addr>().is_ok() {
@@ -734,7 +3090,7 @@ fn format_host(hostname: &str, port: u16) -> String {
 }
 
 async fn serve_html_root(State(state): State<SharedMarkdownState>) -> impl IntoResponse {
-    let mut state = state.lock().await;
+    let state = state.read().await;
 
     let relative_path = match state.get_sorted_filenames().into_iter().next() {
         Some(name) => name,
@@ -759,15 +3115,16 @@ async fn serve_file(
     let relative_path = path.strip_prefix('/').unwrap_or(&path);
 
     if relative_path.ends_with(".md") || relative_path.ends_with(".markdown") {
-        let mut state = state.lock().await;
+        let state = state.read().await;
+        let key = normalize_key(relative_path);
 
-        if !state.tracked_files.contains_key(relative_path) {
+        if !state.tracked_files.contains_key(&key) {
             return (StatusCode::NOT_FOUND, Html("File not found".to_string())).into_response();
         }
 
-        let _ = state.refresh_file(relative_path);
+        let _ = state.refresh_file(&key);
 
-        let (status, html) = render_markdown(&state, relative_path).await;
+        let (status, html) = render_markdown(&state, &key).await;
         (status, html).into_response()
     } else if is_image_file(relative_path) {
         serve_static_file_inner(relative_path.to_string(), state).await
@@ -788,10 +3145,10 @@ async fn render_markdown(state: &MarkdownState, current_file: &str) -> (StatusCo
         }
     };
 
-    let (content, has_mermaid) = if let Some(tracked) = state.tracked_files.get(current_file) {
-        let html = &tracked.html;
+    let (content, has_mermaid) = if let Some(entry) = state.tracked_files.get(current_file) {
+        let html = entry.read().html.clone();
         let mermaid = html.contains(r#"class="language-mermaid""#);
-        (Value::from_safe_string(html.clone()), mermaid)
+        (Value::from_safe_string(html), mermaid)
     } else {
         return (StatusCode::NOT_FOUND, Html("File not found".to_string()));
     };
@@ -842,8 +3199,35 @@ async fn serve_mermaid_js(headers: HeaderMap) -> impl IntoResponse {
     mermaid_response(StatusCode::OK, Some(MERMAID_JS))
 }
 
-async fn server_health() -> impl IntoResponse {
-    (StatusCode::OK, "ready")
+#[derive(Debug, Serialize)]
+struct HealthResponse {
+    status: &'static str,
+    file_count: usize,
+    uptime_seconds: u64,
+    version: &'static str,
+}
+
+/// Reports readiness with a few basics, e.g. a `file_count` of 0 reveals a server
+/// started on an empty directory.
+async fn server_health(State(state): State<SharedMarkdownState>) -> Json<HealthResponse> {
+    let state = state.read().await;
+
+    Json(HealthResponse {
+        status: "ready",
+        file_count: state.tracked_files.len(),
+        uptime_seconds: state.started_at.elapsed().as_secs(),
+        version: env!("CARGO_PKG_VERSION"),
+    })
+}
+
+async fn serve_metrics(State(state): State<SharedMarkdownState>) -> impl IntoResponse {
+    let state = state.read().await;
+    telemetry::set_tracked_files(state.tracked_files.len());
+    telemetry::set_websocket_connections(state.ws_connections.load(Ordering::SeqCst));
+    (
+        [(header::CONTENT_TYPE, "text/plain; version=0.0.4")],
+        telemetry::prometheus().render(),
+    )
 }
 
 fn is_etag_match(headers: &HeaderMap) -> bool {
@@ -873,7 +3257,7 @@ async fn serve_static_file_inner(
     filename: String,
     state: SharedMarkdownState,
 ) -> axum::response::Response {
-    let state = state.lock().await;
+    let state = state.read().await;
 
     let full_path = state.base_dir.join(&filename);
 
@@ -915,7 +3299,7 @@ async fn serve_static_file_inner(
     }
 }
 
-fn is_image_file(file_path: &str) -> bool {
+pub(crate) fn is_image_file(file_path: &str) -> bool {
     let extension = std::path::Path::new(file_path)
         .extension()
         .and_then(|ext| ext.to_str())
@@ -923,11 +3307,22 @@ fn is_image_file(file_path: &str) -> bool {
 
     matches!(
         extension.to_lowercase().as_str(),
-        "png" | "jpg" | "jpeg" | "gif" | "svg" | "webp" | "bmp" | "ico"
+        "png"
+            | "jpg"
+            | "jpeg"
+            | "gif"
+            | "svg"
+            | "webp"
+            | "bmp"
+            | "ico"
+            | "avif"
+            | "tiff"
+            | "tif"
+            | "heic"
     )
 }
 
-fn guess_image_content_type(file_path: &str) -> String {
+pub(crate) fn guess_image_content_type(file_path: &str) -> String {
     let extension = std::path::Path::new(file_path)
         .extension()
         .and_then(|ext| ext.to_str())
@@ -941,6 +3336,9 @@ fn guess_image_content_type(file_path: &str) -> String {
         "webp" => "image/webp",
         "bmp" => "image/bmp",
         "ico" => "image/x-icon",
+        "avif" => "image/avif",
+        "tiff" | "tif" => "image/tiff",
+        "heic" => "image/heic",
         _ => "application/octet-stream",
     }
     .to_string()
@@ -949,25 +3347,100 @@ fn guess_image_content_type(file_path: &str) -> String {
 async fn websocket_handler(
     ws: WebSocketUpgrade,
     State(state): State<SharedMarkdownState>,
-) -> impl IntoResponse {
-    ws.on_upgrade(move |socket| handle_websocket(socket, state))
+) -> axum::response::Response {
+    let connection = {
+        let state = state.read().await;
+        let limit = state.config.max_ws_connections;
+        match ConnectionGuard::acquire(&state.ws_connections, limit) {
+            Some(connection) => connection,
+            None => {
+                tracing::warn!("refusing WebSocket connection: {limit} already open");
+                return (
+                    StatusCode::SERVICE_UNAVAILABLE,
+                    "Too many WebSocket connections",
+                )
+                    .into_response();
+            }
+        }
+    };
+
+    // The slot is held until the connection closes, or freed right away if the
+    // upgrade fails and the callback is dropped
+    ws.on_upgrade(move |socket| handle_websocket(socket, state, connection))
 }
 
-async fn handle_websocket(socket: WebSocket, state: SharedMarkdownState) {
+async fn handle_websocket(
+    socket: WebSocket,
+    state: SharedMarkdownState,
+    _connection: ConnectionGuard,
+) {
     let (mut sender, mut receiver) = socket.split();
 
-    let mut change_rx = {
-        let state = state.lock().await;
-        state.change_tx.subscribe()
+    let (mut change_rx, connected_seq, sessions) = {
+        let state = state.read().await;
+        let (change_rx, last_seq) = state.change_tx.subscribe();
+        (change_rx, last_seq, state.sessions.clone())
     };
+    let client_id = lock_sessions(&sessions).register(connected_seq);
+
+    let connected = ServerMessage::Connected {
+        client_id,
+        server_time: SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .map_or(0, |elapsed| elapsed.as_millis() as u64),
+        last_seq: connected_seq,
+    };
+    if let Ok(json) = serde_json::to_string(&connected) {
+        if sender.send(Message::Text(json)).await.is_err() {
+            return;
+        }
+    }
+
+    // Replies to individual client requests are serialized and funneled to the send
+    // task, which owns the socket sender.
+    let (reply_tx, mut reply_rx) = mpsc::channel::<String>(16);
+    let recv_sessions = sessions.clone();
 
     let recv_task = tokio::spawn(async move {
         while let Some(msg) = receiver.next().await {
             match msg {
                 Ok(Message::Text(text)) => {
-                    if let Ok(client_msg) = serde_json::from_str::<ClientMessage>(&text) {
-                        match client_msg {
-                            ClientMessage::Ping | ClientMessage::RequestRefresh => {}
+                    let Ok(client_msg) = serde_json::from_str::<ClientMessage>(&text) else {
+                        continue;
+                    };
+                    let replies = match client_msg {
+                        ClientMessage::Ping | ClientMessage::RequestRefresh => Vec::new(),
+                        ClientMessage::RequestFileList => {
+                            let files = state.read().await.get_sorted_filenames();
+                            vec![serde_json::to_string(&ServerMessage::FileList { files })]
+                        }
+                        ClientMessage::Reconnect {
+                            client_id: previous_id,
+                            last_seq,
+                        } => {
+                            // Events after `connected_seq` reach this connection anyway
+                            let known = lock_sessions(&recv_sessions).resume(previous_id);
+                            let missed = if known {
+                                let state = state.read().await;
+                                state.change_tx.replay(last_seq, connected_seq)
+                            } else {
+                                None
+                            };
+                            match missed {
+                                Some(events) => {
+                                    if let Some(last) = events.last() {
+                                        lock_sessions(&recv_sessions).record(client_id, last.seq);
+                                    }
+                                    events.iter().map(serde_json::to_string).collect()
+                                }
+                                // Unknown session or too much missed: start over
+                                None => vec![serde_json::to_string(&ServerMessage::Reload)],
+                            }
+                        }
+                    };
+                    for json in replies.into_iter().flatten() {
+                        if reply_tx.send(json).await.is_err() {
+                            return;
                         }
                     }
                 }
@@ -978,11 +3451,25 @@ async fn handle_websocket(socket: WebSocket, state: SharedMarkdownState) {
     });
 
     let send_task = tokio::spawn(async move {
-        while let Ok(reload_msg) = change_rx.recv().await {
-            if let Ok(json) = serde_json::to_string(&reload_msg) {
-                if sender.send(Message::Text(json)).await.is_err() {
-                    break;
-                }
+        loop {
+            let json = tokio::select! {
+                broadcast = change_rx.recv() => match broadcast {
+                    Ok(event) => {
+                        lock_sessions(&sessions).record(client_id, event.seq);
+                        match serde_json::to_string(&event) {
+                            Ok(json) => json,
+                            Err(_) => continue,
+                        }
+                    }
+                    Err(_) => break,
+                },
+                reply = reply_rx.recv() => match reply {
+                    Some(json) => json,
+                    None => break,
+                },
+            };
+            if sender.send(Message::Text(json)).await.is_err() {
+                break;
             }
         }
     });
@@ -993,6 +3480,20 @@ async fn handle_websocket(socket: WebSocket, state: SharedMarkdownState) {
     }
 }
 
+fn lock_index(index: &std::sync::Mutex<FileIndex>) -> std::sync::MutexGuard<'_, FileIndex> {
+    index
+        .lock()
+        .unwrap_or_else(std::sync::PoisonError::into_inner)
+}
+
+fn lock_sessions(
+    sessions: &std::sync::Mutex<SessionRegistry>,
+) -> std::sync::MutexGuard<'_, SessionRegistry> {
+    sessions
+        .lock()
+        .unwrap_or_else(std::sync::PoisonError::into_inner)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -1029,6 +3530,10 @@ mod tests {
         assert!(is_image_file("test.webp"));
         assert!(is_image_file("test.bmp"));
         assert!(is_image_file("test.ico"));
+        assert!(is_image_file("test.avif"));
+        assert!(is_image_file("test.tiff"));
+        assert!(is_image_file("test.tif"));
+        assert!(is_image_file("test.heic"));
 
         assert!(is_image_file("test.PNG"));
         assert!(is_image_file("test.JPG"));
@@ -1053,6 +3558,10 @@ mod tests {
         assert_eq!(guess_image_content_type("test.webp"), "image/webp");
         assert_eq!(guess_image_content_type("test.bmp"), "image/bmp");
         assert_eq!(guess_image_content_type("test.ico"), "image/x-icon");
+        assert_eq!(guess_image_content_type("test.avif"), "image/avif");
+        assert_eq!(guess_image_content_type("test.tiff"), "image/tiff");
+        assert_eq!(guess_image_content_type("test.TIF"), "image/tiff");
+        assert_eq!(guess_image_content_type("test.heic"), "image/heic");
 
         assert_eq!(guess_image_content_type("test.PNG"), "image/png");
         assert_eq!(guess_image_content_type("test.JPG"), "image/jpeg");
@@ -1068,7 +3577,9 @@ mod tests {
     fn test_scan_markdown_files_empty_directory() {
         let temp_dir = tempdir().expect("Failed to create temp dir");
 
-        let result = scan_markdown_files(temp_dir.path()).expect("Failed to scan");
+        let result = scan_markdown_files(temp_dir.path())
+            .expect("Failed to scan")
+            .files;
         assert_eq!(result.len(), 0);
     }
 
@@ -1083,7 +3594,9 @@ mod tests {
         fs::write(temp_dir.path().join("test.txt"), "text").expect("Failed to write");
         fs::write(temp_dir.path().join("README"), "readme").expect("Failed to write");
 
-        let result = scan_markdown_files(temp_dir.path()).expect("Failed to scan");
+        let result = scan_markdown_files(temp_dir.path())
+            .expect("Failed to scan")
+            .files;
 
         assert_eq!(result.len(), 3);
 
@@ -1104,7 +3617,9 @@ mod tests {
         fs::create_dir(&sub_dir).expect("Failed to create subdir");
         fs::write(sub_dir.join("nested.md"), "# Nested").expect("Failed to write");
 
-        let result = scan_markdown_files(temp_dir.path()).expect("Failed to scan");
+        let result = scan_markdown_files(temp_dir.path())
+            .expect("Failed to scan")
+            .files;
 
         assert_eq!(result.len(), 2);
         let filenames: Vec<_> = result
@@ -1135,7 +3650,9 @@ mod tests {
         fs::create_dir(&folder3).expect("Failed to create folder3");
         fs::write(folder3.join("file3.md"), "# File 3").expect("Failed to write");
 
-        let result = scan_markdown_files(temp_dir.path()).expect("Failed to scan");
+        let result = scan_markdown_files(temp_dir.path())
+            .expect("Failed to scan")
+            .files;
 
         assert_eq!(result.len(), 4);
     }
@@ -1155,12 +3672,422 @@ mod tests {
         fs::create_dir(&non_md_folder).expect("Failed to create non_md folder");
         fs::write(non_md_folder.join("file.txt"), "Text file").expect("Failed to write");
 
-        let result = scan_markdown_files(temp_dir.path()).expect("Failed to scan");
+        let result = scan_markdown_files(temp_dir.path())
+            .expect("Failed to scan")
+            .files;
 
         assert_eq!(result.len(), 1);
         assert_eq!(result[0].file_name().unwrap().to_str().unwrap(), "root.md");
     }
 
+    #[tokio::test]
+    async fn test_request_timeout() {
+        use tower::ServiceExt;
+
+        let router = Router::new()
+            .route(
+                "/slow",
+                get(|| async {
+                    tokio::time::sleep(Duration::from_secs(5)).await;
+                    "done"
+                }),
+            )
+            .route("/fast", get(|| async { "done" }))
+            .layer(axum::middleware::from_fn_with_state(
+                Duration::from_millis(50),
+                request_timeout,
+            ));
+        let request = |path: &str, upgrade: bool| {
+            let mut builder = axum::http::Request::get(path);
+            if upgrade {
+                builder = builder.header(header::UPGRADE, "websocket");
+            }
+            builder.body(axum::body::Body::empty()).unwrap()
+        };
+
+        let response = router
+            .clone()
+            .oneshot(request("/slow", false))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::GATEWAY_TIMEOUT);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        assert_eq!(&body[..], br#"{"error":"request timeout"}"#);
+
+        let response = router
+            .clone()
+            .oneshot(request("/fast", false))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        // Upgrades aren't timed, so this one outlives the timeout
+        let upgrade = tokio::time::timeout(
+            Duration::from_millis(200),
+            router.oneshot(request("/slow", true)),
+        )
+        .await;
+        assert!(upgrade.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_markdown_state_canonicalizes_base_dir() {
+        let temp_dir = tempdir().expect("Failed to create temp dir");
+        let docs = temp_dir.path().join("docs");
+        fs::create_dir(&docs).expect("Failed to create docs");
+        fs::write(docs.join("index.md"), "# Docs").expect("Failed to write");
+        fs::write(docs.join("image.png"), b"png").expect("Failed to write");
+        fs::write(temp_dir.path().join("secret.png"), b"secret").expect("Failed to write");
+
+        // `docs/../docs` is the same directory, spelled with a `..` component
+        let base_dir = docs.join("..").join("docs");
+        let state = MarkdownState::new(
+            base_dir,
+            vec![docs.join("index.md")],
+            true,
+            RouterConfig::default(),
+        )
+        .expect("Failed to create state");
+        assert_eq!(state.base_dir, docs.canonicalize().unwrap());
+        let state = Arc::new(RwLock::new(state));
+
+        let response = serve_static_file_inner("image.png".to_string(), state.clone()).await;
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let response = serve_static_file_inner("../secret.png".to_string(), state).await;
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_check_consistency_reports_files_moved_outside_base_dir() {
+        let temp_dir = tempdir().expect("Failed to create temp dir");
+        let docs = temp_dir.path().join("docs");
+        fs::create_dir(&docs).expect("Failed to create docs");
+        fs::write(docs.join("index.md"), "# Docs").expect("Failed to write");
+        fs::write(docs.join("linked.md"), "# Linked").expect("Failed to write");
+        let state = MarkdownState::new(
+            docs.clone(),
+            vec![docs.join("index.md"), docs.join("linked.md")],
+            true,
+            RouterConfig::default(),
+        )
+        .expect("Failed to create state");
+        assert!(state.check_consistency().is_consistent());
+
+        // Replaced by a link to a file with the same content, outside the tree
+        fs::write(temp_dir.path().join("outside.md"), "# Linked").expect("Failed to write");
+        fs::remove_file(docs.join("linked.md")).expect("Failed to remove");
+        std::os::unix::fs::symlink(temp_dir.path().join("outside.md"), docs.join("linked.md"))
+            .expect("Failed to create symlink");
+        assert_eq!(
+            state.check_consistency(),
+            ConsistencyReport {
+                out_of_tree: vec!["linked.md".to_string()],
+                ..ConsistencyReport::default()
+            }
+        );
+    }
+
+    #[test]
+    fn test_broken_links_are_cached_until_files_change() {
+        let temp_dir = tempdir().expect("Failed to create temp dir");
+        let guide = temp_dir.path().join("guide.md");
+        fs::write(&guide, "[Setup](setup.md)").expect("Failed to write");
+        let mut state = MarkdownState::new(
+            temp_dir.path().to_path_buf(),
+            vec![guide],
+            true,
+            RouterConfig::default(),
+        )
+        .expect("Failed to create state");
+
+        let broken = state.broken_links();
+        assert_eq!(broken.len(), 1);
+        assert!(Arc::ptr_eq(&broken, &state.broken_links()));
+        assert_eq!(state.orphans().len(), 1);
+
+        state
+            .create_file("setup.md", "[Guide](guide.md)")
+            .expect("Failed to create file");
+        assert!(state.broken_links().is_empty());
+        assert!(state.orphans().is_empty());
+
+        state
+            .update_file("setup.md", "# Setup")
+            .expect("Failed to update file");
+        assert_eq!(state.orphans().len(), 1);
+
+        state
+            .delete_file("setup.md")
+            .expect("Failed to delete file");
+        assert_eq!(state.broken_links().len(), 1);
+    }
+
+    #[test]
+    fn test_tag_cloud_follows_file_changes() {
+        let temp_dir = tempdir().expect("Failed to create temp dir");
+        let paths: Vec<PathBuf> = ["a.md", "b.md"]
+            .iter()
+            .map(|name| {
+                let path = temp_dir.path().join(name);
+                fs::write(&path, "---\ntags: [rust]\n---\n").expect("Failed to write");
+                path
+            })
+            .collect();
+        let config = RouterConfig {
+            lazy: true,
+            ..RouterConfig::default()
+        };
+        let mut state = MarkdownState::new(temp_dir.path().to_path_buf(), paths, true, config)
+            .expect("Failed to create state");
+        let cloud = |state: &MarkdownState| -> Vec<(String, Vec<String>)> {
+            state
+                .tag_cloud(1, true)
+                .into_iter()
+                .map(|tag| (tag.tag, tag.files.unwrap_or_default()))
+                .collect()
+        };
+        let files = |names: &[&str]| names.iter().map(|name| name.to_string()).collect();
+
+        assert_eq!(
+            cloud(&state),
+            [("rust".to_string(), files(&["a.md", "b.md"]))]
+        );
+
+        state
+            .update_file("b.md", "---\ntags: [web]\n---\n")
+            .expect("Failed to update file");
+        state
+            .rename_file("a.md", "c.md")
+            .expect("Failed to rename file");
+        assert_eq!(
+            cloud(&state),
+            [
+                ("rust".to_string(), files(&["c.md"])),
+                ("web".to_string(), files(&["b.md"])),
+            ]
+        );
+
+        state.delete_file("c.md").expect("Failed to delete file");
+        assert_eq!(cloud(&state), [("web".to_string(), files(&["b.md"]))]);
+    }
+
+    #[test]
+    fn test_title_index_follows_file_changes() {
+        let temp_dir = tempdir().expect("Failed to create temp dir");
+        let intro = temp_dir.path().join("intro.md");
+        fs::write(&intro, "# Introduction").expect("Failed to write");
+        let mut state = MarkdownState::new(
+            temp_dir.path().to_path_buf(),
+            vec![intro],
+            true,
+            RouterConfig::default(),
+        )
+        .expect("Failed to create state");
+        let titles = |state: &MarkdownState| -> Vec<(String, Vec<String>)> {
+            state
+                .files_by_title()
+                .iter()
+                .map(|(title, files)| (title.clone(), files.iter().cloned().collect()))
+                .collect()
+        };
+
+        state
+            .create_file("guide/intro.md", "# Introduction")
+            .expect("Failed to create file");
+        state
+            .create_file("notes.md", "No heading")
+            .expect("Failed to create file");
+        assert_eq!(
+            titles(&state),
+            [(
+                "Introduction".to_string(),
+                vec!["guide/intro.md".to_string(), "intro.md".to_string()]
+            )]
+        );
+
+        state
+            .update_file("intro.md", "# Overview")
+            .expect("Failed to update file");
+        state
+            .delete_file("guide/intro.md")
+            .expect("Failed to delete file");
+        assert_eq!(
+            titles(&state),
+            [("Overview".to_string(), vec!["intro.md".to_string()])]
+        );
+    }
+
+    fn create_ordered_state(
+        files: &[&str],
+        orderings: &[(&str, &str)],
+    ) -> (MarkdownState, tempfile::TempDir) {
+        let temp_dir = tempdir().expect("Failed to create temp dir");
+        let base_dir = temp_dir.path().to_path_buf();
+        for file in files {
+            let path = base_dir.join(file);
+            fs::create_dir_all(path.parent().unwrap()).expect("Failed to create dir");
+            fs::write(&path, "# Doc").expect("Failed to write");
+        }
+        for (directory, listing) in orderings {
+            fs::write(base_dir.join(directory).join(ORDERING_FILE), listing)
+                .expect("Failed to write ordering");
+        }
+        let tracked = files.iter().map(|file| base_dir.join(file)).collect();
+        let state = MarkdownState::new(base_dir, tracked, true, RouterConfig::default())
+            .expect("Failed to create state");
+        (state, temp_dir)
+    }
+
+    #[test]
+    fn test_sorted_filenames_follow_partial_ordering() {
+        let (state, _temp_dir) = create_ordered_state(
+            &["a.md", "b.md", "c.md", "d.md", "setup.md"],
+            &[("", "setup.md\n\n  c.md  \nmissing.md\n")],
+        );
+
+        assert_eq!(
+            state.get_sorted_filenames(),
+            ["setup.md", "c.md", "a.md", "b.md", "d.md"]
+        );
+        assert_eq!(
+            state.neighbors("a.md"),
+            (Some("c.md".to_string()), Some("b.md".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_sorted_filenames_use_ordering_of_each_directory() {
+        let (state, _temp_dir) = create_ordered_state(
+            &[
+                "README.md",
+                "guide/advanced.md",
+                "guide/intro.md",
+                "guide/setup.md",
+                "reference/api.md",
+                "reference/cli.md",
+            ],
+            // The base directory can order subdirectories too
+            &[("", "reference\n"), ("guide", "intro.md\nsetup.md\n")],
+        );
+
+        assert_eq!(
+            state.get_sorted_filenames(),
+            [
+                "reference/api.md",
+                "reference/cli.md",
+                "README.md",
+                "guide/intro.md",
+                "guide/setup.md",
+                "guide/advanced.md",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_orderings_are_read_once_until_reloaded() {
+        let (mut state, temp_dir) =
+            create_ordered_state(&["a.md", "b.md", "c.md"], &[("", "c.md\n")]);
+        let ordering_file = temp_dir.path().join(ORDERING_FILE);
+
+        fs::write(&ordering_file, "b.md\n").expect("Failed to write ordering");
+        assert_eq!(state.get_sorted_filenames(), ["c.md", "a.md", "b.md"]);
+        state.reload_ordering("");
+        assert_eq!(state.get_sorted_filenames(), ["b.md", "a.md", "c.md"]);
+
+        fs::remove_file(&ordering_file).expect("Failed to remove ordering");
+        state.rescan_directory().expect("Failed to rescan");
+        assert_eq!(state.get_sorted_filenames(), ["a.md", "b.md", "c.md"]);
+    }
+
+    #[test]
+    fn test_sorted_filenames_without_ordering_are_alphabetical() {
+        let files = ["B.md", "a-b.md", "a/x.md", "a/y.md", "b.md"];
+        let (state, _temp_dir) = create_ordered_state(&files, &[]);
+
+        let mut expected = files.to_vec();
+        expected.sort();
+        assert_eq!(state.get_sorted_filenames(), expected);
+    }
+
+    #[test]
+    fn test_tracked_file_keys_use_forward_slashes() {
+        let temp_dir = tempdir().expect("Failed to create temp dir");
+        fs::create_dir(temp_dir.path().join("guide")).expect("Failed to create dir");
+        let path = temp_dir.path().join("guide").join("intro.md");
+        fs::write(&path, "# Intro").expect("Failed to write");
+
+        let mut state = MarkdownState::new(
+            temp_dir.path().to_path_buf(),
+            vec![],
+            true,
+            RouterConfig::default(),
+        )
+        .expect("Failed to create state");
+        // A key as built from a Windows path
+        let tracked = TrackedFile::unloaded(path, "guide\\intro.md".to_string()).unwrap();
+        state.insert_tracked("guide\\intro.md", tracked);
+
+        assert_eq!(state.get_sorted_filenames(), ["guide/intro.md"]);
+        state
+            .refresh_file(&normalize_key("guide/intro.md"))
+            .expect("Failed to refresh");
+        assert_eq!(
+            &*state.tracked_files["guide/intro.md"].read().markdown,
+            "# Intro"
+        );
+    }
+
+    #[test]
+    fn test_markdown_state_restore_from_snapshot() {
+        let temp_dir = tempdir().expect("Failed to create temp dir");
+        let dir = temp_dir.path();
+        for name in ["same.md", "changed.md", "deleted.md"] {
+            fs::write(dir.join(name), format!("# {name}")).expect("Failed to write");
+        }
+        let scan = scan_markdown_files(dir).expect("Failed to scan");
+        let state =
+            MarkdownState::new(dir.to_path_buf(), scan.files, true, RouterConfig::default())
+                .expect("Failed to create state");
+        let snapshot = state.serialize_snapshot();
+
+        fs::write(dir.join("changed.md"), "# Changed").expect("Failed to write");
+        fs::File::options()
+            .write(true)
+            .open(dir.join("changed.md"))
+            .and_then(|f| f.set_modified(SystemTime::UNIX_EPOCH))
+            .expect("Failed to set mtime");
+        fs::remove_file(dir.join("deleted.md")).expect("Failed to remove");
+        fs::write(dir.join("new.md"), "# New").expect("Failed to write");
+
+        let restored = MarkdownState::restore_from_snapshot(
+            &snapshot,
+            dir.to_path_buf(),
+            RouterConfig::default(),
+        )
+        .expect("Failed to restore");
+        let mut paths: Vec<_> = restored.tracked_files.keys().cloned().collect();
+        paths.sort();
+        assert_eq!(paths, ["changed.md", "new.md", "same.md"]);
+        assert!(!restored.tracked_files["same.md"].read().loaded);
+        assert!(restored.tracked_files["changed.md"].read().loaded);
+        assert_eq!(
+            &*restored.tracked_files["changed.md"].read().markdown,
+            "# Changed"
+        );
+
+        let other_dir = tempdir().expect("Failed to create temp dir");
+        assert!(MarkdownState::restore_from_snapshot(
+            &snapshot,
+            other_dir.path().to_path_buf(),
+            RouterConfig::default()
+        )
+        .is_err());
+    }
+
     #[test]
     fn test_scan_markdown_files_handles_filename_conflicts() {
         // Test that files with the same name in different folders are both tracked
@@ -1179,7 +4106,9 @@ mod tests {
         fs::create_dir(&folder2).expect("Failed to create folder2");
         fs::write(folder2.join("file.md"), "# Folder2 File").expect("Failed to write");
 
-        let result = scan_markdown_files(temp_dir.path()).expect("Failed to scan");
+        let result = scan_markdown_files(temp_dir.path())
+            .expect("Failed to scan")
+            .files;
 
         // Should find all 3 files with the same name
         assert_eq!(result.len(), 3);
@@ -1204,11 +4133,77 @@ mod tests {
         fs::write(temp_dir.path().join("test3.Md"), "# Test 3").expect("Failed to write");
         fs::write(temp_dir.path().join("test4.MARKDOWN"), "# Test 4").expect("Failed to write");
 
-        let result = scan_markdown_files(temp_dir.path()).expect("Failed to scan");
+        let result = scan_markdown_files(temp_dir.path())
+            .expect("Failed to scan")
+            .files;
 
         assert_eq!(result.len(), 4);
     }
 
+    #[test]
+    fn test_scan_markdown_files_skips_hidden_entries() {
+        let temp_dir = tempdir().expect("Failed to create temp dir");
+
+        fs::write(temp_dir.path().join("visible.md"), "# Visible").expect("Failed to write");
+        fs::write(temp_dir.path().join(".template.md"), "# Hidden").expect("Failed to write");
+        let hidden_dir = temp_dir.path().join(".github");
+        fs::create_dir(&hidden_dir).expect("Failed to create dir");
+        fs::write(hidden_dir.join("PULL_REQUEST_TEMPLATE.md"), "# PR").expect("Failed to write");
+
+        let result = scan_markdown_files(temp_dir.path())
+            .expect("Failed to scan")
+            .files;
+        assert_eq!(result, vec![temp_dir.path().join("visible.md")]);
+
+        let result = scan_markdown_files_with_options(temp_dir.path(), true)
+            .expect("Failed to scan")
+            .files;
+        assert_eq!(result.len(), 3);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_scan_markdown_files_skips_unreadable_directories() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let temp_dir = tempdir().expect("Failed to create temp dir");
+        fs::write(temp_dir.path().join("readable.md"), "# Readable").expect("Failed to write");
+        let locked = temp_dir.path().join("locked");
+        fs::create_dir(&locked).expect("Failed to create dir");
+        fs::write(locked.join("secret.md"), "# Secret").expect("Failed to write");
+        fs::set_permissions(&locked, fs::Permissions::from_mode(0o000))
+            .expect("Failed to set permissions");
+
+        let readable_anyway = fs::read_dir(&locked).is_ok();
+        let result = scan_markdown_files(temp_dir.path());
+        fs::set_permissions(&locked, fs::Permissions::from_mode(0o755))
+            .expect("Failed to restore permissions");
+
+        // Permissions aren't enforced for root, so there is nothing to skip
+        if readable_anyway {
+            return;
+        }
+        let result = result.expect("Scan should succeed with partial results");
+        assert_eq!(result.files, vec![temp_dir.path().join("readable.md")]);
+        assert_eq!(result.skipped.len(), 1);
+        assert_eq!(result.skipped[0].0, locked);
+    }
+
+    #[test]
+    fn test_scan_markdown_files_missing_directory_is_an_error() {
+        let temp_dir = tempdir().expect("Failed to create temp dir");
+
+        assert!(scan_markdown_files(&temp_dir.path().join("missing")).is_err());
+    }
+
+    #[test]
+    fn test_is_hidden_path() {
+        assert!(is_hidden_path(".template.md"));
+        assert!(is_hidden_path("docs/.obsidian/notes.md"));
+        assert!(!is_hidden_path("docs/notes.md"));
+        assert!(!is_hidden_path("../docs/notes.md"));
+    }
+
     #[test]
     fn test_format_host() {
         assert_eq!(format_host("127.0.0.1", 3000), "127.0.0.1:3000");
@@ -1221,6 +4216,207 @@ mod tests {
         assert_eq!(format_host("2001:db8::1", 8080), "[2001:db8::1]:8080");
     }
 
+    fn file_set(names: &[&str]) -> std::collections::HashSet<String> {
+        names.iter().map(|name| name.to_string()).collect()
+    }
+
+    fn hash_map(entries: &[(&str, &str)]) -> HashMap<String, md5::Digest> {
+        entries
+            .iter()
+            .map(|(name, content)| (name.to_string(), md5::compute(content)))
+            .collect()
+    }
+
+    fn describe(changes: Vec<FileChangeType>) -> Vec<String> {
+        changes
+            .into_iter()
+            .map(|change| match change {
+                FileChangeType::Renamed { old_name, new_name } => {
+                    format!("renamed {old_name} -> {new_name}")
+                }
+                FileChangeType::Removed { name } => format!("removed {name}"),
+                FileChangeType::Added { name } => format!("added {name}"),
+                FileChangeType::Bulk {
+                    added,
+                    removed,
+                    modified,
+                } => format!("bulk +{added:?} -{removed:?} ~{modified:?}"),
+                FileChangeType::Other => "other".to_string(),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_detect_file_changes_rename_requires_matching_hash() {
+        let old_hashes = hash_map(&[("a.md", "same"), ("keep.md", "keep")]);
+        let new_hashes = hash_map(&[("b.md", "same"), ("keep.md", "keep")]);
+
+        let changes = detect_file_changes(
+            &file_set(&["a.md", "keep.md"]),
+            &file_set(&["b.md", "keep.md"]),
+            &old_hashes,
+            &new_hashes,
+        );
+
+        assert_eq!(describe(changes), vec!["renamed a.md -> b.md"]);
+    }
+
+    #[test]
+    fn test_detect_file_changes_unrelated_add_and_remove_is_not_a_rename() {
+        let old_hashes = hash_map(&[("moved-away.md", "old content")]);
+        let new_hashes = hash_map(&[("dropped-in.md", "new content")]);
+
+        let changes = detect_file_changes(
+            &file_set(&["moved-away.md"]),
+            &file_set(&["dropped-in.md"]),
+            &old_hashes,
+            &new_hashes,
+        );
+
+        assert_eq!(
+            describe(changes),
+            vec!["removed moved-away.md", "added dropped-in.md"]
+        );
+    }
+
+    #[test]
+    fn test_detect_file_changes_multiple_simultaneous_changes() {
+        let old_hashes = hash_map(&[("a.md", "A"), ("b.md", "B"), ("c.md", "C")]);
+        let new_hashes = hash_map(&[("c.md", "C"), ("x.md", "B"), ("y.md", "Y"), ("z.md", "Z")]);
+
+        let changes = detect_file_changes(
+            &file_set(&["a.md", "b.md", "c.md"]),
+            &file_set(&["c.md", "x.md", "y.md", "z.md"]),
+            &old_hashes,
+            &new_hashes,
+        );
+
+        assert_eq!(
+            describe(changes),
+            vec![r#"bulk +["x.md", "y.md", "z.md"] -["a.md", "b.md"] ~[]"#]
+        );
+    }
+
+    #[test]
+    fn test_detect_file_changes_two_changes_are_sent_individually() {
+        let old_hashes = hash_map(&[("a.md", "A"), ("keep.md", "K")]);
+        let new_hashes = hash_map(&[("keep.md", "K2")]);
+
+        let changes = detect_file_changes(
+            &file_set(&["a.md", "keep.md"]),
+            &file_set(&["keep.md"]),
+            &old_hashes,
+            &new_hashes,
+        );
+
+        // A content change counts towards the threshold but has no event of its own
+        assert_eq!(describe(changes), vec!["removed a.md"]);
+    }
+
+    #[test]
+    fn test_detect_file_changes_three_changes_are_bulk() {
+        let old_hashes = hash_map(&[("a.md", "A"), ("b.md", "B"), ("keep.md", "K")]);
+        let new_hashes = hash_map(&[("b.md", "B2"), ("keep.md", "K"), ("new.md", "N")]);
+
+        let changes = detect_file_changes(
+            &file_set(&["a.md", "b.md", "keep.md"]),
+            &file_set(&["b.md", "keep.md", "new.md"]),
+            &old_hashes,
+            &new_hashes,
+        );
+
+        assert_eq!(
+            describe(changes),
+            vec![r#"bulk +["new.md"] -["a.md"] ~["b.md"]"#]
+        );
+    }
+
+    #[test]
+    fn test_detect_file_changes_bulk_lists_renames_as_remove_and_add() {
+        let old_hashes = hash_map(&[("a.md", "A"), ("b.md", "B"), ("c.md", "C")]);
+        let new_hashes = hash_map(&[("x.md", "A"), ("y.md", "B"), ("z.md", "C")]);
+
+        let changes = detect_file_changes(
+            &file_set(&["a.md", "b.md", "c.md"]),
+            &file_set(&["x.md", "y.md", "z.md"]),
+            &old_hashes,
+            &new_hashes,
+        );
+
+        assert_eq!(
+            describe(changes),
+            vec![r#"bulk +["x.md", "y.md", "z.md"] -["a.md", "b.md", "c.md"] ~[]"#]
+        );
+    }
+
+    #[test]
+    fn test_mention_context_cuts_on_character_boundaries() {
+        let markdown = format!("{}Guide{}", "é".repeat(60), "ü\n".repeat(30));
+        let start = markdown.find("Guide").unwrap();
+
+        let context = mention_context(&markdown, start, start + "Guide".len());
+        assert_eq!(
+            context,
+            format!("...{}Guide{}", "é".repeat(50), " ü".repeat(25).trim_start()) + "..."
+        );
+    }
+
+    #[test]
+    fn test_forward_watcher_result_requests_rewatch_for_lost_watches() {
+        let temp_dir = tempdir().expect("Failed to create temp dir");
+        let watched = WatchedDir::new(temp_dir.path());
+        let (tx, mut rx) = mpsc::channel(10);
+
+        forward_watcher_result(Err(notify::Error::watch_not_found()), Some(&watched), &tx);
+        assert!(matches!(rx.try_recv(), Ok(WatcherMessage::RewatchNeeded)));
+
+        forward_watcher_result(Err(notify::Error::path_not_found()), Some(&watched), &tx);
+        forward_watcher_result(Err(notify::Error::watch_not_found()), None, &tx);
+        assert!(rx.try_recv().is_err());
+
+        forward_watcher_result(Ok(Event::default()), None, &tx);
+        assert!(matches!(rx.try_recv(), Ok(WatcherMessage::Event(_))));
+    }
+
+    #[test]
+    fn test_forward_watcher_result_io_error_rewatches_only_for_lost_base_dir() {
+        let temp_dir = tempdir().expect("Failed to create temp dir");
+        let base_dir = temp_dir.path().join("docs");
+        fs::create_dir(&base_dir).expect("Failed to create dir");
+        let watched = WatchedDir::new(&base_dir);
+        let (tx, mut rx) = mpsc::channel(10);
+        let io_error = || {
+            let error = std::io::Error::new(std::io::ErrorKind::PermissionDenied, "denied");
+            Err(notify::Error::io(error).add_path(base_dir.join("secret.md")))
+        };
+
+        // One file failing doesn't mean the watch is gone
+        forward_watcher_result(io_error(), Some(&watched), &tx);
+        assert!(rx.try_recv().is_err());
+
+        fs::rename(&base_dir, temp_dir.path().join("old")).expect("Failed to rename");
+        forward_watcher_result(io_error(), Some(&watched), &tx);
+        assert!(matches!(rx.try_recv(), Ok(WatcherMessage::RewatchNeeded)));
+
+        // Replaced by a different directory
+        fs::create_dir(&base_dir).expect("Failed to create dir");
+        forward_watcher_result(io_error(), Some(&watched), &tx);
+        assert!(matches!(rx.try_recv(), Ok(WatcherMessage::RewatchNeeded)));
+
+        forward_watcher_result(io_error(), None, &tx);
+        assert!(rx.try_recv().is_err());
+    }
+
+    #[test]
+    fn test_detect_file_changes_without_list_changes_is_other() {
+        let hashes = hash_map(&[("a.md", "A")]);
+
+        let changes =
+            detect_file_changes(&file_set(&["a.md"]), &file_set(&["a.md"]), &hashes, &hashes);
+
+        assert_eq!(describe(changes), vec!["other"]);
+    }
+
     #[tokio::test]
     async fn test_file_watcher_detects_new_files_in_subdirectories() {
         use axum_test::TestServer;
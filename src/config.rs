@@ -0,0 +1,339 @@
+use anyhow::{Context, Result};
+use serde::Deserialize;
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use crate::app::RouterConfig;
+use crate::lint::{LintConfig, Severity};
+
+pub const DEFAULT_HOSTNAME: &str = "127.0.0.1";
+pub const DEFAULT_PORT: u16 = 3000;
+pub const DEFAULT_WATCH_INTERVAL_MS: u64 = 1000;
+
+/// Settings loaded from a `--config` TOML file.
+///
+/// Mirrors the command-line flags so a configuration can be committed to a repo.
+/// Every value is optional: command-line flags are layered on top with [`Config::merge`]
+/// and anything still unset falls back to the built-in defaults.
+///
+/// ```toml
+/// [server]
+/// path = "docs"
+/// hostname = "0.0.0.0"
+/// port = 8080
+/// lazy_load = true
+/// streaming_threshold = 1048576
+/// snapshot_file = ".mdserve-snapshot.json"
+/// require_frontmatter = ["title"]
+/// metrics = true
+/// max_ws_connections = 100
+/// timeout = 30
+/// allow_bulk_delete = false
+/// title_from_frontmatter = true
+/// export_css = "export.css"
+/// pandoc_path = "/opt/pandoc/bin/pandoc"
+/// git = true
+/// enable_git = false
+/// verify_on_startup = false
+/// lint_rules = { img-alt = "error", single-h1 = "ignore" }
+/// max_inline_content = 50
+/// max_import_size = 52428800
+/// max_export_size = 209715200
+/// root_files = ["start-here.md"]
+/// no_orphan_check = ["**/index.md", "**/README.md", "archive/**"]
+/// public_url = "https://docs.example.com"
+///
+/// [watch]
+/// rescan_delay = 300
+/// exclude_patterns = ["drafts/**", "*.tmp.md"]
+/// watcher_debounce = 500
+/// include_hidden = false
+/// poll = true
+/// watch_interval = 1000
+/// recovery = true
+/// ```
+#[derive(Debug, Default, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub server: ServerConfig,
+    pub watch: WatchConfig,
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ServerConfig {
+    /// Markdown file or directory to serve, relative to the config file
+    pub path: Option<PathBuf>,
+    pub hostname: Option<String>,
+    pub port: Option<u16>,
+    /// Read files on first request instead of at startup
+    pub lazy_load: Option<bool>,
+    /// Files larger than this many bytes are streamed by the file API
+    pub streaming_threshold: Option<usize>,
+    /// Restore directory state from this file at startup and save it on shutdown,
+    /// relative to the config file
+    pub snapshot_file: Option<PathBuf>,
+    /// Frontmatter fields every markdown file should set
+    pub require_frontmatter: Vec<String>,
+    /// Serve Prometheus metrics at `GET /__metrics`
+    pub metrics: Option<bool>,
+    /// WebSocket connections allowed at once
+    pub max_ws_connections: Option<usize>,
+    /// Seconds before an unanswered request gets a 504
+    pub timeout: Option<u64>,
+    /// Accept `DELETE /api/files`, which deletes many files at once
+    pub allow_bulk_delete: Option<bool>,
+    /// List each file's title in `GET /api/files`
+    pub title_from_frontmatter: Option<bool>,
+    /// Stylesheet for `export/html` documents, relative to the config file
+    pub export_css: Option<PathBuf>,
+    /// pandoc executable for `export/pdf`, if not in `PATH`
+    pub pandoc_path: Option<PathBuf>,
+    /// Serve file history by running git
+    pub git: Option<bool>,
+    /// Also serve file contributors, blame and restore from git
+    pub enable_git: Option<bool>,
+    /// Check the tracked files against the disk at startup and log what differs
+    pub verify_on_startup: Option<bool>,
+    /// Severities of the `lint` rules by name, replacing their defaults
+    pub lint_rules: BTreeMap<String, Severity>,
+    /// Most files `GET /api/files?include_content=true` returns at once
+    pub max_inline_content: Option<usize>,
+    /// Largest ZIP upload, and most markdown extracted from it, in bytes
+    pub max_import_size: Option<usize>,
+    /// Most bytes of files and images in a workspace export
+    pub max_export_size: Option<usize>,
+    /// Glob patterns of entry points never reported as orphaned
+    pub root_files: Vec<String>,
+    /// Glob patterns of files left out of the orphan check, replacing the default
+    /// index pages and READMEs
+    pub no_orphan_check: Option<Vec<String>>,
+    /// URL the statically exported files are published at, for the sitemap
+    pub public_url: Option<String>,
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct WatchConfig {
+    /// Delay in milliseconds before rescanning after a rename or removal
+    pub rescan_delay: Option<u64>,
+    /// Glob patterns, relative to the served directory, of files to skip (config file only)
+    pub exclude_patterns: Vec<String>,
+    /// Poll interval in milliseconds for polling watcher backends (config file only)
+    pub watcher_debounce: Option<u64>,
+    /// Serve hidden files and files in hidden directories (names starting with `.`)
+    pub include_hidden: Option<bool>,
+    /// Poll the file system for changes instead of relying on native events
+    pub poll: Option<bool>,
+    /// Poll interval in milliseconds when `poll` is set
+    pub watch_interval: Option<u64>,
+    /// Re-create the watcher when it loses the served directory
+    pub recovery: Option<bool>,
+}
+
+impl Config {
+    /// Reads and parses a TOML config file.
+    ///
+    /// Relative `server.path`, `server.snapshot_file` and `server.export_css` values are
+    /// resolved against the directory containing the config
+    /// file, so the file keeps working regardless of where `mdserve` is launched from.
+    pub fn load(path: &Path) -> Result<Self> {
+        let content = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read config file {}", path.display()))?;
+        let mut config: Config = toml::from_str(&content)
+            .with_context(|| format!("Failed to parse config file {}", path.display()))?;
+        LintConfig::new(config.server.lint_rules.clone())
+            .map_err(anyhow::Error::msg)
+            .with_context(|| format!("Invalid lint_rules in config file {}", path.display()))?;
+
+        if let Some(config_dir) = path.parent() {
+            for relative in [
+                &mut config.server.path,
+                &mut config.server.snapshot_file,
+                &mut config.server.export_css,
+            ]
+            .into_iter()
+            .flatten()
+            {
+                if relative.is_relative() {
+                    *relative = config_dir.join(&*relative);
+                }
+            }
+        }
+
+        Ok(config)
+    }
+
+    /// Layers `overrides` (typically the command-line flags) on top of `self`.
+    ///
+    /// Scalar values set in `overrides` win; exclude patterns, required frontmatter
+    /// fields and root files from both sides are kept. Lint rules are merged rule by rule.
+    pub fn merge(self, overrides: Config) -> Config {
+        let mut exclude_patterns = self.watch.exclude_patterns;
+        exclude_patterns.extend(overrides.watch.exclude_patterns);
+        let mut require_frontmatter = self.server.require_frontmatter;
+        for field in overrides.server.require_frontmatter {
+            if !require_frontmatter.contains(&field) {
+                require_frontmatter.push(field);
+            }
+        }
+
+        let mut root_files = self.server.root_files;
+        for pattern in overrides.server.root_files {
+            if !root_files.contains(&pattern) {
+                root_files.push(pattern);
+            }
+        }
+        let mut lint_rules = self.server.lint_rules;
+        lint_rules.extend(overrides.server.lint_rules);
+
+        Config {
+            server: ServerConfig {
+                path: overrides.server.path.or(self.server.path),
+                hostname: overrides.server.hostname.or(self.server.hostname),
+                port: overrides.server.port.or(self.server.port),
+                lazy_load: overrides.server.lazy_load.or(self.server.lazy_load),
+                streaming_threshold: overrides
+                    .server
+                    .streaming_threshold
+                    .or(self.server.streaming_threshold),
+                snapshot_file: overrides.server.snapshot_file.or(self.server.snapshot_file),
+                require_frontmatter,
+                metrics: overrides.server.metrics.or(self.server.metrics),
+                max_ws_connections: overrides
+                    .server
+                    .max_ws_connections
+                    .or(self.server.max_ws_connections),
+                timeout: overrides.server.timeout.or(self.server.timeout),
+                allow_bulk_delete: overrides
+                    .server
+                    .allow_bulk_delete
+                    .or(self.server.allow_bulk_delete),
+                title_from_frontmatter: overrides
+                    .server
+                    .title_from_frontmatter
+                    .or(self.server.title_from_frontmatter),
+                export_css: overrides.server.export_css.or(self.server.export_css),
+                pandoc_path: overrides.server.pandoc_path.or(self.server.pandoc_path),
+                git: overrides.server.git.or(self.server.git),
+                enable_git: overrides.server.enable_git.or(self.server.enable_git),
+                verify_on_startup: overrides
+                    .server
+                    .verify_on_startup
+                    .or(self.server.verify_on_startup),
+                lint_rules,
+                max_inline_content: overrides
+                    .server
+                    .max_inline_content
+                    .or(self.server.max_inline_content),
+                max_import_size: overrides
+                    .server
+                    .max_import_size
+                    .or(self.server.max_import_size),
+                max_export_size: overrides
+                    .server
+                    .max_export_size
+                    .or(self.server.max_export_size),
+                root_files,
+                no_orphan_check: overrides
+                    .server
+                    .no_orphan_check
+                    .or(self.server.no_orphan_check),
+                public_url: overrides.server.public_url.or(self.server.public_url),
+            },
+            watch: WatchConfig {
+                rescan_delay: overrides.watch.rescan_delay.or(self.watch.rescan_delay),
+                exclude_patterns,
+                watcher_debounce: overrides
+                    .watch
+                    .watcher_debounce
+                    .or(self.watch.watcher_debounce),
+                include_hidden: overrides.watch.include_hidden.or(self.watch.include_hidden),
+                poll: overrides.watch.poll.or(self.watch.poll),
+                watch_interval: overrides.watch.watch_interval.or(self.watch.watch_interval),
+                recovery: overrides.watch.recovery.or(self.watch.recovery),
+            },
+        }
+    }
+
+    pub fn hostname(&self) -> &str {
+        self.server.hostname.as_deref().unwrap_or(DEFAULT_HOSTNAME)
+    }
+
+    pub fn port(&self) -> u16 {
+        self.server.port.unwrap_or(DEFAULT_PORT)
+    }
+
+    pub fn include_hidden(&self) -> bool {
+        self.watch.include_hidden.unwrap_or(false)
+    }
+
+    /// The interval to poll for changes at, or `None` when native file system events are
+    /// used. `watch_interval` is ignored unless `poll` is set.
+    pub fn poll_interval(&self) -> Option<Duration> {
+        self.watch.poll.unwrap_or(false).then(|| {
+            Duration::from_millis(
+                self.watch
+                    .watch_interval
+                    .unwrap_or(DEFAULT_WATCH_INTERVAL_MS),
+            )
+        })
+    }
+
+    /// Builds the router settings, using defaults for anything left unset.
+    pub fn router_config(&self) -> RouterConfig {
+        let mut router_config = RouterConfig {
+            exclude_patterns: self.watch.exclude_patterns.clone(),
+            lazy: self.server.lazy_load.unwrap_or(false),
+            watcher_debounce_ms: self.watch.watcher_debounce,
+            poll_interval: self.poll_interval(),
+            include_hidden: self.include_hidden(),
+            snapshot_file: self.server.snapshot_file.clone(),
+            required_frontmatter: self.server.require_frontmatter.clone(),
+            metrics: self.server.metrics.unwrap_or(false),
+            allow_bulk_delete: self.server.allow_bulk_delete.unwrap_or(false),
+            title_from_frontmatter: self.server.title_from_frontmatter.unwrap_or(false),
+            export_css: self.server.export_css.clone(),
+            pandoc_path: self.server.pandoc_path.clone(),
+            git_history: self.server.git.unwrap_or(true),
+            enable_git: self.server.enable_git.unwrap_or(false),
+            verify_on_startup: self.server.verify_on_startup.unwrap_or(false),
+            lint_rules: LintConfig {
+                rules: self.server.lint_rules.clone(),
+            },
+            watch_recovery: self.watch.recovery.unwrap_or(true),
+            root_files: self.server.root_files.clone(),
+            public_url: self.server.public_url.clone(),
+            ..RouterConfig::default()
+        };
+        if let Some(delay) = self.watch.rescan_delay {
+            router_config.rescan_delay = Duration::from_millis(delay);
+        }
+        if let Some(threshold) = self.server.streaming_threshold {
+            router_config.streaming_threshold = threshold;
+        }
+        if let Some(max_ws_connections) = self.server.max_ws_connections {
+            router_config.max_ws_connections = max_ws_connections;
+        }
+        if let Some(max_inline_content) = self.server.max_inline_content {
+            router_config.max_inline_content = max_inline_content;
+        }
+        if let Some(max_import_size) = self.server.max_import_size {
+            router_config.max_import_size = max_import_size;
+        }
+        if let Some(max_export_size) = self.server.max_export_size {
+            router_config.max_export_size = max_export_size;
+        }
+        if let Some(no_orphan_check) = &self.server.no_orphan_check {
+            router_config.no_orphan_check = no_orphan_check.clone();
+        }
+        if let Some(timeout) = self.server.timeout {
+            router_config.request_timeout = Duration::from_secs(timeout);
+        }
+        router_config
+    }
+}
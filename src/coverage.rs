@@ -0,0 +1,137 @@
+//! Per-file health summary for quality dashboards: how many links and images resolve,
+//! how many tasks are done and whether the file has a title and a description.
+
+use markdown::mdast::Node;
+use serde::Serialize;
+
+use crate::app::MarkdownState;
+use crate::export::resolve_local_image;
+use crate::parser::{extract_summary, extract_tasks, parse_mdast, walk};
+use crate::validate::resolve_internal_link;
+
+const LINKS_WEIGHT: f64 = 0.3;
+const IMAGES_WEIGHT: f64 = 0.2;
+const TITLE_WEIGHT: f64 = 0.15;
+const DESCRIPTION_WEIGHT: f64 = 0.1;
+const TASKS_WEIGHT: f64 = 0.25;
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub(crate) struct FileCoverage {
+    pub(crate) total_links: usize,
+    /// Links to markdown files that aren't tracked
+    pub(crate) broken_links: usize,
+    pub(crate) total_images: usize,
+    /// Local images that don't exist
+    pub(crate) missing_images: usize,
+    pub(crate) total_tasks: usize,
+    pub(crate) completed_tasks: usize,
+    /// Whether the file has a `# Heading`
+    pub(crate) has_title: bool,
+    /// Whether the file has a top-level paragraph to summarize it by
+    pub(crate) has_description: bool,
+    /// Weighted average of the above between 0 and 1, rounded to two decimals
+    pub(crate) health_score: f64,
+}
+
+/// Checks `markdown`, the contents of `relative_path`, against the tracked files and the
+/// images on disk. Remote links and images are counted but never reported.
+pub(crate) fn file_coverage(
+    relative_path: &str,
+    markdown: &str,
+    state: &MarkdownState,
+) -> FileCoverage {
+    let mut total_links = 0;
+    let mut broken_links = 0;
+    let mut total_images = 0;
+    let mut missing_images = 0;
+    if let Some(root) = parse_mdast(markdown) {
+        walk(&root, &mut |node| match node {
+            Node::Link(link) => {
+                total_links += 1;
+                if resolve_internal_link(relative_path, &link.url)
+                    .is_some_and(|target| !state.tracked_files.contains_key(&target))
+                {
+                    broken_links += 1;
+                }
+            }
+            Node::Image(image) => {
+                total_images += 1;
+                if is_local(&image.url)
+                    && resolve_local_image(&state.base_dir, relative_path, &image.url).is_none()
+                {
+                    missing_images += 1;
+                }
+            }
+            _ => {}
+        });
+    }
+
+    let tasks = extract_tasks(markdown);
+    let summary = extract_summary(markdown);
+    let mut coverage = FileCoverage {
+        total_links,
+        broken_links,
+        total_images,
+        missing_images,
+        total_tasks: tasks.len(),
+        completed_tasks: tasks.iter().filter(|task| task.checked).count(),
+        has_title: summary.title.is_some(),
+        has_description: !summary.summary.is_empty(),
+        health_score: 0.0,
+    };
+    coverage.health_score = health_score(&coverage);
+    coverage
+}
+
+fn is_local(url: &str) -> bool {
+    !(url.starts_with("//") || url.starts_with("data:") || url.contains("://"))
+}
+
+/// The share of `total` that is fine, or 1 if there is nothing to check.
+fn ratio(fine: usize, total: usize) -> f64 {
+    if total == 0 {
+        1.0
+    } else {
+        fine as f64 / total as f64
+    }
+}
+
+fn health_score(coverage: &FileCoverage) -> f64 {
+    let score = LINKS_WEIGHT
+        * ratio(
+            coverage.total_links - coverage.broken_links,
+            coverage.total_links,
+        )
+        + IMAGES_WEIGHT
+            * ratio(
+                coverage.total_images - coverage.missing_images,
+                coverage.total_images,
+            )
+        + TITLE_WEIGHT * f64::from(u8::from(coverage.has_title))
+        + DESCRIPTION_WEIGHT * f64::from(u8::from(coverage.has_description))
+        + TASKS_WEIGHT * ratio(coverage.completed_tasks, coverage.total_tasks);
+    (score * 100.0).round() / 100.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_health_score_weights() {
+        let coverage = FileCoverage {
+            total_links: 4,
+            broken_links: 1,
+            total_images: 2,
+            missing_images: 2,
+            total_tasks: 4,
+            completed_tasks: 2,
+            has_title: true,
+            has_description: false,
+            health_score: 0.0,
+        };
+
+        // 0.3 * 0.75 + 0.2 * 0 + 0.15 + 0.1 * 0 + 0.25 * 0.5
+        assert_eq!(health_score(&coverage), 0.5);
+    }
+}
@@ -0,0 +1,226 @@
+//! Paragraph-level duplicate detection across tracked files, for content that was
+//! copy-pasted from one document into another, and files sharing a title.
+//!
+//! Paragraphs are compared by the Jaccard similarity of their word sets. Only pairs that
+//! share at least one 5-word shingle are compared at all, so unrelated files cost no
+//! more than building the shingle index.
+
+use serde::Serialize;
+use std::collections::{BTreeSet, HashMap, HashSet};
+
+use crate::app::MarkdownState;
+use crate::parser::{parse_mdast, walk};
+
+/// Similarity at or above which two paragraphs count as duplicates, unless the request
+/// sets its own.
+pub(crate) const DEFAULT_THRESHOLD: f64 = 0.8;
+/// Words per shingle. Paragraphs with fewer words are never reported.
+const SHINGLE_SIZE: usize = 5;
+/// How many files [`find_duplicates`] returns at most.
+const MAX_RESULTS: usize = 5;
+
+/// Another file with paragraphs similar to the checked one.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub(crate) struct DuplicateMatch {
+    pub(crate) path: String,
+    /// Mean similarity of the matching paragraphs, rounded to two decimals
+    pub(crate) similarity: f64,
+    /// Paragraphs of the checked file with a duplicate in this file
+    pub(crate) matching_sections: usize,
+}
+
+struct Paragraph {
+    words: HashSet<String>,
+    shingles: HashSet<String>,
+}
+
+/// The paragraphs of `markdown` that are long enough to have shingles.
+fn paragraphs(markdown: &str) -> Vec<Paragraph> {
+    let Some(root) = parse_mdast(markdown) else {
+        return Vec::new();
+    };
+
+    let mut paragraphs = Vec::new();
+    walk(&root, &mut |node| {
+        if !matches!(node, markdown::mdast::Node::Paragraph(_)) {
+            return;
+        }
+        let words: Vec<String> = node
+            .to_string()
+            .split(|c: char| !c.is_alphanumeric())
+            .filter(|word| !word.is_empty())
+            .map(str::to_lowercase)
+            .collect();
+        if words.len() < SHINGLE_SIZE {
+            return;
+        }
+        paragraphs.push(Paragraph {
+            shingles: words
+                .windows(SHINGLE_SIZE)
+                .map(|window| window.join(" "))
+                .collect(),
+            words: words.into_iter().collect(),
+        });
+    });
+    paragraphs
+}
+
+fn jaccard(a: &HashSet<String>, b: &HashSet<String>) -> f64 {
+    let shared = a.intersection(b).count();
+    shared as f64 / (a.len() + b.len() - shared) as f64
+}
+
+/// The files among `others` (path and markdown) with paragraphs whose similarity to a
+/// paragraph of `markdown` is at least `threshold`, most similar first.
+pub(crate) fn find_duplicates<'a>(
+    markdown: &str,
+    others: impl IntoIterator<Item = (&'a str, &'a str)>,
+    threshold: f64,
+) -> Vec<DuplicateMatch> {
+    let target = paragraphs(markdown);
+    let others: Vec<(&str, Vec<Paragraph>)> = others
+        .into_iter()
+        .map(|(path, markdown)| (path, paragraphs(markdown)))
+        .collect();
+
+    // Posting list from each shingle to the (file, paragraph) pairs containing it
+    let mut index: HashMap<&str, Vec<(usize, usize)>> = HashMap::new();
+    for (file, (_, file_paragraphs)) in others.iter().enumerate() {
+        for (i, paragraph) in file_paragraphs.iter().enumerate() {
+            for shingle in &paragraph.shingles {
+                index.entry(shingle.as_str()).or_default().push((file, i));
+            }
+        }
+    }
+
+    // Sum of similarities and number of matching paragraphs, by file
+    let mut matches: HashMap<usize, (f64, usize)> = HashMap::new();
+    for paragraph in &target {
+        let candidates: BTreeSet<(usize, usize)> = paragraph
+            .shingles
+            .iter()
+            .filter_map(|shingle| index.get(shingle.as_str()))
+            .flatten()
+            .copied()
+            .collect();
+
+        // Each paragraph counts at most once per file, with its best match there
+        let mut best: HashMap<usize, f64> = HashMap::new();
+        for (file, i) in candidates {
+            let similarity = jaccard(&paragraph.words, &others[file].1[i].words);
+            let entry = best.entry(file).or_default();
+            *entry = entry.max(similarity);
+        }
+        for (file, similarity) in best {
+            if similarity >= threshold {
+                let entry = matches.entry(file).or_default();
+                entry.0 += similarity;
+                entry.1 += 1;
+            }
+        }
+    }
+
+    let mut duplicates: Vec<DuplicateMatch> = matches
+        .into_iter()
+        .map(|(file, (total, count))| DuplicateMatch {
+            path: others[file].0.to_string(),
+            similarity: (total / count as f64 * 100.0).round() / 100.0,
+            matching_sections: count,
+        })
+        .collect();
+    duplicates.sort_by(|a, b| {
+        b.similarity
+            .total_cmp(&a.similarity)
+            .then(b.matching_sections.cmp(&a.matching_sections))
+            .then_with(|| a.path.cmp(&b.path))
+    });
+    duplicates.truncate(MAX_RESULTS);
+    duplicates
+}
+
+/// A first `# Heading` used by more than one tracked file.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub(crate) struct DuplicateTitle {
+    pub(crate) title: String,
+    /// Files with this title, sorted by path
+    pub(crate) files: Vec<String>,
+}
+
+/// Titles shared by two or more tracked files, sorted by title. A file's title here is
+/// the text of its first level-1 heading, compared exactly; the frontmatter `title`
+/// doesn't count. Filters the title index of [`MarkdownState::files_by_title`].
+pub(crate) fn duplicate_titles(state: &MarkdownState) -> Vec<DuplicateTitle> {
+    state
+        .files_by_title()
+        .iter()
+        .filter(|(_, files)| files.len() > 1)
+        .map(|(title, files)| DuplicateTitle {
+            title: title.clone(),
+            files: files.iter().cloned().collect(),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PARAGRAPH: &str = "The quick brown fox jumps over the lazy dog near the river bank.";
+
+    #[test]
+    fn test_identical_paragraphs_match() {
+        let markdown = format!("# A\n\n{PARAGRAPH}\n\nShort one.\n");
+        let other =
+            format!("# B\n\nIntro that is unrelated to anything else here.\n\n{PARAGRAPH}\n");
+
+        assert_eq!(
+            find_duplicates(&markdown, [("b.md", other.as_str())], DEFAULT_THRESHOLD),
+            vec![DuplicateMatch {
+                path: "b.md".to_string(),
+                similarity: 1.0,
+                matching_sections: 1,
+            }]
+        );
+    }
+
+    #[test]
+    fn test_threshold_filters_modified_paragraphs() {
+        let modified = "The quick brown fox jumps over the lazy dog near the old mill.";
+        let others = [("b.md", modified)];
+
+        // 9 shared words out of 13 distinct ones
+        let matches = find_duplicates(PARAGRAPH, others, 0.6);
+        assert_eq!(matches.len(), 1);
+        assert_eq!(matches[0].similarity, 0.69);
+        assert!(find_duplicates(PARAGRAPH, others, DEFAULT_THRESHOLD).is_empty());
+    }
+
+    #[test]
+    fn test_paragraphs_without_shared_shingles_are_not_compared() {
+        // Same words, but no five of them in the same order
+        let shuffled = "bank river the near dog lazy the over jumps fox brown quick the";
+
+        assert!(find_duplicates(PARAGRAPH, [("b.md", shuffled)], 0.0).is_empty());
+    }
+
+    #[test]
+    fn test_results_are_ranked_and_limited() {
+        let markdown = format!("{PARAGRAPH}\n\nA second paragraph that is long enough to count.\n");
+        let both = markdown.clone();
+        let one = PARAGRAPH.to_string();
+        let others: Vec<(String, String)> = (0..6)
+            .map(|i| (format!("{i}.md"), one.clone()))
+            .chain([("z.md".to_string(), both)])
+            .collect();
+
+        let matches = find_duplicates(
+            &markdown,
+            others.iter().map(|(p, m)| (p.as_str(), m.as_str())),
+            DEFAULT_THRESHOLD,
+        );
+
+        let paths: Vec<&str> = matches.iter().map(|m| m.path.as_str()).collect();
+        assert_eq!(paths, vec!["z.md", "0.md", "1.md", "2.md", "3.md"]);
+        assert_eq!(matches[0].matching_sections, 2);
+    }
+}
@@ -0,0 +1,519 @@
+//! Self-contained exports of a tracked file or the whole workspace, for sharing outside
+//! the server, and the sitemap of a statically exported site.
+
+use anyhow::{bail, Context, Result};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use markdown::mdast::Node;
+use percent_encoding::{percent_decode_str, utf8_percent_encode, AsciiSet, CONTROLS};
+use std::{
+    collections::{BTreeMap, HashMap},
+    fs,
+    io::{Cursor, Write},
+    ops::Range,
+    path::{Path, PathBuf},
+    process::Stdio,
+};
+use tokio::process::Command;
+use uuid::Uuid;
+use zip::{write::SimpleFileOptions, CompressionMethod, ZipWriter};
+
+use crate::app::{guess_image_content_type, is_image_file, MarkdownState};
+use crate::history::civil_from_days;
+use crate::parser;
+
+/// Stylesheet of exported documents unless `--export-css` names another one.
+pub(crate) const DEFAULT_CSS: &str = "\
+body { max-width: 48rem; margin: 2rem auto; padding: 0 1rem; font-family: -apple-system, BlinkMacSystemFont, 'Segoe UI', Helvetica, Arial, sans-serif; line-height: 1.6; color: #1f2328; }
+h1, h2 { border-bottom: 1px solid #d1d9e0; padding-bottom: 0.3em; }
+a { color: #0969da; }
+code { font-family: ui-monospace, SFMono-Regular, Menlo, Consolas, monospace; background: #f6f8fa; padding: 0.2em 0.4em; border-radius: 6px; }
+pre { background: #f6f8fa; padding: 1rem; overflow: auto; border-radius: 6px; }
+pre code { padding: 0; }
+blockquote { margin: 0; padding: 0 1em; color: #59636e; border-left: 0.25em solid #d1d9e0; }
+table { border-collapse: collapse; }
+th, td { border: 1px solid #d1d9e0; padding: 6px 13px; }
+img { max-width: 100%; }
+";
+
+/// The local image an `<img>` or markdown image `src` points at, resolved the way a
+/// browser viewing `relative_path` would: relative to the file's directory, or to
+/// `base_dir` when it starts with `/`.
+///
+/// Returns `None` for remote and `data:` URLs, files that aren't images or don't exist,
+/// and paths leading outside `base_dir`.
+pub(crate) fn resolve_local_image(
+    base_dir: &Path,
+    relative_path: &str,
+    src: &str,
+) -> Option<PathBuf> {
+    if src.starts_with("//") || src.starts_with("data:") || src.contains("://") {
+        return None;
+    }
+    let src = src.split(['?', '#']).next()?;
+    let src = percent_decode_str(src).decode_utf8().ok()?;
+    if !is_image_file(&src) {
+        return None;
+    }
+
+    let path = match src.strip_prefix('/') {
+        Some(from_root) => base_dir.join(from_root),
+        None => base_dir
+            .join(relative_path)
+            .parent()
+            .unwrap_or(base_dir)
+            .join(&*src),
+    };
+    let path = path.canonicalize().ok()?;
+    path.starts_with(base_dir.canonicalize().ok()?)
+        .then_some(path)
+}
+
+/// Replaces the `src` of every `<img>` tag in `html` with what `replace` returns for it,
+/// leaving tags for which it returns `None` alone. `replace` gets the value with `&amp;`
+/// decoded.
+fn replace_img_sources(html: &str, mut replace: impl FnMut(&str) -> Option<String>) -> String {
+    let mut output = String::with_capacity(html.len());
+    let mut rest = html;
+    while let Some(tag_start) = rest.find("<img") {
+        let tag_end = rest[tag_start..]
+            .find('>')
+            .map_or(rest.len(), |end| tag_start + end);
+        let tag = &rest[tag_start..tag_end];
+        output.push_str(&rest[..tag_start]);
+        match src_range(tag)
+            .and_then(|src| Some((replace(&tag[src.clone()].replace("&amp;", "&"))?, src)))
+        {
+            Some((new_src, src)) => {
+                output.push_str(&tag[..src.start]);
+                output.push_str(&new_src);
+                output.push_str(&tag[src.end..]);
+            }
+            None => output.push_str(tag),
+        }
+        rest = &rest[tag_end..];
+    }
+    output.push_str(rest);
+    output
+}
+
+/// Byte range of the quoted `src` attribute value in an `<img ...>` tag.
+fn src_range(tag: &str) -> Option<Range<usize>> {
+    ['"', '\''].into_iter().find_map(|quote| {
+        let start = tag.find(&format!("src={quote}"))? + 5;
+        let len = tag[start..].find(quote)?;
+        Some(start..start + len)
+    })
+}
+
+/// Replaces the `src` of every `<img>` tag in `html` that points at a local image with a
+/// base64 `data:` URI of that image. Other images are left alone.
+fn inline_images(html: &str, base_dir: &Path, relative_path: &str) -> String {
+    replace_img_sources(html, |src| {
+        let image = resolve_local_image(base_dir, relative_path, src)?;
+        let contents = fs::read(&image).ok()?;
+        Some(format!(
+            "data:{};base64,{}",
+            guess_image_content_type(&image.to_string_lossy()),
+            STANDARD.encode(contents)
+        ))
+    })
+}
+
+/// Renders `markdown`, the content of `relative_path` under `base_dir`, as a complete
+/// HTML5 document that needs nothing else to display: `stylesheet` is inlined and local
+/// images are embedded as `data:` URIs.
+///
+/// The title is the first `# Heading`, or the file name if there is none.
+pub(crate) fn render_standalone_html(
+    markdown: &str,
+    base_dir: &Path,
+    relative_path: &str,
+    stylesheet: &str,
+) -> Result<String> {
+    let body = inline_images(
+        &MarkdownState::markdown_to_html(markdown)?,
+        base_dir,
+        relative_path,
+    );
+    let title = parser::extract_summary(markdown).title.unwrap_or_else(|| {
+        relative_path
+            .rsplit('/')
+            .next()
+            .unwrap_or(relative_path)
+            .to_string()
+    });
+
+    Ok(format!(
+        "<!DOCTYPE html>\n\
+         <html lang=\"en\">\n\
+         <head>\n\
+         <meta charset=\"utf-8\">\n\
+         <meta name=\"viewport\" content=\"width=device-width, initial-scale=1\">\n\
+         <title>{}</title>\n\
+         <style>\n{stylesheet}</style>\n\
+         </head>\n\
+         <body>\n\
+         <main>\n{body}</main>\n\
+         </body>\n\
+         </html>\n",
+        parser::escape_html(&title)
+    ))
+}
+
+/// Temporary files removed when dropped, including when a timed out request is
+/// cancelled mid-conversion.
+struct TempFiles(Vec<PathBuf>);
+
+impl Drop for TempFiles {
+    fn drop(&mut self) {
+        for path in &self.0 {
+            let _ = fs::remove_file(path);
+        }
+    }
+}
+
+/// Converts `markdown` to PDF with the `pandoc` executable at `pandoc`, run in
+/// `working_dir` so that relative image paths resolve.
+///
+/// PDF output needs a LaTeX engine besides pandoc itself; when it is missing the error
+/// carries pandoc's message.
+pub(crate) async fn render_pdf(
+    pandoc: &Path,
+    markdown: &str,
+    working_dir: &Path,
+) -> Result<Vec<u8>> {
+    let id = Uuid::new_v4();
+    let input = std::env::temp_dir().join(format!("mdserve-{id}.md"));
+    let output = std::env::temp_dir().join(format!("mdserve-{id}.pdf"));
+    let _cleanup = TempFiles(vec![input.clone(), output.clone()]);
+
+    tokio::fs::write(&input, markdown)
+        .await
+        .context("Failed to write pandoc input")?;
+    let result = Command::new(pandoc)
+        .args(["--from=markdown", "--to=pdf", "-o"])
+        .arg(&output)
+        .arg(&input)
+        .current_dir(working_dir)
+        .stdin(Stdio::null())
+        .kill_on_drop(true)
+        .output()
+        .await
+        .with_context(|| format!("Failed to run {}", pandoc.display()))?;
+    if !result.status.success() {
+        bail!(
+            "pandoc failed ({}): {}",
+            result.status,
+            String::from_utf8_lossy(&result.stderr).trim()
+        );
+    }
+
+    tokio::fs::read(&output)
+        .await
+        .context("pandoc did not write a PDF")
+}
+
+/// Characters to escape in the image paths written into exported markdown.
+const URL_PATH: &AsciiSet = &CONTROLS
+    .add(b' ')
+    .add(b'"')
+    .add(b'<')
+    .add(b'>')
+    .add(b'(')
+    .add(b')')
+    .add(b'%');
+
+/// The local images `markdown` (`relative_path` under `base_dir`) references, each given
+/// a unique name in the archive's `images/` directory.
+#[derive(Default)]
+struct BundledImages {
+    /// Archive name by image path
+    names: HashMap<PathBuf, String>,
+    /// Images in the order they were first referenced
+    images: Vec<(String, PathBuf)>,
+}
+
+impl BundledImages {
+    /// The archive name of the image `src` points at, as a URL relative to the archived
+    /// markdown, or `None` if it isn't a local image.
+    fn url(&mut self, base_dir: &Path, relative_path: &str, src: &str) -> Option<String> {
+        let image = resolve_local_image(base_dir, relative_path, src)?;
+        let name = match self.names.get(&image) {
+            Some(name) => name.clone(),
+            None => {
+                let name = self.unique_name(&image);
+                self.names.insert(image.clone(), name.clone());
+                self.images.push((name.clone(), image));
+                name
+            }
+        };
+        Some(utf8_percent_encode(&name, URL_PATH).to_string())
+    }
+
+    /// `images/<file name>`, numbered when another image already has that name.
+    fn unique_name(&self, image: &Path) -> String {
+        let file_name = image.file_name().unwrap_or_default().to_string_lossy();
+        let (stem, extension) = file_name
+            .rsplit_once('.')
+            .map_or((&*file_name, String::new()), |(stem, extension)| {
+                (stem, format!(".{extension}"))
+            });
+        let taken = |name: &String| self.images.iter().any(|(taken, _)| taken == name);
+
+        let mut name = format!("images/{file_name}");
+        let mut n = 2;
+        while taken(&name) {
+            name = format!("images/{stem}-{n}{extension}");
+            n += 1;
+        }
+        name
+    }
+}
+
+/// The start offset of `url` within `source` where it is the destination after `marker`
+/// (`](` of an image, `]:` of a definition), if it is written there verbatim.
+fn destination_start(source: &str, marker: &str, url: &str) -> Option<usize> {
+    let after_marker = source.find(marker)? + marker.len();
+    let offset = source[after_marker..].find(url)?;
+    source[after_marker..after_marker + offset]
+        .trim_start_matches([' ', '\t', '\n', '<'])
+        .is_empty()
+        .then_some(after_marker + offset)
+}
+
+/// Rewrites the local image paths in `markdown` to point into `images/`, collecting the
+/// images on the way.
+fn rewrite_image_paths(
+    markdown: &str,
+    base_dir: &Path,
+    relative_path: &str,
+    images: &mut BundledImages,
+) -> String {
+    let Some(root) = parser::parse_mdast(markdown) else {
+        return markdown.to_string();
+    };
+
+    // Replacements of byte ranges of `markdown`, in document order
+    let mut edits: Vec<(Range<usize>, String)> = Vec::new();
+    parser::walk(&root, &mut |node| {
+        let Some(position) = node.position() else {
+            return;
+        };
+        let start = position.start.offset;
+        let source = &markdown[start..position.end.offset];
+        let (marker, url) = match node {
+            Node::Image(image) => ("](", &image.url),
+            Node::Definition(definition) => ("]:", &definition.url),
+            Node::Html(_) => {
+                let rewritten =
+                    replace_img_sources(source, |src| images.url(base_dir, relative_path, src));
+                if rewritten != source {
+                    edits.push((start..position.end.offset, rewritten));
+                }
+                return;
+            }
+            _ => return,
+        };
+        let Some(url_start) = destination_start(source, marker, url) else {
+            return;
+        };
+        if let Some(new_url) = images.url(base_dir, relative_path, url) {
+            let url_start = start + url_start;
+            edits.push((url_start..url_start + url.len(), new_url));
+        }
+    });
+
+    let mut rewritten = markdown.to_string();
+    for (range, replacement) in edits.into_iter().rev() {
+        rewritten.replace_range(range, &replacement);
+    }
+    rewritten
+}
+
+/// Builds a ZIP archive of the markdown of `relative_path` under `base_dir` together with
+/// the local images it references.
+///
+/// The markdown is stored at the root under its file name and the images in `images/`,
+/// with the image paths in the markdown rewritten to match.
+pub(crate) fn build_zip(markdown: &str, base_dir: &Path, relative_path: &str) -> Result<Vec<u8>> {
+    let mut images = BundledImages::default();
+    let markdown = rewrite_image_paths(markdown, base_dir, relative_path, &mut images);
+    let file_name = relative_path.rsplit('/').next().unwrap_or(relative_path);
+
+    let mut archive = ZipWriter::new(Cursor::new(Vec::new()));
+    let options = SimpleFileOptions::default().compression_method(CompressionMethod::Deflated);
+    archive.start_file(file_name, options)?;
+    archive.write_all(markdown.as_bytes())?;
+    for (name, path) in &images.images {
+        let contents =
+            fs::read(path).with_context(|| format!("Failed to read {}", path.display()))?;
+        archive.start_file(name.as_str(), options)?;
+        archive.write_all(&contents)?;
+    }
+
+    Ok(archive.finish()?.into_inner())
+}
+
+/// The local images `markdown`, the content of `relative_path` under `base_dir`,
+/// references in image syntax, link definitions or `<img>` tags.
+pub(crate) fn referenced_images(
+    markdown: &str,
+    base_dir: &Path,
+    relative_path: &str,
+) -> Vec<PathBuf> {
+    let Some(root) = parser::parse_mdast(markdown) else {
+        return Vec::new();
+    };
+
+    let mut images = Vec::new();
+    parser::walk(&root, &mut |node| match node {
+        Node::Image(image) => {
+            images.extend(resolve_local_image(base_dir, relative_path, &image.url))
+        }
+        Node::Definition(definition) => images.extend(resolve_local_image(
+            base_dir,
+            relative_path,
+            &definition.url,
+        )),
+        Node::Html(html) => {
+            replace_img_sources(&html.value, |src| {
+                images.extend(resolve_local_image(base_dir, relative_path, src));
+                None
+            });
+        }
+        _ => {}
+    });
+    images
+}
+
+/// Builds a ZIP archive of the whole workspace: `files`, the markdown of the tracked
+/// files by relative path, and `images`, image files by the relative path to store them
+/// at. Both keep the served directory's structure, so links between them still work.
+pub(crate) fn build_workspace_zip(
+    files: &[(String, String)],
+    images: &BTreeMap<String, PathBuf>,
+) -> Result<Vec<u8>> {
+    let mut archive = ZipWriter::new(Cursor::new(Vec::new()));
+    let options = SimpleFileOptions::default().compression_method(CompressionMethod::Deflated);
+    for (name, markdown) in files {
+        archive.start_file(name.as_str(), options)?;
+        archive.write_all(markdown.as_bytes())?;
+    }
+    for (name, path) in images {
+        let contents =
+            fs::read(path).with_context(|| format!("Failed to read {}", path.display()))?;
+        archive.start_file(name.as_str(), options)?;
+        archive.write_all(&contents)?;
+    }
+
+    Ok(archive.finish()?.into_inner())
+}
+
+/// Characters to escape in the paths of sitemap URLs.
+const SITEMAP_PATH: &AsciiSet = &URL_PATH.add(b'#').add(b'?');
+
+/// Builds a `sitemap.xml` with a `<url>` for each of `files`, given by relative path and
+/// modification time in Unix seconds. Each file is located at `public_url` followed by
+/// its path with `.html` instead of the markdown extension, as a static export names
+/// it. Files at the top level get priority 1.0 and nested ones 0.8.
+pub(crate) fn build_sitemap(public_url: &str, files: &[(String, u64)]) -> String {
+    let base = public_url.trim_end_matches('/');
+    let mut xml = String::from(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<urlset xmlns=\"http://www.sitemaps.org/schemas/sitemap/0.9\">\n",
+    );
+    for (path, modified) in files {
+        let page = match path.rsplit_once('.') {
+            Some((stem, extension))
+                if extension.eq_ignore_ascii_case("md")
+                    || extension.eq_ignore_ascii_case("markdown") =>
+            {
+                format!("{stem}.html")
+            }
+            _ => format!("{path}.html"),
+        };
+        let loc = format!("{base}/{}", utf8_percent_encode(&page, SITEMAP_PATH));
+        let (year, month, day) = civil_from_days((*modified / 86_400) as i64);
+        let priority = if path.contains('/') { "0.8" } else { "1.0" };
+        xml.push_str(&format!(
+            "  <url>\n    <loc>{}</loc>\n    <lastmod>{year:04}-{month:02}-{day:02}</lastmod>\n    <changefreq>weekly</changefreq>\n    <priority>{priority}</priority>\n  </url>\n",
+            parser::escape_html(&loc)
+        ));
+    }
+    xml.push_str("</urlset>\n");
+    xml
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_resolve_local_image() {
+        let temp_dir = tempdir().expect("Failed to create temp dir");
+        let base_dir = temp_dir.path().canonicalize().unwrap();
+        fs::create_dir_all(base_dir.join("guide/img")).unwrap();
+        fs::write(base_dir.join("guide/img/a b.png"), "png").unwrap();
+        fs::write(base_dir.join("logo.svg"), "svg").unwrap();
+        fs::write(base_dir.join("notes.txt"), "text").unwrap();
+
+        let resolve = |src| resolve_local_image(&base_dir, "guide/intro.md", src);
+        assert_eq!(
+            resolve("img/a%20b.png?v=2"),
+            Some(base_dir.join("guide/img/a b.png"))
+        );
+        assert_eq!(resolve("../logo.svg"), Some(base_dir.join("logo.svg")));
+        assert_eq!(resolve("/logo.svg"), Some(base_dir.join("logo.svg")));
+
+        for unresolved in [
+            "https://example.com/logo.svg",
+            "//example.com/logo.svg",
+            "data:image/png;base64,AAAA",
+            "../notes.txt",
+            "missing.png",
+            "../../outside.png",
+        ] {
+            assert_eq!(resolve(unresolved), None, "{unresolved}");
+        }
+    }
+
+    #[test]
+    fn test_inline_images_only_replaces_local_sources() {
+        let temp_dir = tempdir().expect("Failed to create temp dir");
+        let base_dir = temp_dir.path().canonicalize().unwrap();
+        fs::write(base_dir.join("dot.png"), [1, 2, 3]).unwrap();
+
+        let html = r#"<p><img src="dot.png" alt="Dot" /> <img alt='x' src='dot.png'> <img src="https://example.com/a.png" /></p>"#;
+        assert_eq!(
+            inline_images(html, &base_dir, "doc.md"),
+            r#"<p><img src="data:image/png;base64,AQID" alt="Dot" /> <img alt='x' src='data:image/png;base64,AQID'> <img src="https://example.com/a.png" /></p>"#
+        );
+    }
+
+    #[test]
+    fn test_build_sitemap() {
+        let files = [
+            ("index.md".to_string(), 1_709_164_800),
+            ("guide/a b&c.markdown".to_string(), 0),
+        ];
+
+        assert_eq!(
+            build_sitemap("https://docs.example.com/", &files),
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+             <urlset xmlns=\"http://www.sitemaps.org/schemas/sitemap/0.9\">\n\
+             \x20 <url>\n\
+             \x20   <loc>https://docs.example.com/index.html</loc>\n\
+             \x20   <lastmod>2024-02-29</lastmod>\n\
+             \x20   <changefreq>weekly</changefreq>\n\
+             \x20   <priority>1.0</priority>\n\
+             \x20 </url>\n\
+             \x20 <url>\n\
+             \x20   <loc>https://docs.example.com/guide/a%20b&amp;c.html</loc>\n\
+             \x20   <lastmod>1970-01-01</lastmod>\n\
+             \x20   <changefreq>weekly</changefreq>\n\
+             \x20   <priority>0.8</priority>\n\
+             \x20 </url>\n\
+             </urlset>\n"
+        );
+    }
+}
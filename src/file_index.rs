@@ -0,0 +1,82 @@
+//! Indexes from values found in the files' content, like frontmatter tags, to the files
+//! using them, updated one file at a time as files change.
+
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+/// Tracked files by key. Files are added with every key they have, possibly none, and
+/// removed again when they change or go away.
+#[derive(Debug, Default)]
+pub(crate) struct FileIndex {
+    files_by_key: BTreeMap<String, BTreeSet<String>>,
+    /// The keys each indexed file was added with
+    keys_by_file: HashMap<String, Vec<String>>,
+}
+
+impl FileIndex {
+    /// Whether `path` was added since it last changed.
+    pub(crate) fn contains(&self, path: &str) -> bool {
+        self.keys_by_file.contains_key(path)
+    }
+
+    /// Indexes `path` under `keys`, replacing the keys it had.
+    pub(crate) fn insert(&mut self, path: &str, keys: impl IntoIterator<Item = String>) {
+        self.remove(path);
+        let keys: Vec<String> = keys.into_iter().collect();
+        for key in &keys {
+            self.files_by_key
+                .entry(key.clone())
+                .or_default()
+                .insert(path.to_string());
+        }
+        self.keys_by_file.insert(path.to_string(), keys);
+    }
+
+    pub(crate) fn remove(&mut self, path: &str) {
+        let Some(keys) = self.keys_by_file.remove(path) else {
+            return;
+        };
+        for key in keys {
+            if let Some(files) = self.files_by_key.get_mut(&key) {
+                files.remove(path);
+                if files.is_empty() {
+                    self.files_by_key.remove(&key);
+                }
+            }
+        }
+    }
+
+    /// Every key with its files, both in order.
+    pub(crate) fn iter(&self) -> impl Iterator<Item = (&String, &BTreeSet<String>)> {
+        self.files_by_key.iter()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn keys(index: &FileIndex) -> Vec<(&str, Vec<&str>)> {
+        index
+            .iter()
+            .map(|(key, files)| (key.as_str(), files.iter().map(String::as_str).collect()))
+            .collect()
+    }
+
+    #[test]
+    fn test_insert_replaces_and_remove_forgets_keys() {
+        let mut index = FileIndex::default();
+        index.insert("b.md", ["rust".to_string(), "web".to_string()]);
+        index.insert("a.md", ["rust".to_string(), "rust".to_string()]);
+        index.insert("c.md", []);
+        assert!(index.contains("c.md"));
+        assert_eq!(
+            keys(&index),
+            [("rust", vec!["a.md", "b.md"]), ("web", vec!["b.md"])]
+        );
+
+        index.insert("b.md", ["cli".to_string()]);
+        index.remove("a.md");
+        assert!(!index.contains("a.md"));
+        assert_eq!(keys(&index), [("cli", vec!["b.md"])]);
+    }
+}
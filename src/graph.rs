@@ -0,0 +1,190 @@
+//! Link graph of the tracked files with per-file metadata, for visualizations, and the
+//! files nothing links to.
+//!
+//! Building it reads every file, so the result is cached for a few seconds and shared
+//! by all requests in that window.
+
+use markdown::mdast::Node;
+use serde::Serialize;
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use crate::app::MarkdownState;
+use crate::parser::{self, parse_mdast, walk};
+use crate::validate::resolve_internal_link;
+
+/// How long a built graph is served before it is built again.
+const TTL: Duration = Duration::from_secs(10);
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub(crate) struct GraphNode {
+    /// Path of the file
+    pub(crate) id: String,
+    pub(crate) title: Option<String>,
+    pub(crate) word_count: usize,
+    pub(crate) tag_count: usize,
+    /// Links in the file, to tracked files or not
+    pub(crate) link_count: usize,
+    /// Other files linking to this one
+    pub(crate) backlink_count: usize,
+}
+
+/// Links from one tracked file to another.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub(crate) struct GraphEdge {
+    pub(crate) source: String,
+    pub(crate) target: String,
+    /// How many times `source` links to `target`
+    pub(crate) count: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub(crate) struct WorkspaceGraph {
+    pub(crate) nodes: Vec<GraphNode>,
+    pub(crate) edges: Vec<GraphEdge>,
+}
+
+/// Builds the graph of the loaded files, nodes and edges sorted by path.
+pub(crate) fn build_graph(state: &MarkdownState) -> WorkspaceGraph {
+    let mut nodes = Vec::new();
+    let mut counts: BTreeMap<(String, String), usize> = BTreeMap::new();
+    for path in state.get_sorted_filenames() {
+        let markdown = state.tracked_files[&path].read().markdown.clone();
+        let mut link_count = 0;
+        if let Some(root) = parse_mdast(&markdown) {
+            walk(&root, &mut |node| {
+                let Node::Link(link) = node else {
+                    return;
+                };
+                link_count += 1;
+                if let Some(target) = resolve_internal_link(&path, &link.url)
+                    .filter(|target| *target != path && state.tracked_files.contains_key(target))
+                {
+                    *counts.entry((path.clone(), target)).or_default() += 1;
+                }
+            });
+        }
+
+        nodes.push(GraphNode {
+            title: state.file_title(&path),
+            word_count: state
+                .file_summary(&path)
+                .map_or(0, |summary| summary.word_count),
+            tag_count: parser::frontmatter_tags(&markdown).len(),
+            link_count,
+            backlink_count: 0,
+            id: path,
+        });
+    }
+
+    let edges: Vec<GraphEdge> = counts
+        .into_iter()
+        .map(|((source, target), count)| GraphEdge {
+            source,
+            target,
+            count,
+        })
+        .collect();
+    let mut backlinks: HashMap<&str, usize> = HashMap::new();
+    for edge in &edges {
+        *backlinks.entry(edge.target.as_str()).or_default() += 1;
+    }
+    for node in &mut nodes {
+        node.backlink_count = backlinks.get(node.id.as_str()).copied().unwrap_or(0);
+    }
+
+    WorkspaceGraph { nodes, edges }
+}
+
+/// A file no other tracked file links to.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub(crate) struct OrphanedFile {
+    pub(crate) path: String,
+    pub(crate) word_count: usize,
+}
+
+/// The tracked files without links from other tracked files, sorted by path. Root
+/// files and files left out of the orphan check are never listed, and neither is
+/// anything in single-file mode. Requests go through [`MarkdownState::orphans`].
+pub(crate) fn find_orphans(state: &MarkdownState) -> Vec<OrphanedFile> {
+    if !state.is_directory_mode {
+        return Vec::new();
+    }
+    state.load_all_files();
+
+    let paths = state.get_sorted_filenames();
+    let mut linked = HashSet::new();
+    for path in &paths {
+        let Some(links) = state.file_links(path) else {
+            continue;
+        };
+        linked.extend(
+            links
+                .iter()
+                .filter_map(|link| resolve_internal_link(path, &link.url))
+                .filter(|target| target != path),
+        );
+    }
+
+    let mut orphans: Vec<OrphanedFile> = paths
+        .into_iter()
+        .filter(|path| !linked.contains(path) && !state.is_orphan_check_exempt(path))
+        .map(|path| OrphanedFile {
+            word_count: state
+                .file_summary(&path)
+                .map_or(0, |summary| summary.word_count),
+            path,
+        })
+        .collect();
+    orphans.sort_by(|a, b| a.path.cmp(&b.path));
+    orphans
+}
+
+/// The last graph built and when, see [`TTL`].
+#[derive(Debug, Default)]
+pub(crate) struct GraphCache {
+    graph: Option<(Instant, Arc<WorkspaceGraph>)>,
+}
+
+impl GraphCache {
+    /// The cached graph, unless it has expired.
+    pub(crate) fn get(&self) -> Option<Arc<WorkspaceGraph>> {
+        let (built_at, graph) = self.graph.as_ref()?;
+        (built_at.elapsed() < TTL).then(|| graph.clone())
+    }
+
+    pub(crate) fn insert(&mut self, graph: WorkspaceGraph) -> Arc<WorkspaceGraph> {
+        let graph = Arc::new(graph);
+        self.graph = Some((Instant::now(), graph.clone()));
+        graph
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn graph() -> WorkspaceGraph {
+        WorkspaceGraph {
+            nodes: Vec::new(),
+            edges: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_graph_cache_returns_graph_until_expired() {
+        let mut cache = GraphCache::default();
+        assert_eq!(cache.get(), None);
+
+        cache.insert(graph());
+        assert_eq!(cache.get().as_deref(), Some(&graph()));
+
+        if let Some((built_at, _)) = &mut cache.graph {
+            *built_at -= TTL;
+        }
+        assert_eq!(cache.get(), None);
+    }
+}
@@ -0,0 +1,475 @@
+//! Commit history and authorship of tracked files from git, for servers run inside a
+//! repository.
+
+use serde::Serialize;
+use std::{collections::HashMap, ffi::OsStr, io::ErrorKind, path::Path, process::Command};
+
+/// How many commits are listed unless the request asks for another number.
+pub(crate) const DEFAULT_HISTORY_LIMIT: usize = 20;
+/// How many contributors are listed unless the request asks for another number.
+pub(crate) const DEFAULT_CONTRIBUTORS_LIMIT: usize = 10;
+
+/// Separates the fields of a commit in the `git log` output. Commits are separated by
+/// NUL (`-z`). Neither can appear in a name, email or subject, unlike `|` or newlines.
+const FIELD_SEPARATOR: char = '\x1f';
+const LOG_FORMAT: &str = "--pretty=format:%H%x1f%an%x1f%ae%x1f%aI%x1f%s";
+
+/// A commit that changed a file.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub(crate) struct CommitEntry {
+    pub(crate) hash: String,
+    pub(crate) author: String,
+    pub(crate) email: String,
+    /// Author date, ISO 8601
+    pub(crate) date: String,
+    pub(crate) subject: String,
+}
+
+/// Someone who committed changes to a file, identified by email address.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub(crate) struct Contributor {
+    /// Author name of the most recent commit
+    pub(crate) name: String,
+    pub(crate) email: String,
+    pub(crate) commits: usize,
+    /// Author date of the most recent commit as `YYYY-MM-DD`
+    pub(crate) last_commit: String,
+}
+
+/// Why git could not tell about a file.
+#[derive(Debug)]
+pub(crate) enum HistoryError {
+    /// git isn't installed, or the file isn't in a git repository
+    Unavailable(String),
+    /// The commit asked for doesn't exist or doesn't contain the file
+    NotFound(String),
+    Failed(String),
+}
+
+impl std::fmt::Display for HistoryError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            HistoryError::Unavailable(message)
+            | HistoryError::NotFound(message)
+            | HistoryError::Failed(message) => write!(f, "{message}"),
+        }
+    }
+}
+
+impl std::error::Error for HistoryError {}
+
+/// A line of a file with the commit that last changed it.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub(crate) struct BlameLine {
+    /// 1-based line number
+    pub(crate) line: usize,
+    pub(crate) content: String,
+    pub(crate) commit: String,
+    pub(crate) author: String,
+    /// Author date as `YYYY-MM-DD`, in the author's time zone
+    pub(crate) date: String,
+}
+
+/// Runs git with `args` in the directory of the file at `path`, returning its output.
+///
+/// Fails with [`HistoryError::Unavailable`] if git isn't installed or the file isn't in a
+/// repository, and [`HistoryError::Failed`] with git's error output otherwise.
+fn run_git<S: AsRef<OsStr>>(
+    path: &Path,
+    args: impl IntoIterator<Item = S>,
+) -> Result<String, HistoryError> {
+    let output = Command::new("git")
+        .args(args)
+        .current_dir(path.parent().unwrap_or(Path::new(".")))
+        .output()
+        .map_err(|e| match e.kind() {
+            ErrorKind::NotFound => HistoryError::Unavailable("git is not installed".to_string()),
+            _ => HistoryError::Failed(format!("Failed to run git: {e}")),
+        })?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        if stderr.contains("not a git repository") {
+            return Err(HistoryError::Unavailable(
+                "Not in a git repository".to_string(),
+            ));
+        }
+        return Err(HistoryError::Failed(stderr.trim().to_string()));
+    }
+    String::from_utf8(output.stdout)
+        .map_err(|_| HistoryError::Failed("git output is not valid UTF-8".to_string()))
+}
+
+/// Turns [`HistoryError::Failed`] errors whose message contains one of `errors` into
+/// `error`.
+fn reclassify(
+    result: Result<String, HistoryError>,
+    errors: &[&str],
+    error: fn(String) -> HistoryError,
+) -> Result<String, HistoryError> {
+    result.map_err(|e| match e {
+        HistoryError::Failed(message) if errors.iter().any(|e| message.contains(e)) => {
+            error(message)
+        }
+        e => e,
+    })
+}
+
+/// The last `limit` commits that changed the file at `path`, newest first, following it
+/// across renames. A file git doesn't track has no commits.
+pub(crate) fn file_history(path: &Path, limit: usize) -> Result<Vec<CommitEntry>, HistoryError> {
+    let max_count = format!("--max-count={limit}");
+    let output = run_git(
+        path,
+        [
+            OsStr::new("log"),
+            OsStr::new("--follow"),
+            OsStr::new("-z"),
+            OsStr::new(LOG_FORMAT),
+            OsStr::new(&max_count),
+            OsStr::new("--"),
+            path.as_os_str(),
+        ],
+    )?;
+    Ok(parse_log(&output))
+}
+
+/// The `limit` people who made the most commits to the file at `path`, following it
+/// across renames.
+pub(crate) fn file_contributors(
+    path: &Path,
+    limit: usize,
+) -> Result<Vec<Contributor>, HistoryError> {
+    let output = run_git(
+        path,
+        [
+            OsStr::new("log"),
+            OsStr::new("--follow"),
+            OsStr::new("-z"),
+            OsStr::new(LOG_FORMAT),
+            OsStr::new("--"),
+            path.as_os_str(),
+        ],
+    )?;
+    let mut contributors = contributors(&parse_log(&output));
+    contributors.truncate(limit);
+    Ok(contributors)
+}
+
+/// Groups `commits`, newest first, by author email, ignoring case. The most commits come
+/// first, then the most recent.
+fn contributors(commits: &[CommitEntry]) -> Vec<Contributor> {
+    let mut contributors: Vec<Contributor> = Vec::new();
+    let mut by_email: HashMap<String, usize> = HashMap::new();
+    for commit in commits {
+        let index = *by_email
+            .entry(commit.email.to_lowercase())
+            .or_insert_with(|| {
+                contributors.push(Contributor {
+                    name: commit.author.clone(),
+                    email: commit.email.clone(),
+                    commits: 0,
+                    last_commit: commit.date.chars().take(10).collect(),
+                });
+                contributors.len() - 1
+            });
+        contributors[index].commits += 1;
+    }
+    // Stable, so ties keep the order of their most recent commit
+    contributors.sort_by_key(|contributor| std::cmp::Reverse(contributor.commits));
+    contributors
+}
+
+/// Who last changed each line of the file at `path`. Lines changed since the last commit
+/// are attributed to `Not Committed Yet` with an all-zero commit hash.
+///
+/// A file that was never committed is [`HistoryError::Unavailable`].
+pub(crate) fn file_blame(path: &Path) -> Result<Vec<BlameLine>, HistoryError> {
+    let output = reclassify(
+        run_git(
+            path,
+            [
+                OsStr::new("blame"),
+                OsStr::new("--line-porcelain"),
+                OsStr::new("--"),
+                path.as_os_str(),
+            ],
+        ),
+        &["no such path", "does not have any commits"],
+        HistoryError::Unavailable,
+    )?;
+    Ok(parse_blame_output(&output))
+}
+
+/// Whether `hash` looks like a full or abbreviated commit hash, so it can't be taken for
+/// an option or a revision expression.
+pub(crate) fn is_commit_hash(hash: &str) -> bool {
+    (4..=40).contains(&hash.len()) && hash.bytes().all(|b| b.is_ascii_hexdigit())
+}
+
+/// The content of the file at `path` as of commit `hash`, from `git show`.
+///
+/// [`HistoryError::NotFound`] if there is no such commit or the file isn't in it.
+pub(crate) fn file_at_commit(path: &Path, hash: &str) -> Result<String, HistoryError> {
+    if !is_commit_hash(hash) {
+        return Err(HistoryError::NotFound(format!(
+            "Invalid commit hash '{hash}'"
+        )));
+    }
+    let file_name = path.file_name().unwrap_or_default().to_string_lossy();
+    reclassify(
+        run_git(path, ["show".to_string(), format!("{hash}:./{file_name}")]),
+        &[
+            "invalid object name",
+            "bad revision",
+            "does not exist in",
+            "exists on disk, but not in",
+        ],
+        HistoryError::NotFound,
+    )
+}
+
+/// Parses `git log -z` output in [`LOG_FORMAT`], skipping malformed records.
+fn parse_log(output: &str) -> Vec<CommitEntry> {
+    output
+        .split('\0')
+        .filter_map(|record| {
+            let mut fields = record.trim_start_matches('\n').split(FIELD_SEPARATOR);
+            let entry = CommitEntry {
+                hash: fields.next().filter(|hash| !hash.is_empty())?.to_string(),
+                author: fields.next()?.to_string(),
+                email: fields.next()?.to_string(),
+                date: fields.next()?.to_string(),
+                subject: fields.next()?.to_string(),
+            };
+            fields.next().is_none().then_some(entry)
+        })
+        .collect()
+}
+
+/// Parses `git blame --line-porcelain` output: for every line, a `<commit> <original
+/// line> <final line> [<group size>]` header, `key value` headers and the content
+/// prefixed by a tab.
+pub(crate) fn parse_blame_output(output: &str) -> Vec<BlameLine> {
+    let mut lines = Vec::new();
+    let mut commit = "";
+    let mut line = 0;
+    let mut author = "";
+    let mut author_time = 0;
+    let mut author_tz = "+0000";
+
+    for row in output.lines() {
+        if let Some(content) = row.strip_prefix('\t') {
+            lines.push(BlameLine {
+                line,
+                content: content.to_string(),
+                commit: commit.to_string(),
+                author: author.to_string(),
+                date: civil_date(author_time, author_tz),
+            });
+            continue;
+        }
+
+        let (key, value) = row.split_once(' ').unwrap_or((row, ""));
+        match key {
+            "author" => author = value,
+            "author-time" => author_time = value.parse().unwrap_or(0),
+            "author-tz" => author_tz = value,
+            _ if key.len() == 40 && key.bytes().all(|b| b.is_ascii_hexdigit()) => {
+                commit = key;
+                line = value
+                    .split(' ')
+                    .nth(1)
+                    .and_then(|n| n.parse().ok())
+                    .unwrap_or(0);
+            }
+            _ => {}
+        }
+    }
+    lines
+}
+
+/// The `YYYY-MM-DD` date of the Unix `timestamp` in the time zone `tz` (`+0100`).
+fn civil_date(timestamp: i64, tz: &str) -> String {
+    let offset_minutes = match (tz.get(..1), tz.get(1..3), tz.get(3..5)) {
+        (Some(sign), Some(hours), Some(minutes)) => {
+            let minutes =
+                hours.parse::<i64>().unwrap_or(0) * 60 + minutes.parse::<i64>().unwrap_or(0);
+            if sign == "-" {
+                -minutes
+            } else {
+                minutes
+            }
+        }
+        _ => 0,
+    };
+    let days = (timestamp + offset_minutes * 60).div_euclid(86_400);
+    let (year, month, day) = civil_from_days(days);
+    format!("{year:04}-{month:02}-{day:02}")
+}
+
+/// Year, month and day of the date `days` days after 1970-01-01, in the proleptic
+/// Gregorian calendar (Howard Hinnant's `civil_from_days`).
+pub(crate) fn civil_from_days(days: i64) -> (i64, i64, i64) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let day_of_era = z.rem_euclid(146_097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 {
+        month_index + 3
+    } else {
+        month_index - 9
+    };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_log() {
+        let output = "abc123\x1fJane Doe\x1fjane@example.com\x1f2024-05-01T10:00:00+02:00\x1fFix | \"quotes\" and \\ slashes\0\
+                      def456\x1fJohn\x1fjohn@example.com\x1f2024-04-30T09:00:00+00:00\x1fInitial commit";
+
+        assert_eq!(
+            parse_log(output),
+            vec![
+                CommitEntry {
+                    hash: "abc123".to_string(),
+                    author: "Jane Doe".to_string(),
+                    email: "jane@example.com".to_string(),
+                    date: "2024-05-01T10:00:00+02:00".to_string(),
+                    subject: "Fix | \"quotes\" and \\ slashes".to_string(),
+                },
+                CommitEntry {
+                    hash: "def456".to_string(),
+                    author: "John".to_string(),
+                    email: "john@example.com".to_string(),
+                    date: "2024-04-30T09:00:00+00:00".to_string(),
+                    subject: "Initial commit".to_string(),
+                },
+            ]
+        );
+        assert_eq!(parse_log(""), vec![]);
+    }
+
+    #[test]
+    fn test_contributors() {
+        let output = "a1\x1fJane Doe\x1fjane@example.com\x1f2024-05-01T10:00:00+02:00\x1fLatest\0\
+                      b2\x1fJohn\x1fjohn@example.com\x1f2024-04-20T09:00:00+00:00\x1fTweak\0\
+                      c3\x1fJane\x1fJane@Example.com\x1f2024-04-10T09:00:00+00:00\x1fEdit\0\
+                      d4\x1fAlex\x1falex@example.com\x1f2024-03-05T09:00:00+00:00\x1fFix\0\
+                      e5\x1fJane\x1fjane@example.com\x1f2024-03-01T09:00:00+00:00\x1fAdd";
+
+        assert_eq!(
+            contributors(&parse_log(output)),
+            vec![
+                Contributor {
+                    name: "Jane Doe".to_string(),
+                    email: "jane@example.com".to_string(),
+                    commits: 3,
+                    last_commit: "2024-05-01".to_string(),
+                },
+                Contributor {
+                    name: "John".to_string(),
+                    email: "john@example.com".to_string(),
+                    commits: 1,
+                    last_commit: "2024-04-20".to_string(),
+                },
+                Contributor {
+                    name: "Alex".to_string(),
+                    email: "alex@example.com".to_string(),
+                    commits: 1,
+                    last_commit: "2024-03-05".to_string(),
+                },
+            ]
+        );
+        assert_eq!(contributors(&[]), vec![]);
+    }
+
+    const BLAME_OUTPUT: &str = "\
+4f1a9c2e8b7d6a5f4e3d2c1b0a9f8e7d6c5b4a39 1 1 2
+author Jane Doe
+author-mail <jane@example.com>
+author-time 1705312800
+author-tz +0100
+committer Jane Doe
+committer-mail <jane@example.com>
+committer-time 1705312800
+committer-tz +0100
+summary Add guide
+boundary
+filename guide.md
+\t# Guide
+4f1a9c2e8b7d6a5f4e3d2c1b0a9f8e7d6c5b4a39 2 2
+author Jane Doe
+author-mail <jane@example.com>
+author-time 1705312800
+author-tz +0100
+committer Jane Doe
+committer-mail <jane@example.com>
+committer-time 1705312800
+committer-tz +0100
+summary Add guide
+boundary
+filename guide.md
+\t
+9e8d7c6b5a4f3e2d1c0b9a8f7e6d5c4b3a2f1e0d 2 3 1
+author John Smith
+author-mail <john@example.com>
+author-time 1709251200
+author-tz -0500
+committer John Smith
+committer-mail <john@example.com>
+committer-time 1709251200
+committer-tz -0500
+summary author-time 0 in the summary doesn't confuse the parser
+previous 4f1a9c2e8b7d6a5f4e3d2c1b0a9f8e7d6c5b4a39 guide.md
+filename guide.md
+\t\tIndented with a tab
+";
+
+    #[test]
+    fn test_parse_blame_output() {
+        let blame = parse_blame_output(BLAME_OUTPUT);
+
+        let lines: Vec<(usize, &str, &str, &str)> = blame
+            .iter()
+            .map(|l| {
+                (
+                    l.line,
+                    l.content.as_str(),
+                    l.author.as_str(),
+                    l.date.as_str(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            lines,
+            vec![
+                (1, "# Guide", "Jane Doe", "2024-01-15"),
+                (2, "", "Jane Doe", "2024-01-15"),
+                // 2024-03-01 00:00 UTC is still February 29th at UTC-5
+                (3, "\tIndented with a tab", "John Smith", "2024-02-29"),
+            ]
+        );
+        assert_eq!(blame[0].commit, "4f1a9c2e8b7d6a5f4e3d2c1b0a9f8e7d6c5b4a39");
+        assert_eq!(blame[2].commit, "9e8d7c6b5a4f3e2d1c0b9a8f7e6d5c4b3a2f1e0d");
+        assert_eq!(parse_blame_output(""), vec![]);
+    }
+
+    #[test]
+    fn test_civil_date() {
+        assert_eq!(civil_date(0, "+0000"), "1970-01-01");
+        assert_eq!(civil_date(951_782_400, "+0000"), "2000-02-29");
+        assert_eq!(civil_date(1_704_067_199, "+0000"), "2023-12-31");
+        assert_eq!(civil_date(1_704_067_199, "+0030"), "2024-01-01");
+        assert_eq!(civil_date(-1, "+0000"), "1969-12-31");
+    }
+}
@@ -0,0 +1,192 @@
+//! Responses to recent `PUT` requests by `Idempotency-Key`, so a client retrying after a
+//! timeout gets the original result instead of saving a second time.
+
+use axum::http::StatusCode;
+use lru::LruCache;
+use std::{
+    num::NonZeroUsize,
+    time::{Duration, Instant},
+};
+
+/// How many keys are remembered before the least recently used is forgotten.
+const CAPACITY: usize = 256;
+/// How long a response is replayed for.
+const TTL: Duration = Duration::from_secs(5 * 60);
+
+/// What a keyed request asked for: the MD5 of its file path, resource and body. A key
+/// only replays a response for the request it was first used with.
+pub(crate) type Fingerprint = md5::Digest;
+
+/// The [`Fingerprint`] of a `PUT` to `path` (the file and resource, as requested) with
+/// `body`.
+pub(crate) fn fingerprint(path: &str, body: &[u8]) -> Fingerprint {
+    let mut context = md5::Context::new();
+    context.consume(path.as_bytes());
+    // Paths can't contain NUL, so no path and body split the same bytes differently
+    context.consume([0]);
+    context.consume(body);
+    context.compute()
+}
+
+#[derive(Debug)]
+struct StoredResponse {
+    fingerprint: Fingerprint,
+    /// `None` while the request is still being handled
+    response: Option<(StatusCode, serde_json::Value)>,
+    stored_at: Instant,
+}
+
+/// What to do with a request carrying an `Idempotency-Key`, see
+/// [`IdempotencyStore::reserve`].
+#[derive(Debug, PartialEq)]
+pub(crate) enum Reservation {
+    /// The key is new and now held for this request until it is completed or released
+    Reserved,
+    /// The same request was handled before; this is its response
+    Replay(StatusCode, serde_json::Value),
+    /// The same request is being handled right now
+    InFlight,
+    /// The key was used for a different request
+    Mismatch,
+}
+
+#[derive(Debug)]
+pub(crate) struct IdempotencyStore {
+    responses: LruCache<String, StoredResponse>,
+}
+
+impl Default for IdempotencyStore {
+    fn default() -> Self {
+        IdempotencyStore {
+            responses: LruCache::new(NonZeroUsize::new(CAPACITY).expect("capacity is not zero")),
+        }
+    }
+}
+
+impl IdempotencyStore {
+    /// Looks `key` up for a request with `fingerprint`, holding the key for it if it
+    /// wasn't used yet or has expired.
+    pub(crate) fn reserve(&mut self, key: &str, fingerprint: Fingerprint) -> Reservation {
+        if let Some(stored) = self.responses.get(key) {
+            if stored.stored_at.elapsed() < TTL {
+                return match &stored.response {
+                    _ if stored.fingerprint != fingerprint => Reservation::Mismatch,
+                    Some((status, body)) => Reservation::Replay(*status, body.clone()),
+                    None => Reservation::InFlight,
+                };
+            }
+        }
+
+        self.responses.put(
+            key.to_string(),
+            StoredResponse {
+                fingerprint,
+                response: None,
+                stored_at: Instant::now(),
+            },
+        );
+        Reservation::Reserved
+    }
+
+    /// Stores the response to the request `key` was reserved for.
+    pub(crate) fn complete(&mut self, key: &str, status: StatusCode, body: serde_json::Value) {
+        if let Some(stored) = self.responses.get_mut(key) {
+            stored.response = Some((status, body));
+            stored.stored_at = Instant::now();
+        }
+    }
+
+    /// Frees a reserved key without a response, so the request can be retried.
+    pub(crate) fn release(&mut self, key: &str) {
+        if self
+            .responses
+            .peek(key)
+            .is_some_and(|stored| stored.response.is_none())
+        {
+            self.responses.pop(key);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn reserved(store: &mut IdempotencyStore, key: &str, fingerprint: Fingerprint) {
+        assert_eq!(store.reserve(key, fingerprint), Reservation::Reserved);
+    }
+
+    #[test]
+    fn test_idempotency_store_replays_completed_request() {
+        let mut store = IdempotencyStore::default();
+        let request = fingerprint("a.md", b"body");
+        reserved(&mut store, "key", request);
+        assert_eq!(store.reserve("key", request), Reservation::InFlight);
+
+        store.complete("key", StatusCode::OK, json!({ "markdown": "# A" }));
+        assert_eq!(
+            store.reserve("key", request),
+            Reservation::Replay(StatusCode::OK, json!({ "markdown": "# A" }))
+        );
+        reserved(&mut store, "other", request);
+    }
+
+    #[test]
+    fn test_idempotency_store_rejects_key_reused_for_other_request() {
+        let mut store = IdempotencyStore::default();
+        reserved(&mut store, "key", fingerprint("a.md", b"body"));
+        assert_eq!(
+            store.reserve("key", fingerprint("b.md", b"body")),
+            Reservation::Mismatch
+        );
+
+        store.complete("key", StatusCode::OK, json!(null));
+        assert_eq!(
+            store.reserve("key", fingerprint("a.md", b"other body")),
+            Reservation::Mismatch
+        );
+        assert_ne!(fingerprint("a.md", b"/x"), fingerprint("a.md/", b"x"));
+    }
+
+    #[test]
+    fn test_idempotency_store_release_frees_key() {
+        let mut store = IdempotencyStore::default();
+        let request = fingerprint("a.md", b"body");
+        reserved(&mut store, "key", request);
+
+        store.release("key");
+        reserved(&mut store, "key", request);
+
+        // Completed responses stay
+        store.complete("key", StatusCode::OK, json!(null));
+        store.release("key");
+        assert_eq!(
+            store.reserve("key", request),
+            Reservation::Replay(StatusCode::OK, json!(null))
+        );
+    }
+
+    #[test]
+    fn test_idempotency_store_forgets_least_recently_used() {
+        let mut store = IdempotencyStore::default();
+        let request = fingerprint("a.md", b"");
+        for i in 0..=CAPACITY {
+            reserved(&mut store, &i.to_string(), request);
+        }
+
+        reserved(&mut store, "0", request);
+        assert_eq!(store.reserve("2", request), Reservation::InFlight);
+    }
+
+    #[test]
+    fn test_idempotency_store_expires_entries() {
+        let mut store = IdempotencyStore::default();
+        let request = fingerprint("a.md", b"");
+        reserved(&mut store, "key", request);
+        store.complete("key", StatusCode::OK, json!(null));
+        store.responses.get_mut("key").unwrap().stored_at -= TTL;
+
+        reserved(&mut store, "key", fingerprint("b.md", b""));
+    }
+}
@@ -0,0 +1,158 @@
+//! Reading the markdown files out of an uploaded ZIP archive, for bulk imports.
+
+use std::{
+    fmt,
+    io::{Cursor, Read},
+    path::{Component, Path},
+};
+use zip::ZipArchive;
+
+use crate::app::{is_markdown_file, normalize_key};
+
+/// The markdown entries of an archive. Other entries are left out.
+#[derive(Debug, Default, PartialEq)]
+pub(crate) struct ArchiveContents {
+    /// Path and content of each UTF-8 markdown file, in archive order
+    pub(crate) files: Vec<(String, String)>,
+    /// Markdown files that aren't UTF-8 text
+    pub(crate) skipped: Vec<String>,
+    /// Entries whose path would leave the directory they are extracted to
+    pub(crate) errors: Vec<String>,
+}
+
+#[derive(Debug, PartialEq)]
+pub(crate) enum ImportError {
+    InvalidArchive(String),
+    /// The markdown files add up to more than the limit, in bytes
+    TooLarge(u64),
+}
+
+impl fmt::Display for ImportError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ImportError::InvalidArchive(e) => write!(f, "Invalid ZIP archive: {e}"),
+            ImportError::TooLarge(limit) => {
+                write!(f, "Archive content is larger than {limit} bytes")
+            }
+        }
+    }
+}
+
+impl std::error::Error for ImportError {}
+
+/// Reads the `.md` and `.markdown` entries of a ZIP archive, keyed by their path inside
+/// it. Nothing is extracted unless their uncompressed sizes add up to at most
+/// `max_size` bytes; the sizes are checked again while reading, since the archive's
+/// directory can't be trusted.
+pub(crate) fn read_archive(data: &[u8], max_size: u64) -> Result<ArchiveContents, ImportError> {
+    let mut archive = ZipArchive::new(Cursor::new(data))
+        .map_err(|e| ImportError::InvalidArchive(e.to_string()))?;
+
+    let mut declared_size: u64 = 0;
+    for index in 0..archive.len() {
+        let entry = archive
+            .by_index_raw(index)
+            .map_err(|e| ImportError::InvalidArchive(e.to_string()))?;
+        if entry.is_file() && is_markdown_file(Path::new(entry.name())) {
+            declared_size = declared_size.saturating_add(entry.size());
+        }
+    }
+    if declared_size > max_size {
+        return Err(ImportError::TooLarge(max_size));
+    }
+
+    let mut contents = ArchiveContents::default();
+    let mut remaining = max_size;
+    for index in 0..archive.len() {
+        let mut entry = archive
+            .by_index(index)
+            .map_err(|e| ImportError::InvalidArchive(e.to_string()))?;
+        let name = entry.name().to_string();
+        if !entry.is_file() || !is_markdown_file(Path::new(&name)) {
+            continue;
+        }
+        // `..`, absolute paths and drive prefixes could write outside the target
+        let Some(path) = entry.enclosed_name().filter(|path| {
+            path.components()
+                .all(|component| matches!(component, Component::Normal(_)))
+        }) else {
+            contents.errors.push(name);
+            continue;
+        };
+
+        let mut bytes = Vec::new();
+        entry
+            .by_ref()
+            .take(remaining.saturating_add(1))
+            .read_to_end(&mut bytes)
+            .map_err(|e| ImportError::InvalidArchive(e.to_string()))?;
+        remaining = remaining
+            .checked_sub(bytes.len() as u64)
+            .ok_or(ImportError::TooLarge(max_size))?;
+
+        let path = normalize_key(&path.to_string_lossy());
+        match String::from_utf8(bytes) {
+            Ok(markdown) => contents.files.push((path, markdown)),
+            Err(_) => contents.skipped.push(path),
+        }
+    }
+    Ok(contents)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+    use zip::{write::SimpleFileOptions, ZipWriter};
+
+    fn zip(entries: &[(&str, &[u8])]) -> Vec<u8> {
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        for (name, content) in entries {
+            writer
+                .start_file(*name, SimpleFileOptions::default())
+                .unwrap();
+            writer.write_all(content).unwrap();
+        }
+        writer.finish().unwrap().into_inner()
+    }
+
+    #[test]
+    fn test_read_archive() {
+        let data = zip(&[
+            ("docs/intro.md", b"# Intro"),
+            ("docs/image.png", b"png"),
+            ("notes.markdown", b"# Notes"),
+            ("binary.md", b"\xff\xfe"),
+            ("../escape.md", b"# Escape"),
+            ("/etc/absolute.md", b"# Absolute"),
+        ]);
+
+        assert_eq!(
+            read_archive(&data, 1024),
+            Ok(ArchiveContents {
+                files: vec![
+                    ("docs/intro.md".to_string(), "# Intro".to_string()),
+                    ("notes.markdown".to_string(), "# Notes".to_string()),
+                ],
+                skipped: vec!["binary.md".to_string()],
+                errors: vec!["../escape.md".to_string(), "/etc/absolute.md".to_string()],
+            })
+        );
+    }
+
+    #[test]
+    fn test_read_archive_limits_size() {
+        let data = zip(&[
+            ("a.md", b"12345"),
+            ("b.md", b"67890"),
+            ("big.png", &[0; 100]),
+        ]);
+
+        assert!(read_archive(&data, 10).is_ok());
+        assert_eq!(read_archive(&data, 9), Err(ImportError::TooLarge(9)));
+        assert!(matches!(
+            read_archive(b"not a zip", 10),
+            Err(ImportError::InvalidArchive(_))
+        ));
+    }
+}
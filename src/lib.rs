@@ -1,3 +1,23 @@
 // Minimal lib.rs to support integration tests
+mod api;
 pub mod app;
-pub use app::{new_router, scan_markdown_files, serve_markdown, ServerMessage};
+pub mod config;
+mod coverage;
+mod duplicates;
+mod export;
+mod file_index;
+mod graph;
+mod history;
+mod idempotency;
+mod import;
+pub mod lint;
+mod parser;
+mod session;
+mod telemetry;
+mod timeline;
+mod validate;
+pub use app::{
+    new_router, new_router_with_config, scan_markdown_files, scan_markdown_files_with_options,
+    serve_markdown, RouterConfig, ScanResult, ServerMessage,
+};
+pub use config::Config;
@@ -0,0 +1,481 @@
+//! Built-in markdown linter for content that hasn't been saved yet.
+//!
+//! Unlike [`crate::validate`], the checks only look at the document itself, never at
+//! the other tracked files.
+
+use markdown::mdast::Node;
+use serde::{Deserialize, Serialize};
+use std::{collections::BTreeMap, fmt, str::FromStr};
+
+use crate::parser::{node_source, parse_mdast, start_line, walk};
+
+/// How an issue found by a rule is reported.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Error,
+    Warning,
+    Info,
+    /// The rule isn't checked at all
+    Ignore,
+}
+
+impl FromStr for Severity {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "error" => Ok(Self::Error),
+            "warning" => Ok(Self::Warning),
+            "info" => Ok(Self::Info),
+            "ignore" => Ok(Self::Ignore),
+            _ => Err(format!(
+                "Unknown severity '{s}', expected 'error', 'warning', 'info' or 'ignore'"
+            )),
+        }
+    }
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Error => "error",
+            Self::Warning => "warning",
+            Self::Info => "info",
+            Self::Ignore => "ignore",
+        })
+    }
+}
+
+/// Every rule with its default severity, see [`lint_markdown`].
+pub const RULES: [(&str, Severity); 5] = [
+    ("heading-increment", Severity::Warning),
+    ("single-h1", Severity::Warning),
+    ("unclosed-code-fence", Severity::Error),
+    ("img-alt", Severity::Warning),
+    ("empty-link", Severity::Error),
+];
+
+/// Severities that replace the defaults in [`RULES`].
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct LintConfig {
+    /// Keyed by rule name. Names that aren't in [`RULES`] are never looked up.
+    pub rules: BTreeMap<String, Severity>,
+}
+
+impl LintConfig {
+    /// Checks that every rule in `rules` exists.
+    pub fn new(rules: BTreeMap<String, Severity>) -> Result<Self, String> {
+        if let Some(unknown) = rules
+            .keys()
+            .find(|rule| !RULES.iter().any(|(name, _)| name == rule))
+        {
+            return Err(format!("Unknown lint rule '{unknown}'"));
+        }
+        Ok(Self { rules })
+    }
+
+    pub fn severity(&self, rule: &str) -> Severity {
+        self.rules.get(rule).copied().unwrap_or_else(|| {
+            RULES
+                .iter()
+                .find(|(name, _)| *name == rule)
+                .map_or(Severity::Ignore, |(_, severity)| *severity)
+        })
+    }
+
+    fn is_checked(&self, rule: &str) -> bool {
+        self.severity(rule) != Severity::Ignore
+    }
+}
+
+/// Parses `rule=severity` pairs separated by commas, e.g.
+/// `img-alt=error,single-h1=ignore`.
+pub fn parse_lint_rules(spec: &str) -> Result<BTreeMap<String, Severity>, String> {
+    let rules = spec
+        .split(',')
+        .map(str::trim)
+        .filter(|pair| !pair.is_empty())
+        .map(|pair| {
+            let (rule, severity) = pair
+                .split_once('=')
+                .ok_or_else(|| format!("Expected 'rule=severity', got '{pair}'"))?;
+            Ok((rule.trim().to_string(), severity.trim().parse()?))
+        })
+        .collect::<Result<BTreeMap<_, _>, String>>()?;
+    LintConfig::new(rules).map(|config| config.rules)
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct LintIssue {
+    /// 1-based line number of the offending node
+    pub line: usize,
+    /// Short identifier of the rule, e.g. `heading-increment`
+    pub rule: &'static str,
+    pub message: String,
+}
+
+/// Problems found by [`lint_markdown`] by severity, each list in document order.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct LintReport {
+    pub errors: Vec<LintIssue>,
+    pub warnings: Vec<LintIssue>,
+    pub info: Vec<LintIssue>,
+}
+
+impl LintReport {
+    fn push(&mut self, config: &LintConfig, issue: LintIssue) {
+        match config.severity(issue.rule) {
+            Severity::Error => self.errors.push(issue),
+            Severity::Warning => self.warnings.push(issue),
+            Severity::Info => self.info.push(issue),
+            Severity::Ignore => {}
+        }
+    }
+}
+
+/// Lints `content` with the following rules, at the severity `config` gives them
+/// (default in parentheses). Rules set to [`Severity::Ignore`] aren't checked.
+///
+/// - `heading-increment` (warning): a heading skips a level, e.g. H1 followed by H3
+/// - `single-h1` (warning): the document doesn't start with an H1, or has more than one
+/// - `unclosed-code-fence` (error): a fenced code block runs to the end of the document
+/// - `img-alt` (warning): an HTML `<img>` tag has no `alt` attribute
+/// - `empty-link` (error): a link has no destination, e.g. `[text]()`
+pub fn lint_markdown(content: &str, config: &LintConfig) -> LintReport {
+    let mut report = LintReport::default();
+    if RULES.iter().all(|(rule, _)| !config.is_checked(rule)) {
+        return report;
+    }
+    let Some(root) = parse_mdast(content) else {
+        return report;
+    };
+    let check_increment = config.is_checked("heading-increment");
+    let check_h1 = config.is_checked("single-h1");
+    let check_fences = config.is_checked("unclosed-code-fence");
+    let check_img_alt = config.is_checked("img-alt");
+    let check_links = config.is_checked("empty-link");
+
+    let first_block = root
+        .children()
+        .into_iter()
+        .flatten()
+        .find(|child| !matches!(child, Node::Yaml(_) | Node::Toml(_)));
+    let starts_with_h1 = matches!(first_block, Some(Node::Heading(heading)) if heading.depth == 1);
+
+    let mut previous_level = None;
+    let mut h1_count = 0;
+    walk(&root, &mut |node| {
+        let line = start_line(node);
+        match node {
+            Node::Heading(heading) => {
+                if let Some(previous) =
+                    previous_level.filter(|&p| check_increment && heading.depth > p + 1)
+                {
+                    report.push(
+                        config,
+                        LintIssue {
+                            line,
+                            rule: "heading-increment",
+                            message: format!(
+                                "Heading level skips from H{previous} to H{}",
+                                heading.depth
+                            ),
+                        },
+                    );
+                }
+                previous_level = Some(heading.depth);
+
+                if heading.depth == 1 {
+                    h1_count += 1;
+                    if check_h1 && h1_count > 1 {
+                        report.push(
+                            config,
+                            LintIssue {
+                                line,
+                                rule: "single-h1",
+                                message: "Document has more than one H1 heading".to_string(),
+                            },
+                        );
+                    }
+                }
+            }
+            Node::Code(_) if check_fences && is_unclosed_fence(node_source(content, node)) => {
+                report.push(
+                    config,
+                    LintIssue {
+                        line,
+                        rule: "unclosed-code-fence",
+                        message: "Fenced code block is never closed".to_string(),
+                    },
+                );
+            }
+            Node::Html(html) if check_img_alt => {
+                for _ in 0..count_images_without_alt(&html.value) {
+                    report.push(
+                        config,
+                        LintIssue {
+                            line,
+                            rule: "img-alt",
+                            message: "HTML <img> tag is missing an alt attribute".to_string(),
+                        },
+                    );
+                }
+            }
+            Node::Link(link) if check_links && link.url.trim().is_empty() => {
+                report.push(
+                    config,
+                    LintIssue {
+                        line,
+                        rule: "empty-link",
+                        message: format!("Link '{}' has an empty destination", node.to_string()),
+                    },
+                );
+            }
+            _ => {}
+        }
+    });
+
+    if check_h1 && !starts_with_h1 {
+        let message = if h1_count == 0 {
+            "Document has no H1 heading"
+        } else {
+            "Document should start with an H1 heading"
+        };
+        report.push(
+            config,
+            LintIssue {
+                line: first_block.map_or(1, start_line),
+                rule: "single-h1",
+                message: message.to_string(),
+            },
+        );
+    }
+
+    // The missing H1 is only known at the end
+    for issues in [&mut report.errors, &mut report.warnings, &mut report.info] {
+        issues.sort_by_key(|issue| issue.line);
+    }
+    report
+}
+
+/// Whether a code block's source opens with a fence that no later line closes.
+///
+/// Indented code blocks have no fence and are never unclosed.
+fn is_unclosed_fence(source: &str) -> bool {
+    let mut lines = source.lines();
+    let Some((fence_char, fence_len)) = lines.next().and_then(fence) else {
+        return false;
+    };
+    // Inside block quotes every line carries the `>` markers as well
+    let closed = lines
+        .last()
+        .and_then(fence)
+        .is_some_and(|(c, len)| c == fence_char && len >= fence_len);
+    !closed
+}
+
+/// The fence character and length a line starts with, ignoring indentation and block
+/// quote markers.
+fn fence(line: &str) -> Option<(char, usize)> {
+    let line = line.trim_start_matches(|c: char| c.is_whitespace() || c == '>');
+    let fence_char = line.chars().next().filter(|c| matches!(c, '`' | '~'))?;
+    let len = line.chars().take_while(|&c| c == fence_char).count();
+    (len >= 3).then_some((fence_char, len))
+}
+
+/// Counts `<img>` tags in an HTML fragment that have no `alt` attribute.
+fn count_images_without_alt(html: &str) -> usize {
+    let lower = html.to_ascii_lowercase();
+    lower
+        .match_indices("<img")
+        .filter(|(start, _)| {
+            let tag = &lower[*start..];
+            let tag = &tag[..tag.find('>').map_or(tag.len(), |end| end + 1)];
+            // Attributes are separated by whitespace, so ` alt=` can't be part of `data-alt=`
+            !tag.split_whitespace()
+                .skip(1)
+                .any(|attribute| attribute == "alt" || attribute.starts_with("alt="))
+        })
+        .count()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lint(content: &str) -> LintReport {
+        lint_markdown(content, &LintConfig::default())
+    }
+
+    fn rules(issues: &[LintIssue]) -> Vec<(usize, &'static str)> {
+        issues
+            .iter()
+            .map(|issue| (issue.line, issue.rule))
+            .collect()
+    }
+
+    #[test]
+    fn test_clean_document_has_no_issues() {
+        let content = "---\ntitle: Clean\n---\n# Title\n\n## Section\n\n```rust\nfn main() {}\n```\n\n<img src=\"a.png\" alt=\"A\">\n\n[Link](other.md)\n";
+
+        assert_eq!(lint(content), LintReport::default());
+    }
+
+    #[test]
+    fn test_heading_increment() {
+        let report = lint("# Title\n\n### Too deep\n\n## Fine\n\n#### Too deep again\n");
+
+        assert_eq!(
+            rules(&report.warnings),
+            vec![(3, "heading-increment"), (7, "heading-increment")]
+        );
+        assert_eq!(
+            report.warnings[0].message,
+            "Heading level skips from H1 to H3"
+        );
+        assert!(report.errors.is_empty());
+    }
+
+    #[test]
+    fn test_single_h1() {
+        let missing = lint("## Section\n\nText\n");
+        assert_eq!(rules(&missing.warnings), vec![(1, "single-h1")]);
+        assert_eq!(missing.warnings[0].message, "Document has no H1 heading");
+
+        let late = lint("Intro\n\n# Title\n");
+        assert_eq!(rules(&late.warnings), vec![(1, "single-h1")]);
+        assert_eq!(
+            late.warnings[0].message,
+            "Document should start with an H1 heading"
+        );
+
+        let multiple = lint("# One\n\n# Two\n");
+        assert_eq!(rules(&multiple.warnings), vec![(3, "single-h1")]);
+        assert_eq!(
+            multiple.warnings[0].message,
+            "Document has more than one H1 heading"
+        );
+    }
+
+    #[test]
+    fn test_unclosed_code_fence() {
+        let report = lint("# Title\n\n```rust\nfn main() {}\n");
+        assert_eq!(rules(&report.errors), vec![(3, "unclosed-code-fence")]);
+
+        let short_close = lint("# Title\n\n~~~~\ncode\n~~~\n");
+        assert_eq!(rules(&short_close.errors), vec![(3, "unclosed-code-fence")]);
+
+        for closed in [
+            "# Title\n\n~~~\ncode\n~~~~\n",
+            "# Title\n\n> ```\n> quoted\n> ```\n",
+            "# Title\n\n    indented code\n",
+        ] {
+            assert!(lint(closed).errors.is_empty(), "{closed}");
+        }
+    }
+
+    #[test]
+    fn test_img_alt() {
+        let report = lint(
+            "# Title\n\n<img src=\"a.png\">\n\n<p><IMG data-alt=\"x\" src=\"b.png\"/> <img alt src=\"c.png\"></p>\n",
+        );
+
+        assert_eq!(
+            rules(&report.warnings),
+            vec![(3, "img-alt"), (5, "img-alt")]
+        );
+    }
+
+    #[test]
+    fn test_empty_link() {
+        let report = lint("# Title\n\nSee [nothing]() and [something](a.md).\n");
+
+        assert_eq!(rules(&report.errors), vec![(3, "empty-link")]);
+        assert_eq!(
+            report.errors[0].message,
+            "Link 'nothing' has an empty destination"
+        );
+    }
+
+    #[test]
+    fn test_configured_severities() {
+        let config = LintConfig::new(BTreeMap::from([
+            ("empty-link".to_string(), Severity::Warning),
+            ("heading-increment".to_string(), Severity::Info),
+            ("single-h1".to_string(), Severity::Ignore),
+            ("img-alt".to_string(), Severity::Error),
+        ]))
+        .expect("Valid rules");
+        let report = lint_markdown(
+            "## Section
+
+#### Deep
+
+[nothing]()
+
+<img src=\"a.png\">
+
+```
+code
+",
+            &config,
+        );
+
+        assert_eq!(
+            rules(&report.errors),
+            vec![(7, "img-alt"), (9, "unclosed-code-fence")]
+        );
+        assert_eq!(rules(&report.warnings), vec![(5, "empty-link")]);
+        assert_eq!(rules(&report.info), vec![(3, "heading-increment")]);
+    }
+
+    #[test]
+    fn test_all_rules_ignored() {
+        let config = LintConfig::new(
+            RULES
+                .iter()
+                .map(|(rule, _)| (rule.to_string(), Severity::Ignore))
+                .collect(),
+        )
+        .expect("Valid rules");
+
+        assert_eq!(
+            lint_markdown(
+                "## Section
+
+[nothing]()
+",
+                &config
+            ),
+            LintReport::default()
+        );
+    }
+
+    #[test]
+    fn test_parse_lint_rules() {
+        assert_eq!(
+            parse_lint_rules("img-alt=error, single-h1 = ignore,"),
+            Ok(BTreeMap::from([
+                ("img-alt".to_string(), Severity::Error),
+                ("single-h1".to_string(), Severity::Ignore),
+            ]))
+        );
+        assert_eq!(parse_lint_rules(""), Ok(BTreeMap::new()));
+        assert_eq!(
+            parse_lint_rules("missing-h1=ignore"),
+            Err("Unknown lint rule 'missing-h1'".to_string())
+        );
+        assert_eq!(
+            parse_lint_rules("img-alt=fatal"),
+            Err(
+                "Unknown severity 'fatal', expected 'error', 'warning', 'info' or 'ignore'"
+                    .to_string()
+            )
+        );
+        assert_eq!(
+            parse_lint_rules("img-alt"),
+            Err("Expected 'rule=severity', got 'img-alt'".to_string())
+        );
+    }
+}
@@ -1,8 +1,11 @@
 use anyhow::Result;
 use clap::Parser;
-use std::path::PathBuf;
+use std::{collections::BTreeMap, path::PathBuf};
+use tracing_subscriber::EnvFilter;
 
-use mdserve::{scan_markdown_files, serve_markdown};
+use mdserve::config::{ServerConfig, WatchConfig};
+use mdserve::lint::{parse_lint_rules, Severity};
+use mdserve::{scan_markdown_files_with_options, serve_markdown, Config};
 
 #[derive(Parser)]
 #[command(name = "mdserve")]
@@ -10,21 +13,249 @@ use mdserve::{scan_markdown_files, serve_markdown};
 #[command(version)]
 struct Args {
     /// Path to markdown file or directory to serve
-    path: PathBuf,
+    path: Option<PathBuf>,
 
-    /// Hostname (domain or IP address) to listen on
-    #[arg(short = 'H', long, default_value = "127.0.0.1")]
-    hostname: String,
+    /// Load settings from a TOML config file (flags on the command line take precedence)
+    #[arg(short, long, value_name = "FILE")]
+    config: Option<PathBuf>,
 
-    /// Port to serve on
-    #[arg(short, long, default_value = "3000")]
-    port: u16,
+    /// Hostname (domain or IP address) to listen on [default: 127.0.0.1]
+    #[arg(short = 'H', long)]
+    hostname: Option<String>,
+
+    /// Port to serve on [default: 3000]
+    #[arg(short, long)]
+    port: Option<u16>,
+
+    /// Milliseconds to wait before rescanning the directory after a rename or removal [default: 200]
+    #[arg(long, value_name = "MS")]
+    rescan_delay: Option<u64>,
+
+    /// Read markdown files on first request instead of at startup (for very large directories)
+    #[arg(long, overrides_with = "no_lazy_load")]
+    lazy_load: bool,
+
+    /// Read every markdown file at startup, overriding `lazy_load` in the config file
+    #[arg(long, overrides_with = "lazy_load")]
+    no_lazy_load: bool,
+
+    /// Stream file API responses for files larger than this many bytes [default: 1048576]
+    #[arg(long, value_name = "BYTES")]
+    streaming_threshold: Option<usize>,
+
+    /// Restore directory state from this file at startup and save it on shutdown
+    #[arg(long, value_name = "FILE")]
+    snapshot_file: Option<PathBuf>,
+
+    /// Warn about markdown files whose frontmatter lacks this field (repeatable)
+    #[arg(long = "require-frontmatter", value_name = "FIELD")]
+    require_frontmatter: Vec<String>,
+
+    /// Poll the file system for changes instead of relying on native events (for network
+    /// mounts, containers and other setups where live reload doesn't fire)
+    #[arg(long, overrides_with = "no_poll")]
+    poll: bool,
+
+    /// Use native file system events, overriding `poll` in the config file
+    #[arg(long, overrides_with = "poll")]
+    no_poll: bool,
+
+    /// Milliseconds between polls when `--poll` is used [default: 1000]
+    #[arg(long, value_name = "MS")]
+    watch_interval: Option<u64>,
+
+    /// Don't re-create the file watcher when it loses the served directory (for debugging)
+    #[arg(long)]
+    no_watch_recovery: bool,
+
+    /// Refuse WebSocket connections beyond this many at once [default: 100]
+    #[arg(long, value_name = "N")]
+    max_ws_connections: Option<usize>,
+
+    /// Answer requests still running after this many seconds with 504 Gateway Timeout
+    /// [default: 30]
+    #[arg(long, value_name = "SECONDS")]
+    timeout: Option<u64>,
+
+    /// Accept `DELETE /api/files`, which deletes many files at once
+    #[arg(long, overrides_with = "no_allow_bulk_delete")]
+    allow_bulk_delete: bool,
+
+    /// Refuse `DELETE /api/files`, overriding `allow_bulk_delete` in the config file
+    #[arg(long, overrides_with = "allow_bulk_delete")]
+    no_allow_bulk_delete: bool,
+
+    /// List each file's title (frontmatter `title`, else the first `# Heading`) in
+    /// `GET /api/files`
+    #[arg(long, overrides_with = "no_title_from_frontmatter")]
+    title_from_frontmatter: bool,
+
+    /// Leave titles out of `GET /api/files`, overriding `title_from_frontmatter` in the config file
+    #[arg(long, overrides_with = "title_from_frontmatter")]
+    no_title_from_frontmatter: bool,
+
+    /// Stylesheet to inline into documents from `GET /api/files/<path>/export/html`
+    /// instead of the built-in one
+    #[arg(long, value_name = "FILE")]
+    export_css: Option<PathBuf>,
+
+    /// pandoc executable for `GET /api/files/<path>/export/pdf`, if it isn't in `PATH`
+    #[arg(long, value_name = "PATH")]
+    pandoc_path: Option<PathBuf>,
+
+    /// Don't run git for the `history` resource of `/api/files/<path>`, nor for the
+    /// ones `--enable-git` turns on
+    #[arg(long)]
+    no_git: bool,
+
+    /// Serve the `contributors`, `blame` and `restore` resources of `/api/files/<path>`
+    /// from git. `POST .../restore/<hash>` overwrites the file with an old version.
+    #[arg(long, overrides_with = "no_enable_git")]
+    enable_git: bool,
+
+    /// Don't serve the git resources `--enable-git` turns on, overriding `enable_git` in the config file
+    #[arg(long, overrides_with = "enable_git")]
+    no_enable_git: bool,
+
+    /// Check at startup that every tracked file still exists, matches its content in
+    /// memory and is inside the served directory, and log what doesn't
+    #[arg(long, overrides_with = "no_verify_on_startup")]
+    verify_on_startup: bool,
+
+    /// Skip the startup check, overriding `verify_on_startup` in the config file
+    #[arg(long, overrides_with = "verify_on_startup")]
+    no_verify_on_startup: bool,
+
+    /// Severities of the lint rules, e.g. `img-alt=error,single-h1=ignore`. Severities
+    /// are `error`, `warning`, `info` and `ignore`
+    #[arg(long, value_name = "RULE=SEVERITY,...", value_parser = parse_lint_rules)]
+    lint_rules: Option<BTreeMap<String, Severity>>,
+
+    /// Answer `GET /api/files?include_content=true` with 400 Bad Request when it would
+    /// return more than this many files [default: 50]
+    #[arg(long, value_name = "N")]
+    max_inline_content: Option<usize>,
+
+    /// Reject `POST /api/workspace/import` uploads, or the markdown they contain, above
+    /// this many bytes [default: 52428800]
+    #[arg(long, value_name = "BYTES")]
+    max_import_size: Option<usize>,
+
+    /// Answer `GET /api/workspace/export` with 413 when the files and images add up to
+    /// more than this many bytes [default: 209715200]
+    #[arg(long, value_name = "BYTES")]
+    max_export_size: Option<usize>,
+
+    /// Never report files matching this glob as orphaned, e.g. a landing page (repeatable)
+    #[arg(long = "root-files", value_name = "PATTERN")]
+    root_files: Vec<String>,
+
+    /// Leave files matching this glob out of the orphan check, instead of index pages
+    /// and READMEs (repeatable)
+    #[arg(long = "no-orphan-check", value_name = "GLOB")]
+    no_orphan_check: Vec<String>,
+
+    /// URL a static export of the files is published at, e.g.
+    /// `https://docs.example.com`. Required for `GET /api/workspace/sitemap.xml`
+    #[arg(long, value_name = "URL")]
+    public_url: Option<String>,
+
+    /// Serve Prometheus metrics at `/__metrics`
+    #[arg(long, overrides_with = "no_metrics")]
+    metrics: bool,
+
+    /// Don't serve metrics, overriding `metrics` in the config file
+    #[arg(long, overrides_with = "metrics")]
+    no_metrics: bool,
+
+    /// Also serve hidden files and directories (names starting with `.`, e.g. `.github`)
+    #[arg(long, overrides_with = "no_include_hidden")]
+    include_hidden: bool,
+
+    /// Skip hidden files and directories, overriding `include_hidden` in the config file
+    #[arg(long, overrides_with = "include_hidden")]
+    no_include_hidden: bool,
+}
+
+impl Args {
+    /// Converts the flags into a [`Config`] so they can be layered over a config file.
+    fn into_config(self) -> Config {
+        Config {
+            server: ServerConfig {
+                path: self.path,
+                hostname: self.hostname,
+                port: self.port,
+                lazy_load: flag(self.lazy_load, self.no_lazy_load),
+                streaming_threshold: self.streaming_threshold,
+                snapshot_file: self.snapshot_file,
+                require_frontmatter: self.require_frontmatter,
+                metrics: flag(self.metrics, self.no_metrics),
+                max_ws_connections: self.max_ws_connections,
+                timeout: self.timeout,
+                allow_bulk_delete: flag(self.allow_bulk_delete, self.no_allow_bulk_delete),
+                title_from_frontmatter: flag(
+                    self.title_from_frontmatter,
+                    self.no_title_from_frontmatter,
+                ),
+                export_css: self.export_css,
+                pandoc_path: self.pandoc_path,
+                git: self.no_git.then_some(false),
+                enable_git: flag(self.enable_git, self.no_enable_git),
+                verify_on_startup: flag(self.verify_on_startup, self.no_verify_on_startup),
+                lint_rules: self.lint_rules.unwrap_or_default(),
+                max_inline_content: self.max_inline_content,
+                max_import_size: self.max_import_size,
+                max_export_size: self.max_export_size,
+                root_files: self.root_files,
+                no_orphan_check: (!self.no_orphan_check.is_empty()).then_some(self.no_orphan_check),
+                public_url: self.public_url,
+            },
+            watch: WatchConfig {
+                rescan_delay: self.rescan_delay,
+                include_hidden: flag(self.include_hidden, self.no_include_hidden),
+                poll: flag(self.poll, self.no_poll),
+                watch_interval: self.watch_interval,
+                recovery: self.no_watch_recovery.then_some(false),
+                ..WatchConfig::default()
+            },
+        }
+    }
+}
+
+/// The value of a flag with a `--no-…` counterpart, `None` when neither was passed so the
+/// config file decides.
+fn flag(set: bool, unset: bool) -> Option<bool> {
+    match (set, unset) {
+        (true, _) => Some(true),
+        (_, true) => Some(false),
+        _ => None,
+    }
 }
 
 #[tokio::main]
 async fn main() -> Result<()> {
+    // Diagnostics go to stderr; e.g. `RUST_LOG=mdserve=trace` shows every file system event
+    tracing_subscriber::fmt()
+        .with_env_filter(
+            EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("warn")),
+        )
+        .with_writer(std::io::stderr)
+        .init();
+
     let args = Args::parse();
-    let absolute_path = args.path.canonicalize().unwrap_or(args.path);
+    let file_config = match &args.config {
+        Some(config_path) => Config::load(config_path)?,
+        None => Config::default(),
+    };
+    let config = file_config.merge(args.into_config());
+    if config.watch.watch_interval.is_some() && config.poll_interval().is_none() {
+        eprintln!("⚠️  --watch-interval only takes effect together with --poll; ignoring it");
+    }
+
+    let Some(path) = config.server.path.clone() else {
+        anyhow::bail!("No path given: pass one on the command line or set `path` in the [server] section of the config file");
+    };
+    let absolute_path = path.canonicalize().unwrap_or(path);
 
     let (base_dir, tracked_files, is_directory_mode) = if absolute_path.is_file() {
         // Single-file mode: derive parent directory
@@ -36,7 +267,11 @@ async fn main() -> Result<()> {
         (base_dir, tracked_files, false)
     } else if absolute_path.is_dir() {
         // Directory mode: scan directory for markdown files
-        let tracked_files = scan_markdown_files(&absolute_path)?;
+        let scan = scan_markdown_files_with_options(&absolute_path, config.include_hidden())?;
+        for (path, reason) in &scan.skipped {
+            eprintln!("⚠️  Skipping {}: {reason}", path.display());
+        }
+        let tracked_files = scan.files;
         if tracked_files.is_empty() {
             anyhow::bail!("No markdown files found in directory");
         }
@@ -50,10 +285,52 @@ async fn main() -> Result<()> {
         base_dir,
         tracked_files,
         is_directory_mode,
-        args.hostname,
-        args.port,
+        config.hostname(),
+        config.port(),
+        config.router_config(),
     )
     .await?;
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn test_no_flags_override_config_file_true() {
+        let temp_dir = tempfile::tempdir().expect("Failed to create temp dir");
+        let config_path = temp_dir.path().join("mdserve.toml");
+        fs::write(
+            &config_path,
+            "[server]\nlazy_load = true\nmetrics = true\nallow_bulk_delete = true\n\n[watch]\npoll = true\n",
+        )
+        .expect("Failed to write config");
+        let file_config = Config::load(&config_path).expect("Failed to load config");
+
+        let args = Args::try_parse_from(["mdserve", "--no-lazy-load", "--no-poll", "--metrics"])
+            .expect("Failed to parse args");
+        let config = file_config.merge(args.into_config());
+
+        assert_eq!(config.server.lazy_load, Some(false));
+        assert_eq!(config.watch.poll, Some(false));
+        assert_eq!(config.server.metrics, Some(true));
+        assert_eq!(config.server.allow_bulk_delete, Some(true));
+
+        // The last of a flag and its `--no-…` counterpart wins
+        let args = Args::try_parse_from([
+            "mdserve",
+            "--no-metrics",
+            "--metrics",
+            "--include-hidden",
+            "--no-include-hidden",
+        ])
+        .expect("Failed to parse args");
+        let config = args.into_config();
+        assert_eq!(config.server.metrics, Some(true));
+        assert_eq!(config.watch.include_hidden, Some(false));
+        assert_eq!(config.server.enable_git, None);
+    }
+}
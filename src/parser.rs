@@ -0,0 +1,2592 @@
+//! Pure functions that extract structured data from markdown source.
+//!
+//! Everything here works on the raw markdown text and never touches server state,
+//! so the API handlers can call these on any tracked file (or on posted content).
+
+use markdown::mdast::Node;
+use serde::Serialize;
+use similar::{ChangeTag, TextDiff};
+use std::collections::HashMap;
+
+/// A GFM task list item (`- [ ] text` or `- [x] text`).
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Task {
+    /// 1-based line number of the list item
+    pub line: usize,
+    pub text: String,
+    pub checked: bool,
+}
+
+/// A footnote (`[^id]: text`) or link reference definition (`[id]: url "title"`).
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ReferenceEntry {
+    /// The label as written, without `^` for footnotes
+    pub id: String,
+    /// Text of a footnote, or the title of a link reference (empty if it has none)
+    pub definition: String,
+    /// Destination of a link reference, `None` for footnotes
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,
+    /// 1-based line number of the definition
+    pub line: usize,
+}
+
+/// A footnote used with `[^id]`, defined with `[^id]: text`, or both.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct FootnoteEntry {
+    /// The label as written, without `^`
+    pub id: String,
+    /// 1-based line number of the first usage, `None` if the footnote is never used
+    pub usage_line: Option<usize>,
+    /// 1-based line number of the definition, `None` if the footnote isn't defined
+    pub definition_line: Option<usize>,
+    /// Text of the definition, `None` if the footnote isn't defined
+    pub text: Option<String>,
+}
+
+/// Footnotes found by [`extract_footnotes`].
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct FootnoteReport {
+    /// Every footnote, in order of first appearance
+    pub footnotes: Vec<FootnoteEntry>,
+    /// Ids used but never defined
+    pub orphaned_usages: Vec<String>,
+    /// Ids defined but never used
+    pub orphaned_definitions: Vec<String>,
+}
+
+/// Where a [`TodoItem`] was found.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TodoKind {
+    /// A task list item
+    Checkbox,
+    /// A `TODO:`-style marker in prose or a code block
+    Inline,
+    /// A marker in an HTML comment
+    Comment,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TodoPriority {
+    /// `TODO:`, `NOTE:` or a task without a marker
+    Normal,
+    Fixme,
+    Hack,
+}
+
+/// Something left to do, found by [`extract_all_todos`].
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct TodoItem {
+    #[serde(rename = "type")]
+    pub kind: TodoKind,
+    pub priority: TodoPriority,
+    /// Text after the marker, or the task's text
+    pub text: String,
+    /// 1-based line number
+    pub line: usize,
+    /// Only checked tasks are done
+    pub done: bool,
+}
+
+/// A term and one of its definitions from a definition list.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct DefinitionEntry {
+    pub term: String,
+    pub definition: String,
+    /// 1-based line number of the term
+    pub line: usize,
+}
+
+/// Longest `context` [`extract_glossary_terms`] returns, in characters.
+const GLOSSARY_CONTEXT_MAX_CHARS: usize = 200;
+
+/// A term introduced in bold or italics, found by [`extract_glossary_terms`].
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct GlossaryEntry {
+    pub term: String,
+    /// The sentence defining the term as written, whitespace collapsed and cut at 200
+    /// characters
+    pub context: String,
+    /// 1-based line number of the term
+    pub line: usize,
+}
+
+/// A heading and the anchor ID GitHub would assign to it.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct AnchorEntry {
+    pub id: String,
+    pub text: String,
+    pub level: u8,
+}
+
+/// A GFM pipe table with its cells as plain text.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct MarkdownTable {
+    pub headers: Vec<String>,
+    /// Body rows, padded with empty cells to the header width
+    pub rows: Vec<Vec<String>>,
+    /// 1-based line number of the header row
+    pub line: usize,
+}
+
+/// A fenced or indented code block.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct CodeBlock {
+    /// Language from the info string, without attributes such as `,runnable`
+    pub lang: Option<String>,
+    pub code: String,
+    /// Whether the info string marks the block as an example to run, see
+    /// [`extract_code_blocks`]
+    pub runnable: bool,
+    /// 1-based line number of the opening fence
+    pub line: usize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DiagramKind {
+    /// A `mermaid` block
+    Mermaid,
+    /// A `plantuml` or `puml` block
+    Plantuml,
+}
+
+impl std::str::FromStr for DiagramKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "mermaid" => Ok(Self::Mermaid),
+            "plantuml" => Ok(Self::Plantuml),
+            _ => Err(format!(
+                "Unknown diagram type '{s}', expected 'mermaid' or 'plantuml'"
+            )),
+        }
+    }
+}
+
+/// A diagram defined in a fenced code block, found by [`extract_diagrams`].
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct DiagramEntry {
+    #[serde(rename = "type")]
+    pub kind: DiagramKind,
+    /// Content of the code block
+    pub definition: String,
+    /// 1-based line number of the opening fence
+    pub line_start: usize,
+    /// 1-based line number of the closing fence, or the last line if there is none
+    pub line_end: usize,
+}
+
+/// A callout block, found by [`extract_admonitions`].
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Admonition {
+    /// Lowercase type as written, e.g. `note`, `tip`, `warning` or `danger`
+    #[serde(rename = "type")]
+    pub kind: String,
+    /// The given title, or the type capitalized
+    pub title: String,
+    /// Markdown inside the block, without its markers and indentation
+    pub content: String,
+    /// 1-based line number of the opening marker
+    pub line: usize,
+}
+
+/// The service a [`Badge`] image comes from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BadgeSource {
+    /// `shields.io`
+    Shields,
+    /// `badgen.net`
+    Badgen,
+    /// Any other image, `badge.fury.io` included
+    Custom,
+}
+
+/// A status badge image, found by [`extract_badges`].
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Badge {
+    pub alt: String,
+    pub image_url: String,
+    /// Where the badge links to, `None` for a bare image
+    pub link_url: Option<String>,
+    pub source: BadgeSource,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MathKind {
+    /// `$...$`
+    Inline,
+    /// `$$...$$` or `\[...\]`
+    Block,
+}
+
+/// A LaTeX formula, found by [`extract_math`].
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct MathEntry {
+    pub kind: MathKind,
+    /// The formula without its delimiters, trimmed
+    pub latex: String,
+    /// 1-based line number of the opening delimiter
+    pub line: usize,
+}
+
+/// Average prose reading speed, in words per minute.
+const PROSE_WORDS_PER_MINUTE: usize = 200;
+/// Code is read more slowly than prose.
+const CODE_WORDS_PER_MINUTE: usize = 50;
+
+/// A heading with the headings nested under it, for a nested table of contents.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct OutlineEntry {
+    pub id: String,
+    pub text: String,
+    pub level: u8,
+    pub children: Vec<OutlineEntry>,
+}
+
+/// How long a document takes to read.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ReadingTimeEstimate {
+    /// Total time rounded up to whole minutes
+    pub minutes: usize,
+    pub seconds: usize,
+    /// Prose words, excluding code blocks
+    pub words: usize,
+    /// Words inside fenced or indented code blocks
+    pub code_words: usize,
+}
+
+/// How many of some kinds of element a document has.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct ElementCounts {
+    pub headings: usize,
+    /// Inline links, links to definitions and autolinked URLs
+    pub links: usize,
+    pub images: usize,
+}
+
+/// Longest summary [`extract_summary`] returns, in characters.
+const SUMMARY_MAX_CHARS: usize = 300;
+
+/// A short preview of a document for listings.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct DocumentSummary {
+    /// Text of the first level-1 heading, if there is one
+    pub title: Option<String>,
+    /// First non-empty paragraph, whitespace collapsed and cut at 300 characters
+    pub summary: String,
+    /// Words in the document, including code but not frontmatter
+    pub word_count: usize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DiffKind {
+    Equal,
+    Add,
+    Remove,
+}
+
+/// A run of consecutive lines that were all kept, added or removed.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct DiffHunk {
+    pub kind: DiffKind,
+    /// Lines without their line endings
+    pub lines: Vec<String>,
+}
+
+/// Parses markdown into an mdast tree using the same constructs as the HTML renderer.
+pub(crate) fn parse_mdast(markdown: &str) -> Option<Node> {
+    let mut options = markdown::ParseOptions::gfm();
+    options.constructs.frontmatter = true;
+    markdown::to_mdast(markdown, &options).ok()
+}
+
+/// Visits `node` and all of its descendants in document order.
+pub(crate) fn walk<'a>(node: &'a Node, visit: &mut impl FnMut(&'a Node)) {
+    visit(node);
+    if let Some(children) = node.children() {
+        for child in children {
+            walk(child, visit);
+        }
+    }
+}
+
+pub(crate) fn start_line(node: &Node) -> usize {
+    node.position().map(|p| p.start.line).unwrap_or(0)
+}
+
+/// The slice of `markdown` a node was parsed from.
+pub(crate) fn node_source<'a>(markdown: &'a str, node: &Node) -> &'a str {
+    node.position()
+        .and_then(|position| markdown.get(position.start.offset..position.end.offset))
+        .unwrap_or_default()
+}
+
+/// Extracts every task list item, including nested ones, in document order.
+pub fn extract_tasks(markdown: &str) -> Vec<Task> {
+    let Some(root) = parse_mdast(markdown) else {
+        return Vec::new();
+    };
+
+    let mut tasks = Vec::new();
+    walk(&root, &mut |node| {
+        if let Node::ListItem(item) = node {
+            if let Some(checked) = item.checked {
+                // Only the item's own paragraph; nested lists are reported separately
+                let text = item
+                    .children
+                    .iter()
+                    .find(|child| matches!(child, Node::Paragraph(_)))
+                    .map(|paragraph| paragraph.to_string())
+                    .unwrap_or_default();
+                tasks.push(Task {
+                    line: start_line(node),
+                    text: text.trim().to_string(),
+                    checked,
+                });
+            }
+        }
+    });
+    tasks
+}
+
+/// Markers starting an inline or comment [`TodoItem`], matched ignoring case.
+const TODO_MARKERS: [(&str, TodoPriority); 4] = [
+    ("todo:", TodoPriority::Normal),
+    ("fixme:", TodoPriority::Fixme),
+    ("hack:", TodoPriority::Hack),
+    ("note:", TodoPriority::Normal),
+];
+
+/// Collects task list items, `TODO:`, `FIXME:`, `HACK:` and `NOTE:` markers in prose and
+/// code blocks, and markers in HTML comments, sorted by line.
+///
+/// A marker in a task's text sets the task's priority instead of adding an item.
+pub fn extract_all_todos(markdown: &str) -> Vec<TodoItem> {
+    let Some(root) = parse_mdast(markdown) else {
+        return Vec::new();
+    };
+
+    let mut todos: Vec<TodoItem> = extract_tasks(markdown)
+        .into_iter()
+        .map(|task| {
+            let (priority, text) = match todo_marker(&task.text) {
+                Some((priority, rest)) => (priority, rest.to_string()),
+                None => (TodoPriority::Normal, task.text),
+            };
+            TodoItem {
+                kind: TodoKind::Checkbox,
+                priority,
+                text,
+                line: task.line,
+                done: task.checked,
+            }
+        })
+        .collect();
+    let task_lines: Vec<usize> = todos.iter().map(|todo| todo.line).collect();
+
+    let mut add = |kind: TodoKind, source: &str, first_line: usize| {
+        for (index, line) in source.lines().enumerate() {
+            if let Some((priority, text)) = todo_marker(line) {
+                todos.push(TodoItem {
+                    kind,
+                    priority,
+                    text: match kind {
+                        TodoKind::Comment => text.to_string(),
+                        _ => plain_text(text),
+                    },
+                    line: first_line + index,
+                    done: false,
+                });
+            }
+        }
+    };
+    walk(&root, &mut |node| match node {
+        Node::Paragraph(_) | Node::Heading(_) | Node::TableCell(_) => {
+            let first_line = start_line(node);
+            if !task_lines.contains(&first_line) {
+                add(
+                    TodoKind::Inline,
+                    &without_comments(node_source(markdown, node)),
+                    first_line,
+                );
+            }
+        }
+        Node::Code(code) => {
+            // The code starts below the opening fence, if there is one
+            let fenced = node_source(markdown, node)
+                .trim_start()
+                .starts_with(['`', '~']);
+            add(
+                TodoKind::Inline,
+                &code.value,
+                start_line(node) + usize::from(fenced),
+            );
+        }
+        Node::Html(html) => {
+            for (start, _) in html.value.match_indices("<!--") {
+                let body = &html.value[start + 4..];
+                let body = &body[..body.find("-->").unwrap_or(body.len())];
+                let line = start_line(node) + html.value[..start].matches('\n').count();
+                add(TodoKind::Comment, body, line);
+            }
+        }
+        _ => {}
+    });
+
+    todos.sort_by_key(|todo| todo.line);
+    todos
+}
+
+/// The priority and the trimmed text after the first marker in `line`. Markers must
+/// start a word, so `autodoc:` doesn't count.
+fn todo_marker(line: &str) -> Option<(TodoPriority, &str)> {
+    let lower = line.to_ascii_lowercase();
+    TODO_MARKERS
+        .iter()
+        .filter_map(|(marker, priority)| {
+            lower
+                .match_indices(marker)
+                .find(|(start, _)| {
+                    !lower[..*start]
+                        .chars()
+                        .next_back()
+                        .is_some_and(char::is_alphanumeric)
+                })
+                .map(|(start, _)| (start, marker.len(), *priority))
+        })
+        .min_by_key(|(start, _, _)| *start)
+        .map(|(start, len, priority)| (priority, line[start + len..].trim()))
+}
+
+/// `source` with HTML comments blanked out, keeping line breaks so line numbers stay
+/// the same.
+fn without_comments(source: &str) -> String {
+    let mut text = String::with_capacity(source.len());
+    let mut rest = source;
+    while let Some(start) = rest.find("<!--") {
+        text.push_str(&rest[..start]);
+        let end = rest[start..]
+            .find("-->")
+            .map_or(rest.len(), |end| start + end + 3);
+        text.extend(
+            rest[start..end]
+                .chars()
+                .map(|c| if c == '\n' { '\n' } else { ' ' }),
+        );
+        rest = &rest[end..];
+    }
+    text.push_str(rest);
+    text
+}
+
+/// Lists every heading with a GitHub-style anchor ID, in document order.
+///
+/// IDs are lowercased, spaces become hyphens and everything except letters, digits,
+/// hyphens and underscores is dropped (Unicode letters are kept). Repeated IDs get
+/// `-1`, `-2`, ... suffixes, skipping any suffix that is already taken.
+pub fn generate_anchors(markdown: &str) -> Vec<AnchorEntry> {
+    let Some(root) = parse_mdast(markdown) else {
+        return Vec::new();
+    };
+
+    let mut occurrences: HashMap<String, usize> = HashMap::new();
+    let mut anchors = Vec::new();
+    walk(&root, &mut |node| {
+        if let Node::Heading(heading) = node {
+            let text = node.to_string();
+            let slug = slugify(&text);
+
+            let mut id = slug.clone();
+            while occurrences.contains_key(&id) {
+                let count = occurrences.entry(slug.clone()).or_default();
+                *count += 1;
+                id = format!("{slug}-{count}");
+            }
+            occurrences.insert(id.clone(), 0);
+
+            anchors.push(AnchorEntry {
+                id,
+                text,
+                level: heading.depth,
+            });
+        }
+    });
+    anchors
+}
+
+/// Nests `anchors` under the closest preceding heading of a lower level, leaving out
+/// headings deeper than `depth`. Headings without such a parent are at the top level.
+pub fn build_outline(anchors: Vec<AnchorEntry>, depth: u8) -> Vec<OutlineEntry> {
+    let mut outline = Vec::new();
+    // The open headings, each nested under the one before it
+    let mut open: Vec<OutlineEntry> = Vec::new();
+    let close = |open: &mut Vec<OutlineEntry>, outline: &mut Vec<OutlineEntry>| {
+        let entry = open.pop().expect("called with open headings");
+        match open.last_mut() {
+            Some(parent) => parent.children.push(entry),
+            None => outline.push(entry),
+        }
+    };
+
+    for anchor in anchors.into_iter().filter(|anchor| anchor.level <= depth) {
+        while open.last().is_some_and(|entry| entry.level >= anchor.level) {
+            close(&mut open, &mut outline);
+        }
+        open.push(OutlineEntry {
+            id: anchor.id,
+            text: anchor.text,
+            level: anchor.level,
+            children: Vec::new(),
+        });
+    }
+    while !open.is_empty() {
+        close(&mut open, &mut outline);
+    }
+    outline
+}
+
+/// Extracts footnote and link reference definitions in document order, including ones
+/// nothing refers to.
+pub fn parse_references(markdown: &str) -> Vec<ReferenceEntry> {
+    let Some(root) = parse_mdast(markdown) else {
+        return Vec::new();
+    };
+
+    let mut references = Vec::new();
+    walk(&root, &mut |node| {
+        let (label, identifier, definition, url) = match node {
+            Node::FootnoteDefinition(footnote) => (
+                &footnote.label,
+                &footnote.identifier,
+                node.to_string()
+                    .split_whitespace()
+                    .collect::<Vec<_>>()
+                    .join(" "),
+                None,
+            ),
+            Node::Definition(link) => (
+                &link.label,
+                &link.identifier,
+                link.title.clone().unwrap_or_default(),
+                Some(link.url.clone()),
+            ),
+            _ => return,
+        };
+        references.push(ReferenceEntry {
+            id: label.clone().unwrap_or_else(|| identifier.clone()),
+            definition,
+            url,
+            line: start_line(node),
+        });
+    });
+    references
+}
+
+/// Extracts footnote usages and definitions, pairing them by id, and lists the ones
+/// missing their other half.
+///
+/// Markdown only parses a usage as a footnote when its definition exists, so usages
+/// without one are found in the remaining text instead.
+pub fn extract_footnotes(markdown: &str) -> FootnoteReport {
+    let Some(root) = parse_mdast(markdown) else {
+        return FootnoteReport::default();
+    };
+
+    let mut footnotes: Vec<FootnoteEntry> = Vec::new();
+    let mut indices: HashMap<String, usize> = HashMap::new();
+    walk(&root, &mut |node| match node {
+        Node::FootnoteReference(reference) => {
+            let label = reference.label.as_ref().unwrap_or(&reference.identifier);
+            footnote_entry(&mut footnotes, &mut indices, label)
+                .usage_line
+                .get_or_insert(start_line(node));
+        }
+        Node::FootnoteDefinition(footnote) => {
+            let label = footnote.label.as_ref().unwrap_or(&footnote.identifier);
+            let footnote = footnote_entry(&mut footnotes, &mut indices, label);
+            if footnote.definition_line.is_none() {
+                footnote.definition_line = Some(start_line(node));
+                footnote.text = Some(
+                    node.to_string()
+                        .split_whitespace()
+                        .collect::<Vec<_>>()
+                        .join(" "),
+                );
+            }
+        }
+        Node::Text(_) => {
+            let source = node_source(markdown, node);
+            for (offset, label) in undefined_footnote_usages(source) {
+                let line = start_line(node) + source[..offset].matches('\n').count();
+                footnote_entry(&mut footnotes, &mut indices, label)
+                    .usage_line
+                    .get_or_insert(line);
+            }
+        }
+        _ => {}
+    });
+
+    let orphaned_usages = footnotes
+        .iter()
+        .filter(|footnote| footnote.definition_line.is_none())
+        .map(|footnote| footnote.id.clone())
+        .collect();
+    let orphaned_definitions = footnotes
+        .iter()
+        .filter(|footnote| footnote.usage_line.is_none())
+        .map(|footnote| footnote.id.clone())
+        .collect();
+    FootnoteReport {
+        footnotes,
+        orphaned_usages,
+        orphaned_definitions,
+    }
+}
+
+/// The entry for the footnote `label` in `footnotes`, added if it's the first time it
+/// appears. Ids are case-insensitive.
+fn footnote_entry<'a>(
+    footnotes: &'a mut Vec<FootnoteEntry>,
+    indices: &mut HashMap<String, usize>,
+    label: &str,
+) -> &'a mut FootnoteEntry {
+    let index = *indices.entry(label.to_lowercase()).or_insert_with(|| {
+        footnotes.push(FootnoteEntry {
+            id: label.to_string(),
+            usage_line: None,
+            definition_line: None,
+            text: None,
+        });
+        footnotes.len() - 1
+    });
+    &mut footnotes[index]
+}
+
+/// Offsets and labels of the `[^id]` in `text` that markdown left as text, because no
+/// definition matches them. Escaped brackets and labels with whitespace don't count.
+fn undefined_footnote_usages(text: &str) -> Vec<(usize, &str)> {
+    text.match_indices("[^")
+        .filter(|&(offset, _)| !text[..offset].ends_with('\\'))
+        .filter_map(|(offset, _)| {
+            let rest = &text[offset + 2..];
+            let label = &rest[..rest.find(']')?];
+            (!label.is_empty()
+                && !label
+                    .chars()
+                    .any(|c| c.is_whitespace() || c == '[' || c == '^'))
+            .then_some((offset, label))
+        })
+        .collect()
+}
+
+/// Hosts whose images are badges even when they aren't links.
+const BADGE_HOSTS: [&str; 3] = ["shields.io", "badge.fury.io", "badgen.net"];
+
+/// Extracts badges in document order: every linked image (`[![alt](image)](link)`),
+/// and images from a badge service (shields.io, badge.fury.io or badgen.net) that
+/// aren't linked.
+pub fn extract_badges(markdown: &str) -> Vec<Badge> {
+    let mut badges = Vec::new();
+    if let Some(root) = parse_mdast(markdown) {
+        collect_badges(&root, &mut badges);
+    }
+    badges
+}
+
+/// Adds the badges in `node` and its descendants to `badges`.
+fn collect_badges(node: &Node, badges: &mut Vec<Badge>) {
+    match node {
+        Node::Link(link) => {
+            if let [Node::Image(image)] = link.children.as_slice() {
+                badges.push(Badge {
+                    alt: image.alt.clone(),
+                    image_url: image.url.clone(),
+                    link_url: Some(link.url.clone()),
+                    source: badge_source(&image.url),
+                });
+                return;
+            }
+        }
+        Node::Image(image) if BADGE_HOSTS.iter().any(|host| has_host(&image.url, host)) => {
+            badges.push(Badge {
+                alt: image.alt.clone(),
+                image_url: image.url.clone(),
+                link_url: None,
+                source: badge_source(&image.url),
+            });
+        }
+        _ => {}
+    }
+    for child in node.children().map_or(&[][..], Vec::as_slice) {
+        collect_badges(child, badges);
+    }
+}
+
+fn badge_source(url: &str) -> BadgeSource {
+    if has_host(url, "shields.io") {
+        BadgeSource::Shields
+    } else if has_host(url, "badgen.net") {
+        BadgeSource::Badgen
+    } else {
+        BadgeSource::Custom
+    }
+}
+
+/// Whether `url` is an absolute URL on `host` or one of its subdomains.
+fn has_host(url: &str, host: &str) -> bool {
+    let Some((_, rest)) = url.split_once("//") else {
+        return false;
+    };
+    let authority = rest.split(['/', '?', '#']).next().unwrap_or_default();
+    let name = authority
+        .rsplit('@')
+        .next()
+        .unwrap_or_default()
+        .split(':')
+        .next()
+        .unwrap_or_default()
+        .to_ascii_lowercase();
+    name == host
+        || name
+            .strip_suffix(host)
+            .is_some_and(|subdomain| subdomain.ends_with('.'))
+}
+
+/// Extracts LaTeX formulas in document order: `$$...$$` and `\[...\]` blocks, and
+/// inline `$...$` whose opening `$` isn't followed by whitespace and whose closing `$`
+/// isn't preceded by whitespace or followed by a digit, so prices like `$5 and $10`
+/// aren't formulas. `\$` is a literal dollar. Code, frontmatter and HTML are skipped,
+/// and no formula spans a blank line.
+pub fn extract_math(markdown: &str) -> Vec<MathEntry> {
+    let Some(root) = parse_mdast(markdown) else {
+        return Vec::new();
+    };
+
+    // Blank out where delimiters are just text, keeping offsets and line breaks
+    let mut text = markdown.as_bytes().to_vec();
+    walk(&root, &mut |node| {
+        if let Node::Code(_) | Node::InlineCode(_) | Node::Yaml(_) | Node::Toml(_) | Node::Html(_) =
+            node
+        {
+            if let Some(position) = node.position() {
+                for byte in &mut text[position.start.offset..position.end.offset] {
+                    if *byte != b'\n' {
+                        *byte = b' ';
+                    }
+                }
+            }
+        }
+    });
+    let text = String::from_utf8_lossy(&text);
+
+    let mut formulas = Vec::new();
+    let mut offset = 0;
+    while offset < text.len() {
+        let rest = &text[offset..];
+        let found = if let Some(after) = rest.strip_prefix("$$") {
+            closing_delimiter(after, "$$").map(|end| (MathKind::Block, 2, end, 2))
+        } else if let Some(after) = rest.strip_prefix("\\[") {
+            closing_delimiter(after, "\\]").map(|end| (MathKind::Block, 2, end, 2))
+        } else if rest.starts_with('$') && rest[1..].starts_with(|c: char| !c.is_whitespace()) {
+            closing_inline_dollar(&rest[1..]).map(|end| (MathKind::Inline, 1, end, 1))
+        } else {
+            None
+        };
+
+        match found {
+            Some((kind, open, end, close)) => {
+                let latex = rest[open..open + end].trim();
+                if !latex.is_empty() {
+                    formulas.push(MathEntry {
+                        kind,
+                        latex: latex.to_string(),
+                        line: text[..offset].matches('\n').count() + 1,
+                    });
+                }
+                offset += open + end + close;
+            }
+            None => {
+                // Skip an escaped character whole, so `\$` never opens or closes
+                let skip = if rest.starts_with('\\') { 2 } else { 1 };
+                offset += rest
+                    .char_indices()
+                    .nth(skip)
+                    .map_or(rest.len(), |(index, _)| index);
+            }
+        }
+    }
+    formulas
+}
+
+/// Offset in `text` of the first unescaped `delimiter` before a blank line.
+fn closing_delimiter(text: &str, delimiter: &str) -> Option<usize> {
+    let paragraph = &text[..text.find("\n\n").unwrap_or(text.len())];
+    let mut escaped = false;
+    for (index, c) in paragraph.char_indices() {
+        if escaped {
+            escaped = false;
+        } else if paragraph[index..].starts_with(delimiter) {
+            return Some(index);
+        } else if c == '\\' {
+            escaped = true;
+        }
+    }
+    None
+}
+
+/// Offset in `text` of the `$` closing an inline formula, see [`extract_math`].
+fn closing_inline_dollar(text: &str) -> Option<usize> {
+    let paragraph = &text[..text.find("\n\n").unwrap_or(text.len())];
+    let mut escaped = false;
+    let mut previous = ' ';
+    for (index, c) in paragraph.char_indices() {
+        if escaped {
+            escaped = false;
+        } else if c == '\\' {
+            escaped = true;
+        } else if c == '$'
+            && !previous.is_whitespace()
+            && !paragraph[index + 1..].starts_with(|c: char| c.is_ascii_digit())
+        {
+            return Some(index);
+        }
+        previous = c;
+    }
+    None
+}
+
+/// Extracts the terms and definitions of Pandoc-style definition lists and HTML `<dl>`
+/// lists in document order, one entry per definition.
+///
+/// In markdown a definition is a line starting with `:` or `~` and whitespace, right
+/// after its term or after a blank line. Indented lines continue the definition above.
+pub fn extract_definitions(markdown: &str) -> Vec<DefinitionEntry> {
+    let Some(root) = parse_mdast(markdown) else {
+        return Vec::new();
+    };
+
+    let mut definitions = Vec::new();
+    collect_definitions(
+        markdown,
+        root.children().map_or(&[], Vec::as_slice),
+        &mut definitions,
+    );
+    definitions
+}
+
+/// Adds the definitions in `siblings` and their descendants to `definitions`. Markdown
+/// doesn't know definition lists, so they arrive as paragraphs.
+fn collect_definitions(markdown: &str, siblings: &[Node], definitions: &mut Vec<DefinitionEntry>) {
+    // A term ending the previous paragraph, for definitions after a blank line
+    let mut open_term: Option<(String, usize)> = None;
+    for node in siblings {
+        match node {
+            Node::Paragraph(_) => {
+                let first_line = start_line(node);
+                let source = node_source(markdown, node);
+                let mut term = open_term.take();
+                let mut definition: Option<String> = None;
+                let mut push = |term: &Option<(String, usize)>, definition: Option<String>| {
+                    if let (Some((term, line)), Some(definition)) = (term, definition) {
+                        definitions.push(DefinitionEntry {
+                            term: term.clone(),
+                            definition: plain_text(&definition),
+                            line: *line,
+                        });
+                    }
+                };
+                for (index, raw_line) in source.lines().enumerate() {
+                    let line = raw_line.trim_start_matches(|c: char| c == '>' || c.is_whitespace());
+                    if let Some(text) = definition_text(line).filter(|_| term.is_some()) {
+                        push(&term, definition.replace(text.to_string()));
+                    } else if let (Some(definition), true) =
+                        (&mut definition, raw_line.starts_with(char::is_whitespace))
+                    {
+                        definition.push('\n');
+                        definition.push_str(line);
+                    } else {
+                        push(&term, definition.take());
+                        term = Some((plain_text(line), first_line + index));
+                    }
+                }
+                let ends_with_term = definition.is_none();
+                push(&term, definition);
+                open_term = term.filter(|_| ends_with_term);
+            }
+            Node::Html(html) => {
+                open_term = None;
+                definitions.extend(html_definitions(&html.value, start_line(node)));
+            }
+            _ => {
+                open_term = None;
+                if let Some(children) = node.children() {
+                    collect_definitions(markdown, children, definitions);
+                }
+            }
+        }
+    }
+}
+
+/// The text after a `:` or `~` definition marker, if `line` starts with one.
+fn definition_text(line: &str) -> Option<&str> {
+    let rest = line.strip_prefix([':', '~'])?;
+    rest.starts_with(char::is_whitespace).then(|| rest.trim())
+}
+
+/// The `<dt>` terms and `<dd>` definitions in an HTML fragment starting at `first_line`.
+/// Definitions before any term are skipped.
+fn html_definitions(html: &str, first_line: usize) -> Vec<DefinitionEntry> {
+    let lower = html.to_ascii_lowercase();
+    let mut definitions = Vec::new();
+    let mut term: Option<(String, usize)> = None;
+    for (start, tag) in lower.match_indices("<d") {
+        let rest = &lower[start + tag.len()..];
+        let is_term = match rest.as_bytes() {
+            [b't', b'>' | b' ', ..] => true,
+            [b'd', b'>' | b' ', ..] => false,
+            _ => continue,
+        };
+        let Some(content_start) = rest.find('>').map(|end| start + tag.len() + end + 1) else {
+            continue;
+        };
+        // An element ends at its closing tag or where the next one starts
+        let content_end = ["</dt", "</dd", "<dt", "<dd", "</dl"]
+            .iter()
+            .filter_map(|end| lower[content_start..].find(end))
+            .min()
+            .map_or(html.len(), |end| content_start + end);
+        let text = strip_tags(&html[content_start..content_end]);
+        if is_term {
+            term = Some((text, first_line + html[..start].matches('\n').count()));
+        } else if let Some((term, line)) = &term {
+            definitions.push(DefinitionEntry {
+                term: term.clone(),
+                definition: text,
+                line: *line,
+            });
+        }
+    }
+    definitions
+}
+
+/// The text of an HTML fragment without its tags, whitespace collapsed.
+fn strip_tags(html: &str) -> String {
+    let mut text = String::new();
+    let mut in_tag = false;
+    for c in html.chars() {
+        match c {
+            '<' => in_tag = true,
+            '>' => in_tag = false,
+            c if !in_tag => text.push(c),
+            _ => {}
+        }
+    }
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// Extracts terms that are defined where they are emphasized, in document order:
+/// `**term** (definition)`, `**term**: definition` (or `**term:** definition`) and
+/// `*term* — definition`. Bold and italics work the same in each pattern. Terms defined
+/// more than once, ignoring case, are listed at their first definition.
+pub fn extract_glossary_terms(markdown: &str) -> Vec<GlossaryEntry> {
+    let Some(root) = parse_mdast(markdown) else {
+        return Vec::new();
+    };
+
+    let mut entries: Vec<GlossaryEntry> = Vec::new();
+    walk(&root, &mut |node| {
+        let Node::Paragraph(paragraph) = node else {
+            return;
+        };
+        for pair in paragraph.children.windows(2) {
+            let [term_node @ (Node::Strong(_) | Node::Emphasis(_)), Node::Text(next)] = pair else {
+                continue;
+            };
+            let term = term_node.to_string();
+            let (term, defined) = match term.trim().strip_suffix(':') {
+                Some(term) => (term.trim_end(), !next.value.trim().is_empty()),
+                None => (term.trim(), starts_definition(&next.value)),
+            };
+            if !defined
+                || term.is_empty()
+                || entries
+                    .iter()
+                    .any(|entry| entry.term.to_lowercase() == term.to_lowercase())
+            {
+                continue;
+            }
+            entries.push(GlossaryEntry {
+                term: term.to_string(),
+                context: enclosing_sentence(markdown, node, term_node)
+                    .split_whitespace()
+                    .collect::<Vec<_>>()
+                    .join(" ")
+                    .chars()
+                    .take(GLOSSARY_CONTEXT_MAX_CHARS)
+                    .collect(),
+                line: start_line(term_node),
+            });
+        }
+    });
+    entries
+}
+
+/// Whether the text right after an emphasized term defines it: `: ...`, ` (...` or
+/// ` — ...`.
+fn starts_definition(text: &str) -> bool {
+    let rest = text.trim_start();
+    let definition = if let Some(definition) = text.strip_prefix(':') {
+        definition
+    } else if let Some(definition) = rest.strip_prefix('(') {
+        definition
+    } else if let Some(definition) = rest.strip_prefix(['—', '–']) {
+        definition
+    } else {
+        return false;
+    };
+    !definition.trim().is_empty()
+}
+
+/// The source of the sentence in `paragraph` that `inner` is part of. Sentences end at
+/// `.`, `!` or `?` followed by whitespace.
+fn enclosing_sentence<'a>(markdown: &'a str, paragraph: &Node, inner: &Node) -> &'a str {
+    let source = node_source(markdown, paragraph);
+    let (Some(paragraph), Some(inner)) = (paragraph.position(), inner.position()) else {
+        return source;
+    };
+    let inner_start = inner.start.offset - paragraph.start.offset;
+    let inner_end = inner.end.offset - paragraph.start.offset;
+    let is_sentence_end = |index: usize| {
+        source[index..].starts_with(['.', '!', '?'])
+            && source[index + 1..]
+                .chars()
+                .next()
+                .is_none_or(char::is_whitespace)
+    };
+
+    let start = source[..inner_start]
+        .char_indices()
+        .rev()
+        .find(|&(index, _)| is_sentence_end(index))
+        .map_or(0, |(index, _)| index + 1);
+    let end = source[inner_end..]
+        .char_indices()
+        .find(|&(index, _)| is_sentence_end(inner_end + index))
+        .map_or(source.len(), |(index, _)| inner_end + index + 1);
+    source[start..end].trim()
+}
+
+/// Extracts every GFM table in document order.
+///
+/// Tables are read from the same parse tree the page is rendered from, so escaped pipes,
+/// inline formatting and table boundaries match what the reader sees.
+pub fn extract_tables(markdown: &str) -> Vec<MarkdownTable> {
+    let Some(root) = parse_mdast(markdown) else {
+        return Vec::new();
+    };
+
+    let mut tables = Vec::new();
+    walk(&root, &mut |node| {
+        if let Node::Table(table) = node {
+            let mut rows = table.children.iter().map(|row| {
+                row.children()
+                    .map(|cells| cells.iter().map(|cell| cell.to_string().trim().to_string()))
+                    .into_iter()
+                    .flatten()
+                    .collect::<Vec<_>>()
+            });
+            let headers = rows.next().unwrap_or_default();
+            let rows = rows
+                .map(|mut row| {
+                    row.resize(headers.len().max(row.len()), String::new());
+                    row
+                })
+                .collect();
+
+            tables.push(MarkdownTable {
+                headers,
+                rows,
+                line: start_line(node),
+            });
+        }
+    });
+    tables
+}
+
+/// Extracts every code block in document order.
+///
+/// A block is runnable when its info string has a `runnable` attribute, e.g.
+/// ` ```rust,runnable ` or ` ```python # runnable `, or ends in `,run`.
+pub fn extract_code_blocks(markdown: &str) -> Vec<CodeBlock> {
+    let Some(root) = parse_mdast(markdown) else {
+        return Vec::new();
+    };
+
+    let mut blocks = Vec::new();
+    walk(&root, &mut |node| {
+        if let Node::Code(code) = node {
+            let info = [code.lang.as_deref(), code.meta.as_deref()]
+                .into_iter()
+                .flatten()
+                .collect::<Vec<_>>()
+                .join(" ");
+            let runnable = info.ends_with(",run")
+                || info
+                    .split(|c: char| c == ',' || c == '#' || c.is_whitespace())
+                    .any(|attribute| attribute == "runnable");
+            blocks.push(CodeBlock {
+                lang: code
+                    .lang
+                    .as_deref()
+                    .and_then(|lang| lang.split(',').next())
+                    .filter(|lang| !lang.is_empty())
+                    .map(str::to_string),
+                code: code.value.clone(),
+                runnable,
+                line: start_line(node),
+            });
+        }
+    });
+    blocks
+}
+
+/// Extracts Mermaid (`mermaid`) and PlantUML (`plantuml` or `puml`) diagrams from
+/// fenced code blocks in document order. The info string is compared ignoring case.
+pub fn extract_diagrams(markdown: &str) -> Vec<DiagramEntry> {
+    let Some(root) = parse_mdast(markdown) else {
+        return Vec::new();
+    };
+
+    let mut diagrams = Vec::new();
+    walk(&root, &mut |node| {
+        let Node::Code(code) = node else {
+            return;
+        };
+        let kind = match code
+            .lang
+            .as_deref()
+            .and_then(|lang| lang.split(',').next())
+            .map(str::to_ascii_lowercase)
+            .as_deref()
+        {
+            Some("mermaid") => DiagramKind::Mermaid,
+            Some("plantuml" | "puml") => DiagramKind::Plantuml,
+            _ => return,
+        };
+        diagrams.push(DiagramEntry {
+            kind,
+            definition: code.value.clone(),
+            line_start: start_line(node),
+            line_end: node.position().map_or(0, |position| position.end.line),
+        });
+    });
+    diagrams
+}
+
+/// Types GitHub renders `> [!TYPE]` alerts for.
+const GITHUB_ALERT_TYPES: [&str; 5] = ["note", "tip", "important", "warning", "caution"];
+
+/// Extracts admonitions in document order, including nested ones. Three syntaxes are
+/// recognized:
+///
+/// - GitHub alerts: a blockquote whose first line is `[!NOTE]`, `[!TIP]`, `[!IMPORTANT]`,
+///   `[!WARNING]` or `[!CAUTION]`
+/// - MkDocs: `!!! type "Title"` (or collapsible `???`) followed by lines indented four
+///   more spaces
+/// - Docusaurus: `:::type Title` or `:::type[Title]` up to a closing `:::`
+///
+/// Markers in fenced code blocks and frontmatter are ignored.
+pub fn extract_admonitions(markdown: &str) -> Vec<Admonition> {
+    let Some(root) = parse_mdast(markdown) else {
+        return Vec::new();
+    };
+
+    let mut admonitions = Vec::new();
+    // Lines of fenced code and frontmatter, where markers are just text
+    let mut literal_lines = Vec::new();
+    walk(&root, &mut |node| match node {
+        Node::Blockquote(_) => {
+            admonitions.extend(github_alert(node_source(markdown, node), start_line(node)))
+        }
+        Node::Code(_) | Node::Yaml(_)
+            if matches!(node, Node::Yaml(_))
+                || node_source(markdown, node)
+                    .trim_start()
+                    .starts_with(['`', '~']) =>
+        {
+            if let Some(position) = node.position() {
+                literal_lines.push(position.start.line..=position.end.line);
+            }
+        }
+        _ => {}
+    });
+
+    let lines: Vec<&str> = markdown.lines().collect();
+    let is_literal = |index: usize| {
+        literal_lines
+            .iter()
+            .any(|range| range.contains(&(index + 1)))
+    };
+    for (index, line) in lines.iter().enumerate() {
+        if is_literal(index) {
+            continue;
+        }
+        if let Some((indent, kind, title)) = mkdocs_marker(line) {
+            let body = lines[index + 1..]
+                .iter()
+                .take_while(|line| line.trim().is_empty() || indentation(line) >= indent + 4)
+                .map(|line| dedent(line, indent + 4))
+                .collect::<Vec<_>>();
+            admonitions.push(admonition(kind, title, &body, index + 1));
+        } else if let Some((kind, title)) = docusaurus_marker(line) {
+            let mut depth = 0;
+            let end = (index + 1..lines.len()).find(|&end| {
+                if is_literal(end) {
+                    return false;
+                }
+                if docusaurus_marker(lines[end]).is_some() {
+                    depth += 1;
+                } else if is_docusaurus_close(lines[end]) {
+                    if depth == 0 {
+                        return true;
+                    }
+                    depth -= 1;
+                }
+                false
+            });
+            // Docusaurus leaves unclosed blocks as text
+            if let Some(end) = end {
+                admonitions.push(admonition(kind, title, &lines[index + 1..end], index + 1));
+            }
+        }
+    }
+    admonitions.sort_by_key(|admonition| admonition.line);
+    admonitions
+}
+
+fn admonition(kind: String, title: Option<String>, body: &[&str], line: usize) -> Admonition {
+    Admonition {
+        title: title.unwrap_or_else(|| {
+            let mut chars = kind.chars();
+            chars
+                .next()
+                .map(|first| first.to_uppercase().chain(chars).collect())
+                .unwrap_or_default()
+        }),
+        kind,
+        content: body.join("\n").trim_matches('\n').to_string(),
+        line,
+    }
+}
+
+/// The admonition a blockquote is, if its first line is a GitHub `[!TYPE]` marker.
+fn github_alert(source: &str, line: usize) -> Option<Admonition> {
+    let mut lines = source.lines().map(|line| {
+        let unquoted = line.trim_start().strip_prefix('>').unwrap_or(line);
+        unquoted.strip_prefix(' ').unwrap_or(unquoted)
+    });
+    let kind = lines
+        .next()?
+        .trim()
+        .strip_prefix("[!")?
+        .strip_suffix(']')?
+        .to_ascii_lowercase();
+    if !GITHUB_ALERT_TYPES.contains(&kind.as_str()) {
+        return None;
+    }
+    Some(admonition(kind, None, &lines.collect::<Vec<_>>(), line))
+}
+
+/// Indentation, type and title of an MkDocs `!!! type "Title"` line. The type is the
+/// first of its classes, and `""` asks for no title.
+fn mkdocs_marker(line: &str) -> Option<(usize, String, Option<String>)> {
+    let rest = line.trim_start();
+    let indent = indentation(line);
+    let rest = ["!!!", "???+", "???"]
+        .into_iter()
+        .find_map(|marker| rest.strip_prefix(marker))?;
+    if !rest.starts_with(' ') {
+        return None;
+    }
+    let (classes, title) = match rest.split_once('"') {
+        Some((classes, title)) => (classes, Some(title.trim_end().strip_suffix('"')?)),
+        None => (rest, None),
+    };
+    let kind = admonition_type(classes.split_whitespace().next()?)?;
+    Some((indent, kind, title.map(str::to_string)))
+}
+
+/// Type and title of a Docusaurus `:::type Title` or `:::type[Title]` line.
+fn docusaurus_marker(line: &str) -> Option<(String, Option<String>)> {
+    let rest = line.trim_start();
+    let colons = rest.len() - rest.trim_start_matches(':').len();
+    if colons < 3 {
+        return None;
+    }
+    let rest = &rest[colons..];
+    let end = rest
+        .find(|c: char| c == '[' || c.is_whitespace())
+        .unwrap_or(rest.len());
+    let kind = admonition_type(&rest[..end])?;
+    let title = rest[end..].trim();
+    let title = match title.strip_prefix('[') {
+        Some(bracketed) => bracketed.strip_suffix(']')?,
+        None => title,
+    };
+    Some((kind, (!title.is_empty()).then(|| title.to_string())))
+}
+
+fn is_docusaurus_close(line: &str) -> bool {
+    let line = line.trim();
+    line.len() >= 3 && line.chars().all(|c| c == ':')
+}
+
+/// `word` lowercased if it can be an admonition type.
+fn admonition_type(word: &str) -> Option<String> {
+    (!word.is_empty()
+        && word
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_'))
+    .then(|| word.to_ascii_lowercase())
+}
+
+/// Width of the leading whitespace of `line`, tabs counting as four spaces.
+fn indentation(line: &str) -> usize {
+    line.chars()
+        .take_while(|c| c.is_whitespace())
+        .map(|c| if c == '\t' { 4 } else { 1 })
+        .sum()
+}
+
+/// `line` without its first `width` columns of indentation.
+fn dedent(line: &str, width: usize) -> &str {
+    let mut removed = 0;
+    for (offset, c) in line.char_indices() {
+        if removed >= width || !c.is_whitespace() {
+            return &line[offset..];
+        }
+        removed += if c == '\t' { 4 } else { 1 };
+    }
+    ""
+}
+
+/// Estimates reading time at 200 words per minute for prose and 50 for code blocks.
+pub fn estimate_reading_time(markdown: &str) -> ReadingTimeEstimate {
+    let mut words = 0;
+    let mut code_words = 0;
+    if let Some(root) = parse_mdast(markdown) {
+        walk(&root, &mut |node| match node {
+            Node::Text(text) => words += text.value.split_whitespace().count(),
+            Node::InlineCode(code) => words += code.value.split_whitespace().count(),
+            Node::Code(code) => code_words += code.value.split_whitespace().count(),
+            _ => {}
+        });
+    }
+
+    let seconds = (words * 60 / PROSE_WORDS_PER_MINUTE) + (code_words * 60 / CODE_WORDS_PER_MINUTE);
+    ReadingTimeEstimate {
+        minutes: seconds.div_ceil(60),
+        seconds,
+        words,
+        code_words,
+    }
+}
+
+/// Counts the headings, links and images in `markdown`.
+pub fn element_counts(markdown: &str) -> ElementCounts {
+    let mut counts = ElementCounts::default();
+    if let Some(root) = parse_mdast(markdown) {
+        walk(&root, &mut |node| match node {
+            Node::Heading(_) => counts.headings += 1,
+            Node::Link(_) | Node::LinkReference(_) => counts.links += 1,
+            Node::Image(_) | Node::ImageReference(_) => counts.images += 1,
+            _ => {}
+        });
+    }
+    counts
+}
+
+/// Builds a [`DocumentSummary`] from the first `# Heading` and the first paragraph.
+///
+/// Only top-level paragraphs count, so text inside lists, quotes or tables is skipped.
+pub fn extract_summary(markdown: &str) -> DocumentSummary {
+    let Some(root) = parse_mdast(markdown) else {
+        return DocumentSummary {
+            title: None,
+            summary: String::new(),
+            word_count: 0,
+        };
+    };
+
+    let mut title = None;
+    let mut word_count = 0;
+    walk(&root, &mut |node| match node {
+        Node::Heading(heading) => {
+            let text = node.to_string();
+            // Counted per block rather than per text node, so punctuation split off by
+            // inline markup isn't taken for a word
+            word_count += text.split_whitespace().count();
+            if heading.depth == 1 && title.is_none() {
+                title = Some(text.trim().to_string());
+            }
+        }
+        Node::Paragraph(_) | Node::TableCell(_) => {
+            word_count += node.to_string().split_whitespace().count();
+        }
+        Node::Code(code) => word_count += code.value.split_whitespace().count(),
+        _ => {}
+    });
+
+    let summary = root
+        .children()
+        .into_iter()
+        .flatten()
+        .filter(|child| matches!(child, Node::Paragraph(_)))
+        .map(|paragraph| {
+            paragraph
+                .to_string()
+                .split_whitespace()
+                .collect::<Vec<_>>()
+                .join(" ")
+        })
+        .find(|text| !text.is_empty())
+        .map(|text| text.chars().take(SUMMARY_MAX_CHARS).collect())
+        .unwrap_or_default();
+
+    DocumentSummary {
+        title,
+        summary,
+        word_count,
+    }
+}
+
+/// How often each word occurs in the headings of `markdown`, lowercased: the
+/// term-frequency vector files are compared by for structural similarity.
+pub fn heading_terms(markdown: &str) -> HashMap<String, usize> {
+    let mut terms = HashMap::new();
+    let Some(root) = parse_mdast(markdown) else {
+        return terms;
+    };
+
+    walk(&root, &mut |node| {
+        if !matches!(node, Node::Heading(_)) {
+            return;
+        }
+        for word in node
+            .to_string()
+            .split(|c: char| !c.is_alphanumeric())
+            .filter(|word| !word.is_empty())
+        {
+            *terms.entry(word.to_lowercase()).or_insert(0) += 1;
+        }
+    });
+    terms
+}
+
+/// The text of `markdown` without its syntax, frontmatter or raw HTML, for search. Code
+/// is kept without its fences; words are separated by single spaces.
+pub fn plain_text(markdown: &str) -> String {
+    let Some(root) = parse_mdast(markdown) else {
+        return String::new();
+    };
+
+    let mut blocks = Vec::new();
+    walk(&root, &mut |node| match node {
+        Node::Paragraph(_) | Node::Heading(_) | Node::TableCell(_) => {
+            let mut text = String::new();
+            push_inline_text(node, &mut text);
+            blocks.push(text);
+        }
+        Node::Code(code) => blocks.push(code.value.clone()),
+        _ => {}
+    });
+    blocks
+        .iter()
+        .flat_map(|block| block.split_whitespace())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Appends the text under the inline `node` to `text`, skipping raw HTML.
+fn push_inline_text(node: &Node, text: &mut String) {
+    match node {
+        Node::Text(t) => text.push_str(&t.value),
+        Node::InlineCode(code) => text.push_str(&code.value),
+        Node::Break(_) => text.push(' '),
+        _ => {
+            for child in node.children().into_iter().flatten() {
+                push_inline_text(child, text);
+            }
+        }
+    }
+}
+
+/// Escapes the characters that are special in HTML text and attribute values.
+pub fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// Replaces `{{key}}` placeholders in `template` with the HTML-escaped value of `key` in
+/// `vars`. Whitespace around the key is ignored.
+///
+/// Placeholders for keys not in `vars` are left as they are, and so are runs of more
+/// than two braces, so `{{{{key}}}}` can show a placeholder literally.
+pub fn substitute_variables(template: &str, vars: &HashMap<String, String>) -> String {
+    let mut output = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(start) = rest.find("{{") {
+        output.push_str(&rest[..start]);
+        rest = &rest[start..];
+
+        let braces = rest.len() - rest.trim_start_matches('{').len();
+        if braces > 2 {
+            output.push_str(&rest[..braces]);
+            rest = &rest[braces..];
+            continue;
+        }
+
+        let inner = &rest[2..];
+        let value = inner.find("}}").and_then(|end| {
+            let closing = &inner[end..];
+            let closing_braces = closing.len() - closing.trim_start_matches('}').len();
+            let key = inner[..end].trim();
+            let is_key = !key.is_empty()
+                && key
+                    .chars()
+                    .all(|c| c.is_alphanumeric() || matches!(c, '_' | '-' | '.'));
+            (closing_braces == 2 && is_key)
+                .then(|| vars.get(key))
+                .flatten()
+                .map(|value| (value, end))
+        });
+        match value {
+            Some((value, end)) => {
+                output.push_str(&escape_html(value));
+                rest = &inner[end + 2..];
+            }
+            None => {
+                output.push_str("{{");
+                rest = inner;
+            }
+        }
+    }
+    output.push_str(rest);
+    output
+}
+
+/// Line-level diff from `old` to `new`. Identical inputs produce no hunks.
+pub fn diff_lines(old: &str, new: &str) -> Vec<DiffHunk> {
+    if old == new {
+        return Vec::new();
+    }
+
+    let mut hunks: Vec<DiffHunk> = Vec::new();
+    for change in TextDiff::from_lines(old, new).iter_all_changes() {
+        let kind = match change.tag() {
+            ChangeTag::Equal => DiffKind::Equal,
+            ChangeTag::Insert => DiffKind::Add,
+            ChangeTag::Delete => DiffKind::Remove,
+        };
+        let line = change.value().trim_end_matches(['\n', '\r']).to_string();
+
+        match hunks.last_mut() {
+            Some(hunk) if hunk.kind == kind => hunk.lines.push(line),
+            _ => hunks.push(DiffHunk {
+                kind,
+                lines: vec![line],
+            }),
+        }
+    }
+    hunks
+}
+
+fn slugify(text: &str) -> String {
+    text.trim()
+        .to_lowercase()
+        .chars()
+        .filter(|c| c.is_alphanumeric() || matches!(c, '-' | '_' | ' '))
+        .map(|c| if c == ' ' { '-' } else { c })
+        .collect()
+}
+
+/// Splits a leading frontmatter block (YAML `---` or TOML `+++` fences) from the
+/// document body.
+///
+/// Returns the text between the fences, if there is a block, and the rest of the
+/// document after the closing fence's line ending.
+pub fn split_frontmatter(markdown: &str) -> (Option<&str>, &str) {
+    let Some(Node::Root(root)) = parse_mdast(markdown) else {
+        return (None, markdown);
+    };
+    let position = match root.children.first() {
+        Some(Node::Yaml(yaml)) => yaml.position.as_ref(),
+        Some(Node::Toml(toml)) => toml.position.as_ref(),
+        _ => None,
+    };
+    let Some(end) = position.map(|p| p.end.offset) else {
+        return (None, markdown);
+    };
+
+    let rest = &markdown[end..];
+    let body = rest
+        .strip_prefix("\r\n")
+        .or_else(|| rest.strip_prefix('\n'))
+        .unwrap_or(rest);
+    // Between the opening fence's line and the closing fence's line
+    let fences = &markdown[..end];
+    let yaml_start = fences.find('\n').map_or(end, |i| i + 1);
+    let yaml_end = fences.rfind('\n').map_or(end, |i| i + 1).max(yaml_start);
+    (Some(&markdown[yaml_start..yaml_end]), body)
+}
+
+/// Parses the frontmatter block, YAML or TOML, into a YAML value.
+///
+/// Returns `None` if there is no frontmatter or it doesn't parse.
+pub fn parse_frontmatter(markdown: &str) -> Option<serde_yaml::Value> {
+    let (Some(frontmatter), _) = split_frontmatter(markdown) else {
+        return None;
+    };
+    if markdown.starts_with("+++") {
+        let table: toml::Table = toml::from_str(frontmatter).ok()?;
+        serde_yaml::to_value(table).ok()
+    } else {
+        serde_yaml::from_str(frontmatter).ok()
+    }
+}
+
+/// The `tags` listed in the frontmatter, in order. A string is a comma-separated list
+/// of tags; non-string entries are skipped.
+pub fn frontmatter_tags(markdown: &str) -> Vec<String> {
+    let Some(frontmatter) = parse_frontmatter(markdown) else {
+        return Vec::new();
+    };
+    match frontmatter.get("tags") {
+        Some(serde_yaml::Value::String(tags)) => tags
+            .split(',')
+            .map(str::trim)
+            .filter(|tag| !tag.is_empty())
+            .map(str::to_string)
+            .collect(),
+        Some(serde_yaml::Value::Sequence(tags)) => tags
+            .iter()
+            .filter_map(|tag| tag.as_str().map(str::to_string))
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+/// The frontmatter `title`, if it is a non-empty string.
+pub fn frontmatter_title(markdown: &str) -> Option<String> {
+    let frontmatter = parse_frontmatter(markdown)?;
+    let title = frontmatter.get("title")?.as_str()?.trim();
+    (!title.is_empty()).then(|| title.to_string())
+}
+
+/// Returns `markdown` with its frontmatter replaced by `frontmatter` as a YAML block,
+/// or added if there was none. A null value removes the block. The body is left
+/// untouched.
+pub fn replace_frontmatter(
+    markdown: &str,
+    frontmatter: &serde_yaml::Value,
+) -> Result<String, serde_yaml::Error> {
+    let (_, body) = split_frontmatter(markdown);
+    if frontmatter.is_null() {
+        return Ok(body.to_string());
+    }
+    let yaml = serde_yaml::to_string(frontmatter)?;
+    Ok(format!("---\n{yaml}---\n{body}"))
+}
+
+/// Returns `markdown` with the task on `line` (1-based) marked as `checked`.
+///
+/// Returns `None` if there is no task list item starting on that line.
+pub fn set_task_checked(markdown: &str, line: usize, checked: bool) -> Option<String> {
+    if !extract_tasks(markdown).iter().any(|task| task.line == line) {
+        return None;
+    }
+
+    let mut lines: Vec<&str> = markdown.split_inclusive('\n').collect();
+    let source_line = lines.get(line.checked_sub(1)?)?;
+    let marker_start = ["[ ]", "[x]", "[X]"]
+        .iter()
+        .filter_map(|marker| source_line.find(marker))
+        .min()?;
+
+    let replacement = if checked { "[x]" } else { "[ ]" };
+    let updated_line = format!(
+        "{}{}{}",
+        &source_line[..marker_start],
+        replacement,
+        &source_line[marker_start + 3..]
+    );
+    lines[line - 1] = &updated_line;
+    Some(lines.concat())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_extract_tasks() {
+        let markdown =
+            "# Todo\n\n- [ ] Write tests\n- [x] Ship it\n- plain item\n  - [X] Nested *done*\n";
+
+        let tasks = extract_tasks(markdown);
+
+        assert_eq!(
+            tasks,
+            vec![
+                Task {
+                    line: 3,
+                    text: "Write tests".to_string(),
+                    checked: false
+                },
+                Task {
+                    line: 4,
+                    text: "Ship it".to_string(),
+                    checked: true
+                },
+                Task {
+                    line: 6,
+                    text: "Nested done".to_string(),
+                    checked: true
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_extract_tasks_line_numbers_account_for_frontmatter() {
+        let markdown = "---\ntitle: Plan\n---\n\n- [ ] First\n";
+
+        let tasks = extract_tasks(markdown);
+
+        assert_eq!(tasks.len(), 1);
+        assert_eq!(tasks[0].line, 5);
+    }
+
+    #[test]
+    fn test_extract_tasks_ignores_code_blocks() {
+        let markdown = "```\n- [ ] not a task\n```\n";
+
+        assert!(extract_tasks(markdown).is_empty());
+    }
+
+    #[test]
+    fn test_heading_terms() {
+        let terms = heading_terms(
+            "# Setup Guide\n\nSetup text is not counted.\n\n## Guide: *Advanced* setup\n",
+        );
+
+        let mut terms: Vec<(&str, usize)> = terms.iter().map(|(w, n)| (w.as_str(), *n)).collect();
+        terms.sort();
+        assert_eq!(terms, vec![("advanced", 1), ("guide", 2), ("setup", 2)]);
+    }
+
+    #[test]
+    fn test_plain_text() {
+        let markdown = "---\ntitle: Hidden\n---\n# Guide\n\nSome **bold** and _emphasis_ with a [link](https://example.com) and `code`.\n\n```rust\nfn main() {}\n```\n\n<div>markup</div>\n";
+
+        assert_eq!(
+            plain_text(markdown),
+            "Guide Some bold and emphasis with a link and code. fn main() {}"
+        );
+    }
+
+    fn variables(pairs: &[(&str, &str)]) -> HashMap<String, String> {
+        pairs
+            .iter()
+            .map(|(key, value)| (key.to_string(), value.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn test_substitute_variables() {
+        let vars = variables(&[("version", "1.2.3"), ("product", "Acme")]);
+
+        assert_eq!(
+            substitute_variables("# {{product}} {{ version }}\n\n{{version}}!", &vars),
+            "# Acme 1.2.3\n\n1.2.3!"
+        );
+        assert_eq!(
+            substitute_variables("No placeholders", &vars),
+            "No placeholders"
+        );
+    }
+
+    #[test]
+    fn test_substitute_variables_leaves_missing_keys() {
+        let vars = variables(&[("version", "1.2.3")]);
+
+        assert_eq!(
+            substitute_variables(
+                "{{missing}} {{version}} {{}} {{not a key}} {{version",
+                &vars
+            ),
+            "{{missing}} 1.2.3 {{}} {{not a key}} {{version"
+        );
+    }
+
+    #[test]
+    fn test_substitute_variables_escapes_values() {
+        let vars = variables(&[("name", "<script>alert(\"x\")</script> & co")]);
+
+        assert_eq!(
+            substitute_variables("Hi {{name}}", &vars),
+            "Hi &lt;script&gt;alert(&quot;x&quot;)&lt;/script&gt; &amp; co"
+        );
+    }
+
+    #[test]
+    fn test_substitute_variables_ignores_nested_braces() {
+        let vars = variables(&[("version", "1.2.3")]);
+
+        assert_eq!(
+            substitute_variables("{{{{version}}}} {{{version}}} {{version}}}", &vars),
+            "{{{{version}}}} {{{version}}} {{version}}}"
+        );
+        assert_eq!(
+            substitute_variables("{{{{ {{version}}", &vars),
+            "{{{{ 1.2.3"
+        );
+    }
+
+    /// The outline as `H1 Text (H2 Child, ...), ...`, for comparing outlines.
+    fn outline_shape(outline: &[OutlineEntry]) -> String {
+        outline
+            .iter()
+            .map(|entry| {
+                let mut shape = format!("H{} {}", entry.level, entry.text);
+                if !entry.children.is_empty() {
+                    shape.push_str(&format!(" ({})", outline_shape(&entry.children)));
+                }
+                shape
+            })
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+
+    #[test]
+    fn test_build_outline() {
+        let anchors = generate_anchors(
+            "## Preface\n\n# One\n\n### Deep\n\n## Two\n\n#### Deeper\n\n# Three\n",
+        );
+
+        let outline = build_outline(anchors.clone(), 6);
+        assert_eq!(
+            outline_shape(&outline),
+            "H2 Preface, H1 One (H3 Deep, H2 Two (H4 Deeper)), H1 Three"
+        );
+        assert_eq!(outline[1].id, "one");
+
+        assert_eq!(
+            outline_shape(&build_outline(anchors.clone(), 2)),
+            "H2 Preface, H1 One (H2 Two), H1 Three"
+        );
+        assert_eq!(
+            outline_shape(&build_outline(anchors, 1)),
+            "H1 One, H1 Three"
+        );
+    }
+
+    #[test]
+    fn test_parse_references() {
+        let markdown = "# Paper\n\nMUST[^1] and SHOULD[^rfc] per [the spec][Spec].\n\n[^1]: See RFC 2119.\n[^rfc]: Key words,\n    defined *precisely*.\n\n[Spec]: https://www.rfc-editor.org/rfc/rfc2119 \"RFC 2119\"\n[unused]: ./notes.md\n\n```\n[^code]: not a footnote\n```\n";
+
+        assert_eq!(
+            parse_references(markdown),
+            vec![
+                ReferenceEntry {
+                    id: "1".to_string(),
+                    definition: "See RFC 2119.".to_string(),
+                    url: None,
+                    line: 5,
+                },
+                ReferenceEntry {
+                    id: "rfc".to_string(),
+                    definition: "Key words, defined precisely.".to_string(),
+                    url: None,
+                    line: 6,
+                },
+                ReferenceEntry {
+                    id: "Spec".to_string(),
+                    definition: "RFC 2119".to_string(),
+                    url: Some("https://www.rfc-editor.org/rfc/rfc2119".to_string()),
+                    line: 9,
+                },
+                ReferenceEntry {
+                    id: "unused".to_string(),
+                    definition: String::new(),
+                    url: Some("./notes.md".to_string()),
+                    line: 10,
+                },
+            ]
+        );
+        assert_eq!(parse_references("No references here."), vec![]);
+    }
+
+    #[test]
+    fn test_extract_footnotes() {
+        let markdown = "# Paper\n\nMUST[^1], SHOULD[^missing] and MAY[^1].\n\\[^escaped] and `[^code]`\n\n[^1]: See RFC 2119,\n    *section* 5.\n[^unused]: Never cited.\n";
+
+        assert_eq!(
+            extract_footnotes(markdown),
+            FootnoteReport {
+                footnotes: vec![
+                    FootnoteEntry {
+                        id: "1".to_string(),
+                        usage_line: Some(3),
+                        definition_line: Some(6),
+                        text: Some("See RFC 2119, section 5.".to_string()),
+                    },
+                    FootnoteEntry {
+                        id: "missing".to_string(),
+                        usage_line: Some(3),
+                        definition_line: None,
+                        text: None,
+                    },
+                    FootnoteEntry {
+                        id: "unused".to_string(),
+                        usage_line: None,
+                        definition_line: Some(8),
+                        text: Some("Never cited.".to_string()),
+                    },
+                ],
+                orphaned_usages: vec!["missing".to_string()],
+                orphaned_definitions: vec!["unused".to_string()],
+            }
+        );
+        assert_eq!(
+            extract_footnotes("No footnotes."),
+            FootnoteReport::default()
+        );
+    }
+
+    fn badge(alt: &str, image_url: &str, link_url: Option<&str>, source: BadgeSource) -> Badge {
+        Badge {
+            alt: alt.to_string(),
+            image_url: image_url.to_string(),
+            link_url: link_url.map(str::to_string),
+            source,
+        }
+    }
+
+    #[test]
+    fn test_extract_badges_shields() {
+        let markdown = "# Project\n\n[![CI](https://img.shields.io/github/actions/workflow/status/o/r/ci.yml)](https://github.com/o/r/actions) ![License](https://img.shields.io/badge/license-MIT-blue)\n\n![Diagram](https://example.com/shields.io.png)\n";
+
+        assert_eq!(
+            extract_badges(markdown),
+            vec![
+                badge(
+                    "CI",
+                    "https://img.shields.io/github/actions/workflow/status/o/r/ci.yml",
+                    Some("https://github.com/o/r/actions"),
+                    BadgeSource::Shields
+                ),
+                badge(
+                    "License",
+                    "https://img.shields.io/badge/license-MIT-blue",
+                    None,
+                    BadgeSource::Shields
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_extract_badges_badgen() {
+        let markdown = "[![npm](https://badgen.net/npm/v/pkg)](https://npmjs.com/package/pkg)\n![Downloads](https://badgen.net/npm/dm/pkg)\n";
+
+        assert_eq!(
+            extract_badges(markdown),
+            vec![
+                badge(
+                    "npm",
+                    "https://badgen.net/npm/v/pkg",
+                    Some("https://npmjs.com/package/pkg"),
+                    BadgeSource::Badgen
+                ),
+                badge(
+                    "Downloads",
+                    "https://badgen.net/npm/dm/pkg",
+                    None,
+                    BadgeSource::Badgen
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_extract_badges_custom() {
+        let markdown = "[![Coverage](https://ci.example.com/coverage.svg)](https://ci.example.com) ![Gem](https://badge.fury.io/rb/gem.svg)\n\n![Screenshot](screenshot.png)\n\n[Text link](https://example.com)\n\n```\n[![Code](https://img.shields.io/x)](https://example.com)\n```\n";
+
+        assert_eq!(
+            extract_badges(markdown),
+            vec![
+                badge(
+                    "Coverage",
+                    "https://ci.example.com/coverage.svg",
+                    Some("https://ci.example.com"),
+                    BadgeSource::Custom
+                ),
+                badge(
+                    "Gem",
+                    "https://badge.fury.io/rb/gem.svg",
+                    None,
+                    BadgeSource::Custom
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_extract_math() {
+        let markdown = "# Physics\n\nEnergy $E = mc^2$ and $$\\sum_{i=1}^n i$$ inline.\n\n$$\nF = ma\n$$\n\n\\[\n\\int_0^1 x\\,dx\n\\]\n\nCosts $5 and $10, \\$x\\$, $ spaced $ and `$code$`.\n\n```\n$$not math$$\n```\n";
+
+        let entries = extract_math(markdown);
+
+        let formulas: Vec<(MathKind, &str, usize)> = entries
+            .iter()
+            .map(|formula| (formula.kind, formula.latex.as_str(), formula.line))
+            .collect();
+        assert_eq!(
+            formulas,
+            vec![
+                (MathKind::Inline, "E = mc^2", 3),
+                (MathKind::Block, "\\sum_{i=1}^n i", 3),
+                (MathKind::Block, "F = ma", 5),
+                (MathKind::Block, "\\int_0^1 x\\,dx", 9),
+            ]
+        );
+        assert!(extract_math("No math, just $5.").is_empty());
+    }
+
+    #[test]
+    fn test_extract_diagrams() {
+        let markdown = "# Design\n\n```mermaid\ngraph LR\n  A --> B\n```\n\n~~~PUML\n@startuml\nA -> B\n@enduml\n~~~\n\n```plantuml,large\nclass A\n```\n\n```rust\nfn main() {}\n```\n\n    mermaid\n";
+
+        assert_eq!(
+            extract_diagrams(markdown),
+            vec![
+                DiagramEntry {
+                    kind: DiagramKind::Mermaid,
+                    definition: "graph LR\n  A --> B".to_string(),
+                    line_start: 3,
+                    line_end: 6,
+                },
+                DiagramEntry {
+                    kind: DiagramKind::Plantuml,
+                    definition: "@startuml\nA -> B\n@enduml".to_string(),
+                    line_start: 8,
+                    line_end: 12,
+                },
+                DiagramEntry {
+                    kind: DiagramKind::Plantuml,
+                    definition: "class A".to_string(),
+                    line_start: 14,
+                    line_end: 16,
+                },
+            ]
+        );
+        assert_eq!("mermaid".parse(), Ok(DiagramKind::Mermaid));
+        assert!("puml".parse::<DiagramKind>().is_err());
+    }
+
+    #[test]
+    fn test_element_counts() {
+        let markdown = "# Title\n\n## Links\n\n[inline](a.md), [reference][ref], <https://example.com> and www.example.com.\n\n![image](a.png) ![ref image][ref]\n\n[ref]: b.png\n";
+
+        assert_eq!(
+            element_counts(markdown),
+            ElementCounts {
+                headings: 2,
+                links: 4,
+                images: 2,
+            }
+        );
+    }
+
+    #[test]
+    fn test_generate_anchors() {
+        let markdown = "# Getting Started\n\n## Installation\n\n### `cargo install` & more!\n\nSetext\n------\n";
+
+        let anchors = generate_anchors(markdown);
+
+        let summary: Vec<(&str, &str, u8)> = anchors
+            .iter()
+            .map(|a| (a.id.as_str(), a.text.as_str(), a.level))
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                ("getting-started", "Getting Started", 1),
+                ("installation", "Installation", 2),
+                ("cargo-install--more", "cargo install & more!", 3),
+                ("setext", "Setext", 2),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_generate_anchors_deduplicates() {
+        let markdown = "# Overview\n# Overview\n# Overview-1\n# Overview\n";
+
+        let ids: Vec<String> = generate_anchors(markdown)
+            .into_iter()
+            .map(|a| a.id)
+            .collect();
+
+        assert_eq!(
+            ids,
+            vec!["overview", "overview-1", "overview-1-1", "overview-2"]
+        );
+    }
+
+    #[test]
+    fn test_generate_anchors_unicode() {
+        let markdown = "# Café Über\n## 日本語 テスト\n## Ünïcödé_snake 🚀\n";
+
+        let ids: Vec<String> = generate_anchors(markdown)
+            .into_iter()
+            .map(|a| a.id)
+            .collect();
+
+        assert_eq!(ids, vec!["café-über", "日本語-テスト", "ünïcödé_snake-"]);
+    }
+
+    #[test]
+    fn test_extract_tables() {
+        let markdown = "Intro\n\n| Name | Version |\n|:-----|--------:|\n| axum | 0.7 |\n|  tokio  |  1.0  |\n";
+
+        assert_eq!(
+            extract_tables(markdown),
+            vec![MarkdownTable {
+                headers: vec!["Name".to_string(), "Version".to_string()],
+                rows: vec![
+                    vec!["axum".to_string(), "0.7".to_string()],
+                    vec!["tokio".to_string(), "1.0".to_string()],
+                ],
+                line: 3,
+            }]
+        );
+    }
+
+    #[test]
+    fn test_extract_code_blocks() {
+        let markdown = "```rust,runnable\nfn main() {}\n```\n\n```python # runnable\nprint(1)\n```\n\n```sh,run\nls\n```\n\n```rust,no_run\nloop {}\n```\n\n```\nplain\n```\n\n    indented\n";
+
+        let blocks = extract_code_blocks(markdown);
+
+        assert_eq!(
+            blocks
+                .iter()
+                .map(|block| (block.line, block.lang.as_deref(), block.runnable))
+                .collect::<Vec<_>>(),
+            vec![
+                (1, Some("rust"), true),
+                (5, Some("python"), true),
+                (9, Some("sh"), true),
+                (13, Some("rust"), false),
+                (17, None, false),
+                (21, None, false),
+            ]
+        );
+        assert_eq!(blocks[0].code, "fn main() {}");
+        assert_eq!(blocks[5].code, "indented");
+    }
+
+    #[test]
+    fn test_extract_all_todos() {
+        let markdown = "# Plan\n\n- [ ] Write docs\n- [x] FIXME: broken link\n\nTODO: add **examples**\nand a fixme: later\n\n<!-- HACK: remove\nnote: keep this -->\n\n```rust\n// todo: handle errors\nlet autodoc: u8 = 1;\n```\n\nText <!-- TODO: inline comment --> here\n";
+
+        let todos = extract_all_todos(markdown);
+
+        assert_eq!(
+            todos
+                .iter()
+                .map(|todo| (
+                    todo.line,
+                    todo.kind,
+                    todo.priority,
+                    todo.text.as_str(),
+                    todo.done
+                ))
+                .collect::<Vec<_>>(),
+            vec![
+                (
+                    3,
+                    TodoKind::Checkbox,
+                    TodoPriority::Normal,
+                    "Write docs",
+                    false
+                ),
+                (
+                    4,
+                    TodoKind::Checkbox,
+                    TodoPriority::Fixme,
+                    "broken link",
+                    true
+                ),
+                (
+                    6,
+                    TodoKind::Inline,
+                    TodoPriority::Normal,
+                    "add examples",
+                    false
+                ),
+                (7, TodoKind::Inline, TodoPriority::Fixme, "later", false),
+                (9, TodoKind::Comment, TodoPriority::Hack, "remove", false),
+                (
+                    10,
+                    TodoKind::Comment,
+                    TodoPriority::Normal,
+                    "keep this",
+                    false
+                ),
+                (
+                    13,
+                    TodoKind::Inline,
+                    TodoPriority::Normal,
+                    "handle errors",
+                    false
+                ),
+                (
+                    17,
+                    TodoKind::Comment,
+                    TodoPriority::Normal,
+                    "inline comment",
+                    false
+                ),
+            ]
+        );
+        assert!(extract_all_todos("# Done\n\nNothing to see.\n").is_empty());
+    }
+
+    fn definitions(markdown: &str) -> Vec<(String, String, usize)> {
+        extract_definitions(markdown)
+            .into_iter()
+            .map(|entry| (entry.term, entry.definition, entry.line))
+            .collect()
+    }
+
+    fn entry(term: &str, definition: &str, line: usize) -> (String, String, usize) {
+        (term.to_string(), definition.to_string(), line)
+    }
+
+    #[test]
+    fn test_extract_definitions() {
+        let markdown = "# Terms\n\nAPI\n:   Application Programming\n    Interface\n\nCLI\n\n:   Command-line interface\n\n*Crate*\n  ~ A compilation unit\n  ~ A package on crates.io\n";
+
+        assert_eq!(
+            definitions(markdown),
+            vec![
+                entry("API", "Application Programming Interface", 3),
+                entry("CLI", "Command-line interface", 7),
+                entry("Crate", "A compilation unit", 11),
+                entry("Crate", "A package on crates.io", 11),
+            ]
+        );
+        assert!(definitions("# Title\n\nText: with a colon\n").is_empty());
+        assert!(definitions("```\nTerm\n: in code\n```\n").is_empty());
+    }
+
+    #[test]
+    fn test_extract_definitions_from_html() {
+        let markdown = "Intro\n\n<dl>\n  <dt>HTTP</dt>\n  <dd>Hypertext <b>Transfer</b> Protocol</dd>\n  <DD class=\"alt\">A protocol\n<DT>URL<dd>Uniform Resource Locator\n</dl>\n";
+
+        assert_eq!(
+            definitions(markdown),
+            vec![
+                entry("HTTP", "Hypertext Transfer Protocol", 4),
+                entry("HTTP", "A protocol", 4),
+                entry("URL", "Uniform Resource Locator", 7),
+            ]
+        );
+    }
+
+    fn admonitions(markdown: &str) -> Vec<(String, String, String, usize)> {
+        extract_admonitions(markdown)
+            .into_iter()
+            .map(|a| (a.kind, a.title, a.content, a.line))
+            .collect()
+    }
+
+    fn callout(
+        kind: &str,
+        title: &str,
+        content: &str,
+        line: usize,
+    ) -> (String, String, String, usize) {
+        (
+            kind.to_string(),
+            title.to_string(),
+            content.to_string(),
+            line,
+        )
+    }
+
+    #[test]
+    fn test_extract_admonitions_nested_and_in_code() {
+        let markdown = "::::danger[Careful]\n\n!!! tip \"\"\n    Indented\n\n        code\n\n:::note\nInner\n:::\n\n::::\n\n```\n!!! note\n:::tip\n:::\n```\n\n:::info\nNever closed\n";
+
+        assert_eq!(
+            admonitions(markdown),
+            vec![
+                callout(
+                    "danger",
+                    "Careful",
+                    "!!! tip \"\"\n    Indented\n\n        code\n\n:::note\nInner\n:::",
+                    1
+                ),
+                callout("tip", "", "Indented\n\n    code", 3),
+                callout("note", "Note", "Inner", 8),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_extract_glossary_terms_context_and_duplicates() {
+        let markdown = "# Terms\n\nIntro. A **crate** (compilation unit) is what cargo builds! See\nthe book.\n\n- ***Workspace***: several crates\n- **Crate** — defined again\n\nNot **bold** text, and *italic*. **Empty**:\n";
+        let entries = extract_glossary_terms(markdown);
+
+        let terms: Vec<(&str, &str, usize)> = entries
+            .iter()
+            .map(|entry| (entry.term.as_str(), entry.context.as_str(), entry.line))
+            .collect();
+        assert_eq!(
+            terms,
+            vec![
+                (
+                    "crate",
+                    "A **crate** (compilation unit) is what cargo builds!",
+                    3
+                ),
+                ("Workspace", "***Workspace***: several crates", 6),
+            ]
+        );
+
+        let long = format!("**Term** ({}).\n", "word ".repeat(100));
+        assert_eq!(
+            extract_glossary_terms(&long)[0].context.chars().count(),
+            200
+        );
+    }
+
+    #[test]
+    fn test_extract_tables_escaped_pipes_and_empty_cells() {
+        let markdown = "| a | b | c |\n|---|---|---|\n| x \\| y | `p\\|q` |\n|  | only |\n";
+
+        let tables = extract_tables(markdown);
+
+        assert_eq!(
+            tables[0].rows,
+            vec![
+                vec!["x | y".to_string(), "p|q".to_string(), String::new()],
+                vec![String::new(), "only".to_string(), String::new()],
+            ]
+        );
+    }
+
+    #[test]
+    fn test_extract_tables_back_to_back() {
+        // A heading ends a table even without a blank line
+        let markdown = "| a |\n|---|\n| 1 |\n## Next\n| b |\n|---|\n| 2 |\n";
+
+        let tables = extract_tables(markdown);
+
+        assert_eq!(tables.len(), 2);
+        assert_eq!(
+            (tables[0].headers.clone(), tables[0].line),
+            (vec!["a".to_string()], 1)
+        );
+        assert_eq!(
+            (tables[1].headers.clone(), tables[1].line),
+            (vec!["b".to_string()], 5)
+        );
+    }
+
+    #[test]
+    fn test_estimate_reading_time() {
+        // 1000 words of prose should take about five minutes
+        let paragraph = "The quick brown fox jumps over the lazy dog again. ".repeat(10);
+        let markdown = format!("# Title\n\n{}", format!("{paragraph}\n\n").repeat(10));
+
+        let estimate = estimate_reading_time(&markdown);
+
+        assert_eq!(estimate.words, 1001);
+        assert_eq!(estimate.code_words, 0);
+        assert!((270..=330).contains(&estimate.seconds), "{estimate:?}");
+        assert_eq!(estimate.minutes, 5);
+    }
+
+    #[test]
+    fn test_estimate_reading_time_counts_code_separately() {
+        let markdown =
+            "Run `cargo build` now.\n\n```rust\nfn main() {\n    println!(\"hi\");\n}\n```\n";
+
+        let estimate = estimate_reading_time(markdown);
+
+        assert_eq!(estimate.words, 4);
+        assert_eq!(estimate.code_words, 5);
+        assert_eq!(estimate.seconds, 1 + 6);
+        assert_eq!(estimate.minutes, 1);
+    }
+
+    #[test]
+    fn test_estimate_reading_time_empty() {
+        let estimate = estimate_reading_time("");
+
+        assert_eq!((estimate.minutes, estimate.seconds), (0, 0));
+    }
+
+    #[test]
+    fn test_extract_summary() {
+        let markdown = "---\ntitle: ignored\n---\n## Overview\n\n# Installation Guide\n\n- a list\n\nThis guide walks\nyou through `setup`.\n\nMore text.\n";
+
+        let summary = extract_summary(markdown);
+
+        assert_eq!(summary.title.as_deref(), Some("Installation Guide"));
+        assert_eq!(summary.summary, "This guide walks you through setup.");
+        assert_eq!(summary.word_count, 13);
+    }
+
+    #[test]
+    fn test_extract_summary_truncates_and_handles_missing_parts() {
+        let long = extract_summary(&"é ".repeat(400));
+        assert_eq!(long.title, None);
+        assert_eq!(long.summary.chars().count(), 300);
+
+        let empty = extract_summary("# Only a title\n");
+        assert_eq!(empty.summary, "");
+        assert_eq!(empty.word_count, 3);
+    }
+
+    #[test]
+    fn test_frontmatter_tags() {
+        assert_eq!(
+            frontmatter_tags("---\ntags: [rust, async, 3]\n---\n# Post\n"),
+            vec!["rust", "async"]
+        );
+        assert_eq!(
+            frontmatter_tags("+++\ntags = \"rust\"\n+++\n"),
+            vec!["rust"]
+        );
+        assert_eq!(
+            frontmatter_tags("---\ntags: rust, async ,\n---\n"),
+            vec!["rust", "async"]
+        );
+        assert!(frontmatter_tags("---\ntitle: Untagged\n---\n").is_empty());
+        assert!(frontmatter_tags("# No frontmatter\n").is_empty());
+    }
+
+    #[test]
+    fn test_frontmatter_title() {
+        assert_eq!(
+            frontmatter_title("---\ntitle: \" Post \"\n---\n# Heading\n"),
+            Some("Post".to_string())
+        );
+        assert_eq!(frontmatter_title("---\ntitle: \"\"\n---\n"), None);
+        assert_eq!(frontmatter_title("---\ntitle: [a, b]\n---\n"), None);
+        assert_eq!(frontmatter_title("# No frontmatter\n"), None);
+    }
+
+    #[test]
+    fn test_diff_lines() {
+        let old = "# Title\nkeep\nold line\nend\n";
+        let new = "# Title\nkeep\nnew line\r\nextra\nend\n";
+
+        let summary: Vec<(DiffKind, Vec<String>)> = diff_lines(old, new)
+            .into_iter()
+            .map(|hunk| (hunk.kind, hunk.lines))
+            .collect();
+
+        assert_eq!(
+            summary,
+            vec![
+                (DiffKind::Equal, vec!["# Title".into(), "keep".into()]),
+                (DiffKind::Remove, vec!["old line".into()]),
+                (DiffKind::Add, vec!["new line".into(), "extra".into()]),
+                (DiffKind::Equal, vec!["end".into()]),
+            ]
+        );
+        assert!(diff_lines(old, old).is_empty());
+    }
+
+    #[test]
+    fn test_set_task_checked() {
+        let markdown = "- [ ] One\r\n- [x] Two [ ] literal\r\n";
+
+        assert_eq!(
+            set_task_checked(markdown, 1, true).as_deref(),
+            Some("- [x] One\r\n- [x] Two [ ] literal\r\n")
+        );
+        assert_eq!(
+            set_task_checked(markdown, 2, false).as_deref(),
+            Some("- [ ] One\r\n- [ ] Two [ ] literal\r\n")
+        );
+        assert_eq!(set_task_checked(markdown, 3, true), None);
+        assert_eq!(set_task_checked("plain [ ] text\n", 1, true), None);
+    }
+
+    #[test]
+    fn test_split_frontmatter() {
+        assert_eq!(
+            split_frontmatter("---\ntitle: Hi\n---\n# Body\n"),
+            (Some("title: Hi\n"), "# Body\n")
+        );
+        assert_eq!(split_frontmatter("---\n---\nBody"), (Some(""), "Body"));
+        assert_eq!(
+            split_frontmatter("+++\ntitle = \"Hi\"\n+++\r\nBody"),
+            (Some("title = \"Hi\"\n"), "Body")
+        );
+        assert_eq!(
+            split_frontmatter("# No frontmatter\n"),
+            (None, "# No frontmatter\n")
+        );
+        // A thematic break later in the document is not frontmatter
+        assert_eq!(split_frontmatter("Text\n\n---\n"), (None, "Text\n\n---\n"));
+    }
+
+    #[test]
+    fn test_parse_frontmatter() {
+        let yaml = parse_frontmatter("---\ntitle: Hi\n---\n# Body\n").unwrap();
+        assert_eq!(yaml["title"].as_str(), Some("Hi"));
+        let toml = parse_frontmatter("+++\ntitle = \"Hi\"\n+++\n# Body\n").unwrap();
+        assert_eq!(toml["title"].as_str(), Some("Hi"));
+        assert_eq!(parse_frontmatter("# Body\n"), None);
+        assert_eq!(parse_frontmatter("---\n[unclosed\n---\n"), None);
+    }
+
+    #[test]
+    fn test_replace_frontmatter() {
+        let frontmatter: serde_yaml::Value =
+            serde_yaml::from_str("title: New\ntags: [a, b]").unwrap();
+        let expected = "---\ntitle: New\ntags:\n- a\n- b\n---\n# Body\n";
+
+        assert_eq!(
+            replace_frontmatter("---\ntitle: Old\n---\n# Body\n", &frontmatter).unwrap(),
+            expected
+        );
+        assert_eq!(
+            replace_frontmatter("# Body\n", &frontmatter).unwrap(),
+            expected
+        );
+        assert_eq!(
+            replace_frontmatter("---\ntitle: Old\n---\n# Body\n", &serde_yaml::Value::Null)
+                .unwrap(),
+            "# Body\n"
+        );
+    }
+}
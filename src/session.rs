@@ -0,0 +1,245 @@
+//! Numbered change notifications and WebSocket sessions, so a client that reconnects
+//! after a network interruption can catch up on the events it missed.
+
+use serde::Serialize;
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+};
+use tokio::sync::broadcast;
+use uuid::Uuid;
+
+use crate::app::ServerMessage;
+
+/// How many of the most recent events are kept for replay.
+pub(crate) const EVENT_BUFFER_SIZE: usize = 50;
+/// Sessions kept for clients that may reconnect, beyond which the stalest are dropped.
+const MAX_SESSIONS: usize = 1024;
+
+/// A broadcast [`ServerMessage`] with its sequence number, e.g.
+/// `{"seq": 7, "type": "Reload"}`.
+#[derive(Debug, Clone, Serialize)]
+pub(crate) struct SequencedMessage {
+    pub(crate) seq: u64,
+    #[serde(flatten)]
+    pub(crate) message: ServerMessage,
+}
+
+struct RecentEvents {
+    /// Sequence number of the last event sent, 0 before the first
+    last_seq: u64,
+    events: VecDeque<SequencedMessage>,
+}
+
+/// Sends change notifications to every connected client, numbering them from 1 and
+/// keeping the last [`EVENT_BUFFER_SIZE`] for replay.
+pub(crate) struct EventBroadcaster {
+    tx: broadcast::Sender<SequencedMessage>,
+    // Also held while sending, so sequence numbers go out in order
+    recent: Mutex<RecentEvents>,
+}
+
+impl EventBroadcaster {
+    pub(crate) fn new() -> Self {
+        let (tx, _) = broadcast::channel(16);
+        EventBroadcaster {
+            tx,
+            recent: Mutex::new(RecentEvents {
+                last_seq: 0,
+                events: VecDeque::with_capacity(EVENT_BUFFER_SIZE),
+            }),
+        }
+    }
+
+    fn recent(&self) -> std::sync::MutexGuard<'_, RecentEvents> {
+        self.recent
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+    }
+
+    /// Numbers `message` and sends it to all subscribers. It is buffered even when
+    /// nobody is connected.
+    pub(crate) fn send(&self, message: ServerMessage) {
+        let mut recent = self.recent();
+        recent.last_seq += 1;
+        let event = SequencedMessage {
+            seq: recent.last_seq,
+            message,
+        };
+
+        if recent.events.len() == EVENT_BUFFER_SIZE {
+            recent.events.pop_front();
+        }
+        recent.events.push_back(event.clone());
+        let _ = self.tx.send(event);
+    }
+
+    /// Subscribes to events sent from now on, together with the sequence number of the
+    /// last event sent before.
+    pub(crate) fn subscribe(&self) -> (broadcast::Receiver<SequencedMessage>, u64) {
+        let recent = self.recent();
+        (self.tx.subscribe(), recent.last_seq)
+    }
+
+    /// The buffered events numbered after `after` up to and including `until`.
+    ///
+    /// Returns `None` if some of them have already been dropped from the buffer.
+    pub(crate) fn replay(&self, after: u64, until: u64) -> Option<Vec<SequencedMessage>> {
+        if after >= until {
+            return Some(Vec::new());
+        }
+
+        let recent = self.recent();
+        let oldest = recent.events.front().map_or(recent.last_seq + 1, |e| e.seq);
+        if oldest > after + 1 {
+            return None;
+        }
+
+        Some(
+            recent
+                .events
+                .iter()
+                .filter(|event| event.seq > after && event.seq <= until)
+                .cloned()
+                .collect(),
+        )
+    }
+}
+
+/// The last event sequence number delivered to each client, by server-assigned ID.
+///
+/// Sessions outlive their connection so a client can resume one with
+/// `ClientMessage::Reconnect`.
+#[derive(Debug, Default)]
+pub(crate) struct SessionRegistry {
+    sessions: HashMap<Uuid, u64>,
+}
+
+impl SessionRegistry {
+    /// Starts a session for a client that has seen everything up to `last_seq`.
+    pub(crate) fn register(&mut self, last_seq: u64) -> Uuid {
+        // Sessions that fell too far behind can't be replayed any more
+        let cutoff = last_seq.saturating_sub(EVENT_BUFFER_SIZE as u64);
+        self.sessions.retain(|_, seen| *seen >= cutoff);
+        if self.sessions.len() >= MAX_SESSIONS {
+            let mut by_seen: Vec<(u64, Uuid)> = self
+                .sessions
+                .iter()
+                .map(|(id, seen)| (*seen, *id))
+                .collect();
+            by_seen.sort_unstable();
+            for (_, id) in &by_seen[..=self.sessions.len() - MAX_SESSIONS] {
+                self.sessions.remove(id);
+            }
+        }
+
+        let client_id = Uuid::new_v4();
+        self.sessions.insert(client_id, last_seq);
+        client_id
+    }
+
+    /// Notes that the client has been sent the event numbered `seq`.
+    pub(crate) fn record(&mut self, client_id: Uuid, seq: u64) {
+        if let Some(seen) = self.sessions.get_mut(&client_id) {
+            *seen = (*seen).max(seq);
+        }
+    }
+
+    /// Ends a previous session that a reconnected client is taking over, returning
+    /// whether it was known.
+    pub(crate) fn resume(&mut self, client_id: Uuid) -> bool {
+        self.sessions.remove(&client_id).is_some()
+    }
+}
+
+/// One of the limited WebSocket connection slots, given back when dropped.
+pub(crate) struct ConnectionGuard {
+    open: Arc<AtomicUsize>,
+}
+
+impl ConnectionGuard {
+    /// Takes a slot unless `limit` connections are already open.
+    pub(crate) fn acquire(open: &Arc<AtomicUsize>, limit: usize) -> Option<Self> {
+        open.fetch_update(Ordering::SeqCst, Ordering::SeqCst, |count| {
+            (count < limit).then_some(count + 1)
+        })
+        .ok()?;
+        Some(ConnectionGuard { open: open.clone() })
+    }
+}
+
+impl Drop for ConnectionGuard {
+    fn drop(&mut self) {
+        self.open.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_event_broadcaster_numbers_and_replays_events() {
+        let broadcaster = EventBroadcaster::new();
+        let (mut rx, last_seq) = broadcaster.subscribe();
+        assert_eq!(last_seq, 0);
+
+        broadcaster.send(ServerMessage::Reload);
+        broadcaster.send(ServerMessage::FileAdded {
+            name: "new.md".to_string(),
+        });
+        assert_eq!(rx.try_recv().unwrap().seq, 1);
+
+        let replayed: Vec<u64> = broadcaster
+            .replay(1, 2)
+            .unwrap()
+            .iter()
+            .map(|event| event.seq)
+            .collect();
+        assert_eq!(replayed, vec![2]);
+        assert!(broadcaster.replay(2, 2).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_event_broadcaster_replay_fails_once_events_are_dropped() {
+        let broadcaster = EventBroadcaster::new();
+        for _ in 0..EVENT_BUFFER_SIZE + 5 {
+            broadcaster.send(ServerMessage::Reload);
+        }
+
+        let last = (EVENT_BUFFER_SIZE + 5) as u64;
+        assert!(broadcaster.replay(0, last).is_none());
+        assert!(broadcaster.replay(4, last).is_none());
+        assert_eq!(
+            broadcaster.replay(5, last).unwrap().len(),
+            EVENT_BUFFER_SIZE
+        );
+    }
+
+    #[test]
+    fn test_session_registry_resume() {
+        let mut registry = SessionRegistry::default();
+        let client_id = registry.register(0);
+        registry.record(client_id, 3);
+
+        assert!(registry.resume(client_id));
+        assert!(!registry.resume(client_id));
+        assert!(!registry.resume(Uuid::new_v4()));
+    }
+
+    #[test]
+    fn test_connection_guard_enforces_limit() {
+        let open = Arc::new(AtomicUsize::new(0));
+        let first = ConnectionGuard::acquire(&open, 2).unwrap();
+        let _second = ConnectionGuard::acquire(&open, 2).unwrap();
+        assert!(ConnectionGuard::acquire(&open, 2).is_none());
+        assert_eq!(open.load(Ordering::SeqCst), 2);
+
+        drop(first);
+        assert_eq!(open.load(Ordering::SeqCst), 1);
+        assert!(ConnectionGuard::acquire(&open, 2).is_some());
+    }
+}
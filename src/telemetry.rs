@@ -0,0 +1,81 @@
+//! Prometheus metrics served by `GET /__metrics` when `--metrics` is given.
+//!
+//! The `metrics` macros are no-ops until a recorder is installed, so the instrumentation
+//! below costs nothing when the endpoint is disabled.
+
+use axum::{
+    extract::{MatchedPath, Request},
+    middleware::Next,
+    response::Response,
+};
+use metrics_exporter_prometheus::{PrometheusBuilder, PrometheusHandle};
+use std::{sync::OnceLock, time::Instant};
+
+/// Upper bounds, in seconds, of the request duration histogram buckets.
+const DURATION_BUCKETS: &[f64] = &[0.001, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 5.0];
+
+static PROMETHEUS: OnceLock<PrometheusHandle> = OnceLock::new();
+
+/// Installs the process-wide Prometheus recorder on first use and returns its handle.
+///
+/// Metrics are global to the process, so every router built with metrics enabled shares
+/// one recorder.
+pub(crate) fn prometheus() -> PrometheusHandle {
+    PROMETHEUS
+        .get_or_init(|| {
+            let recorder = PrometheusBuilder::new()
+                .set_buckets(DURATION_BUCKETS)
+                .expect("duration buckets are not empty")
+                .build_recorder();
+            let handle = recorder.handle();
+            if metrics::set_global_recorder(recorder).is_err() {
+                tracing::warn!(
+                    "another metrics recorder is already installed; /__metrics will stay empty"
+                );
+            }
+            handle
+        })
+        .clone()
+}
+
+/// Counts every routed request and records how long it took, labelled by route pattern
+/// (e.g. `/api/files/*path`) rather than the raw path to keep the label set small.
+pub(crate) async fn track_requests(request: Request, next: Next) -> Response {
+    let method = request.method().to_string();
+    let path = request.extensions().get::<MatchedPath>().map_or_else(
+        || request.uri().path().to_string(),
+        |p| p.as_str().to_string(),
+    );
+    let start = Instant::now();
+
+    let response = next.run(request).await;
+
+    metrics::counter!(
+        "mdserve_request_total",
+        "method" => method.clone(),
+        "path" => path.clone(),
+        "status" => response.status().as_u16().to_string(),
+    )
+    .increment(1);
+    metrics::histogram!(
+        "mdserve_request_duration_seconds",
+        "method" => method,
+        "path" => path,
+    )
+    .record(start.elapsed().as_secs_f64());
+
+    response
+}
+
+pub(crate) fn set_tracked_files(count: usize) {
+    metrics::gauge!("mdserve_tracked_files_total").set(count as f64);
+}
+
+pub(crate) fn set_websocket_connections(count: usize) {
+    metrics::gauge!("mdserve_websocket_connections_active").set(count as f64);
+}
+
+/// Counts a reload sent to clients because a watched file changed on disk.
+pub(crate) fn record_file_reload() {
+    metrics::counter!("mdserve_file_reload_events_total").increment(1);
+}
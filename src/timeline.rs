@@ -0,0 +1,178 @@
+//! File modification activity over time, from the modification times of the tracked
+//! files as last read. Periods are UTC calendar days, weeks starting on Monday, or
+//! months.
+
+use serde::Serialize;
+use std::str::FromStr;
+
+use crate::history::civil_from_days;
+
+const SECONDS_PER_DAY: u64 = 86_400;
+
+/// Most periods one timeline may have, so a wide range at a fine granularity can't
+/// build an unbounded response.
+pub(crate) const MAX_TIMELINE_PERIODS: usize = 10_000;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub(crate) enum Granularity {
+    /// `YYYY-MM-DD`
+    #[default]
+    Day,
+    /// `YYYY-MM-DD` of the Monday starting the week
+    Week,
+    /// `YYYY-MM`
+    Month,
+}
+
+impl FromStr for Granularity {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "day" => Ok(Self::Day),
+            "week" => Ok(Self::Week),
+            "month" => Ok(Self::Month),
+            _ => Err(format!(
+                "Unknown granularity '{s}', expected 'day', 'week' or 'month'"
+            )),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub(crate) struct TimelineEntry {
+    pub(crate) period: String,
+    /// Tracked files last modified in the period
+    pub(crate) files_modified: usize,
+}
+
+/// Counts the modification times `modified`, in Unix seconds, per period from `start`
+/// to `end`, both inclusive. Periods without modifications are listed with a count of
+/// 0.
+///
+/// Fails when `start` is after `end` or the range has more than
+/// [`MAX_TIMELINE_PERIODS`] periods.
+pub(crate) fn build_timeline(
+    modified: &[u64],
+    granularity: Granularity,
+    start: u64,
+    end: u64,
+) -> Result<Vec<TimelineEntry>, String> {
+    if start > end {
+        return Err("start must not be after end".to_string());
+    }
+    let start_day = (start / SECONDS_PER_DAY) as i64;
+    let end_day = (end / SECONDS_PER_DAY) as i64;
+    let period_count = match granularity {
+        Granularity::Day => end_day - start_day + 1,
+        Granularity::Week => (end_day - week_start(start_day)) / 7 + 1,
+        Granularity::Month => {
+            let (start_year, start_month, _) = civil_from_days(start_day);
+            let (end_year, end_month, _) = civil_from_days(end_day);
+            (end_year - start_year) * 12 + end_month - start_month + 1
+        }
+    };
+    if period_count > MAX_TIMELINE_PERIODS as i64 {
+        return Err(format!(
+            "The range spans {period_count} periods, more than {MAX_TIMELINE_PERIODS}; use a coarser granularity or a shorter range"
+        ));
+    }
+
+    let mut timeline: Vec<TimelineEntry> = Vec::with_capacity(period_count as usize);
+    let mut day = start_day;
+    while day <= end_day {
+        timeline.push(TimelineEntry {
+            period: period(day, granularity),
+            files_modified: 0,
+        });
+        day = next_period(day, granularity);
+    }
+    for &time in modified
+        .iter()
+        .filter(|&&time| (start..=end).contains(&time))
+    {
+        let label = period((time / SECONDS_PER_DAY) as i64, granularity);
+        // Periods are generated in order and their labels sort chronologically
+        if let Ok(index) = timeline.binary_search_by(|entry| entry.period.cmp(&label)) {
+            timeline[index].files_modified += 1;
+        }
+    }
+    Ok(timeline)
+}
+
+/// The label of the period containing `day`, in days since 1970-01-01.
+fn period(day: i64, granularity: Granularity) -> String {
+    let day = match granularity {
+        Granularity::Week => week_start(day),
+        Granularity::Day | Granularity::Month => day,
+    };
+    let (year, month, day) = civil_from_days(day);
+    match granularity {
+        Granularity::Day | Granularity::Week => format!("{year:04}-{month:02}-{day:02}"),
+        Granularity::Month => format!("{year:04}-{month:02}"),
+    }
+}
+
+/// The first day of the period after the one containing `day`.
+fn next_period(day: i64, granularity: Granularity) -> i64 {
+    match granularity {
+        Granularity::Day => day + 1,
+        Granularity::Week => week_start(day) + 7,
+        Granularity::Month => {
+            let (_, _, day_of_month) = civil_from_days(day);
+            let mut next = day - day_of_month + 29;
+            while civil_from_days(next).2 != 1 {
+                next += 1;
+            }
+            next
+        }
+    }
+}
+
+/// The Monday on or before `day`. 1970-01-01 was a Thursday.
+fn week_start(day: i64) -> i64 {
+    day - (day + 3).rem_euclid(7)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn periods(timeline: &[TimelineEntry]) -> Vec<(&str, usize)> {
+        timeline
+            .iter()
+            .map(|entry| (entry.period.as_str(), entry.files_modified))
+            .collect()
+    }
+
+    #[test]
+    fn test_build_timeline_buckets() {
+        // 2024-02-28 12:00, 2024-02-29 00:00 and 2024-03-04 (a Monday) 08:00 UTC
+        let modified = [1_709_121_600, 1_709_164_800, 1_709_539_200];
+        let start = 1_709_078_400;
+        let end = 1_709_600_000;
+
+        let days = build_timeline(&modified, Granularity::Day, start, end).unwrap();
+        assert_eq!(days.len(), 7);
+        assert_eq!(
+            periods(&days)[..3],
+            [("2024-02-28", 1), ("2024-02-29", 1), ("2024-03-01", 0)]
+        );
+        assert_eq!(periods(&days)[6], ("2024-03-05", 0));
+        assert_eq!(
+            periods(&build_timeline(&modified, Granularity::Week, start, end).unwrap()),
+            [("2024-02-26", 2), ("2024-03-04", 1)]
+        );
+        assert_eq!(
+            periods(&build_timeline(&modified, Granularity::Month, start, end).unwrap()),
+            [("2024-02", 2), ("2024-03", 1)]
+        );
+    }
+
+    #[test]
+    fn test_build_timeline_rejects_bad_ranges() {
+        assert!(build_timeline(&[], Granularity::Day, 10, 5).is_err());
+        assert!(build_timeline(&[], Granularity::Day, 0, 10_000 * SECONDS_PER_DAY).is_err());
+        assert!(build_timeline(&[], Granularity::Month, 0, 10_000 * SECONDS_PER_DAY).is_ok());
+    }
+}
@@ -0,0 +1,227 @@
+//! Lint checks for markdown that is about to be saved.
+
+use markdown::mdast::Node;
+use serde::Serialize;
+use std::path::Path;
+
+use crate::app::{is_markdown_file, MarkdownState};
+use crate::parser::{parse_frontmatter, parse_mdast, start_line, walk};
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ValidationError {
+    /// 1-based line number of the offending node
+    pub line: usize,
+    pub message: String,
+}
+
+/// Returns the `required` frontmatter fields that `content` lacks, in the given order.
+///
+/// A field set to null counts as missing; without frontmatter every field is missing.
+pub fn missing_frontmatter_fields(content: &str, required: &[String]) -> Vec<String> {
+    let frontmatter = parse_frontmatter(content);
+    required
+        .iter()
+        .filter(|field| {
+            frontmatter
+                .as_ref()
+                .and_then(|frontmatter| frontmatter.get(field.as_str()))
+                .is_none_or(|value| value.is_null())
+        })
+        .cloned()
+        .collect()
+}
+
+/// Checks `content`, the prospective contents of `relative_path`, for problems:
+///
+/// - links to markdown files that aren't tracked
+/// - images without alt text
+/// - headings that skip a level (e.g. H1 followed by H3)
+///
+/// Errors are returned in document order.
+pub fn validate_markdown(
+    relative_path: &str,
+    content: &str,
+    state: &MarkdownState,
+) -> Vec<ValidationError> {
+    let Some(root) = parse_mdast(content) else {
+        return Vec::new();
+    };
+
+    let mut errors = Vec::new();
+    let mut previous_level = None;
+    walk(&root, &mut |node| {
+        let message = match node {
+            Node::Link(link) => resolve_internal_link(relative_path, &link.url)
+                .filter(|target| !state.tracked_files.contains_key(target))
+                .map(|_| format!("Internal link '{}' not found", link.url)),
+            Node::Image(image) if image.alt.trim().is_empty() => {
+                Some(format!("Image '{}' is missing alt text", image.url))
+            }
+            Node::Heading(heading) => {
+                let skipped = previous_level.filter(|&previous| heading.depth > previous + 1);
+                previous_level = Some(heading.depth);
+                skipped.map(|previous| {
+                    format!("Heading level skips from H{previous} to H{}", heading.depth)
+                })
+            }
+            _ => None,
+        };
+
+        if let Some(message) = message {
+            errors.push(ValidationError {
+                line: start_line(node),
+                message,
+            });
+        }
+    });
+    errors
+}
+
+/// A link in a markdown file, as written.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct MarkdownLink {
+    pub(crate) url: String,
+    /// Plain text of the link
+    pub(crate) text: String,
+}
+
+/// Inline links of `markdown` in document order.
+pub(crate) fn markdown_links(markdown: &str) -> Vec<MarkdownLink> {
+    let Some(root) = parse_mdast(markdown) else {
+        return Vec::new();
+    };
+
+    let mut links = Vec::new();
+    walk(&root, &mut |node| {
+        if let Node::Link(link) = node {
+            links.push(MarkdownLink {
+                url: link.url.clone(),
+                text: node.to_string(),
+            });
+        }
+    });
+    links
+}
+
+/// A link from a tracked file to a markdown file that isn't tracked.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub(crate) struct BrokenLink {
+    /// Path of the linking file
+    pub(crate) source: String,
+    /// The link as written
+    pub(crate) link: String,
+    pub(crate) text: String,
+}
+
+/// Every broken internal link of the tracked files, by file and then in document order.
+///
+/// Each file's links are parsed once per content change (see
+/// [`MarkdownState::file_links`]). Requests go through [`MarkdownState::broken_links`],
+/// which keeps the result until a file changes.
+pub(crate) fn broken_links(state: &MarkdownState) -> Vec<BrokenLink> {
+    state.load_all_files();
+    let mut broken = Vec::new();
+    for source in state.get_sorted_filenames() {
+        let Some(links) = state.file_links(&source) else {
+            continue;
+        };
+        for link in links.iter() {
+            if is_broken_link(state, &source, &link.url) {
+                broken.push(BrokenLink {
+                    source: source.clone(),
+                    link: link.url.clone(),
+                    text: link.text.clone(),
+                });
+            }
+        }
+    }
+    broken
+}
+
+/// Whether every internal link of the tracked file `relative_path` points at a tracked
+/// file. Stops at the first broken one.
+pub(crate) fn internal_links_valid(state: &MarkdownState, relative_path: &str) -> bool {
+    state.file_links(relative_path).is_none_or(|links| {
+        links
+            .iter()
+            .all(|link| !is_broken_link(state, relative_path, &link.url))
+    })
+}
+
+/// Whether `url`, linked from `source`, points at a markdown file that isn't tracked.
+fn is_broken_link(state: &MarkdownState, source: &str, url: &str) -> bool {
+    resolve_internal_link(source, url)
+        .is_some_and(|target| !state.tracked_files.contains_key(&target))
+}
+
+/// Resolves a link found in `from_file` to a tracked-file key, if it points at a local
+/// markdown file. External URLs, anchors and links to other file types return `None`.
+pub(crate) fn resolve_internal_link(from_file: &str, url: &str) -> Option<String> {
+    if url.contains("://") || url.starts_with("mailto:") {
+        return None;
+    }
+    let target = url.split(['#', '?']).next().unwrap_or_default();
+    if target.is_empty() || !is_markdown_file(Path::new(target)) {
+        return None;
+    }
+
+    // Relative links start from the linking file's directory, absolute ones from the root
+    let mut segments: Vec<&str> = if target.starts_with('/') {
+        Vec::new()
+    } else {
+        let mut segments: Vec<&str> = from_file.split('/').collect();
+        segments.pop();
+        segments
+    };
+
+    for segment in target.split('/') {
+        match segment {
+            "" | "." => {}
+            ".." => {
+                // Escapes the served directory, so it can never be tracked
+                segments.pop()?;
+            }
+            _ => segments.push(segment),
+        }
+    }
+    Some(segments.join("/"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_resolve_internal_link() {
+        assert_eq!(
+            resolve_internal_link("guide/intro.md", "./setup.md").as_deref(),
+            Some("guide/setup.md")
+        );
+        assert_eq!(
+            resolve_internal_link("guide/intro.md", "../README.md#usage").as_deref(),
+            Some("README.md")
+        );
+        assert_eq!(
+            resolve_internal_link("guide/intro.md", "/api/index.markdown").as_deref(),
+            Some("api/index.markdown")
+        );
+        assert_eq!(
+            resolve_internal_link("intro.md", "https://example.com/a.md"),
+            None
+        );
+        assert_eq!(resolve_internal_link("intro.md", "#section"), None);
+        assert_eq!(resolve_internal_link("intro.md", "image.png"), None);
+    }
+
+    #[test]
+    fn test_missing_frontmatter_fields() {
+        let required = vec!["title".to_string(), "author".to_string()];
+
+        assert_eq!(
+            missing_frontmatter_fields("---\ntitle: Hi\nauthor: ~\n---\n# Body\n", &required),
+            ["author"]
+        );
+        assert_eq!(missing_frontmatter_fields("# Body\n", &required), required);
+        assert!(missing_frontmatter_fields("# Body\n", &[]).is_empty());
+    }
+}
@@ -1,5 +1,13 @@
-use axum_test::TestServer;
-use mdserve::{new_router, scan_markdown_files, ServerMessage};
+use axum::http::Method;
+use axum_test::multipart::{MultipartForm, Part};
+use axum_test::{TestServer, TestWebSocket};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use mdserve::lint::{LintConfig, Severity};
+use mdserve::{
+    new_router, new_router_with_config, scan_markdown_files, scan_markdown_files_with_options,
+    Config, RouterConfig, ServerMessage,
+};
+use std::collections::BTreeMap;
 use std::fs;
 use std::time::Duration;
 use tempfile::{tempdir, Builder, NamedTempFile, TempDir};
@@ -65,7 +73,9 @@ fn create_directory_server_impl(use_http: bool) -> (TestServer, TempDir) {
         .expect("Failed to write test3.md");
 
     let base_dir = temp_dir.path().to_path_buf();
-    let tracked_files = scan_markdown_files(&base_dir).expect("Failed to scan markdown files");
+    let tracked_files = scan_markdown_files(&base_dir)
+        .expect("Failed to scan markdown files")
+        .files;
     let is_directory_mode = true;
 
     let router =
@@ -113,6 +123,22 @@ async fn test_server_starts_and_serves_basic_markdown() {
     assert!(body.contains("data-theme=\"dark\""));
 }
 
+/// Opens `/ws` and consumes the `Connected` greeting every connection starts with.
+async fn connect_websocket(server: &TestServer) -> TestWebSocket {
+    let mut websocket = server.get_websocket("/ws").await.into_websocket().await;
+    let greeting = tokio::time::timeout(
+        Duration::from_secs(WEBSOCKET_TIMEOUT_SECS),
+        websocket.receive_json::<ServerMessage>(),
+    )
+    .await
+    .expect("Timeout waiting for Connected");
+    assert!(
+        matches!(greeting, ServerMessage::Connected { .. }),
+        "Expected Connected first, got {greeting:?}"
+    );
+    websocket
+}
+
 #[tokio::test]
 async fn test_websocket_connection() {
     let (server, _temp_file) = create_test_server_with_http("# WebSocket Test").await;
@@ -126,12 +152,11 @@ async fn test_websocket_connection() {
 async fn test_file_modification_updates_via_websocket() {
     let (server, temp_file) = create_test_server_with_http("# Original Content").await;
 
-    let mut websocket = server.get_websocket("/ws").await.into_websocket().await;
+    let mut websocket = connect_websocket(&server).await;
 
     // Modify the file
     fs::write(&temp_file, "# Modified Content").expect("Failed to modify file");
 
-
     // Should receive reload signal via WebSocket (with timeout)
     let update_result = tokio::time::timeout(
         Duration::from_secs(WEBSOCKET_TIMEOUT_SECS),
@@ -631,14 +656,13 @@ async fn test_directory_mode_file_order() {
 async fn test_directory_mode_websocket_file_modification() {
     let (server, temp_dir) = create_directory_server_with_http().await;
 
-    let mut websocket = server.get_websocket("/ws").await.into_websocket().await;
+    let mut websocket = connect_websocket(&server).await;
 
     // Modify one of the tracked files
     let test_file = temp_dir.path().join("test1.md");
     fs::write(&test_file, "# Modified Test 1\n\nContent has changed")
         .expect("Failed to modify file");
 
-
     // Should receive reload signal via WebSocket
     let update_result = tokio::time::timeout(
         Duration::from_secs(WEBSOCKET_TIMEOUT_SECS),
@@ -664,13 +688,12 @@ async fn test_directory_mode_websocket_file_modification() {
 async fn test_directory_mode_new_file_triggers_reload() {
     let (server, temp_dir) = create_directory_server_with_http().await;
 
-    let mut websocket = server.get_websocket("/ws").await.into_websocket().await;
+    let mut websocket = connect_websocket(&server).await;
 
     // Create a new markdown file in the directory
     let new_file = temp_dir.path().join("test4.md");
     fs::write(&new_file, "# Test 4\n\nThis is a new file").expect("Failed to create new file");
 
-
     // Should receive reload signal via WebSocket
     let update_result = tokio::time::timeout(
         Duration::from_secs(WEBSOCKET_TIMEOUT_SECS),
@@ -727,7 +750,6 @@ async fn test_editor_save_simulation_single_file_mode() {
     // Simulate editor save: rename to backup
     fs::rename(&file_path, &backup_path).expect("Failed to rename to backup");
 
-
     // CRITICAL: File should still be accessible (not 404) even though renamed
     let during_save_response = server.get("/").await;
     assert_eq!(
@@ -739,7 +761,6 @@ async fn test_editor_save_simulation_single_file_mode() {
     // Create new file with updated content
     fs::write(&file_path, "# Updated\n\nUpdated content").expect("Failed to write new file");
 
-
     // Verify updated content is now served
     let final_response = server.get("/").await;
     assert_eq!(final_response.status_code(), 200);
@@ -773,7 +794,6 @@ async fn test_editor_save_simulation_directory_mode() {
     // Simulate editor save: rename to backup
     fs::rename(&file_path, &backup_path).expect("Failed to rename to backup");
 
-
     // CRITICAL: File should still be accessible during save
     let during_save_response = server.get("/test1.md").await;
     assert_eq!(
@@ -785,7 +805,6 @@ async fn test_editor_save_simulation_directory_mode() {
     // Create new file with updated content
     fs::write(&file_path, "# Test 1 Updated\n\nUpdated content").expect("Failed to write new file");
 
-
     // Verify updated content
     let final_response = server.get("/test1.md").await;
     assert_eq!(final_response.status_code(), 200);
@@ -803,7 +822,7 @@ async fn test_editor_save_simulation_directory_mode() {
 async fn test_no_404_during_editor_save_sequence() {
     // Tests that HTTP requests during each step of the save never see 404
     let (server, temp_dir) = create_directory_server_with_http().await;
-    let mut websocket = server.get_websocket("/ws").await.into_websocket().await;
+    let mut websocket = connect_websocket(&server).await;
 
     let file_path = temp_dir.path().join("test1.md");
     let backup_path = temp_dir.path().join("test1.md~");
@@ -886,7 +905,7 @@ async fn test_temp_file_rename_triggers_reload_single_file_mode() {
     let (server, temp_file) = create_test_server_with_http("# Original\n\nOriginal content").await;
 
     // Connect WebSocket to receive reload notifications
-    let mut websocket = server.get_websocket("/ws").await.into_websocket().await;
+    let mut websocket = connect_websocket(&server).await;
 
     let file_path = temp_file.path().to_path_buf();
     let temp_write_path = file_path.with_extension("md.tmp.12345");
@@ -906,11 +925,9 @@ async fn test_temp_file_rename_triggers_reload_single_file_mode() {
     )
     .expect("Failed to write temp file");
 
-
     // Rename temp file over original (atomic operation)
     fs::rename(&temp_write_path, &file_path).expect("Failed to rename temp file");
 
-
     // Should receive reload signal via WebSocket
     let update_result = tokio::time::timeout(
         Duration::from_secs(WEBSOCKET_TIMEOUT_SECS),
@@ -953,7 +970,7 @@ async fn test_temp_file_rename_triggers_reload_directory_mode() {
     let (server, temp_dir) = create_directory_server_with_http().await;
 
     // Connect WebSocket to receive reload notifications
-    let mut websocket = server.get_websocket("/ws").await.into_websocket().await;
+    let mut websocket = connect_websocket(&server).await;
 
     let file_path = temp_dir.path().join("test1.md");
     let temp_write_path = temp_dir.path().join("test1.md.tmp.67890");
@@ -973,11 +990,9 @@ async fn test_temp_file_rename_triggers_reload_directory_mode() {
     )
     .expect("Failed to write temp file");
 
-
     // Rename temp file over original
     fs::rename(&temp_write_path, &file_path).expect("Failed to rename temp file");
 
-
     // Should receive reload signal
     let update_result = tokio::time::timeout(
         Duration::from_secs(WEBSOCKET_TIMEOUT_SECS),
@@ -1019,7 +1034,7 @@ async fn test_directory_mode_file_removal_updates_sidebar() {
     // Test that when a file is removed, it disappears from the sidebar
     let (server, temp_dir) = create_directory_server_with_http().await;
 
-    let mut websocket = server.get_websocket("/ws").await.into_websocket().await;
+    let mut websocket = connect_websocket(&server).await;
 
     // Verify initial state - test2.markdown exists in sidebar
     let initial_response = server.get("/test1.md").await;
@@ -1073,7 +1088,7 @@ async fn test_directory_mode_removed_file_returns_404() {
     // Test that accessing a removed file returns 404
     let (server, temp_dir) = create_directory_server_with_http().await;
 
-    let mut websocket = server.get_websocket("/ws").await.into_websocket().await;
+    let mut websocket = connect_websocket(&server).await;
 
     // Verify file is accessible initially
     let initial_response = server.get("/test2.markdown").await;
@@ -1106,7 +1121,7 @@ async fn test_directory_mode_file_rename_updates_sidebar() {
     // Test that when a file is renamed, only the new name appears in sidebar
     let (server, temp_dir) = create_directory_server_with_http().await;
 
-    let mut websocket = server.get_websocket("/ws").await.into_websocket().await;
+    let mut websocket = connect_websocket(&server).await;
 
     // Verify initial state - test2.markdown exists
     let initial_response = server.get("/test1.md").await;
@@ -1165,12 +1180,115 @@ async fn test_directory_mode_file_rename_updates_sidebar() {
     );
 }
 
+#[tokio::test]
+async fn test_websocket_request_file_list_matches_api() {
+    let (server, _temp_dir) = create_directory_server_with_http().await;
+
+    let mut websocket = connect_websocket(&server).await;
+    websocket.send_text(r#"{"type":"RequestFileList"}"#).await;
+
+    let message = tokio::time::timeout(
+        Duration::from_secs(WEBSOCKET_TIMEOUT_SECS),
+        websocket.receive_json::<ServerMessage>(),
+    )
+    .await
+    .expect("Timeout waiting for FileList");
+
+    let api: serde_json::Value = server.get("/api/files").await.json();
+    let api_files: Vec<String> = api["files"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|file| file["path"].as_str().unwrap().to_string())
+        .collect();
+
+    assert_eq!(message, ServerMessage::FileList { files: api_files });
+}
+
+async fn receive_value(websocket: &mut TestWebSocket) -> serde_json::Value {
+    tokio::time::timeout(
+        Duration::from_secs(WEBSOCKET_TIMEOUT_SECS),
+        websocket.receive_json::<serde_json::Value>(),
+    )
+    .await
+    .expect("Timeout waiting for WebSocket message")
+}
+
+#[tokio::test]
+async fn test_websocket_reconnect_replays_missed_events() {
+    let (server, temp_file) = create_test_server_with_http("# Original").await;
+
+    let mut first = server.get_websocket("/ws").await.into_websocket().await;
+    let connected = receive_value(&mut first).await;
+    assert_eq!(connected["type"], "Connected");
+    assert_eq!(connected["last_seq"], 0);
+    assert!(connected["server_time"].as_u64().unwrap() > 0);
+    let client_id = connected["client_id"].as_str().unwrap().to_string();
+    first.close().await;
+
+    // Another client shows when the change has gone out. The save broadcasts one
+    // Reload, but the watcher may report the write again afterwards.
+    let mut observer = connect_websocket(&server).await;
+    let file_name = temp_file.path().file_name().unwrap().to_str().unwrap();
+    server
+        .put(&format!("/api/files/{file_name}"))
+        .json(&serde_json::json!({ "markdown": "# Modified" }))
+        .await
+        .assert_status_ok();
+    let event = receive_value(&mut observer).await;
+    assert_eq!(event["type"], "Reload");
+    assert!(event["seq"].as_u64().unwrap() >= 1);
+
+    let mut second = server.get_websocket("/ws").await.into_websocket().await;
+    let connected = receive_value(&mut second).await;
+    let connected_seq = connected["last_seq"].as_u64().unwrap();
+    assert!(connected_seq >= 1);
+    assert_ne!(connected["client_id"], client_id.as_str());
+
+    second
+        .send_json(&serde_json::json!({
+            "type": "Reconnect",
+            "client_id": client_id,
+            "last_seq": 0,
+        }))
+        .await;
+    // Every event after the client's last_seq up to this connection is replayed in
+    // order; later ones reach this connection live and may come in between
+    let mut replayed = Vec::new();
+    while replayed.last() != Some(&connected_seq) {
+        let message = receive_value(&mut second).await;
+        assert_eq!(message["type"], "Reload");
+        let seq = message["seq"].as_u64().unwrap();
+        if seq <= connected_seq {
+            replayed.push(seq);
+        }
+    }
+    assert_eq!(replayed, (1..=connected_seq).collect::<Vec<_>>());
+}
+
+#[tokio::test]
+async fn test_websocket_reconnect_with_unknown_session_reloads() {
+    let (server, _temp_file) = create_test_server_with_http("# Test").await;
+
+    let mut websocket = connect_websocket(&server).await;
+    websocket
+        .send_json(&serde_json::json!({
+            "type": "Reconnect",
+            "client_id": "00000000-0000-0000-0000-000000000000",
+            "last_seq": 0,
+        }))
+        .await;
+
+    let message = receive_value(&mut websocket).await;
+    assert_eq!(message, serde_json::json!({ "type": "Reload" }));
+}
+
 #[tokio::test]
 async fn test_directory_mode_renamed_file_accessible_with_new_name() {
     // Test that a renamed file is accessible under its new name
     let (server, temp_dir) = create_directory_server_with_http().await;
 
-    let mut websocket = server.get_websocket("/ws").await.into_websocket().await;
+    let mut websocket = connect_websocket(&server).await;
 
     // Verify original file is accessible
     let original_response = server.get("/test2.markdown").await;
@@ -1222,7 +1340,7 @@ async fn test_directory_mode_file_rename_preserves_content() {
     // Verify that renaming a file preserves its content
     let (server, temp_dir) = create_directory_server_with_http().await;
 
-    let mut websocket = server.get_websocket("/ws").await.into_websocket().await;
+    let mut websocket = connect_websocket(&server).await;
 
     // Get content before rename
     let before_response = server.get("/test2.markdown").await;
@@ -1265,7 +1383,7 @@ async fn test_rename_currently_displayed_file_redirects_to_new_name() {
     // Test that renaming the currently displayed file sends a redirect message
     let (server, temp_dir) = create_directory_server_with_http().await;
 
-    let mut websocket = server.get_websocket("/ws").await.into_websocket().await;
+    let mut websocket = connect_websocket(&server).await;
 
     // Load test2.markdown initially
     let initial_response = server.get("/test2.markdown").await;
@@ -1311,7 +1429,7 @@ async fn test_remove_currently_displayed_file_redirects_to_home() {
     // Test that removing the currently displayed file sends a redirect message
     let (server, temp_dir) = create_directory_server_with_http().await;
 
-    let mut websocket = server.get_websocket("/ws").await.into_websocket().await;
+    let mut websocket = connect_websocket(&server).await;
 
     // Load test2.markdown initially
     let initial_response = server.get("/test2.markdown").await;
@@ -1356,7 +1474,7 @@ async fn test_remove_and_create_different_files_does_not_trigger_rename() {
     // does NOT trigger a FileRenamed message (should be generic Reload)
     let (server, temp_dir) = create_directory_server_with_http().await;
 
-    let mut websocket = server.get_websocket("/ws").await.into_websocket().await;
+    let mut websocket = connect_websocket(&server).await;
 
     // Load test2.markdown initially
     let initial_response = server.get("/test2.markdown").await;
@@ -1368,8 +1486,11 @@ async fn test_remove_and_create_different_files_does_not_trigger_rename() {
     let new_path = temp_dir.path().join("new-file.md");
 
     fs::remove_file(&removed_path).expect("Failed to remove file");
-    fs::write(&new_path, "# Completely Different Content\n\nThis is a new file with different content.")
-        .expect("Failed to create new file");
+    fs::write(
+        &new_path,
+        "# Completely Different Content\n\nThis is a new file with different content.",
+    )
+    .expect("Failed to create new file");
 
     // Wait for file watcher to detect changes
 
@@ -1410,12 +1531,14 @@ async fn test_folder_based_routing_single_level() {
     let folder1 = temp_dir.path().join("folder1");
     fs::create_dir(&folder1).expect("Failed to create folder1");
     fs::write(folder1.join("doc.md"), "# Folder1 Doc").expect("Failed to write file");
-    
+
     fs::write(temp_dir.path().join("root.md"), "# Root Doc").expect("Failed to write root file");
 
     let base_dir = temp_dir.path().to_path_buf();
-    let tracked_files = scan_markdown_files(&base_dir).expect("Failed to scan");
-    
+    let tracked_files = scan_markdown_files(&base_dir)
+        .expect("Failed to scan")
+        .files;
+
     let router = new_router(base_dir, tracked_files, true).expect("Failed to create router");
     let server = TestServer::new(router).expect("Failed to create test server");
 
@@ -1423,7 +1546,7 @@ async fn test_folder_based_routing_single_level() {
     let response = server.get("/folder1/doc.md").await;
     assert_eq!(response.status_code(), 200);
     assert!(response.text().contains("Folder1 Doc"));
-    
+
     // Test accessing root file
     let response = server.get("/root.md").await;
     assert_eq!(response.status_code(), 200);
@@ -1437,14 +1560,16 @@ async fn test_folder_based_routing_nested_folders() {
     // Create nested folder structure
     let folder1 = temp_dir.path().join("folder1");
     fs::create_dir(&folder1).expect("Failed to create folder1");
-    
+
     let folder2 = folder1.join("folder2");
     fs::create_dir(&folder2).expect("Failed to create folder2");
     fs::write(folder2.join("nested.md"), "# Nested Doc").expect("Failed to write file");
 
     let base_dir = temp_dir.path().to_path_buf();
-    let tracked_files = scan_markdown_files(&base_dir).expect("Failed to scan");
-    
+    let tracked_files = scan_markdown_files(&base_dir)
+        .expect("Failed to scan")
+        .files;
+
     let router = new_router(base_dir, tracked_files, true).expect("Failed to create router");
     let server = TestServer::new(router).expect("Failed to create test server");
 
@@ -1460,18 +1585,20 @@ async fn test_folder_based_routing_same_filename_different_folders() {
 
     // Create files with same name in different locations
     fs::write(temp_dir.path().join("doc.md"), "# Root Doc").expect("Failed to write root file");
-    
+
     let folder1 = temp_dir.path().join("folder1");
     fs::create_dir(&folder1).expect("Failed to create folder1");
     fs::write(folder1.join("doc.md"), "# Folder1 Doc").expect("Failed to write folder1 file");
-    
+
     let folder2 = temp_dir.path().join("folder2");
     fs::create_dir(&folder2).expect("Failed to create folder2");
     fs::write(folder2.join("doc.md"), "# Folder2 Doc").expect("Failed to write folder2 file");
 
     let base_dir = temp_dir.path().to_path_buf();
-    let tracked_files = scan_markdown_files(&base_dir).expect("Failed to scan");
-    
+    let tracked_files = scan_markdown_files(&base_dir)
+        .expect("Failed to scan")
+        .files;
+
     let router = new_router(base_dir, tracked_files, true).expect("Failed to create router");
     let server = TestServer::new(router).expect("Failed to create test server");
 
@@ -1479,11 +1606,11 @@ async fn test_folder_based_routing_same_filename_different_folders() {
     let response = server.get("/doc.md").await;
     assert_eq!(response.status_code(), 200);
     assert!(response.text().contains("Root Doc"));
-    
+
     let response = server.get("/folder1/doc.md").await;
     assert_eq!(response.status_code(), 200);
     assert!(response.text().contains("Folder1 Doc"));
-    
+
     let response = server.get("/folder2/doc.md").await;
     assert_eq!(response.status_code(), 200);
     assert!(response.text().contains("Folder2 Doc"));
@@ -1498,8 +1625,10 @@ async fn test_folder_based_routing_404_for_nonexistent_path() {
     fs::write(folder1.join("doc.md"), "# Doc").expect("Failed to write file");
 
     let base_dir = temp_dir.path().to_path_buf();
-    let tracked_files = scan_markdown_files(&base_dir).expect("Failed to scan");
-    
+    let tracked_files = scan_markdown_files(&base_dir)
+        .expect("Failed to scan")
+        .files;
+
     let router = new_router(base_dir, tracked_files, true).expect("Failed to create router");
     let server = TestServer::new(router).expect("Failed to create test server");
 
@@ -1526,7 +1655,9 @@ async fn test_tree_structure_root_files_only() {
     fs::write(temp_dir.path().join("c.md"), "# File C").expect("Failed to write c.md");
 
     let base_dir = temp_dir.path().to_path_buf();
-    let tracked_files = scan_markdown_files(&base_dir).expect("Failed to scan");
+    let tracked_files = scan_markdown_files(&base_dir)
+        .expect("Failed to scan")
+        .files;
 
     let router = new_router(base_dir, tracked_files, true).expect("Failed to create router");
     let server = TestServer::new(router).expect("Failed to create test server");
@@ -1564,7 +1695,9 @@ async fn test_tree_structure_single_level_folders() {
     fs::write(folder2.join("reference.md"), "# Reference").expect("Failed to write reference.md");
 
     let base_dir = temp_dir.path().to_path_buf();
-    let tracked_files = scan_markdown_files(&base_dir).expect("Failed to scan");
+    let tracked_files = scan_markdown_files(&base_dir)
+        .expect("Failed to scan")
+        .files;
 
     let router = new_router(base_dir, tracked_files, true).expect("Failed to create router");
     let server = TestServer::new(router).expect("Failed to create test server");
@@ -1578,8 +1711,14 @@ async fn test_tree_structure_single_level_folders() {
 
     // Check folder names appear (will be implemented as tree structure)
     // For now we just verify that files with folder paths appear
-    assert!(html.contains("docs") || html.contains("docs/intro.md") || html.contains("docs\\intro.md"));
-    assert!(html.contains("api") || html.contains("api/reference.md") || html.contains("api\\reference.md"));
+    assert!(
+        html.contains("docs") || html.contains("docs/intro.md") || html.contains("docs\\intro.md")
+    );
+    assert!(
+        html.contains("api")
+            || html.contains("api/reference.md")
+            || html.contains("api\\reference.md")
+    );
 }
 
 #[tokio::test]
@@ -1600,7 +1739,9 @@ async fn test_tree_structure_nested_folders() {
     fs::write(advanced.join("expert.md"), "# Expert").expect("Failed to write expert.md");
 
     let base_dir = temp_dir.path().to_path_buf();
-    let tracked_files = scan_markdown_files(&base_dir).expect("Failed to scan");
+    let tracked_files = scan_markdown_files(&base_dir)
+        .expect("Failed to scan")
+        .files;
 
     let router = new_router(base_dir, tracked_files, true).expect("Failed to create router");
     let server = TestServer::new(router).expect("Failed to create test server");
@@ -1622,7 +1763,8 @@ async fn test_tree_structure_mixed_root_and_folders() {
 
     // Create mix of root files and folders
     fs::write(temp_dir.path().join("readme.md"), "# README").expect("Failed to write readme.md");
-    fs::write(temp_dir.path().join("changelog.md"), "# Changelog").expect("Failed to write changelog.md");
+    fs::write(temp_dir.path().join("changelog.md"), "# Changelog")
+        .expect("Failed to write changelog.md");
 
     let docs = temp_dir.path().join("docs");
     fs::create_dir(&docs).expect("Failed to create docs folder");
@@ -1633,7 +1775,9 @@ async fn test_tree_structure_mixed_root_and_folders() {
     fs::write(examples.join("hello.md"), "# Hello").expect("Failed to write hello.md");
 
     let base_dir = temp_dir.path().to_path_buf();
-    let tracked_files = scan_markdown_files(&base_dir).expect("Failed to scan");
+    let tracked_files = scan_markdown_files(&base_dir)
+        .expect("Failed to scan")
+        .files;
 
     let router = new_router(base_dir, tracked_files, true).expect("Failed to create router");
     let server = TestServer::new(router).expect("Failed to create test server");
@@ -1673,7 +1817,9 @@ async fn test_remove_all_files_from_folder_folder_disappears() {
     fs::write(docs.join("file2.md"), "# File 2").expect("Failed to write file2.md");
 
     let base_dir = temp_dir.path().to_path_buf();
-    let tracked_files = scan_markdown_files(&base_dir).expect("Failed to scan");
+    let tracked_files = scan_markdown_files(&base_dir)
+        .expect("Failed to scan")
+        .files;
 
     let router = new_router(base_dir, tracked_files, true).expect("Failed to create router");
     let server = TestServer::builder()
@@ -1681,7 +1827,7 @@ async fn test_remove_all_files_from_folder_folder_disappears() {
         .build(router)
         .expect("Failed to create test server");
 
-    let mut websocket = server.get_websocket("/ws").await.into_websocket().await;
+    let mut websocket = connect_websocket(&server).await;
 
     // Verify folder appears in sidebar initially
     let response = server.get("/root.md").await;
@@ -1704,7 +1850,10 @@ async fn test_remove_all_files_from_folder_folder_disappears() {
     let response = server.get("/root.md").await;
     assert_eq!(response.status_code(), 200);
     let html = response.text();
-    assert!(!html.contains("data-folder-path=\"docs\""), "Empty folder should not appear in sidebar");
+    assert!(
+        !html.contains("data-folder-path=\"docs\""),
+        "Empty folder should not appear in sidebar"
+    );
 }
 
 #[tokio::test]
@@ -1721,10 +1870,13 @@ async fn test_remove_all_files_from_nested_folder() {
 
     let tutorials = docs.join("tutorials");
     fs::create_dir(&tutorials).expect("Failed to create tutorials folder");
-    fs::write(tutorials.join("tutorial1.md"), "# Tutorial 1").expect("Failed to write tutorial1.md");
+    fs::write(tutorials.join("tutorial1.md"), "# Tutorial 1")
+        .expect("Failed to write tutorial1.md");
 
     let base_dir = temp_dir.path().to_path_buf();
-    let tracked_files = scan_markdown_files(&base_dir).expect("Failed to scan");
+    let tracked_files = scan_markdown_files(&base_dir)
+        .expect("Failed to scan")
+        .files;
 
     let router = new_router(base_dir, tracked_files, true).expect("Failed to create router");
     let server = TestServer::builder()
@@ -1732,7 +1884,7 @@ async fn test_remove_all_files_from_nested_folder() {
         .build(router)
         .expect("Failed to create test server");
 
-    let mut websocket = server.get_websocket("/ws").await.into_websocket().await;
+    let mut websocket = connect_websocket(&server).await;
 
     // Remove all files from tutorials folder
     fs::remove_file(tutorials.join("tutorial1.md")).expect("Failed to remove tutorial1.md");
@@ -1747,9 +1899,19 @@ async fn test_remove_all_files_from_nested_folder() {
     // Verify tutorials folder is gone but docs folder remains
     let response = server.get("/root.md").await;
     let html = response.text();
-    assert!(html.contains("data-folder-path=\"docs\""), "Parent folder should still appear");
-    assert!(!html.contains("data-folder-path=\"docs/tutorials\"") && !html.contains("data-folder-path=\"docs\\tutorials\""), "Empty nested folder should not appear");
-    assert!(html.contains("intro.md"), "Parent folder file should still be accessible");
+    assert!(
+        html.contains("data-folder-path=\"docs\""),
+        "Parent folder should still appear"
+    );
+    assert!(
+        !html.contains("data-folder-path=\"docs/tutorials\"")
+            && !html.contains("data-folder-path=\"docs\\tutorials\""),
+        "Empty nested folder should not appear"
+    );
+    assert!(
+        html.contains("intro.md"),
+        "Parent folder file should still be accessible"
+    );
 }
 
 #[tokio::test]
@@ -1766,7 +1928,9 @@ async fn test_physically_removing_entire_folder() {
     fs::write(docs.join("file2.md"), "# File 2").expect("Failed to write file2.md");
 
     let base_dir = temp_dir.path().to_path_buf();
-    let tracked_files = scan_markdown_files(&base_dir).expect("Failed to scan");
+    let tracked_files = scan_markdown_files(&base_dir)
+        .expect("Failed to scan")
+        .files;
 
     let router = new_router(base_dir, tracked_files, true).expect("Failed to create router");
     let server = TestServer::builder()
@@ -1774,7 +1938,7 @@ async fn test_physically_removing_entire_folder() {
         .build(router)
         .expect("Failed to create test server");
 
-    let mut websocket = server.get_websocket("/ws").await.into_websocket().await;
+    let mut websocket = connect_websocket(&server).await;
 
     // Physically remove the entire folder
     fs::remove_dir_all(&docs).expect("Failed to remove docs folder");
@@ -1790,11 +1954,18 @@ async fn test_physically_removing_entire_folder() {
     let response = server.get("/root.md").await;
     assert_eq!(response.status_code(), 200);
     let html = response.text();
-    assert!(!html.contains("data-folder-path=\"docs\""), "Physically removed folder should not appear in sidebar");
+    assert!(
+        !html.contains("data-folder-path=\"docs\""),
+        "Physically removed folder should not appear in sidebar"
+    );
 
     // Files from removed folder should return 404
     let removed_file_response = server.get("/docs/file1.md").await;
-    assert_eq!(removed_file_response.status_code(), 404, "Files from removed folder should return 404");
+    assert_eq!(
+        removed_file_response.status_code(),
+        404,
+        "Files from removed folder should return 404"
+    );
 }
 
 // ===========================
@@ -1812,7 +1983,9 @@ async fn test_root_route_shows_first_file_alphabetically() {
     fs::write(temp_dir.path().join("middle.md"), "# Middle").expect("Failed to write middle.md");
 
     let base_dir = temp_dir.path().to_path_buf();
-    let tracked_files = scan_markdown_files(&base_dir).expect("Failed to scan");
+    let tracked_files = scan_markdown_files(&base_dir)
+        .expect("Failed to scan")
+        .files;
 
     let router = new_router(base_dir, tracked_files, true).expect("Failed to create router");
     let server = TestServer::new(router).expect("Failed to create test server");
@@ -1823,9 +1996,18 @@ async fn test_root_route_shows_first_file_alphabetically() {
     let html = response.text();
 
     // Should display "apple.md" content (first alphabetically)
-    assert!(html.contains("<h1>Apple</h1>"), "Root route should show first file alphabetically (apple.md)");
-    assert!(!html.contains("<h1>Zebra</h1>"), "Root route should not show zebra.md");
-    assert!(!html.contains("<h1>Middle</h1>"), "Root route should not show middle.md");
+    assert!(
+        html.contains("<h1>Apple</h1>"),
+        "Root route should show first file alphabetically (apple.md)"
+    );
+    assert!(
+        !html.contains("<h1>Zebra</h1>"),
+        "Root route should not show zebra.md"
+    );
+    assert!(
+        !html.contains("<h1>Middle</h1>"),
+        "Root route should not show middle.md"
+    );
 }
 
 #[tokio::test]
@@ -1841,10 +2023,13 @@ async fn test_root_route_with_nested_folders_shows_first_file() {
     fs::create_dir(&docs).expect("Failed to create docs folder");
     fs::write(docs.join("aaa.md"), "# AAA in Docs").expect("Failed to write aaa.md");
 
-    fs::write(temp_dir.path().join("root-zebra.md"), "# Root Zebra").expect("Failed to write root-zebra.md");
+    fs::write(temp_dir.path().join("root-zebra.md"), "# Root Zebra")
+        .expect("Failed to write root-zebra.md");
 
     let base_dir = temp_dir.path().to_path_buf();
-    let tracked_files = scan_markdown_files(&base_dir).expect("Failed to scan");
+    let tracked_files = scan_markdown_files(&base_dir)
+        .expect("Failed to scan")
+        .files;
 
     let router = new_router(base_dir, tracked_files, true).expect("Failed to create router");
     let server = TestServer::new(router).expect("Failed to create test server");
@@ -1855,8 +2040,14 @@ async fn test_root_route_with_nested_folders_shows_first_file() {
     let html = response.text();
 
     // Should display docs/aaa.md content (first alphabetically when folders are considered)
-    assert!(html.contains("<h1>AAA in Docs</h1>"), "Root route should show first file from docs folder");
-    assert!(!html.contains("<h1>Root Zebra</h1>"), "Root route should not show root-zebra.md");
+    assert!(
+        html.contains("<h1>AAA in Docs</h1>"),
+        "Root route should show first file from docs folder"
+    );
+    assert!(
+        !html.contains("<h1>Root Zebra</h1>"),
+        "Root route should not show root-zebra.md"
+    );
 }
 
 #[tokio::test]
@@ -1874,7 +2065,9 @@ async fn test_root_route_with_only_nested_files() {
     fs::write(folder2.join("aaa.md"), "# AAA in Folder2").expect("Failed to write aaa.md");
 
     let base_dir = temp_dir.path().to_path_buf();
-    let tracked_files = scan_markdown_files(&base_dir).expect("Failed to scan");
+    let tracked_files = scan_markdown_files(&base_dir)
+        .expect("Failed to scan")
+        .files;
 
     let router = new_router(base_dir, tracked_files, true).expect("Failed to create router");
     let server = TestServer::new(router).expect("Failed to create test server");
@@ -1885,6 +2078,5444 @@ async fn test_root_route_with_only_nested_files() {
     let html = response.text();
 
     // Should display folder1/zzz.md (folder1 < folder2 alphabetically)
-    assert!(html.contains("<h1>ZZZ</h1>"), "Root route should show first file from first folder alphabetically");
-    assert!(!html.contains("<h1>AAA in Folder2</h1>"), "Root route should not show file from folder2");
+    assert!(
+        html.contains("<h1>ZZZ</h1>"),
+        "Root route should show first file from first folder alphabetically"
+    );
+    assert!(
+        !html.contains("<h1>AAA in Folder2</h1>"),
+        "Root route should not show file from folder2"
+    );
+}
+
+// ===========================
+// Config File Tests
+// ===========================
+
+fn write_config(dir: &std::path::Path, content: &str) -> std::path::PathBuf {
+    let config_path = dir.join("mdserve.toml");
+    fs::write(&config_path, content).expect("Failed to write config file");
+    config_path
+}
+
+#[test]
+fn test_config_file_loads_server_and_watch_sections() {
+    let temp_dir = tempdir().expect("Failed to create temp dir");
+    let config_path = write_config(
+        temp_dir.path(),
+        r#"
+[server]
+path = "docs"
+hostname = "0.0.0.0"
+port = 8080
+
+[watch]
+rescan_delay = 500
+exclude_patterns = ["drafts/**"]
+watcher_debounce = 250
+"#,
+    );
+
+    let config = Config::load(&config_path).expect("Failed to load config");
+
+    // Relative paths are resolved against the config file's directory
+    assert_eq!(config.server.path, Some(temp_dir.path().join("docs")));
+    assert_eq!(config.hostname(), "0.0.0.0");
+    assert_eq!(config.port(), 8080);
+    assert_eq!(config.watch.rescan_delay, Some(500));
+    assert_eq!(config.watch.exclude_patterns, vec!["drafts/**"]);
+
+    let router_config = config.router_config();
+    assert_eq!(router_config.rescan_delay, Duration::from_millis(500));
+    assert_eq!(router_config.exclude_patterns, vec!["drafts/**"]);
+    assert_eq!(router_config.watcher_debounce_ms, Some(250));
+}
+
+#[test]
+fn test_config_file_cli_flags_override_file_values() {
+    let temp_dir = tempdir().expect("Failed to create temp dir");
+    let config_path = write_config(
+        temp_dir.path(),
+        "[server]\nhostname = \"0.0.0.0\"\nport = 8080\n\n[watch]\nrescan_delay = 500\n",
+    );
+    let file_config = Config::load(&config_path).expect("Failed to load config");
+
+    // Equivalent of `mdserve --config mdserve.toml --port 9000`
+    let mut cli_config = Config::default();
+    cli_config.server.port = Some(9000);
+
+    let config = file_config.merge(cli_config);
+
+    assert_eq!(config.port(), 9000, "CLI flag should override config file");
+    assert_eq!(
+        config.hostname(),
+        "0.0.0.0",
+        "Unset CLI flag should keep config value"
+    );
+    assert_eq!(config.watch.rescan_delay, Some(500));
+}
+
+#[test]
+fn test_config_defaults_when_nothing_is_set() {
+    let config = Config::default().merge(Config::default());
+
+    assert_eq!(config.hostname(), "127.0.0.1");
+    assert_eq!(config.port(), 3000);
+    assert_eq!(
+        config.router_config().rescan_delay,
+        RouterConfig::default().rescan_delay
+    );
+}
+
+#[test]
+fn test_config_watch_interval_only_applies_when_polling() {
+    let mut config = Config::default();
+    config.watch.watch_interval = Some(250);
+    assert_eq!(config.router_config().poll_interval, None);
+
+    config.watch.poll = Some(true);
+    assert_eq!(
+        config.router_config().poll_interval,
+        Some(Duration::from_millis(250))
+    );
+
+    config.watch.watch_interval = None;
+    assert_eq!(
+        config.router_config().poll_interval,
+        Some(Duration::from_millis(1000))
+    );
+}
+
+#[tokio::test]
+async fn test_poll_watcher_sends_reload_on_change() {
+    let temp_dir = tempdir().expect("Failed to create temp dir");
+    let file_path = temp_dir.path().join("test.md");
+    fs::write(&file_path, "# Original").expect("Failed to write");
+
+    let config = RouterConfig {
+        poll_interval: Some(Duration::from_millis(50)),
+        ..RouterConfig::default()
+    };
+    let router = new_router_with_config(
+        temp_dir.path().to_path_buf(),
+        vec![file_path.clone()],
+        false,
+        config,
+    )
+    .expect("Failed to create router");
+    let server = TestServer::builder()
+        .http_transport()
+        .build(router)
+        .expect("Failed to create test server");
+
+    let mut websocket = connect_websocket(&server).await;
+    fs::write(&file_path, "# Modified").expect("Failed to modify file");
+
+    let message = tokio::time::timeout(
+        Duration::from_secs(WEBSOCKET_TIMEOUT_SECS),
+        websocket.receive_json::<ServerMessage>(),
+    )
+    .await
+    .expect("Timeout waiting for Reload from the poll watcher");
+    assert_eq!(message, ServerMessage::Reload);
+}
+
+#[test]
+fn test_config_file_lint_rules() {
+    let temp_dir = tempdir().expect("Failed to create temp dir");
+    let config_path = write_config(
+        temp_dir.path(),
+        "[server]\nlint_rules = { img-alt = \"error\", single-h1 = \"ignore\" }\n",
+    );
+    let file_config = Config::load(&config_path).expect("Failed to load config");
+
+    // Equivalent of `mdserve --config mdserve.toml --lint-rules img-alt=info`
+    let mut cli_config = Config::default();
+    cli_config.server.lint_rules = BTreeMap::from([("img-alt".to_string(), Severity::Info)]);
+
+    let lint_rules = file_config.merge(cli_config).router_config().lint_rules;
+    assert_eq!(lint_rules.severity("img-alt"), Severity::Info);
+    assert_eq!(lint_rules.severity("single-h1"), Severity::Ignore);
+    assert_eq!(lint_rules.severity("empty-link"), Severity::Error);
+
+    let config_path = write_config(
+        temp_dir.path(),
+        "[server]\nlint_rules = { missing-h1 = \"ignore\" }\n",
+    );
+    assert!(Config::load(&config_path).is_err());
+}
+
+#[test]
+fn test_config_file_rejects_unknown_keys() {
+    let temp_dir = tempdir().expect("Failed to create temp dir");
+    let config_path = write_config(temp_dir.path(), "[server]\nprot = 8080\n");
+
+    assert!(Config::load(&config_path).is_err());
+}
+
+#[tokio::test]
+async fn test_config_exclude_patterns_skip_matching_files() {
+    let temp_dir = tempdir().expect("Failed to create temp dir");
+    fs::write(temp_dir.path().join("published.md"), "# Published").expect("Failed to write");
+    let drafts = temp_dir.path().join("drafts");
+    fs::create_dir(&drafts).expect("Failed to create drafts");
+    fs::write(drafts.join("wip.md"), "# Work in progress").expect("Failed to write");
+
+    let base_dir = temp_dir.path().to_path_buf();
+    let tracked_files = scan_markdown_files(&base_dir)
+        .expect("Failed to scan")
+        .files;
+    let config = RouterConfig {
+        exclude_patterns: vec!["drafts/**".to_string()],
+        ..RouterConfig::default()
+    };
+
+    let router = new_router_with_config(base_dir, tracked_files, true, config)
+        .expect("Failed to create router");
+    let server = TestServer::new(router).expect("Failed to create test server");
+
+    assert_eq!(server.get("/published.md").await.status_code(), 200);
+    assert_eq!(server.get("/drafts/wip.md").await.status_code(), 404);
+    assert!(!server.get("/").await.text().contains("wip.md"));
+}
+
+// ===========================
+// Task API Tests
+// ===========================
+
+fn create_tasks_server() -> (TestServer, TempDir) {
+    let temp_dir = tempdir().expect("Failed to create temp dir");
+    fs::write(
+        temp_dir.path().join("project.md"),
+        "# Project\n\n- [ ] Write tests\n- [x] Write code\n",
+    )
+    .expect("Failed to write project.md");
+    let notes = temp_dir.path().join("notes");
+    fs::create_dir(&notes).expect("Failed to create notes");
+    fs::write(notes.join("ideas.md"), "- [ ] Try the API\n").expect("Failed to write ideas.md");
+
+    let base_dir = temp_dir.path().to_path_buf();
+    let tracked_files = scan_markdown_files(&base_dir)
+        .expect("Failed to scan")
+        .files;
+    let router = new_router(base_dir, tracked_files, true).expect("Failed to create router");
+    let server = TestServer::new(router).expect("Failed to create test server");
+
+    (server, temp_dir)
+}
+
+#[tokio::test]
+async fn test_api_tasks_aggregates_all_files() {
+    let (server, _temp_dir) = create_tasks_server();
+
+    let response = server.get("/api/tasks").await;
+
+    assert_eq!(response.status_code(), 200);
+    let tasks: serde_json::Value = response.json();
+    assert_eq!(
+        tasks,
+        serde_json::json!([
+            {"file": "notes/ideas.md", "line": 1, "text": "Try the API", "checked": false},
+            {"file": "project.md", "line": 3, "text": "Write tests", "checked": false},
+            {"file": "project.md", "line": 4, "text": "Write code", "checked": true},
+        ])
+    );
+}
+
+#[tokio::test]
+async fn test_api_tasks_filters() {
+    let (server, _temp_dir) = create_tasks_server();
+
+    let tasks: serde_json::Value = server.get("/api/tasks?checked=true").await.json();
+    assert_eq!(tasks.as_array().unwrap().len(), 1);
+    assert_eq!(tasks[0]["text"], "Write code");
+
+    let tasks: serde_json::Value = server
+        .get("/api/tasks?file=project.md&checked=false")
+        .await
+        .json();
+    assert_eq!(tasks.as_array().unwrap().len(), 1);
+    assert_eq!(tasks[0]["text"], "Write tests");
+}
+
+#[tokio::test]
+async fn test_api_tasks_patch_toggles_and_writes_file() {
+    let (server, temp_dir) = create_tasks_server();
+
+    let response = server
+        .patch("/api/tasks")
+        .json(&serde_json::json!({"file": "project.md", "line": 3, "checked": true}))
+        .await;
+
+    assert_eq!(response.status_code(), 200);
+    let task: serde_json::Value = response.json();
+    assert_eq!(task["checked"], true);
+
+    let on_disk = fs::read_to_string(temp_dir.path().join("project.md")).unwrap();
+    assert!(on_disk.contains("- [x] Write tests"));
+
+    let tasks: serde_json::Value = server.get("/api/tasks?checked=false").await.json();
+    assert_eq!(tasks.as_array().unwrap().len(), 1);
+    assert_eq!(tasks[0]["file"], "notes/ideas.md");
+
+    // The other markdown extension finds the file too
+    let response = server
+        .patch("/api/tasks")
+        .json(&serde_json::json!({"file": "project.markdown", "line": 3, "checked": false}))
+        .await;
+    assert_eq!(response.status_code(), 200);
+    let task: serde_json::Value = response.json();
+    assert_eq!(task["file"], "project.md");
+    assert_eq!(task["checked"], false);
+}
+
+#[tokio::test]
+async fn test_api_tasks_patch_unknown_task_returns_404() {
+    let (server, _temp_dir) = create_tasks_server();
+
+    let response = server
+        .patch("/api/tasks")
+        .json(&serde_json::json!({"file": "project.md", "line": 1, "checked": true}))
+        .await;
+    assert_eq!(response.status_code(), 404);
+
+    let response = server
+        .patch("/api/tasks")
+        .json(&serde_json::json!({"file": "missing.md", "line": 1, "checked": true}))
+        .await;
+    assert_eq!(response.status_code(), 404);
+}
+
+#[tokio::test]
+async fn test_move_out_and_drop_in_sends_removed_and_added() {
+    // A file moved out of the tree and an unrelated file moved in during the same
+    // rescan window must not be reported as a rename
+    let (server, temp_dir) = create_directory_server_with_http().await;
+    let outside_dir = tempdir().expect("Failed to create outside dir");
+    let incoming = outside_dir.path().join("incoming.md");
+    fs::write(&incoming, "# Incoming\n\nUnrelated content").expect("Failed to write incoming.md");
+
+    let mut websocket = connect_websocket(&server).await;
+
+    fs::rename(
+        temp_dir.path().join("test2.markdown"),
+        outside_dir.path().join("test2.markdown"),
+    )
+    .expect("Failed to move file out");
+    fs::rename(&incoming, temp_dir.path().join("incoming.md")).expect("Failed to move file in");
+
+    let mut messages = Vec::new();
+    while let Ok(message) = tokio::time::timeout(
+        Duration::from_secs(2),
+        websocket.receive_json::<ServerMessage>(),
+    )
+    .await
+    {
+        messages.push(message);
+    }
+
+    assert!(
+        messages.contains(&ServerMessage::FileRemoved {
+            name: "test2.markdown".to_string()
+        }),
+        "Expected FileRemoved, got {messages:?}"
+    );
+    assert!(
+        messages.contains(&ServerMessage::FileAdded {
+            name: "incoming.md".to_string()
+        }),
+        "Expected FileAdded, got {messages:?}"
+    );
+    assert!(
+        !messages
+            .iter()
+            .any(|message| matches!(message, ServerMessage::FileRenamed { .. })),
+        "Unrelated files must not be reported as a rename, got {messages:?}"
+    );
+}
+
+// ===========================
+// Lazy Loading Tests
+// ===========================
+
+fn create_lazy_server(temp_dir: &TempDir) -> TestServer {
+    let base_dir = temp_dir.path().to_path_buf();
+    let tracked_files = scan_markdown_files(&base_dir)
+        .expect("Failed to scan")
+        .files;
+    let config = RouterConfig {
+        lazy: true,
+        ..RouterConfig::default()
+    };
+    let router = new_router_with_config(base_dir, tracked_files, true, config)
+        .expect("Failed to create router");
+    TestServer::new(router).expect("Failed to create test server")
+}
+
+#[tokio::test]
+async fn test_lazy_loading_does_not_read_files_at_startup() {
+    let temp_dir = tempdir().expect("Failed to create temp dir");
+    fs::write(temp_dir.path().join("good.md"), "# Good").expect("Failed to write");
+    // Not valid UTF-8: eager loading fails on this file at startup
+    fs::write(temp_dir.path().join("broken.md"), [0xff, 0xfe, 0x00]).expect("Failed to write");
+
+    let base_dir = temp_dir.path().to_path_buf();
+    let tracked_files = scan_markdown_files(&base_dir)
+        .expect("Failed to scan")
+        .files;
+    assert!(new_router(base_dir, tracked_files, true).is_err());
+
+    let server = create_lazy_server(&temp_dir);
+
+    let response = server.get("/good.md").await;
+    assert_eq!(response.status_code(), 200);
+    assert!(response.text().contains("<h1>Good</h1>"));
+}
+
+#[tokio::test]
+async fn test_lazy_loading_reads_current_content_on_first_access() {
+    let temp_dir = tempdir().expect("Failed to create temp dir");
+    fs::write(temp_dir.path().join("doc.md"), "# Before").expect("Failed to write");
+
+    let server = create_lazy_server(&temp_dir);
+    fs::write(temp_dir.path().join("doc.md"), "# After").expect("Failed to write");
+
+    let response = server.get("/api/files/doc.md").await;
+    assert_eq!(response.status_code(), 200);
+    let body: serde_json::Value = response.json();
+    assert_eq!(body["markdown"], "# After");
+
+    let response = server.get("/doc.md").await;
+    assert!(response.text().contains("<h1>After</h1>"));
+}
+
+#[tokio::test]
+async fn test_lazy_loading_aggregate_endpoints_load_all_files() {
+    let temp_dir = tempdir().expect("Failed to create temp dir");
+    fs::write(temp_dir.path().join("a.md"), "- [ ] Task A").expect("Failed to write");
+    fs::write(temp_dir.path().join("b.md"), "- [x] Task B").expect("Failed to write");
+
+    let server = create_lazy_server(&temp_dir);
+
+    let tasks: serde_json::Value = server.get("/api/tasks").await.json();
+    assert_eq!(tasks.as_array().unwrap().len(), 2);
+}
+
+#[tokio::test]
+async fn test_api_get_file_returns_markdown() {
+    let (server, _temp_dir) = create_directory_server().await;
+
+    let response = server.get("/api/files/test1.md").await;
+    assert_eq!(response.status_code(), 200);
+    let body: serde_json::Value = response.json();
+    assert_eq!(body["markdown"], TEST_FILE_1_CONTENT);
+
+    let response = server.get("/api/files/missing.md").await;
+    assert_eq!(response.status_code(), 404);
+}
+
+#[tokio::test]
+async fn test_api_file_anchors() {
+    let temp_dir = tempdir().expect("Failed to create temp dir");
+    fs::create_dir(temp_dir.path().join("guide")).expect("Failed to create dir");
+    fs::write(
+        temp_dir.path().join("guide/intro.md"),
+        "# Intro\n\n## Installation\n\n## Installation\n",
+    )
+    .expect("Failed to write");
+    let server = create_lazy_server(&temp_dir);
+
+    let response = server.get("/api/files/guide/intro.md/anchors").await;
+    assert_eq!(response.status_code(), 200);
+    let anchors: serde_json::Value = response.json();
+    assert_eq!(
+        anchors,
+        serde_json::json!([
+            {"id": "intro", "text": "Intro", "level": 1},
+            {"id": "installation", "text": "Installation", "level": 2},
+            {"id": "installation-1", "text": "Installation", "level": 2},
+        ])
+    );
+
+    let response = server.get("/api/files/guide/intro.md/unknown").await;
+    assert_eq!(response.status_code(), 404);
+
+    let response = server.get("/api/files/guide/missing.md/anchors").await;
+    assert_eq!(response.status_code(), 404);
+}
+
+#[tokio::test]
+async fn test_api_file_toc() {
+    let temp_dir = tempdir().expect("Failed to create temp dir");
+    fs::write(
+        temp_dir.path().join("guide.md"),
+        "# Guide\n\n## Install\n\n### From source\n\n## Usage\n\n# Reference\n",
+    )
+    .expect("Failed to write");
+    let server = create_lazy_server(&temp_dir);
+
+    let response = server.get("/api/files/guide.md/toc").await;
+    assert_eq!(response.status_code(), 200);
+    assert_eq!(
+        response.json::<serde_json::Value>(),
+        serde_json::json!([
+            {"id": "guide", "text": "Guide", "level": 1, "children": [
+                {"id": "install", "text": "Install", "level": 2, "children": [
+                    {"id": "from-source", "text": "From source", "level": 3, "children": []},
+                ]},
+                {"id": "usage", "text": "Usage", "level": 2, "children": []},
+            ]},
+            {"id": "reference", "text": "Reference", "level": 1, "children": []},
+        ])
+    );
+    let nested: serde_json::Value = server
+        .get("/api/files/guide.md/toc?flat=false")
+        .await
+        .json();
+    assert_eq!(nested, response.json::<serde_json::Value>());
+
+    // The flat list has the same headings as the tree, in document order
+    let flat: serde_json::Value = server.get("/api/files/guide.md/toc?flat=true").await.json();
+    assert_eq!(
+        flat,
+        serde_json::json!([
+            {"id": "guide", "text": "Guide", "level": 1},
+            {"id": "install", "text": "Install", "level": 2},
+            {"id": "from-source", "text": "From source", "level": 3},
+            {"id": "usage", "text": "Usage", "level": 2},
+            {"id": "reference", "text": "Reference", "level": 1},
+        ])
+    );
+    let anchors: serde_json::Value = server.get("/api/files/guide.md/anchors").await.json();
+    assert_eq!(flat, anchors);
+
+    let body: serde_json::Value = server.get("/api/files/guide.md/toc?depth=2").await.json();
+    assert_eq!(
+        body,
+        serde_json::json!([
+            {"id": "guide", "text": "Guide", "level": 1, "children": [
+                {"id": "install", "text": "Install", "level": 2, "children": []},
+                {"id": "usage", "text": "Usage", "level": 2, "children": []},
+            ]},
+            {"id": "reference", "text": "Reference", "level": 1, "children": []},
+        ])
+    );
+    let body: serde_json::Value = server
+        .get("/api/files/guide.md/toc?flat=true&depth=1")
+        .await
+        .json();
+    assert_eq!(
+        body,
+        serde_json::json!([
+            {"id": "guide", "text": "Guide", "level": 1},
+            {"id": "reference", "text": "Reference", "level": 1},
+        ])
+    );
+
+    for query in ["depth=0", "depth=7", "flat=true&depth=7", "flat=maybe"] {
+        let response = server
+            .get(&format!("/api/files/guide.md/toc?{query}"))
+            .await;
+        assert_eq!(response.status_code(), 400, "{query}");
+    }
+
+    let response = server.get("/api/files/missing.md/toc").await;
+    assert_eq!(response.status_code(), 404);
+}
+
+#[tokio::test]
+async fn test_api_file_outline_redirects_to_toc() {
+    let temp_dir = tempdir().expect("Failed to create temp dir");
+    fs::write(temp_dir.path().join("guide.md"), "# Guide\n").expect("Failed to write");
+    let server = create_lazy_server(&temp_dir);
+
+    let response = server.get("/api/files/guide.md/outline").await;
+    assert_eq!(response.status_code(), 301);
+    assert_eq!(response.header("location"), "/api/files/guide.md/toc");
+
+    let response = server
+        .get("/api/files/gu%C3%ADa%20docs.md/outline?depth=2")
+        .await;
+    assert_eq!(response.status_code(), 301);
+    assert_eq!(
+        response.header("location"),
+        "/api/files/gu%C3%ADa%20docs.md/toc?depth=2"
+    );
+}
+
+#[tokio::test]
+async fn test_api_lint_markdown() {
+    let (server, _temp_dir) = create_directory_server().await;
+
+    let response = server
+        .post("/api/files/drafts/new.md/lint")
+        .json(&serde_json::json!({ "markdown": "# Title\n\n### Deep\n\n[empty]()\n\n```\ncode\n" }))
+        .await;
+    assert_eq!(response.status_code(), 200);
+    let body: serde_json::Value = response.json();
+    assert_eq!(
+        body,
+        serde_json::json!({
+            "errors": [
+                {"line": 5, "rule": "empty-link", "message": "Link 'empty' has an empty destination"},
+                {"line": 7, "rule": "unclosed-code-fence", "message": "Fenced code block is never closed"},
+            ],
+            "warnings": [
+                {"line": 3, "rule": "heading-increment", "message": "Heading level skips from H1 to H3"},
+            ],
+            "info": [],
+        })
+    );
+
+    let response = server
+        .post("/api/files/test1.md/lint")
+        .json(&serde_json::json!({ "content": "# Wrong field" }))
+        .await;
+    assert_eq!(response.status_code(), 400);
+}
+
+#[tokio::test]
+async fn test_api_lint_rule_severities() {
+    let temp_dir = tempdir().expect("Failed to create temp dir");
+    fs::write(temp_dir.path().join("doc.md"), "# Doc").expect("Failed to write");
+    let config = RouterConfig {
+        lint_rules: LintConfig::new(BTreeMap::from([
+            ("empty-link".to_string(), Severity::Info),
+            ("single-h1".to_string(), Severity::Ignore),
+        ]))
+        .expect("Valid rules"),
+        ..RouterConfig::default()
+    };
+    let router = new_router_with_config(
+        temp_dir.path().to_path_buf(),
+        vec![temp_dir.path().join("doc.md")],
+        true,
+        config,
+    )
+    .expect("Failed to create router");
+    let server = TestServer::new(router).expect("Failed to create test server");
+    let markdown = "## No title\n\n#### Deep\n\n[empty]()\n";
+
+    let body: serde_json::Value = server
+        .post("/api/files/doc.md/lint")
+        .json(&serde_json::json!({ "markdown": markdown }))
+        .await
+        .json();
+    assert_eq!(
+        body,
+        serde_json::json!({
+            "errors": [],
+            "warnings": [
+                {"line": 3, "rule": "heading-increment", "message": "Heading level skips from H2 to H4"},
+            ],
+            "info": [
+                {"line": 5, "rule": "empty-link", "message": "Link 'empty' has an empty destination"},
+            ],
+        })
+    );
+
+    // Rules in the request replace the configured severities
+    let body: serde_json::Value = server
+        .post("/api/files/doc.md/lint")
+        .json(&serde_json::json!({
+            "markdown": markdown,
+            "rules": {"heading-increment": "error", "single-h1": "warning", "empty-link": "ignore"},
+        }))
+        .await
+        .json();
+    assert_eq!(
+        body,
+        serde_json::json!({
+            "errors": [
+                {"line": 3, "rule": "heading-increment", "message": "Heading level skips from H2 to H4"},
+            ],
+            "warnings": [
+                {"line": 1, "rule": "single-h1", "message": "Document has no H1 heading"},
+            ],
+            "info": [],
+        })
+    );
+
+    for rules in [
+        serde_json::json!({"missing-h1": "ignore"}),
+        serde_json::json!({"single-h1": "fatal"}),
+    ] {
+        let response = server
+            .post("/api/files/doc.md/lint")
+            .json(&serde_json::json!({ "markdown": markdown, "rules": rules }))
+            .await;
+        assert_eq!(response.status_code(), 400, "{rules}");
+    }
+}
+
+#[tokio::test]
+async fn test_api_validate_markdown() {
+    let temp_dir = tempdir().expect("Failed to create temp dir");
+    fs::create_dir(temp_dir.path().join("guide")).expect("Failed to create dir");
+    fs::write(temp_dir.path().join("guide/intro.md"), "# Intro").expect("Failed to write");
+    fs::write(temp_dir.path().join("guide/setup.md"), "# Setup").expect("Failed to write");
+    let server = create_lazy_server(&temp_dir);
+
+    let content = "# Intro\n\nSee [setup](./setup.md) and [missing](missing.md).\n\n![](diagram.png)\n\n### Too deep\n";
+    let response = server
+        .post("/api/files/guide/intro.md/validate")
+        .json(&serde_json::json!({ "markdown": content }))
+        .await;
+    assert_eq!(response.status_code(), 200);
+    let body: serde_json::Value = response.json();
+    assert_eq!(
+        body,
+        serde_json::json!({
+            "valid": false,
+            "errors": [
+                {"line": 3, "message": "Internal link 'missing.md' not found"},
+                {"line": 5, "message": "Image 'diagram.png' is missing alt text"},
+                {"line": 7, "message": "Heading level skips from H1 to H3"},
+            ],
+            "missing_required_fields": [],
+        })
+    );
+
+    let response = server
+        .post("/api/files/guide/intro.md/validate")
+        .json(&serde_json::json!({ "markdown": "# Ok\n\n[Intro](/guide/intro.md)\n" }))
+        .await;
+    let body: serde_json::Value = response.json();
+    assert_eq!(
+        body,
+        serde_json::json!({ "valid": true, "errors": [], "missing_required_fields": [] })
+    );
+}
+
+#[tokio::test]
+async fn test_api_list_files_and_directories() {
+    let temp_dir = tempdir().expect("Failed to create temp dir");
+    for dir in ["guide", "reference/api"] {
+        fs::create_dir_all(temp_dir.path().join(dir)).expect("Failed to create dir");
+    }
+    for file in [
+        "README.md",
+        "guide/intro.md",
+        "guide/setup.md",
+        "reference/api/index.md",
+    ] {
+        fs::write(temp_dir.path().join(file), "# Doc").expect("Failed to write");
+    }
+    let server = create_lazy_server(&temp_dir);
+
+    let response = server.get("/api/files").await;
+    assert_eq!(response.header("x-total-count"), "4");
+    assert_eq!(response.header("x-filtered-count"), "4");
+    let body: serde_json::Value = response.json();
+    assert_eq!(
+        body,
+        serde_json::json!({
+            "files": [
+                {"path": "README.md", "directory": ""},
+                {"path": "guide/intro.md", "directory": "guide"},
+                {"path": "guide/setup.md", "directory": "guide"},
+                {"path": "reference/api/index.md", "directory": "reference/api"},
+            ]
+        })
+    );
+
+    let body: serde_json::Value = server.get("/api/directories").await.json();
+    assert_eq!(
+        body,
+        serde_json::json!({ "directories": ["guide", "reference/api"] })
+    );
+}
+
+#[tokio::test]
+async fn test_api_list_files_directory_filters() {
+    let temp_dir = tempdir().expect("Failed to create temp dir");
+    fs::create_dir_all(temp_dir.path().join("guide/advanced")).expect("Failed to create dir");
+    fs::create_dir(temp_dir.path().join("guides")).expect("Failed to create dir");
+    for file in [
+        "README.md",
+        "guide/intro.md",
+        "guide/advanced/tuning.md",
+        "guides/other.md",
+    ] {
+        fs::write(temp_dir.path().join(file), "# Doc").expect("Failed to write");
+    }
+    let server = create_lazy_server(&temp_dir);
+
+    let list = |params: &'static [(&'static str, &'static str)]| {
+        let mut request = server.get("/api/files");
+        for (name, value) in params {
+            request = request.add_query_param(name, value);
+        }
+        async move {
+            let body: serde_json::Value = request.await.json();
+            body["files"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|file| file["path"].as_str().unwrap().to_string())
+                .collect::<Vec<_>>()
+        }
+    };
+
+    assert_eq!(list(&[("recursive", "false")]).await, vec!["README.md"]);
+    assert_eq!(
+        list(&[("dir", "guide")]).await,
+        vec!["guide/advanced/tuning.md", "guide/intro.md"]
+    );
+    assert_eq!(
+        list(&[("dir", "guide/"), ("recursive", "false")]).await,
+        vec!["guide/intro.md"]
+    );
+    assert_eq!(
+        list(&[("dir", "guide/advanced"), ("recursive", "false")]).await,
+        vec!["guide/advanced/tuning.md"]
+    );
+    assert!(list(&[("dir", "missing")]).await.is_empty());
+    assert_eq!(list(&[("recursive", "true")]).await.len(), 4);
+}
+
+#[tokio::test]
+async fn test_api_list_files_sort() {
+    let temp_dir = tempdir().expect("Failed to create temp dir");
+    fs::create_dir_all(temp_dir.path().join("guide/advanced")).expect("Failed to create dir");
+    fs::create_dir_all(temp_dir.path().join("api/v2")).expect("Failed to create dir");
+    for file in [
+        "README.md",
+        "zeta.md",
+        "api/auth.md",
+        "api/v2/users.md",
+        "guide/intro.md",
+        "guide/setup.md",
+        "guide/advanced/tuning.md",
+    ] {
+        fs::write(temp_dir.path().join(file), "# Doc").expect("Failed to write");
+    }
+    let server = create_lazy_server(&temp_dir);
+
+    let list = |params: &'static [(&'static str, &'static str)]| {
+        let mut request = server.get("/api/files");
+        for (name, value) in params {
+            request = request.add_query_param(name, value);
+        }
+        async move {
+            let body: serde_json::Value = request.await.json();
+            body["files"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|file| file["path"].as_str().unwrap().to_string())
+                .collect::<Vec<_>>()
+        }
+    };
+
+    assert_eq!(
+        list(&[("sort", "depth")]).await,
+        vec![
+            "README.md",
+            "zeta.md",
+            "api/auth.md",
+            "guide/intro.md",
+            "guide/setup.md",
+            "api/v2/users.md",
+            "guide/advanced/tuning.md",
+        ]
+    );
+    assert_eq!(
+        list(&[("sort", "directory")]).await,
+        vec![
+            "README.md",
+            "zeta.md",
+            "api/auth.md",
+            "api/v2/users.md",
+            "guide/intro.md",
+            "guide/setup.md",
+            "guide/advanced/tuning.md",
+        ]
+    );
+    assert_eq!(list(&[("sort", "name")]).await, list(&[]).await);
+    assert_eq!(
+        list(&[("sort", "depth"), ("dir", "guide"), ("limit", "2")]).await,
+        vec!["guide/intro.md", "guide/setup.md"]
+    );
+
+    let response = server
+        .get("/api/files")
+        .add_query_param("sort", "size")
+        .await;
+    assert_eq!(response.status_code(), 400);
+}
+
+#[tokio::test]
+async fn test_api_list_files_modified_range() {
+    let temp_dir = tempdir().expect("Failed to create temp dir");
+    // Unix timestamps 1000, 2000 and 3000
+    for (file, secs) in [("old.md", 1000), ("mid.md", 2000), ("new.md", 3000)] {
+        let path = temp_dir.path().join(file);
+        fs::write(&path, "# Doc").expect("Failed to write");
+        fs::File::options()
+            .write(true)
+            .open(&path)
+            .and_then(|f| f.set_modified(std::time::UNIX_EPOCH + Duration::from_secs(secs)))
+            .expect("Failed to set mtime");
+    }
+    let server = create_lazy_server(&temp_dir);
+
+    let list = |query: &'static str| {
+        let request = server.get(&format!("/api/files?{query}"));
+        async move {
+            let response = request.await;
+            assert_eq!(response.status_code(), 200, "{query}");
+            let body: serde_json::Value = response.json();
+            body["files"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|file| file["path"].as_str().unwrap().to_string())
+                .collect::<Vec<_>>()
+        }
+    };
+
+    assert_eq!(list("modified_after=1500").await, vec!["mid.md", "new.md"]);
+    assert_eq!(list("modified_before=2500").await, vec!["mid.md", "old.md"]);
+    assert_eq!(
+        list("modified_after=1500&modified_before=2500").await,
+        vec!["mid.md"]
+    );
+    // Both bounds are exclusive
+    assert!(list("modified_after=2000&modified_before=2000")
+        .await
+        .is_empty());
+    // Combined with the other filters
+    assert_eq!(list("modified_after=1500&q=NEW").await, vec!["new.md"]);
+
+    let response = server.get("/api/files?modified_after=yesterday").await;
+    assert_eq!(response.status_code(), 400);
+}
+
+#[tokio::test]
+async fn test_api_list_files_include_content() {
+    let temp_dir = tempdir().expect("Failed to create temp dir");
+    for name in ["a", "b", "c", "d", "e"] {
+        fs::write(
+            temp_dir.path().join(format!("{name}.md")),
+            format!("# {name}\n"),
+        )
+        .expect("Failed to write");
+    }
+    let base_dir = temp_dir.path().to_path_buf();
+    let tracked_files = scan_markdown_files(&base_dir)
+        .expect("Failed to scan")
+        .files;
+    let config = RouterConfig {
+        lazy: true,
+        max_inline_content: 3,
+        ..RouterConfig::default()
+    };
+    let router = new_router_with_config(base_dir, tracked_files, true, config)
+        .expect("Failed to create router");
+    let server = TestServer::new(router).expect("Failed to create test server");
+
+    let body: serde_json::Value = server.get("/api/files").await.json();
+    assert!(body["files"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .all(|file| file.get("markdown").is_none()));
+    let body: serde_json::Value = server.get("/api/files?include_content=false").await.json();
+    assert_eq!(
+        body["files"][0],
+        serde_json::json!({"path": "a.md", "directory": ""})
+    );
+
+    // Too many files for one response
+    let response = server.get("/api/files?include_content=true").await;
+    assert_eq!(response.status_code(), 400);
+    let response = server.get("/api/files?include_content=true&q=a").await;
+    assert_eq!(response.status_code(), 200);
+
+    let response = server
+        .get("/api/files?include_content=true&limit=2&offset=1")
+        .await;
+    assert_eq!(response.status_code(), 200);
+    assert_eq!(response.header("x-filtered-count"), "5");
+    assert_eq!(
+        response.json::<serde_json::Value>(),
+        serde_json::json!({
+            "files": [
+                {"path": "b.md", "directory": "", "markdown": "# b\n"},
+                {"path": "c.md", "directory": "", "markdown": "# c\n"},
+            ]
+        })
+    );
+
+    // Paging works without content too
+    let body: serde_json::Value = server.get("/api/files?offset=4&limit=3").await.json();
+    assert_eq!(
+        body,
+        serde_json::json!({"files": [{"path": "e.md", "directory": ""}]})
+    );
+}
+
+#[tokio::test]
+async fn test_api_directory_children() {
+    let temp_dir = tempdir().expect("Failed to create temp dir");
+    fs::create_dir_all(temp_dir.path().join("guide/advanced/tuning"))
+        .expect("Failed to create dir");
+    fs::create_dir(temp_dir.path().join("reference")).expect("Failed to create dir");
+    for file in [
+        "README.md",
+        "guide/intro.md",
+        "guide/setup.md",
+        "guide/advanced/caching.md",
+        "guide/advanced/tuning/memory.md",
+        "reference/api.md",
+    ] {
+        fs::write(temp_dir.path().join(file), "# Doc").expect("Failed to write");
+    }
+    let server = create_lazy_server(&temp_dir);
+
+    let body: serde_json::Value = server.get("/api/files/children").await.json();
+    assert_eq!(
+        body,
+        serde_json::json!({ "files": ["README.md"], "directories": ["guide", "reference"] })
+    );
+
+    let body: serde_json::Value = server.get("/api/files/guide/children").await.json();
+    assert_eq!(
+        body,
+        serde_json::json!({ "files": ["intro.md", "setup.md"], "directories": ["advanced"] })
+    );
+
+    let body: serde_json::Value = server
+        .get("/api/files/guide/advanced/children")
+        .await
+        .json();
+    assert_eq!(
+        body,
+        serde_json::json!({ "files": ["caching.md"], "directories": ["tuning"] })
+    );
+
+    let body: serde_json::Value = server
+        .get("/api/files/guide/advanced/tuning/children")
+        .await
+        .json();
+    assert_eq!(
+        body,
+        serde_json::json!({ "files": ["memory.md"], "directories": [] })
+    );
+
+    for missing in ["/api/files/missing/children", "/api/files/gui/children"] {
+        let response = server.get(missing).await;
+        assert_eq!(response.status_code(), 404, "{missing}");
+        assert_eq!(
+            response.json::<serde_json::Value>()["error"],
+            "Directory not found"
+        );
+    }
+}
+
+#[tokio::test]
+async fn test_api_list_files_titles_from_frontmatter() {
+    let temp_dir = tempdir().expect("Failed to create temp dir");
+    for (name, content) in [
+        (
+            "both.md",
+            "---\ntitle: From Frontmatter\n---\n# From Heading\n",
+        ),
+        ("heading.md", "---\nauthor: someone\n---\n# Only Heading\n"),
+        ("none.md", "No title at all.\n"),
+        ("toml.md", "+++\ntitle = \"From TOML\"\n+++\n# Heading\n"),
+    ] {
+        fs::write(temp_dir.path().join(name), content).expect("Failed to write");
+    }
+    let base_dir = temp_dir.path().to_path_buf();
+    let tracked_files = scan_markdown_files(&base_dir)
+        .expect("Failed to scan")
+        .files;
+    let config = RouterConfig {
+        lazy: true,
+        title_from_frontmatter: true,
+        ..RouterConfig::default()
+    };
+    let router = new_router_with_config(base_dir, tracked_files, true, config)
+        .expect("Failed to create router");
+    let server = TestServer::new(router).expect("Failed to create test server");
+
+    let body: serde_json::Value = server.get("/api/files").await.json();
+    assert_eq!(
+        body,
+        serde_json::json!({
+            "files": [
+                {"path": "both.md", "directory": "", "title": "From Frontmatter"},
+                {"path": "heading.md", "directory": "", "title": "Only Heading"},
+                {"path": "none.md", "directory": ""},
+                {"path": "toml.md", "directory": "", "title": "From TOML"},
+            ]
+        })
+    );
+
+    // The cached title follows content changes
+    server
+        .put("/api/files/both.md")
+        .json(&serde_json::json!({ "markdown": "# New Heading\n" }))
+        .await;
+    let body: serde_json::Value = server.get("/api/files").await.json();
+    assert_eq!(body["files"][0]["title"], "New Heading");
+
+    // Without the flag no titles are listed
+    let (server, _temp_dir) = create_directory_server().await;
+    let body: serde_json::Value = server.get("/api/files").await.json();
+    assert!(body["files"][0].get("title").is_none());
+}
+
+#[tokio::test]
+async fn test_mdindex_orders_files() {
+    let temp_dir = tempdir().expect("Failed to create temp dir");
+    fs::create_dir(temp_dir.path().join("guide")).expect("Failed to create dir");
+    for file in [
+        "about.md",
+        "install.md",
+        "usage.md",
+        "guide/advanced.md",
+        "guide/basics.md",
+    ] {
+        fs::write(temp_dir.path().join(file), format!("# {file}")).expect("Failed to write");
+    }
+    // Partial coverage: usage.md isn't listed
+    fs::write(temp_dir.path().join(".mdindex"), "install.md\nabout.md\n").expect("Failed to write");
+    fs::write(temp_dir.path().join("guide/.mdindex"), "basics.md\n").expect("Failed to write");
+    let server = create_lazy_server(&temp_dir);
+
+    let body: serde_json::Value = server.get("/api/files").await.json();
+    let paths: Vec<&str> = body["files"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|file| file["path"].as_str().unwrap())
+        .collect();
+    assert_eq!(
+        paths,
+        vec![
+            "install.md",
+            "about.md",
+            "guide/basics.md",
+            "guide/advanced.md",
+            "usage.md"
+        ]
+    );
+
+    // The root serves the first file in that order
+    assert!(server.get("/").await.text().contains("<h1>install.md</h1>"));
+    let body: serde_json::Value = server.get("/api/files/about.md/neighbors").await.json();
+    assert_eq!(
+        body,
+        serde_json::json!({ "previous": "install.md", "next": "guide/basics.md" })
+    );
+}
+
+#[tokio::test]
+async fn test_mdindex_change_reorders_files() {
+    let (server, temp_dir) = create_directory_server_with_http().await;
+    let mut websocket = connect_websocket(&server).await;
+
+    fs::write(temp_dir.path().join(".mdindex"), "test3.md\n").expect("Failed to write");
+    // Creating the file and writing it can be reported separately, each with a reload
+    tokio::time::timeout(Duration::from_secs(WEBSOCKET_TIMEOUT_SECS), async {
+        loop {
+            let message = websocket.receive_json::<ServerMessage>().await;
+            assert_eq!(message, ServerMessage::Reload);
+            let body: serde_json::Value = server.get("/api/files").await.json();
+            if body["files"][0]["path"] == "test3.md" {
+                break;
+            }
+        }
+    })
+    .await
+    .expect("Timeout waiting for the new order");
+}
+
+#[tokio::test]
+async fn test_api_list_files_path_filter() {
+    let temp_dir = tempdir().expect("Failed to create temp dir");
+    fs::create_dir(temp_dir.path().join("guide")).expect("Failed to create dir");
+    for file in [
+        "guide/intro.md",
+        "guide/advanced.md",
+        "Setup-Guide.md",
+        "README.md",
+    ] {
+        fs::write(temp_dir.path().join(file), "# Doc").expect("Failed to write");
+    }
+    let server = create_lazy_server(&temp_dir);
+
+    let paths = |body: serde_json::Value| -> Vec<String> {
+        body["files"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|file| file["path"].as_str().unwrap().to_string())
+            .collect()
+    };
+
+    let response = server.get("/api/files").add_query_param("q", "GUIDE").await;
+    assert_eq!(response.header("x-total-count"), "4");
+    assert_eq!(response.header("x-filtered-count"), "3");
+    assert_eq!(
+        paths(response.json()),
+        vec!["Setup-Guide.md", "guide/advanced.md", "guide/intro.md"]
+    );
+
+    let response = server
+        .get("/api/files")
+        .add_query_param("q", "nothing")
+        .await;
+    assert_eq!(response.header("x-total-count"), "4");
+    assert_eq!(response.header("x-filtered-count"), "0");
+    assert!(paths(response.json()).is_empty());
+
+    let body = server.get("/api/files").await.json();
+    assert_eq!(paths(body).len(), 4);
+}
+
+#[tokio::test]
+async fn test_watcher_debounce_config_still_detects_changes() {
+    let temp_dir = tempdir().expect("Failed to create temp dir");
+    let file_path = temp_dir.path().join("doc.md");
+    fs::write(&file_path, "# Before").expect("Failed to write");
+
+    let base_dir = temp_dir.path().to_path_buf();
+    let tracked_files = scan_markdown_files(&base_dir)
+        .expect("Failed to scan")
+        .files;
+    let config = RouterConfig {
+        watcher_debounce_ms: Some(50),
+        ..RouterConfig::default()
+    };
+    let router = new_router_with_config(base_dir, tracked_files, true, config)
+        .expect("Failed to create router");
+    let server = TestServer::new(router).expect("Failed to create test server");
+
+    fs::write(&file_path, "# After").expect("Failed to write");
+
+    let updated = tokio::time::timeout(Duration::from_secs(3), async {
+        loop {
+            if server
+                .get("/doc.md")
+                .await
+                .text()
+                .contains("<h1>After</h1>")
+            {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+    })
+    .await;
+    assert!(updated.is_ok(), "File change should be picked up");
+}
+
+// ===========================
+// Hidden File Tests
+// ===========================
+
+fn create_tree_with_hidden_files() -> TempDir {
+    let temp_dir = tempdir().expect("Failed to create temp dir");
+    fs::write(temp_dir.path().join("README.md"), "# Readme").expect("Failed to write");
+    fs::write(temp_dir.path().join(".template.md"), "# Template").expect("Failed to write");
+    fs::create_dir(temp_dir.path().join(".github")).expect("Failed to create dir");
+    fs::write(
+        temp_dir.path().join(".github/CONTRIBUTING.md"),
+        "# Contributing",
+    )
+    .expect("Failed to write");
+    temp_dir
+}
+
+#[tokio::test]
+async fn test_hidden_files_are_skipped_by_default() {
+    let temp_dir = create_tree_with_hidden_files();
+    let base_dir = temp_dir.path().to_path_buf();
+    let tracked_files = scan_markdown_files(&base_dir)
+        .expect("Failed to scan")
+        .files;
+    let router = new_router(base_dir, tracked_files, true).expect("Failed to create router");
+    let server = TestServer::new(router).expect("Failed to create test server");
+
+    let body: serde_json::Value = server.get("/api/files").await.json();
+    assert_eq!(
+        body["files"],
+        serde_json::json!([{"path": "README.md", "directory": ""}])
+    );
+    assert_eq!(server.get("/.template.md").await.status_code(), 404);
+
+    // Hidden files created while running are ignored too
+    fs::write(temp_dir.path().join(".draft.md"), "# Draft").expect("Failed to write");
+    tokio::time::sleep(Duration::from_millis(300)).await;
+    assert_eq!(server.get("/.draft.md").await.status_code(), 404);
+}
+
+#[tokio::test]
+async fn test_include_hidden_serves_hidden_files() {
+    let temp_dir = create_tree_with_hidden_files();
+    let base_dir = temp_dir.path().to_path_buf();
+    let tracked_files = scan_markdown_files_with_options(&base_dir, true)
+        .expect("Failed to scan")
+        .files;
+    let config = RouterConfig {
+        include_hidden: true,
+        ..RouterConfig::default()
+    };
+    let router = new_router_with_config(base_dir, tracked_files, true, config)
+        .expect("Failed to create router");
+    let server = TestServer::new(router).expect("Failed to create test server");
+
+    let body: serde_json::Value = server.get("/api/files").await.json();
+    let paths: Vec<&str> = body["files"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|file| file["path"].as_str().unwrap())
+        .collect();
+    assert_eq!(
+        paths,
+        vec![".github/CONTRIBUTING.md", ".template.md", "README.md"]
+    );
+
+    let response = server.get("/.github/CONTRIBUTING.md").await;
+    assert_eq!(response.status_code(), 200);
+}
+
+// ===========================
+// Rename API Tests
+// ===========================
+
+#[tokio::test]
+async fn test_api_rename_file() {
+    let temp_dir = tempdir().expect("Failed to create temp dir");
+    fs::create_dir(temp_dir.path().join("guide")).expect("Failed to create dir");
+    fs::write(temp_dir.path().join("guide/old.md"), "# Old").expect("Failed to write");
+    fs::write(temp_dir.path().join("guide/taken.md"), "# Taken").expect("Failed to write");
+    let server = create_lazy_server(&temp_dir);
+
+    let response = server
+        .post("/api/files/guide/old.md/rename")
+        .json(&serde_json::json!({ "name": "new.md" }))
+        .await;
+    assert_eq!(response.status_code(), 200);
+    let body: serde_json::Value = response.json();
+    assert_eq!(
+        body,
+        serde_json::json!({ "path": "guide/new.md", "directory": "guide" })
+    );
+
+    assert!(!temp_dir.path().join("guide/old.md").exists());
+    assert!(temp_dir.path().join("guide/new.md").exists());
+    let response = server.get("/guide/new.md").await;
+    assert_eq!(response.status_code(), 200);
+    assert!(response.text().contains("<h1>Old</h1>"));
+    assert_eq!(server.get("/guide/old.md").await.status_code(), 404);
+}
+
+#[tokio::test]
+async fn test_api_rename_file_keeps_its_place_in_mdindex() {
+    let temp_dir = tempdir().expect("Failed to create temp dir");
+    fs::create_dir(temp_dir.path().join("guide")).expect("Failed to create dir");
+    for file in ["guide/intro.md", "guide/old.md", "guide/zebra.md"] {
+        fs::write(temp_dir.path().join(file), "# Doc").expect("Failed to write");
+    }
+    fs::write(
+        temp_dir.path().join("guide/.mdindex"),
+        "zebra.md\n  old.md\nintro.md\n",
+    )
+    .expect("Failed to write");
+    let server = create_lazy_server(&temp_dir);
+
+    let response = server
+        .post("/api/files/guide/old.md/rename")
+        .json(&serde_json::json!({ "name": "new.md" }))
+        .await;
+    assert_eq!(response.status_code(), 200);
+
+    assert_eq!(
+        fs::read_to_string(temp_dir.path().join("guide/.mdindex")).unwrap(),
+        "zebra.md\nnew.md\nintro.md\n"
+    );
+    let body: serde_json::Value = server.get("/api/files/guide/new.md/neighbors").await.json();
+    assert_eq!(
+        body,
+        serde_json::json!({ "previous": "guide/zebra.md", "next": "guide/intro.md" })
+    );
+}
+
+#[tokio::test]
+async fn test_api_rename_file_errors() {
+    let temp_dir = tempdir().expect("Failed to create temp dir");
+    fs::create_dir(temp_dir.path().join("guide")).expect("Failed to create dir");
+    fs::write(temp_dir.path().join("guide/doc.md"), "# Doc").expect("Failed to write");
+    fs::write(temp_dir.path().join("guide/taken.md"), "# Taken").expect("Failed to write");
+    let server = create_lazy_server(&temp_dir);
+
+    let cases = [
+        ("guide/doc.md", "../doc.md", 422),
+        ("guide/doc.md", "sub/doc.md", 422),
+        ("guide/doc.md", "taken.md", 409),
+        ("guide/doc.md", "doc.txt", 400),
+        ("guide/missing.md", "other.md", 404),
+    ];
+    for (file, name, expected_status) in cases {
+        let response = server
+            .post(&format!("/api/files/{file}/rename"))
+            .json(&serde_json::json!({ "name": name }))
+            .await;
+        assert_eq!(
+            response.status_code(),
+            expected_status,
+            "renaming {file} to {name}"
+        );
+    }
+
+    assert!(temp_dir.path().join("guide/doc.md").exists());
+}
+
+#[tokio::test]
+async fn test_api_file_tables() {
+    let temp_dir = tempdir().expect("Failed to create temp dir");
+    fs::write(
+        temp_dir.path().join("deps.md"),
+        "# Deps\n\n| Name | Version |\n|:-----|:-------:|\n| axum | 0.7 |\n| tokio | |\n\n| A |\n|---|\n| 1 |\n",
+    )
+    .expect("Failed to write");
+    let server = create_lazy_server(&temp_dir);
+
+    let response = server.get("/api/files/deps.md/tables").await;
+    assert_eq!(response.status_code(), 200);
+    let tables: serde_json::Value = response.json();
+    assert_eq!(
+        tables,
+        serde_json::json!([
+            {
+                "headers": ["Name", "Version"],
+                "rows": [["axum", "0.7"], ["tokio", ""]],
+                "line": 3,
+            },
+            {"headers": ["A"], "rows": [["1"]], "line": 8},
+        ])
+    );
+}
+
+#[tokio::test]
+async fn test_api_file_codeblocks() {
+    let temp_dir = tempdir().expect("Failed to create temp dir");
+    fs::write(
+        temp_dir.path().join("plain.md"),
+        "# Plain\n\n```rust\nfn main() {}\n```\n\n```python\nprint(1)\n```\n",
+    )
+    .expect("Failed to write");
+    fs::write(
+        temp_dir.path().join("examples.md"),
+        "# Examples\n\n```rust,runnable\nfn main() {}\n```\n\n```Python # runnable\nprint(1)\n```\n\n```rust\nstruct Shown;\n```\n",
+    )
+    .expect("Failed to write");
+    let server = create_lazy_server(&temp_dir);
+
+    let response = server.get("/api/files/plain.md/codeblocks").await;
+    assert_eq!(response.status_code(), 200);
+    assert_eq!(
+        response.json::<serde_json::Value>(),
+        serde_json::json!([
+            {"lang": "rust", "code": "fn main() {}", "runnable": false, "line": 3},
+            {"lang": "python", "code": "print(1)", "runnable": false, "line": 7},
+        ])
+    );
+    let body: serde_json::Value = server
+        .get("/api/files/plain.md/codeblocks?runnable=true")
+        .await
+        .json();
+    assert_eq!(body, serde_json::json!([]));
+
+    let body: serde_json::Value = server
+        .get("/api/files/examples.md/codeblocks?runnable=true")
+        .await
+        .json();
+    assert_eq!(
+        body,
+        serde_json::json!([
+            {"lang": "rust", "code": "fn main() {}", "runnable": true, "line": 3},
+            {"lang": "Python", "code": "print(1)", "runnable": true, "line": 7},
+        ])
+    );
+
+    let body: serde_json::Value = server
+        .get("/api/files/examples.md/codeblocks?lang=rust")
+        .await
+        .json();
+    assert_eq!(body.as_array().unwrap().len(), 2);
+    let body: serde_json::Value = server
+        .get("/api/files/examples.md/codeblocks?lang=python&runnable=true")
+        .await
+        .json();
+    assert_eq!(body[0]["line"], 7);
+    assert_eq!(body.as_array().unwrap().len(), 1);
+
+    let response = server.get("/api/files/missing.md/codeblocks").await;
+    assert_eq!(response.status_code(), 404);
+}
+
+#[tokio::test]
+async fn test_api_file_internal_links_valid() {
+    let temp_dir = tempdir().expect("Failed to create temp dir");
+    fs::write(
+        temp_dir.path().join("doc.md"),
+        "# Doc\n\nSee [other](other.md#usage) and [the web](https://example.com/gone.md).\n",
+    )
+    .expect("Failed to write");
+    fs::write(temp_dir.path().join("other.md"), "# Other").expect("Failed to write");
+    let server = create_lazy_server(&temp_dir);
+
+    let response = server.get("/api/files/doc.md/internal-links-valid").await;
+    assert_eq!(response.status_code(), 200);
+    assert_eq!(
+        response.json::<serde_json::Value>(),
+        serde_json::json!({"valid": true})
+    );
+
+    server
+        .put("/api/files/doc.md")
+        .json(&serde_json::json!({ "markdown": "# Doc\n\n[other](other.md) [gone](gone.md)\n" }))
+        .await;
+    let response = server.get("/api/files/doc.md/internal-links-valid").await;
+    assert_eq!(response.status_code(), 200);
+    assert_eq!(
+        response.json::<serde_json::Value>(),
+        serde_json::json!({"valid": false})
+    );
+
+    let response = server
+        .get("/api/files/missing.md/internal-links-valid")
+        .await;
+    assert_eq!(response.status_code(), 404);
+}
+
+#[tokio::test]
+async fn test_api_file_coverage() {
+    let temp_dir = tempdir().expect("Failed to create temp dir");
+    fs::create_dir(temp_dir.path().join("img")).expect("Failed to create dir");
+    fs::write(temp_dir.path().join("img/logo.png"), PIXEL_PNG).expect("Failed to write");
+    fs::write(
+        temp_dir.path().join("clean.md"),
+        "# Clean\n\nSee [other](other.md), [the web](https://example.com) and ![logo](img/logo.png).\n\n- [x] Done\n",
+    )
+    .expect("Failed to write");
+    fs::write(temp_dir.path().join("other.md"), "# Other\n").expect("Failed to write");
+    fs::write(
+        temp_dir.path().join("messy.md"),
+        "## Notes\n\n- [gone](gone.md)\n- [also gone](sub/missing.md#intro)\n- [other](./other.md)\n- [site](https://example.com)\n\n![logo](img/logo.png) ![missing](img/missing.png) ![remote](https://example.com/a.png)\n\n- [x] One\n- [ ] Two\n- [ ] Three\n- [ ] Four\n",
+    )
+    .expect("Failed to write");
+    let server = create_lazy_server(&temp_dir);
+
+    let response = server.get("/api/files/clean.md/coverage").await;
+    assert_eq!(response.status_code(), 200);
+    assert_eq!(
+        response.json::<serde_json::Value>(),
+        serde_json::json!({
+            "total_links": 2,
+            "broken_links": 0,
+            "total_images": 1,
+            "missing_images": 0,
+            "total_tasks": 1,
+            "completed_tasks": 1,
+            "has_title": true,
+            "has_description": true,
+            "health_score": 1.0,
+        })
+    );
+
+    // 0.3 * 2/4 + 0.2 * 2/3 + 0.25 * 1/4, without title or description
+    let body: serde_json::Value = server.get("/api/files/messy.md/coverage").await.json();
+    assert_eq!(
+        body,
+        serde_json::json!({
+            "total_links": 4,
+            "broken_links": 2,
+            "total_images": 3,
+            "missing_images": 1,
+            "total_tasks": 4,
+            "completed_tasks": 1,
+            "has_title": false,
+            "has_description": false,
+            "health_score": 0.35,
+        })
+    );
+
+    let response = server.get("/api/files/missing.md/coverage").await;
+    assert_eq!(response.status_code(), 404);
+}
+
+#[tokio::test]
+async fn test_api_file_references() {
+    let temp_dir = tempdir().expect("Failed to create temp dir");
+    fs::write(
+        temp_dir.path().join("paper.md"),
+        "# Paper\n\nKey words[^1] are defined[^rfc-2119] in [the RFC][rfc].\n\n[^1]: See RFC 2119.\n[^rfc-2119]: Bradner, 1997.\n\n[rfc]: https://www.rfc-editor.org/rfc/rfc2119 \"RFC 2119\"\n",
+    )
+    .expect("Failed to write");
+    fs::write(temp_dir.path().join("plain.md"), "# Plain\n").expect("Failed to write");
+    let server = create_lazy_server(&temp_dir);
+
+    let response = server.get("/api/files/paper.md/references").await;
+    assert_eq!(response.status_code(), 200);
+    assert_eq!(
+        response.json::<serde_json::Value>(),
+        serde_json::json!([
+            {"id": "1", "definition": "See RFC 2119.", "line": 5},
+            {"id": "rfc-2119", "definition": "Bradner, 1997.", "line": 6},
+            {
+                "id": "rfc",
+                "definition": "RFC 2119",
+                "url": "https://www.rfc-editor.org/rfc/rfc2119",
+                "line": 8,
+            },
+        ])
+    );
+
+    let body: serde_json::Value = server.get("/api/files/plain.md/references").await.json();
+    assert_eq!(body, serde_json::json!([]));
+
+    let response = server.get("/api/files/missing.md/references").await;
+    assert_eq!(response.status_code(), 404);
+}
+
+#[tokio::test]
+async fn test_api_file_footnotes() {
+    let temp_dir = tempdir().expect("Failed to create temp dir");
+    fs::write(
+        temp_dir.path().join("paper.md"),
+        "# Paper\n\nKey words[^1] are defined[^rfc].\n\nSee above[^1].\n\n[^1]: See RFC 2119 for details.\n[^rfc]: RFC 2119.\n",
+    )
+    .expect("Failed to write");
+    fs::write(temp_dir.path().join("plain.md"), "# Plain\n").expect("Failed to write");
+    let server = create_lazy_server(&temp_dir);
+
+    let response = server.get("/api/files/paper.md/footnotes").await;
+    assert_eq!(response.status_code(), 200);
+    assert_eq!(
+        response.json::<serde_json::Value>(),
+        serde_json::json!({
+            "footnotes": [
+                {"id": "1", "usage_line": 3, "definition_line": 7, "text": "See RFC 2119 for details."},
+                {"id": "rfc", "usage_line": 3, "definition_line": 8, "text": "RFC 2119."},
+            ],
+            "orphaned_usages": [],
+            "orphaned_definitions": [],
+        })
+    );
+
+    let body: serde_json::Value = server.get("/api/files/plain.md/footnotes").await.json();
+    assert_eq!(
+        body,
+        serde_json::json!({"footnotes": [], "orphaned_usages": [], "orphaned_definitions": []})
+    );
+
+    let response = server.get("/api/files/missing.md/footnotes").await;
+    assert_eq!(response.status_code(), 404);
+}
+
+#[tokio::test]
+async fn test_api_file_footnotes_orphaned_usages() {
+    let temp_dir = tempdir().expect("Failed to create temp dir");
+    fs::write(
+        temp_dir.path().join("draft.md"),
+        "# Draft\n\nCited[^1] and\nforgotten[^todo].\n\n[^1]: Defined.\n",
+    )
+    .expect("Failed to write");
+    let server = create_lazy_server(&temp_dir);
+
+    let response = server.get("/api/files/draft.md/footnotes").await;
+    assert_eq!(response.status_code(), 200);
+    assert_eq!(
+        response.json::<serde_json::Value>(),
+        serde_json::json!({
+            "footnotes": [
+                {"id": "1", "usage_line": 3, "definition_line": 6, "text": "Defined."},
+                {"id": "todo", "usage_line": 4, "definition_line": null, "text": null},
+            ],
+            "orphaned_usages": ["todo"],
+            "orphaned_definitions": [],
+        })
+    );
+}
+
+#[tokio::test]
+async fn test_api_file_footnotes_orphaned_definitions() {
+    let temp_dir = tempdir().expect("Failed to create temp dir");
+    fs::write(
+        temp_dir.path().join("draft.md"),
+        "# Draft\n\nNo citations left.\n\n[^old]: Removed source.\n",
+    )
+    .expect("Failed to write");
+    let server = create_lazy_server(&temp_dir);
+
+    let response = server.get("/api/files/draft.md/footnotes").await;
+    assert_eq!(response.status_code(), 200);
+    assert_eq!(
+        response.json::<serde_json::Value>(),
+        serde_json::json!({
+            "footnotes": [
+                {"id": "old", "usage_line": null, "definition_line": 5, "text": "Removed source."},
+            ],
+            "orphaned_usages": [],
+            "orphaned_definitions": ["old"],
+        })
+    );
+}
+
+#[tokio::test]
+async fn test_api_file_definitions() {
+    let temp_dir = tempdir().expect("Failed to create temp dir");
+    fs::write(
+        temp_dir.path().join("glossary.md"),
+        "# Glossary\n\nAPI\n:   Application Programming Interface\n\nCrate\n:   A compilation unit\n:   A package on **crates.io**\n\n<dl>\n  <dt>URL</dt>\n  <dd>Uniform Resource Locator</dd>\n</dl>\n\nCLI\n\n~   Command-line interface\n",
+    )
+    .expect("Failed to write");
+    fs::write(temp_dir.path().join("plain.md"), "# Plain\n\nKey: value\n")
+        .expect("Failed to write");
+    let server = create_lazy_server(&temp_dir);
+
+    let response = server.get("/api/files/glossary.md/definitions").await;
+    assert_eq!(response.status_code(), 200);
+    assert_eq!(
+        response.json::<serde_json::Value>(),
+        serde_json::json!([
+            {"term": "API", "definition": "Application Programming Interface", "line": 3},
+            {"term": "Crate", "definition": "A compilation unit", "line": 6},
+            {"term": "Crate", "definition": "A package on crates.io", "line": 6},
+            {"term": "URL", "definition": "Uniform Resource Locator", "line": 11},
+            {"term": "CLI", "definition": "Command-line interface", "line": 15},
+        ])
+    );
+
+    let body: serde_json::Value = server.get("/api/files/plain.md/definitions").await.json();
+    assert_eq!(body, serde_json::json!([]));
+
+    let response = server.get("/api/files/missing.md/definitions").await;
+    assert_eq!(response.status_code(), 404);
+}
+
+#[tokio::test]
+async fn test_api_file_admonitions_github() {
+    let temp_dir = tempdir().expect("Failed to create temp dir");
+    fs::write(
+        temp_dir.path().join("doc.md"),
+        "# Doc\n\n> [!NOTE]\n> Useful information.\n> More of it.\n\n> [!caution]\n> Be careful.\n",
+    )
+    .expect("Failed to write");
+    let server = create_lazy_server(&temp_dir);
+
+    let response = server.get("/api/files/doc.md/admonitions").await;
+    assert_eq!(response.status_code(), 200);
+    assert_eq!(
+        response.json::<serde_json::Value>(),
+        serde_json::json!([
+            {"type": "note", "title": "Note", "content": "Useful information.\nMore of it.", "line": 3},
+            {"type": "caution", "title": "Caution", "content": "Be careful.", "line": 7},
+        ])
+    );
+
+    let response = server.get("/api/files/missing.md/admonitions").await;
+    assert_eq!(response.status_code(), 404);
+}
+
+#[tokio::test]
+async fn test_api_file_admonitions_mkdocs() {
+    let temp_dir = tempdir().expect("Failed to create temp dir");
+    fs::write(
+        temp_dir.path().join("doc.md"),
+        "# Doc\n\n!!! warning \"Breaking change\"\n    The API changed.\n\n    See the changelog.\n\n??? tip\n    Collapsed by default.\n\nAfter the blocks.\n",
+    )
+    .expect("Failed to write");
+    let server = create_lazy_server(&temp_dir);
+
+    let body: serde_json::Value = server.get("/api/files/doc.md/admonitions").await.json();
+    assert_eq!(
+        body,
+        serde_json::json!([
+            {"type": "warning", "title": "Breaking change", "content": "The API changed.\n\nSee the changelog.", "line": 3},
+            {"type": "tip", "title": "Tip", "content": "Collapsed by default.", "line": 8},
+        ])
+    );
+}
+
+#[tokio::test]
+async fn test_api_file_admonitions_docusaurus() {
+    let temp_dir = tempdir().expect("Failed to create temp dir");
+    fs::write(
+        temp_dir.path().join("doc.md"),
+        "# Doc\n\n:::danger Take care\n\nThis deletes **everything**.\n\n:::\n\n:::tip[Pro tip]\nUse the CLI.\n:::\n",
+    )
+    .expect("Failed to write");
+    let server = create_lazy_server(&temp_dir);
+
+    let body: serde_json::Value = server.get("/api/files/doc.md/admonitions").await.json();
+    assert_eq!(
+        body,
+        serde_json::json!([
+            {"type": "danger", "title": "Take care", "content": "This deletes **everything**.", "line": 3},
+            {"type": "tip", "title": "Pro tip", "content": "Use the CLI.", "line": 9},
+        ])
+    );
+}
+
+#[tokio::test]
+async fn test_api_file_admonitions_plain_blockquote() {
+    let temp_dir = tempdir().expect("Failed to create temp dir");
+    fs::write(
+        temp_dir.path().join("doc.md"),
+        "# Doc\n\n> Just a quote.\n\n> [!UNKNOWN]\n> Not a GitHub alert type.\n\n> Mentions [!NOTE] later on.\n",
+    )
+    .expect("Failed to write");
+    let server = create_lazy_server(&temp_dir);
+
+    let body: serde_json::Value = server.get("/api/files/doc.md/admonitions").await.json();
+    assert_eq!(body, serde_json::json!([]));
+}
+
+#[tokio::test]
+async fn test_api_file_badges() {
+    let temp_dir = tempdir().expect("Failed to create temp dir");
+    fs::write(
+        temp_dir.path().join("README.md"),
+        "# Project\n\n[![CI](https://img.shields.io/badge/ci-passing-green)](https://github.com/o/r/actions)\n![Version](https://badgen.net/npm/v/pkg)\n\n![Screenshot](screenshot.png)\n",
+    )
+    .expect("Failed to write");
+    let server = create_lazy_server(&temp_dir);
+
+    let response = server.get("/api/files/README.md/badges").await;
+    assert_eq!(response.status_code(), 200);
+    assert_eq!(
+        response.json::<serde_json::Value>(),
+        serde_json::json!([
+            {
+                "alt": "CI",
+                "image_url": "https://img.shields.io/badge/ci-passing-green",
+                "link_url": "https://github.com/o/r/actions",
+                "source": "shields",
+            },
+            {
+                "alt": "Version",
+                "image_url": "https://badgen.net/npm/v/pkg",
+                "link_url": null,
+                "source": "badgen",
+            },
+        ])
+    );
+
+    let response = server.get("/api/files/missing.md/badges").await;
+    assert_eq!(response.status_code(), 404);
+}
+
+#[tokio::test]
+async fn test_api_file_math_inline() {
+    let temp_dir = tempdir().expect("Failed to create temp dir");
+    fs::write(
+        temp_dir.path().join("physics.md"),
+        "# Physics\n\nEnergy is $E = mc^2$, momentum $p = mv$.\n\nNot $ spaced $ math.\n",
+    )
+    .expect("Failed to write");
+    fs::write(temp_dir.path().join("plain.md"), "# Plain\n").expect("Failed to write");
+    let server = create_lazy_server(&temp_dir);
+
+    let response = server.get("/api/files/physics.md/math").await;
+    assert_eq!(response.status_code(), 200);
+    assert_eq!(
+        response.json::<serde_json::Value>(),
+        serde_json::json!({
+            "count": 2,
+            "formulas": [
+                {"kind": "inline", "latex": "E = mc^2", "line": 3},
+                {"kind": "inline", "latex": "p = mv", "line": 3},
+            ],
+        })
+    );
+
+    let body: serde_json::Value = server.get("/api/files/plain.md/math").await.json();
+    assert_eq!(body, serde_json::json!({"count": 0, "formulas": []}));
+
+    let response = server.get("/api/files/missing.md/math").await;
+    assert_eq!(response.status_code(), 404);
+}
+
+#[tokio::test]
+async fn test_api_file_math_block() {
+    let temp_dir = tempdir().expect("Failed to create temp dir");
+    fs::write(
+        temp_dir.path().join("physics.md"),
+        "# Physics\n\n$$\nF = ma\n$$\n\n\\[\n\\nabla \\cdot E = \\rho\n\\]\n\nSum: $$\\sum_i x_i$$\n",
+    )
+    .expect("Failed to write");
+    let server = create_lazy_server(&temp_dir);
+
+    let response = server.get("/api/files/physics.md/math").await;
+    assert_eq!(response.status_code(), 200);
+    assert_eq!(
+        response.json::<serde_json::Value>(),
+        serde_json::json!({
+            "count": 3,
+            "formulas": [
+                {"kind": "block", "latex": "F = ma", "line": 3},
+                {"kind": "block", "latex": "\\nabla \\cdot E = \\rho", "line": 7},
+                {"kind": "block", "latex": "\\sum_i x_i", "line": 11},
+            ],
+        })
+    );
+}
+
+#[tokio::test]
+async fn test_api_file_math_escaped_dollars() {
+    let temp_dir = tempdir().expect("Failed to create temp dir");
+    fs::write(
+        temp_dir.path().join("prices.md"),
+        "# Prices\n\nA \\$5 fee and a \\$10\\$ refund.\n\nIt costs $5 or $10 today.\n",
+    )
+    .expect("Failed to write");
+    let server = create_lazy_server(&temp_dir);
+
+    let body: serde_json::Value = server.get("/api/files/prices.md/math").await.json();
+    assert_eq!(body, serde_json::json!({"count": 0, "formulas": []}));
+}
+
+#[tokio::test]
+async fn test_api_file_math_in_code() {
+    let temp_dir = tempdir().expect("Failed to create temp dir");
+    fs::write(
+        temp_dir.path().join("shell.md"),
+        "# Shell\n\nRun `echo $HOME$` first.\n\n```sh\necho $$PATH$$\n```\n\n    $indented$\n\nThen $x$.\n",
+    )
+    .expect("Failed to write");
+    let server = create_lazy_server(&temp_dir);
+
+    let body: serde_json::Value = server.get("/api/files/shell.md/math").await.json();
+    assert_eq!(
+        body,
+        serde_json::json!({
+            "count": 1,
+            "formulas": [{"kind": "inline", "latex": "x", "line": 11}],
+        })
+    );
+}
+
+fn create_diagram_server() -> (TestServer, TempDir) {
+    let temp_dir = tempdir().expect("Failed to create temp dir");
+    fs::write(
+        temp_dir.path().join("flow.md"),
+        "# Flow\n\n```mermaid\nflowchart LR\n    A[Start] --> B{Ok?}\n    B -->|Yes| C[Done]\n```\n\n```mermaid\nsequenceDiagram\n    Alice->>Bob: Hello\n    Bob-->>Alice: Hi\n```\n",
+    )
+    .expect("Failed to write");
+    fs::write(
+        temp_dir.path().join("model.md"),
+        "# Model\n\n```puml\n@startuml\nclass User {\n  +name: String\n}\nUser --> Order\n@enduml\n```\n\n```rust\nstruct User;\n```\n",
+    )
+    .expect("Failed to write");
+    let server = create_lazy_server(&temp_dir);
+    (server, temp_dir)
+}
+
+#[tokio::test]
+async fn test_api_file_diagrams() {
+    let (server, _temp_dir) = create_diagram_server();
+
+    let response = server.get("/api/files/flow.md/diagrams").await;
+    assert_eq!(response.status_code(), 200);
+    assert_eq!(
+        response.json::<serde_json::Value>(),
+        serde_json::json!([
+            {
+                "type": "mermaid",
+                "definition": "flowchart LR\n    A[Start] --> B{Ok?}\n    B -->|Yes| C[Done]",
+                "line_start": 3,
+                "line_end": 7,
+            },
+            {
+                "type": "mermaid",
+                "definition": "sequenceDiagram\n    Alice->>Bob: Hello\n    Bob-->>Alice: Hi",
+                "line_start": 9,
+                "line_end": 13,
+            },
+        ])
+    );
+
+    let response = server.get("/api/files/model.md/diagrams").await;
+    assert_eq!(
+        response.json::<serde_json::Value>(),
+        serde_json::json!([{
+            "type": "plantuml",
+            "definition": "@startuml\nclass User {\n  +name: String\n}\nUser --> Order\n@enduml",
+            "line_start": 3,
+            "line_end": 10,
+        }])
+    );
+
+    let body: serde_json::Value = server
+        .get("/api/files/flow.md/diagrams")
+        .add_query_param("type", "plantuml")
+        .await
+        .json();
+    assert_eq!(body, serde_json::json!([]));
+
+    let response = server
+        .get("/api/files/flow.md/diagrams")
+        .add_query_param("type", "graphviz")
+        .await;
+    assert_eq!(response.status_code(), 400);
+}
+
+#[tokio::test]
+async fn test_api_workspace_diagrams() {
+    let (server, _temp_dir) = create_diagram_server();
+
+    let list = |query: &'static str| {
+        let request = server.get(&format!("/api/workspace/diagrams?{query}"));
+        async move {
+            let response = request.await;
+            assert_eq!(response.status_code(), 200, "{query}");
+            let body: serde_json::Value = response.json();
+            body.as_array()
+                .unwrap()
+                .iter()
+                .map(|diagram| {
+                    format!(
+                        "{}:{} {}",
+                        diagram["file"].as_str().unwrap(),
+                        diagram["line_start"],
+                        diagram["type"].as_str().unwrap()
+                    )
+                })
+                .collect::<Vec<_>>()
+        }
+    };
+
+    assert_eq!(
+        list("").await,
+        vec![
+            "flow.md:3 mermaid",
+            "flow.md:9 mermaid",
+            "model.md:3 plantuml"
+        ]
+    );
+    assert_eq!(list("type=plantuml").await, vec!["model.md:3 plantuml"]);
+
+    let response = server
+        .get("/api/workspace/diagrams")
+        .add_query_param("type", "graphviz")
+        .await;
+    assert_eq!(response.status_code(), 400);
+}
+
+#[tokio::test]
+async fn test_api_file_glossary_parentheses() {
+    let temp_dir = tempdir().expect("Failed to create temp dir");
+    fs::write(
+        temp_dir.path().join("doc.md"),
+        "# Intro\n\nServers talk to clients. An **API** (Application Programming Interface) is a contract between them.\n",
+    )
+    .expect("Failed to write");
+    let server = create_lazy_server(&temp_dir);
+
+    let response = server.get("/api/files/doc.md/glossary").await;
+    assert_eq!(response.status_code(), 200);
+    assert_eq!(
+        response.json::<serde_json::Value>(),
+        serde_json::json!([{
+            "term": "API",
+            "context": "An **API** (Application Programming Interface) is a contract between them.",
+            "line": 3,
+        }])
+    );
+
+    let response = server.get("/api/files/missing.md/glossary").await;
+    assert_eq!(response.status_code(), 404);
+}
+
+#[tokio::test]
+async fn test_api_file_glossary_colon() {
+    let temp_dir = tempdir().expect("Failed to create temp dir");
+    fs::write(
+        temp_dir.path().join("doc.md"),
+        "# Terms\n\n**Crate**: the unit of compilation in Rust.\n\n**Workspace:** a set of crates built together.\n",
+    )
+    .expect("Failed to write");
+    let server = create_lazy_server(&temp_dir);
+
+    let body: serde_json::Value = server.get("/api/files/doc.md/glossary").await.json();
+    assert_eq!(
+        body,
+        serde_json::json!([
+            {"term": "Crate", "context": "**Crate**: the unit of compilation in Rust.", "line": 3},
+            {"term": "Workspace", "context": "**Workspace:** a set of crates built together.", "line": 5},
+        ])
+    );
+}
+
+#[tokio::test]
+async fn test_api_file_glossary_dash() {
+    let temp_dir = tempdir().expect("Failed to create temp dir");
+    fs::write(
+        temp_dir.path().join("doc.md"),
+        "# Terms\n\nEvery page has one. The *slug* — the last part of its URL — is made\nfrom the title.\n",
+    )
+    .expect("Failed to write");
+    let server = create_lazy_server(&temp_dir);
+
+    let body: serde_json::Value = server.get("/api/files/doc.md/glossary").await.json();
+    assert_eq!(
+        body,
+        serde_json::json!([{
+            "term": "slug",
+            "context": "The *slug* — the last part of its URL — is made from the title.",
+            "line": 3,
+        }])
+    );
+}
+
+#[tokio::test]
+async fn test_api_file_glossary_without_definitions() {
+    let temp_dir = tempdir().expect("Failed to create temp dir");
+    fs::write(
+        temp_dir.path().join("doc.md"),
+        "# Plain\n\nSome **bold** and *italic* words, but no definitions.\n",
+    )
+    .expect("Failed to write");
+    let server = create_lazy_server(&temp_dir);
+
+    let body: serde_json::Value = server.get("/api/files/doc.md/glossary").await.json();
+    assert_eq!(body, serde_json::json!([]));
+}
+
+#[tokio::test]
+async fn test_api_file_todos() {
+    let temp_dir = tempdir().expect("Failed to create temp dir");
+    fs::write(
+        temp_dir.path().join("plan.md"),
+        "# Plan\n\n- [ ] Write the guide\n- [x] Fix the build\n\nFIXME: the intro is outdated\n\n<!-- TODO: add screenshots -->\n\n```python\n# hack: retry twice\n```\n",
+    )
+    .expect("Failed to write");
+    fs::write(
+        temp_dir.path().join("plain.md"),
+        "# Plain\n\nNothing to do.\n",
+    )
+    .expect("Failed to write");
+    let server = create_lazy_server(&temp_dir);
+
+    let response = server.get("/api/files/plan.md/todos").await;
+    assert_eq!(response.status_code(), 200);
+    assert_eq!(
+        response.json::<serde_json::Value>(),
+        serde_json::json!([
+            {"type": "checkbox", "priority": "normal", "text": "Write the guide", "line": 3, "done": false},
+            {"type": "checkbox", "priority": "normal", "text": "Fix the build", "line": 4, "done": true},
+            {"type": "inline", "priority": "fixme", "text": "the intro is outdated", "line": 6, "done": false},
+            {"type": "comment", "priority": "normal", "text": "add screenshots", "line": 8, "done": false},
+            {"type": "inline", "priority": "hack", "text": "retry twice", "line": 11, "done": false},
+        ])
+    );
+
+    let body: serde_json::Value = server.get("/api/files/plain.md/todos").await.json();
+    assert_eq!(body, serde_json::json!([]));
+
+    let response = server.get("/api/files/missing.md/todos").await;
+    assert_eq!(response.status_code(), 404);
+}
+
+#[tokio::test]
+async fn test_api_file_reading_time() {
+    let temp_dir = tempdir().expect("Failed to create temp dir");
+    let prose = "word ".repeat(400);
+    fs::write(temp_dir.path().join("article.md"), prose).expect("Failed to write");
+    let server = create_lazy_server(&temp_dir);
+
+    let response = server.get("/api/files/article.md/reading-time").await;
+    assert_eq!(response.status_code(), 200);
+    let body: serde_json::Value = response.json();
+    assert_eq!(
+        body,
+        serde_json::json!({ "minutes": 2, "seconds": 120, "words": 400, "code_words": 0 })
+    );
+}
+
+#[tokio::test]
+async fn test_api_file_summary() {
+    let temp_dir = tempdir().expect("Failed to create temp dir");
+    fs::create_dir(temp_dir.path().join("guide")).expect("Failed to create dir");
+    fs::write(
+        temp_dir.path().join("guide/install.md"),
+        "# Installation Guide\n\nThis guide walks you through setup.\n",
+    )
+    .expect("Failed to write");
+    fs::write(temp_dir.path().join("notes.md"), "Just some notes.\n").expect("Failed to write");
+    let server = create_lazy_server(&temp_dir);
+
+    let response = server.get("/api/files/guide/install.md/summary").await;
+    assert_eq!(response.status_code(), 200);
+    let body: serde_json::Value = response.json();
+    assert_eq!(
+        body,
+        serde_json::json!({
+            "title": "Installation Guide",
+            "summary": "This guide walks you through setup.",
+            "word_count": 8,
+        })
+    );
+
+    let body: serde_json::Value = server.get("/api/files/notes.md/summary").await.json();
+    assert_eq!(body["title"], "notes.md");
+    assert_eq!(body["summary"], "Just some notes.");
+
+    let response = server.get("/api/files/missing.md/summary").await;
+    assert_eq!(response.status_code(), 404);
+}
+
+#[tokio::test]
+async fn test_api_file_summary_follows_updates() {
+    let temp_dir = tempdir().expect("Failed to create temp dir");
+    fs::write(
+        temp_dir.path().join("post.md"),
+        "---\ntitle: Post\n---\nOld text.\n",
+    )
+    .expect("Failed to write");
+    let server = create_lazy_server(&temp_dir);
+
+    let body: serde_json::Value = server.get("/api/files/post.md/summary").await.json();
+    assert_eq!(body["summary"], "Old text.");
+
+    let response = server
+        .put("/api/files/post.md/frontmatter")
+        .json(&serde_json::json!({ "frontmatter": null }))
+        .await;
+    assert_eq!(response.status_code(), 200);
+
+    fs::write(temp_dir.path().join("post.md"), "# Post\n\nNew text.\n").expect("Failed to write");
+    // Make sure the modification time moves past the cached copy's
+    let later = std::time::SystemTime::now() + Duration::from_secs(5);
+    fs::File::options()
+        .write(true)
+        .open(temp_dir.path().join("post.md"))
+        .and_then(|file| file.set_modified(later))
+        .expect("Failed to touch file");
+
+    let body: serde_json::Value = server.get("/api/files/post.md/summary").await.json();
+    assert_eq!(body["title"], "Post");
+    assert_eq!(body["summary"], "New text.");
+}
+
+#[tokio::test]
+async fn test_api_file_metadata() {
+    let temp_dir = tempdir().expect("Failed to create temp dir");
+    let markdown = "---\ntitle: Release Plan\ntags: [ops]\n---\n# Plan\n\nShip it [soon](next.md) or [later](https://example.com).\n\n## Steps\n\n![diagram](plan.png)\n\n- [x] Build\n- [ ] Release\n";
+    let file_path = temp_dir.path().join("plan.md");
+    fs::write(&file_path, markdown).expect("Failed to write");
+    fs::write(temp_dir.path().join("empty.md"), "\n\n").expect("Failed to write");
+    let modified = fs::metadata(&file_path)
+        .and_then(|metadata| metadata.modified())
+        .expect("Failed to read metadata")
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap()
+        .as_secs();
+    let server = create_lazy_server(&temp_dir);
+
+    let response = server.get("/api/files/plan.md/metadata").await;
+    assert_eq!(response.status_code(), 200);
+    assert_eq!(
+        response.json::<serde_json::Value>(),
+        serde_json::json!({
+            "path": "plan.md",
+            "size_bytes": markdown.len(),
+            "modified": modified,
+            "hash": format!("{:x}", md5::compute(markdown)),
+            "title": "Release Plan",
+            "word_count": 9,
+            "reading_time_secs": 3,
+            "has_frontmatter": true,
+            "frontmatter_keys": ["title", "tags"],
+            "heading_count": 2,
+            "link_count": 2,
+            "image_count": 1,
+            "task_count": 2,
+            "is_empty": false,
+        })
+    );
+
+    let body: serde_json::Value = server.get("/api/files/empty.md/metadata").await.json();
+    assert_eq!(body["title"], serde_json::Value::Null);
+    assert_eq!(body["word_count"], 0);
+    assert_eq!(body["has_frontmatter"], false);
+    assert_eq!(body["frontmatter_keys"], serde_json::json!([]));
+    assert_eq!(body["is_empty"], true);
+
+    let response = server.get("/api/files/missing.md/metadata").await;
+    assert_eq!(response.status_code(), 404);
+}
+
+#[tokio::test]
+async fn test_api_file_properties() {
+    let temp_dir = tempdir().expect("Failed to create temp dir");
+    fs::write(
+        temp_dir.path().join("plan.md"),
+        "---\nowner: sam\n---\n# Plan\n\nShip it.\n\n- [x] Build\n- [ ] Release\n",
+    )
+    .expect("Failed to write");
+    let server = create_lazy_server(&temp_dir);
+
+    let response = server.get("/api/files/plan.md/properties").await;
+    assert_eq!(response.status_code(), 200);
+    let body: serde_json::Value = response.json();
+    assert_eq!(
+        body,
+        serde_json::json!({
+            "anchors": [{"id": "plan", "text": "Plan", "level": 1}],
+            "reading_time": {"minutes": 1, "seconds": 1, "words": 5, "code_words": 0},
+            "tables": [],
+            "summary": {"title": "Plan", "summary": "Ship it.", "word_count": 5},
+            "frontmatter": {"owner": "sam"},
+            "tasks": [
+                {"line": 8, "text": "Build", "checked": true},
+                {"line": 9, "text": "Release", "checked": false},
+            ],
+        })
+    );
+
+    // Each field matches its own resource
+    let summary: serde_json::Value = server.get("/api/files/plan.md/summary").await.json();
+    assert_eq!(body["summary"], summary);
+
+    let body: serde_json::Value = server
+        .get("/api/files/plan.md/properties")
+        .add_query_param("fields", "summary, tasks")
+        .await
+        .json();
+    let keys: Vec<&String> = body.as_object().unwrap().keys().collect();
+    assert_eq!(keys, ["summary", "tasks"]);
+
+    let response = server
+        .get("/api/files/plan.md/properties")
+        .add_query_param("fields", "toc")
+        .await;
+    assert_eq!(response.status_code(), 400);
+    let body: serde_json::Value = response.json();
+    assert_eq!(
+        body["error"],
+        "Unknown property 'toc', expected one of anchors, reading_time, tables, summary, frontmatter, tasks"
+    );
+
+    let response = server.get("/api/files/missing.md/properties").await;
+    assert_eq!(response.status_code(), 404);
+}
+
+#[tokio::test]
+async fn test_api_file_ancestors() {
+    let temp_dir = tempdir().expect("Failed to create temp dir");
+    fs::create_dir_all(temp_dir.path().join("docs/guide/advanced")).expect("Failed to create dir");
+    fs::write(temp_dir.path().join("readme.md"), "# Readme").expect("Failed to write");
+    fs::write(
+        temp_dir.path().join("docs/guide/advanced/tuning.md"),
+        "# Tuning",
+    )
+    .expect("Failed to write");
+    let server = create_lazy_server(&temp_dir);
+
+    let response = server.get("/api/files/readme.md/ancestors").await;
+    assert_eq!(response.status_code(), 200);
+    assert_eq!(
+        response.json::<serde_json::Value>(),
+        serde_json::json!([{ "name": "readme.md", "path": "readme.md", "is_file": true }])
+    );
+
+    let body: serde_json::Value = server
+        .get("/api/files/docs/guide/advanced/tuning.md/ancestors")
+        .await
+        .json();
+    assert_eq!(
+        body,
+        serde_json::json!([
+            { "name": "docs", "path": "docs/" },
+            { "name": "guide", "path": "docs/guide/" },
+            { "name": "advanced", "path": "docs/guide/advanced/" },
+            { "name": "tuning.md", "path": "docs/guide/advanced/tuning.md", "is_file": true },
+        ])
+    );
+
+    let response = server.get("/api/files/docs/missing.md/ancestors").await;
+    assert_eq!(response.status_code(), 404);
+}
+
+#[tokio::test]
+async fn test_api_file_neighbors() {
+    // Sorted: test1.md, test2.markdown, test3.md
+    let (server, _temp_dir) = create_directory_server().await;
+
+    let first: serde_json::Value = server.get("/api/files/test1.md/neighbors").await.json();
+    assert_eq!(
+        first,
+        serde_json::json!({ "previous": null, "next": "test2.markdown" })
+    );
+
+    let middle: serde_json::Value = server
+        .get("/api/files/test2.markdown/neighbors")
+        .await
+        .json();
+    assert_eq!(
+        middle,
+        serde_json::json!({ "previous": "test1.md", "next": "test3.md" })
+    );
+
+    let last: serde_json::Value = server.get("/api/files/test3.md/neighbors").await.json();
+    assert_eq!(
+        last,
+        serde_json::json!({ "previous": "test2.markdown", "next": null })
+    );
+
+    let response = server.get("/api/files/missing.md/neighbors").await;
+    assert_eq!(response.status_code(), 404);
+}
+
+#[tokio::test]
+async fn test_api_file_neighbors_only_file() {
+    let (server, _temp_file) = create_test_server("# Only").await;
+
+    let files: serde_json::Value = server.get("/api/files").await.json();
+    let path = files["files"][0]["path"].as_str().unwrap();
+
+    let body: serde_json::Value = server
+        .get(&format!("/api/files/{path}/neighbors"))
+        .await
+        .json();
+    assert_eq!(body, serde_json::json!({ "previous": null, "next": null }));
+}
+
+fn create_tagged_server() -> (TestServer, TempDir) {
+    let temp_dir = tempdir().expect("Failed to create temp dir");
+    for (name, tags) in [
+        ("tutorial1.md", "[rust, async, web]"),
+        ("tutorial2.md", "[rust, async]"),
+        ("tutorial3.md", "[web]"),
+        ("untagged.md", "[]"),
+    ] {
+        let content = format!("---\ntags: {tags}\n---\n# {name}\n");
+        fs::write(temp_dir.path().join(name), content).expect("Failed to write");
+    }
+    let server = create_lazy_server(&temp_dir);
+    (server, temp_dir)
+}
+
+#[tokio::test]
+async fn test_api_file_related() {
+    let (server, _temp_dir) = create_tagged_server();
+
+    let response = server.get("/api/files/tutorial1.md/related").await;
+    assert_eq!(response.status_code(), 200);
+    let body: serde_json::Value = response.json();
+    assert_eq!(
+        body,
+        serde_json::json!([
+            {"path": "tutorial2.md", "shared_tags": ["rust", "async"]},
+            {"path": "tutorial3.md", "shared_tags": ["web"]},
+        ])
+    );
+
+    let body: serde_json::Value = server
+        .get("/api/files/tutorial1.md/related")
+        .add_query_param("limit", 1)
+        .await
+        .json();
+    assert_eq!(
+        body,
+        serde_json::json!([{"path": "tutorial2.md", "shared_tags": ["rust", "async"]}])
+    );
+
+    let body: serde_json::Value = server.get("/api/files/tutorial3.md/related").await.json();
+    assert_eq!(
+        body,
+        serde_json::json!([{"path": "tutorial1.md", "shared_tags": ["web"]}])
+    );
+}
+
+#[tokio::test]
+async fn test_api_file_related_without_tags() {
+    let (server, _temp_dir) = create_tagged_server();
+
+    let body: serde_json::Value = server.get("/api/files/untagged.md/related").await.json();
+    assert_eq!(body, serde_json::json!([]));
+
+    let response = server.get("/api/files/missing.md/related").await;
+    assert_eq!(response.status_code(), 404);
+}
+
+/// A server whose watcher won't notice changes during the test, so the in-memory copy of
+/// `doc.md` can be made to diverge from the file on disk.
+fn create_unwatched_server(temp_dir: &TempDir) -> TestServer {
+    let config = RouterConfig {
+        poll_interval: Some(Duration::from_secs(3600)),
+        ..RouterConfig::default()
+    };
+    let router = new_router_with_config(
+        temp_dir.path().to_path_buf(),
+        vec![temp_dir.path().join("doc.md")],
+        true,
+        config,
+    )
+    .expect("Failed to create router");
+    TestServer::new(router).expect("Failed to create test server")
+}
+
+#[tokio::test]
+async fn test_api_file_status() {
+    let temp_dir = tempdir().expect("Failed to create temp dir");
+    let file_path = temp_dir.path().join("doc.md");
+    fs::write(&file_path, "# Original").expect("Failed to write");
+    let modified = fs::metadata(&file_path)
+        .and_then(|metadata| metadata.modified())
+        .expect("Failed to read metadata")
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap()
+        .as_secs();
+    let server = create_unwatched_server(&temp_dir);
+    let original_hash = format!("{:x}", md5::compute("# Original"));
+
+    let body: serde_json::Value = server.get("/api/files/doc.md/status").await.json();
+    assert_eq!(
+        body,
+        serde_json::json!({
+            "path": "doc.md",
+            "in_memory_hash": original_hash,
+            "on_disk_hash": original_hash,
+            "in_sync": true,
+            "last_refresh": modified,
+        })
+    );
+
+    fs::write(&file_path, "# Changed behind the server's back").expect("Failed to write");
+    let body: serde_json::Value = server.get("/api/files/doc.md/status").await.json();
+    assert_eq!(body["in_sync"], false);
+    assert_eq!(body["in_memory_hash"], original_hash);
+    assert_eq!(
+        body["on_disk_hash"],
+        format!("{:x}", md5::compute("# Changed behind the server's back"))
+    );
+    // Checking the status doesn't refresh the file
+    assert_eq!(
+        server
+            .get("/api/files/doc.md/status")
+            .await
+            .json::<serde_json::Value>()["in_sync"],
+        false
+    );
+
+    fs::remove_file(&file_path).expect("Failed to remove");
+    let body: serde_json::Value = server.get("/api/files/doc.md/status").await.json();
+    assert_eq!(body["on_disk_hash"], serde_json::Value::Null);
+    assert_eq!(body["in_sync"], false);
+
+    let response = server.get("/api/files/missing.md/status").await;
+    assert_eq!(response.status_code(), 404);
+}
+
+#[tokio::test]
+async fn test_api_file_hash_chain() {
+    let temp_dir = tempdir().expect("Failed to create temp dir");
+    let file_path = temp_dir.path().join("doc.md");
+    fs::write(&file_path, "# Original").expect("Failed to write");
+    let server = create_unwatched_server(&temp_dir);
+
+    // Loading the file isn't a change
+    let body: serde_json::Value = server.get("/api/files/doc.md/hash-chain").await.json();
+    assert_eq!(body, serde_json::json!([]));
+    let response = server.get("/api/files/doc.md/hash-chain/latest").await;
+    assert_eq!(response.status_code(), 404);
+
+    let versions = ["# One", "# Two", "# Three"];
+    for markdown in versions {
+        let response = server
+            .put("/api/files/doc.md")
+            .json(&serde_json::json!({ "markdown": markdown }))
+            .await;
+        assert_eq!(response.status_code(), 200);
+    }
+
+    let chain: Vec<serde_json::Value> = server.get("/api/files/doc.md/hash-chain").await.json();
+    let hashes: Vec<&str> = chain
+        .iter()
+        .map(|entry| entry["hash"].as_str().unwrap())
+        .collect();
+    let expected: Vec<String> = versions
+        .iter()
+        .map(|markdown| format!("{:x}", md5::compute(markdown)))
+        .collect();
+    assert_eq!(hashes, expected);
+    let timestamps: Vec<u64> = chain
+        .iter()
+        .map(|entry| entry["timestamp"].as_u64().unwrap())
+        .collect();
+    assert!(timestamps.windows(2).all(|pair| pair[0] <= pair[1]));
+
+    let latest: serde_json::Value = server
+        .get("/api/files/doc.md/hash-chain/latest")
+        .await
+        .json();
+    assert_eq!(latest, chain[2]);
+
+    // Changes made behind the server's back are recorded when noticed
+    let mtime = fs::metadata(&file_path).unwrap().modified().unwrap();
+    fs::write(&file_path, "# Four").expect("Failed to write");
+    fs::File::options()
+        .write(true)
+        .open(&file_path)
+        .and_then(|f| f.set_modified(mtime + Duration::from_secs(1)))
+        .expect("Failed to set mtime");
+    let latest: serde_json::Value = server
+        .get("/api/files/doc.md/hash-chain/latest")
+        .await
+        .json();
+    assert_eq!(latest["hash"], format!("{:x}", md5::compute("# Four")));
+
+    let response = server.get("/api/files/missing.md/hash-chain").await;
+    assert_eq!(response.status_code(), 404);
+}
+
+#[tokio::test]
+async fn test_api_file_hash_chain_keeps_last_20_changes() {
+    let temp_dir = tempdir().expect("Failed to create temp dir");
+    fs::write(temp_dir.path().join("doc.md"), "# Original").expect("Failed to write");
+    let server = create_unwatched_server(&temp_dir);
+
+    for i in 0..25 {
+        server
+            .put("/api/files/doc.md")
+            .json(&serde_json::json!({ "markdown": format!("# Version {i}") }))
+            .await;
+    }
+
+    let chain: Vec<serde_json::Value> = server.get("/api/files/doc.md/hash-chain").await.json();
+    assert_eq!(chain.len(), 20);
+    assert_eq!(
+        chain[0]["hash"],
+        format!("{:x}", md5::compute("# Version 5"))
+    );
+    assert_eq!(
+        chain[19]["hash"],
+        format!("{:x}", md5::compute("# Version 24"))
+    );
+}
+
+#[tokio::test]
+async fn test_api_file_mentions() {
+    let temp_dir = tempdir().expect("Failed to create temp dir");
+    for (name, content) in [
+        ("install.md", "# Installation Guide\n\nSteps.\n"),
+        (
+            "index.md",
+            "# Index\n\nFirst, see the installation guide for setup.\n\nThe Installation Guide also covers upgrades.\n",
+        ),
+        ("faq.md", "# FAQ\n\nRead the INSTALLATION GUIDE.\n"),
+        ("other.md", "# Other\n\nNothing relevant.\n"),
+    ] {
+        fs::write(temp_dir.path().join(name), content).expect("Failed to write");
+    }
+    let server = create_lazy_server(&temp_dir);
+
+    let body: serde_json::Value = server.get("/api/files/install.md/mentions").await.json();
+    assert_eq!(
+        body,
+        serde_json::json!([
+            {"path": "faq.md", "context": "# FAQ Read the INSTALLATION GUIDE."},
+            {"path": "index.md", "context": "# Index First, see the installation guide for setup. The Installation Guide also covers up..."},
+            {"path": "index.md", "context": "...First, see the installation guide for setup. The Installation Guide also covers upgrades."},
+        ])
+    );
+
+    // A new title is searched for as soon as the file is saved
+    let response = server
+        .put("/api/files/install.md")
+        .json(&serde_json::json!({ "markdown": "# Setup\n\nSteps.\n" }))
+        .await;
+    assert_eq!(response.status_code(), 200);
+    let body: serde_json::Value = server.get("/api/files/install.md/mentions").await.json();
+    assert_eq!(
+        body,
+        serde_json::json!([
+            {"path": "index.md", "context": "# Index First, see the installation guide for setup. The Installation Guide also covers upgrades."},
+        ])
+    );
+}
+
+#[tokio::test]
+async fn test_api_file_mentions_without_title() {
+    let temp_dir = tempdir().expect("Failed to create temp dir");
+    fs::write(temp_dir.path().join("notes.md"), "Just notes.\n").expect("Failed to write");
+    fs::write(temp_dir.path().join("other.md"), "# Other\n\nnotes.md\n").expect("Failed to write");
+    let server = create_lazy_server(&temp_dir);
+
+    let body: serde_json::Value = server.get("/api/files/notes.md/mentions").await.json();
+    assert_eq!(body, serde_json::json!([]));
+
+    let response = server.get("/api/files/missing.md/mentions").await;
+    assert_eq!(response.status_code(), 404);
+}
+
+#[tokio::test]
+async fn test_api_file_similar() {
+    let temp_dir = tempdir().expect("Failed to create temp dir");
+    let headings = "# Installation\n\n## Requirements\n\n## Configuration\n\n## Troubleshooting\n";
+    for (name, content) in [
+        ("guide.md", format!("{headings}\nSome text.\n")),
+        (
+            "twin.md",
+            headings
+                .to_lowercase()
+                .replace("\n\n", "\n\nDifferent body.\n\n"),
+        ),
+        ("partial.md", "# Installation\n\n## Usage\n".to_string()),
+        ("different.md", "# Cooking\n\n## Recipes\n".to_string()),
+        (
+            "plain.md",
+            "No headings, only Installation text.\n".to_string(),
+        ),
+    ] {
+        fs::write(temp_dir.path().join(name), content).expect("Failed to write");
+    }
+    let server = create_lazy_server(&temp_dir);
+
+    let response = server.get("/api/files/guide.md/similar").await;
+    assert_eq!(response.status_code(), 200);
+    // Installation is one of four heading words in guide.md and one of two in partial.md:
+    // 1 / (2 * sqrt(2))
+    assert_eq!(
+        response.json::<serde_json::Value>(),
+        serde_json::json!([
+            {"path": "twin.md", "score": 1.0},
+            {"path": "partial.md", "score": 0.35},
+        ])
+    );
+
+    // Changed headings are picked up
+    server
+        .put("/api/files/twin.md")
+        .json(&serde_json::json!({ "markdown": "# Cooking\n" }))
+        .await;
+    let body: serde_json::Value = server.get("/api/files/guide.md/similar").await.json();
+    assert_eq!(
+        body,
+        serde_json::json!([{"path": "partial.md", "score": 0.35}])
+    );
+
+    let body: serde_json::Value = server.get("/api/files/plain.md/similar").await.json();
+    assert_eq!(body, serde_json::json!([]));
+
+    let response = server.get("/api/files/missing.md/similar").await;
+    assert_eq!(response.status_code(), 404);
+}
+
+/// Runs git in `dir` as a fixed author and returns its output, panicking if it fails.
+fn git(dir: &std::path::Path, args: &[&str]) -> String {
+    let output = std::process::Command::new("git")
+        .args([
+            "-c",
+            "user.name=Jane Doe",
+            "-c",
+            "user.email=jane@example.com",
+        ])
+        .args(args)
+        .current_dir(dir)
+        .output()
+        .expect("Failed to run git");
+    assert!(output.status.success(), "git {args:?} failed");
+    String::from_utf8(output.stdout).unwrap().trim().to_string()
+}
+
+/// Lazily loading server with the opt-in git resources (blame, contributors, restore) on.
+fn create_git_server(temp_dir: &TempDir) -> TestServer {
+    let base_dir = temp_dir.path().to_path_buf();
+    let tracked_files = scan_markdown_files(&base_dir)
+        .expect("Failed to scan")
+        .files;
+    let config = RouterConfig {
+        lazy: true,
+        enable_git: true,
+        ..RouterConfig::default()
+    };
+    let router = new_router_with_config(base_dir, tracked_files, true, config)
+        .expect("Failed to create router");
+    TestServer::new(router).expect("Failed to create test server")
+}
+
+#[tokio::test]
+async fn test_api_file_history() {
+    let temp_dir = tempdir().expect("Failed to create temp dir");
+    let path = temp_dir.path().join("doc.md");
+    git(temp_dir.path(), &["init", "--quiet"]);
+    fs::write(&path, "# Doc\n").expect("Failed to write");
+    git(temp_dir.path(), &["add", "doc.md"]);
+    git(temp_dir.path(), &["commit", "--quiet", "-m", "Add doc"]);
+    fs::write(&path, "# Doc\n\nMore.\n").expect("Failed to write");
+    git(
+        temp_dir.path(),
+        &[
+            "commit",
+            "--quiet",
+            "-am",
+            "Fix \"quotes\" | pipes\n\nBody line",
+        ],
+    );
+    fs::write(temp_dir.path().join("new.md"), "# New\n").expect("Failed to write");
+    let server = create_lazy_server(&temp_dir);
+
+    let response = server.get("/api/files/doc.md/history").await;
+    assert_eq!(response.status_code(), 200);
+    let commits: Vec<serde_json::Value> = response.json();
+    let subjects: Vec<&str> = commits
+        .iter()
+        .map(|commit| commit["subject"].as_str().unwrap())
+        .collect();
+    assert_eq!(subjects, vec!["Fix \"quotes\" | pipes", "Add doc"]);
+    assert_eq!(commits[0]["author"], "Jane Doe");
+    assert_eq!(commits[0]["email"], "jane@example.com");
+    assert_eq!(commits[0]["hash"].as_str().unwrap().len(), 40);
+    assert!(commits[0]["date"].as_str().unwrap().contains('T'));
+
+    let body: Vec<serde_json::Value> = server.get("/api/files/doc.md/history?limit=1").await.json();
+    assert_eq!(body.len(), 1);
+    assert_eq!(body[0]["subject"], "Fix \"quotes\" | pipes");
+
+    // Not committed yet
+    let body: serde_json::Value = server.get("/api/files/new.md/history").await.json();
+    assert_eq!(body, serde_json::json!([]));
+
+    let response = server.get("/api/files/missing.md/history").await;
+    assert_eq!(response.status_code(), 404);
+}
+
+#[tokio::test]
+async fn test_api_file_blame() {
+    let temp_dir = tempdir().expect("Failed to create temp dir");
+    let path = temp_dir.path().join("doc.md");
+    git(temp_dir.path(), &["init", "--quiet"]);
+    fs::write(&path, "# Doc\n\nFirst.\n").expect("Failed to write");
+    git(temp_dir.path(), &["add", "doc.md"]);
+    git(temp_dir.path(), &["commit", "--quiet", "-m", "Add doc"]);
+    fs::write(&path, "# Doc\n\nFirst.\nSecond.\n").expect("Failed to write");
+    fs::write(temp_dir.path().join("new.md"), "# New\n").expect("Failed to write");
+    let server = create_git_server(&temp_dir);
+
+    let response = server.get("/api/files/doc.md/blame").await;
+    assert_eq!(response.status_code(), 200);
+    let blame: Vec<serde_json::Value> = response.json();
+    let lines: Vec<(u64, &str, &str)> = blame
+        .iter()
+        .map(|line| {
+            (
+                line["line"].as_u64().unwrap(),
+                line["content"].as_str().unwrap(),
+                line["author"].as_str().unwrap(),
+            )
+        })
+        .collect();
+    assert_eq!(
+        lines,
+        vec![
+            (1, "# Doc", "Jane Doe"),
+            (2, "", "Jane Doe"),
+            (3, "First.", "Jane Doe"),
+            (4, "Second.", "Not Committed Yet"),
+        ]
+    );
+    assert_eq!(blame[0]["commit"].as_str().unwrap().len(), 40);
+    assert_eq!(blame[0]["date"].as_str().unwrap().len(), "2024-01-15".len());
+
+    // Never committed
+    let response = server.get("/api/files/new.md/blame").await;
+    assert_eq!(response.status_code(), 501);
+}
+
+#[tokio::test]
+async fn test_api_file_contributors() {
+    let temp_dir = tempdir().expect("Failed to create temp dir");
+    let path = temp_dir.path().join("doc.md");
+    git(temp_dir.path(), &["init", "--quiet"]);
+    for (i, author) in ["jane", "john", "jane"].into_iter().enumerate() {
+        fs::write(&path, format!("# Doc {i}\n")).expect("Failed to write");
+        git(temp_dir.path(), &["add", "doc.md"]);
+        let email = format!("user.email={author}@example.com");
+        let name = format!("user.name={author}");
+        git(
+            temp_dir.path(),
+            &["-c", &name, "-c", &email, "commit", "--quiet", "-m", "Edit"],
+        );
+    }
+    let server = create_git_server(&temp_dir);
+
+    let response = server.get("/api/files/doc.md/contributors").await;
+    assert_eq!(response.status_code(), 200);
+    let contributors: Vec<serde_json::Value> = response.json();
+    let counts: Vec<(&str, &str, u64)> = contributors
+        .iter()
+        .map(|c| {
+            (
+                c["name"].as_str().unwrap(),
+                c["email"].as_str().unwrap(),
+                c["commits"].as_u64().unwrap(),
+            )
+        })
+        .collect();
+    assert_eq!(
+        counts,
+        vec![
+            ("jane", "jane@example.com", 2),
+            ("john", "john@example.com", 1)
+        ]
+    );
+    assert_eq!(contributors[0]["last_commit"].as_str().unwrap().len(), 10);
+
+    let body: Vec<serde_json::Value> = server
+        .get("/api/files/doc.md/contributors?limit=1")
+        .await
+        .json();
+    assert_eq!(body.len(), 1);
+    assert_eq!(body[0]["email"], "jane@example.com");
+}
+
+#[tokio::test]
+async fn test_api_file_restore() {
+    let temp_dir = tempdir().expect("Failed to create temp dir");
+    let path = temp_dir.path().join("doc.md");
+    git(temp_dir.path(), &["init", "--quiet"]);
+    fs::write(&path, "# Version 1\n").expect("Failed to write");
+    git(temp_dir.path(), &["add", "doc.md"]);
+    git(temp_dir.path(), &["commit", "--quiet", "-m", "First"]);
+    let first = git(temp_dir.path(), &["rev-parse", "HEAD"]);
+    fs::write(&path, "# Version 2\n").expect("Failed to write");
+    git(temp_dir.path(), &["commit", "--quiet", "-am", "Second"]);
+    fs::write(&path, "# Work in progress\n").expect("Failed to write");
+    let server = create_git_server(&temp_dir);
+
+    // Abbreviated hashes work too
+    let response = server
+        .get(&format!("/api/files/doc.md/restore/{}", &first[..8]))
+        .await;
+    assert_eq!(response.status_code(), 200);
+    assert_eq!(
+        response.json::<serde_json::Value>(),
+        serde_json::json!({ "markdown": "# Version 1\n", "hash": &first[..8] })
+    );
+    assert_eq!(fs::read_to_string(&path).unwrap(), "# Work in progress\n");
+
+    let response = server
+        .post(&format!("/api/files/doc.md/restore/{first}"))
+        .await;
+    assert_eq!(response.status_code(), 200);
+    assert_eq!(
+        response.json::<serde_json::Value>(),
+        serde_json::json!({ "markdown": "# Version 1\n", "hash": first })
+    );
+    assert_eq!(fs::read_to_string(&path).unwrap(), "# Version 1\n");
+    let body: serde_json::Value = server.get("/api/files/doc.md").await.json();
+    assert_eq!(body["markdown"], "# Version 1\n");
+}
+
+#[tokio::test]
+async fn test_api_file_restore_unknown_commit() {
+    let temp_dir = tempdir().expect("Failed to create temp dir");
+    let path = temp_dir.path().join("doc.md");
+    git(temp_dir.path(), &["init", "--quiet"]);
+    fs::write(&path, "# Doc\n").expect("Failed to write");
+    git(temp_dir.path(), &["add", "doc.md"]);
+    git(temp_dir.path(), &["commit", "--quiet", "-m", "Add doc"]);
+    let head = git(temp_dir.path(), &["rev-parse", "HEAD"]);
+    fs::write(temp_dir.path().join("new.md"), "# New\n").expect("Failed to write");
+    let server = create_git_server(&temp_dir);
+
+    for path in [
+        "/api/files/doc.md/restore/0123456789abcdef0123456789abcdef01234567".to_string(),
+        "/api/files/doc.md/restore/HEAD".to_string(),
+        "/api/files/doc.md/restore/--output=x".to_string(),
+        // The commit exists but doesn't contain the file
+        format!("/api/files/new.md/restore/{head}"),
+    ] {
+        assert_eq!(server.get(&path).await.status_code(), 404, "{path}");
+        assert_eq!(server.post(&path).await.status_code(), 404, "{path}");
+    }
+    assert_eq!(fs::read_to_string(&path).unwrap(), "# Doc\n");
+}
+
+#[tokio::test]
+async fn test_api_file_history_unavailable() {
+    let temp_dir = tempdir().expect("Failed to create temp dir");
+    fs::write(temp_dir.path().join("doc.md"), "# Doc\n").expect("Failed to write");
+    let server = create_lazy_server(&temp_dir);
+
+    let response = server.get("/api/files/doc.md/history").await;
+    assert_eq!(response.status_code(), 501);
+
+    for resource in ["blame", "contributors", "restore/HEAD"] {
+        let response = server.get(&format!("/api/files/doc.md/{resource}")).await;
+        assert_eq!(response.status_code(), 403, "{resource}");
+    }
+    let response = server.post("/api/files/doc.md/restore/HEAD").await;
+    assert_eq!(response.status_code(), 403);
+    assert_eq!(
+        response.json::<serde_json::Value>(),
+        serde_json::json!({ "error": "git is disabled; start mdserve with --enable-git" })
+    );
+    assert_eq!(
+        fs::read_to_string(temp_dir.path().join("doc.md")).unwrap(),
+        "# Doc\n"
+    );
+
+    git(temp_dir.path(), &["init", "--quiet"]);
+    let config = RouterConfig {
+        git_history: false,
+        ..RouterConfig::default()
+    };
+    let router = new_router_with_config(
+        temp_dir.path().to_path_buf(),
+        vec![temp_dir.path().join("doc.md")],
+        true,
+        config,
+    )
+    .expect("Failed to create router");
+    let server = TestServer::new(router).expect("Failed to create test server");
+
+    let response = server.get("/api/files/doc.md/history").await;
+    assert_eq!(response.status_code(), 501);
+    assert_eq!(
+        response.json::<serde_json::Value>(),
+        serde_json::json!({ "error": "git history is disabled" })
+    );
+}
+
+#[tokio::test]
+async fn test_options_lists_allowed_methods() {
+    let (server, _temp_dir) = create_directory_server().await;
+
+    for (path, allowed) in [
+        (
+            "/api/files/test1.md",
+            "GET, HEAD, POST, PUT, PATCH, OPTIONS",
+        ),
+        ("/api/files", "GET, HEAD, DELETE, OPTIONS"),
+        ("/api/tasks", "GET, HEAD, PATCH, OPTIONS"),
+        ("/ws", "GET, HEAD, OPTIONS"),
+    ] {
+        let response = server.method(Method::OPTIONS, path).await;
+        assert_eq!(response.status_code(), 204, "{path}");
+        assert_eq!(response.header("allow"), allowed, "{path}");
+
+        // The list comes from the handlers, so every method on it is routed
+        for method in allowed.split(", ").filter(|method| *method != "HEAD") {
+            let method = Method::from_bytes(method.as_bytes()).unwrap();
+            let response = server.method(method.clone(), path).await;
+            assert_ne!(response.status_code(), 405, "{method} {path}");
+        }
+    }
+
+    let response = server.delete("/api/files/test1.md").await;
+    assert_eq!(response.status_code(), 405);
+}
+
+#[tokio::test]
+async fn test_cors_preflight_lists_allowed_methods() {
+    let (server, _temp_dir) = create_directory_server().await;
+
+    let response = server
+        .method(Method::OPTIONS, "/api/files/test1.md")
+        .add_header("origin", "http://example.com")
+        .add_header("access-control-request-method", "PUT")
+        .await;
+
+    assert_eq!(response.status_code(), 200);
+    assert_eq!(response.header("access-control-allow-origin"), "*");
+    let methods = response.header("access-control-allow-methods");
+    assert_eq!(methods, "GET,HEAD,POST,PUT,PATCH,DELETE,OPTIONS");
+
+    let response = server
+        .get("/api/files")
+        .add_header("origin", "http://example.com")
+        .await;
+    assert_eq!(response.header("access-control-allow-origin"), "*");
+}
+
+#[tokio::test]
+async fn test_api_get_file_download_non_ascii_name() {
+    let temp_dir = tempdir().expect("Failed to create temp dir");
+    fs::write(temp_dir.path().join("résumé \"v2\".md"), "# CV\n").expect("Failed to write");
+    let server = create_lazy_server(&temp_dir);
+
+    let response = server
+        .get("/api/files/r%C3%A9sum%C3%A9%20%22v2%22.md")
+        .add_query_param("format", "raw")
+        .add_query_param("download", true)
+        .await;
+    assert_eq!(response.status_code(), 200);
+    assert_eq!(
+        response.header("content-disposition"),
+        "attachment; filename=\"r_sum_ \\\"v2\\\".md\"; filename*=UTF-8''r%C3%A9sum%C3%A9%20%22v2%22.md"
+    );
+}
+
+#[tokio::test]
+async fn test_api_get_file_raw_format() {
+    let (server, _temp_dir) = create_directory_server().await;
+
+    let response = server
+        .get("/api/files/test1.md")
+        .add_query_param("format", "raw")
+        .await;
+    assert_eq!(response.status_code(), 200);
+    assert_eq!(response.header("content-type"), "text/plain; charset=utf-8");
+    assert_eq!(
+        response.header("content-disposition"),
+        "inline; filename=\"test1.md\""
+    );
+    assert_eq!(response.text(), TEST_FILE_1_CONTENT);
+
+    let response = server
+        .get("/api/files/test1.md")
+        .add_header(
+            axum::http::HeaderName::from_static("accept"),
+            axum::http::HeaderValue::from_static("text/plain"),
+        )
+        .await;
+    assert_eq!(response.header("content-type"), "application/json");
+    let body: serde_json::Value = response.json();
+    assert_eq!(body["markdown"], TEST_FILE_1_CONTENT);
+
+    let response = server
+        .get("/api/files/test1.md")
+        .add_query_param("format", "yaml")
+        .await;
+    assert_eq!(response.status_code(), 400);
+}
+
+#[tokio::test]
+async fn test_api_get_file_download() {
+    let (server, _temp_dir) = create_directory_server().await;
+
+    let response = server
+        .get("/api/files/test1.md")
+        .add_query_param("download", "true")
+        .await;
+    assert_eq!(response.status_code(), 200);
+    assert_eq!(response.header("content-type"), "text/plain; charset=utf-8");
+    assert_eq!(
+        response.header("content-disposition"),
+        "attachment; filename=\"test1.md\""
+    );
+    assert_eq!(response.text(), TEST_FILE_1_CONTENT);
+
+    for query in [None, Some("false")] {
+        let mut request = server.get("/api/files/test1.md");
+        if let Some(download) = query {
+            request = request.add_query_param("download", download);
+        }
+        let response = request.await;
+        assert_eq!(response.header("content-type"), "application/json");
+        assert!(response.maybe_header("content-disposition").is_none());
+        let body: serde_json::Value = response.json();
+        assert_eq!(body["markdown"], TEST_FILE_1_CONTENT);
+    }
+}
+
+#[tokio::test]
+async fn test_api_file_extension_aliases() {
+    let (server, temp_dir) = create_directory_server().await;
+
+    // test2 is stored as .markdown, test1 as .md
+    let body: serde_json::Value = server.get("/api/files/test2.md").await.json();
+    assert_eq!(body["markdown"], TEST_FILE_2_CONTENT);
+    let body: serde_json::Value = server.get("/api/files/test1.markdown").await.json();
+    assert_eq!(body["markdown"], TEST_FILE_1_CONTENT);
+
+    let response = server
+        .get("/api/files/test1.markdown")
+        .add_query_param("format", "raw")
+        .await;
+    assert_eq!(
+        response.header("content-disposition"),
+        "inline; filename=\"test1.md\""
+    );
+
+    let body: serde_json::Value = server
+        .post("/api/files/bulk")
+        .json(&serde_json::json!({ "paths": ["test2.md", "test1.markdown"] }))
+        .await
+        .json();
+    assert_eq!(body["files"][0]["path"], "test2.md");
+    assert_eq!(body["files"][0]["markdown"], TEST_FILE_2_CONTENT);
+    assert_eq!(body["files"][1]["markdown"], TEST_FILE_1_CONTENT);
+
+    let response = server
+        .put("/api/files/test2.md")
+        .json(&serde_json::json!({ "markdown": "# Updated\n" }))
+        .await;
+    assert_eq!(response.status_code(), 200);
+    assert_eq!(
+        fs::read_to_string(temp_dir.path().join("test2.markdown")).unwrap(),
+        "# Updated\n"
+    );
+    assert!(!temp_dir.path().join("test2.md").exists());
+
+    // The POST resources resolve aliases too
+    let body: serde_json::Value = server
+        .post("/api/files/test2.md/diff")
+        .json(&serde_json::json!({ "markdown": "# Updated\n" }))
+        .await
+        .json();
+    assert_eq!(body["diff"], serde_json::json!([]));
+    let response = server
+        .post("/api/files/test2.md/validate")
+        .json(&serde_json::json!({ "markdown": "# Updated\n" }))
+        .await;
+    assert_eq!(response.status_code(), 200);
+    let response = server
+        .post("/api/files/test2.md/rename")
+        .json(&serde_json::json!({ "name": "renamed.markdown" }))
+        .await;
+    assert_eq!(response.status_code(), 200);
+    assert!(temp_dir.path().join("renamed.markdown").exists());
+
+    let response = server.get("/api/files/missing.markdown").await;
+    assert_eq!(response.status_code(), 404);
+}
+
+#[tokio::test]
+async fn test_api_get_file_streams_large_files() {
+    let temp_dir = tempdir().expect("Failed to create temp dir");
+    // Quotes, backslashes, newlines and multi-byte characters all need escaping, and
+    // some of them will straddle chunk boundaries
+    let line = "Line with \"quotes\", a \\ backslash, a tab\tand unicode: héllo wörld ✓\n";
+    // A single code block keeps rendering cheap in debug builds
+    let content = format!(
+        "```\n{}```\n",
+        line.repeat(2 * 1024 * 1024 / line.len() + 1)
+    );
+    fs::write(temp_dir.path().join("big.md"), &content).expect("Failed to write");
+
+    let base_dir = temp_dir.path().to_path_buf();
+    let tracked_files = scan_markdown_files(&base_dir)
+        .expect("Failed to scan")
+        .files;
+    let config = RouterConfig {
+        lazy: true,
+        ..RouterConfig::default()
+    };
+    let router = new_router_with_config(base_dir, tracked_files, true, config)
+        .expect("Failed to create router");
+    let server = TestServer::new(router).expect("Failed to create test server");
+
+    let response = server.get("/api/files/big.md").await;
+    assert_eq!(response.status_code(), 200);
+    assert_eq!(response.header("content-type"), "application/json");
+    let body: serde_json::Value = response.json();
+    assert_eq!(body["markdown"].as_str(), Some(content.as_str()));
+}
+
+// ===========================
+// Diff API Tests
+// ===========================
+
+async fn post_diff(server: &TestServer, markdown: &str) -> serde_json::Value {
+    let response = server
+        .post("/api/files/doc.md/diff")
+        .json(&serde_json::json!({ "markdown": markdown }))
+        .await;
+    assert_eq!(response.status_code(), 200);
+    response.json()
+}
+
+#[tokio::test]
+async fn test_api_diff_file() {
+    let temp_dir = tempdir().expect("Failed to create temp dir");
+    let original = "one\ntwo\nthree\n";
+    fs::write(temp_dir.path().join("doc.md"), original).expect("Failed to write");
+    let server = create_lazy_server(&temp_dir);
+
+    // Identical
+    let body = post_diff(&server, original).await;
+    assert_eq!(body, serde_json::json!({ "diff": [] }));
+
+    // Add only
+    let body = post_diff(&server, "one\ntwo\nthree\nfour\n").await;
+    assert_eq!(
+        body["diff"],
+        serde_json::json!([
+            {"kind": "equal", "lines": ["one", "two", "three"]},
+            {"kind": "add", "lines": ["four"]},
+        ])
+    );
+
+    // Remove only
+    let body = post_diff(&server, "one\nthree\n").await;
+    assert_eq!(
+        body["diff"],
+        serde_json::json!([
+            {"kind": "equal", "lines": ["one"]},
+            {"kind": "remove", "lines": ["two"]},
+            {"kind": "equal", "lines": ["three"]},
+        ])
+    );
+
+    // Mixed
+    let body = post_diff(&server, "zero\none\nTWO\nthree\n").await;
+    assert_eq!(
+        body["diff"],
+        serde_json::json!([
+            {"kind": "add", "lines": ["zero"]},
+            {"kind": "equal", "lines": ["one"]},
+            {"kind": "remove", "lines": ["two"]},
+            {"kind": "add", "lines": ["TWO"]},
+            {"kind": "equal", "lines": ["three"]},
+        ])
+    );
+
+    // Read-only
+    assert_eq!(
+        fs::read_to_string(temp_dir.path().join("doc.md")).unwrap(),
+        original
+    );
+
+    let response = server
+        .post("/api/files/missing.md/diff")
+        .json(&serde_json::json!({ "markdown": "" }))
+        .await;
+    assert_eq!(response.status_code(), 404);
+}
+
+#[tokio::test]
+async fn test_api_get_file_decodes_non_ascii_paths() {
+    let temp_dir = tempdir().expect("Failed to create temp dir");
+    fs::create_dir(temp_dir.path().join("guía")).expect("Failed to create dir");
+    fs::write(temp_dir.path().join("guía/über docs.md"), "# Über").expect("Failed to write");
+    let server = create_lazy_server(&temp_dir);
+
+    let response = server.get("/api/files/gu%C3%ADa/%C3%BCber%20docs.md").await;
+    assert_eq!(response.status_code(), 200);
+    let body: serde_json::Value = response.json();
+    assert_eq!(body["markdown"], "# Über");
+
+    let response = server
+        .get("/api/files/gu%C3%ADa/%C3%BCber%20docs.md/anchors")
+        .await;
+    assert_eq!(response.status_code(), 200);
+
+    // An encoded slash must not act as a path separator
+    let response = server
+        .get("/api/files/gu%C3%ADa%2F%C3%BCber%20docs.md")
+        .await;
+    assert_eq!(response.status_code(), 400);
+    let response = server
+        .get("/api/files/gu%C3%ADa%5C%C3%BCber%20docs.md")
+        .await;
+    assert_eq!(response.status_code(), 400);
+}
+
+// ===========================
+// Duplicate Check API Tests
+// ===========================
+
+const SHARED_PARAGRAPH: &str =
+    "Install the server with cargo and point it at the directory holding your notes.";
+
+#[tokio::test]
+async fn test_api_duplicate_check() {
+    let temp_dir = tempdir().expect("Failed to create temp dir");
+    let files = [
+        (
+            "guide.md",
+            format!("# Guide\n\n{SHARED_PARAGRAPH}\n\nThen open the browser and start reading the rendered pages.\n"),
+        ),
+        ("copy.md", format!("# Copy\n\n{SHARED_PARAGRAPH}\n")),
+        (
+            "edited.md",
+            "# Edited\n\nInstall the server with cargo and point it at the folder holding your notes.\n"
+                .to_string(),
+        ),
+        (
+            "other.md",
+            "# Other\n\nNothing in this file resembles the guide in any way at all.\n".to_string(),
+        ),
+    ];
+    for (name, content) in &files {
+        fs::write(temp_dir.path().join(name), content).expect("Failed to write");
+    }
+    let server = create_lazy_server(&temp_dir);
+
+    // No body uses the default threshold of 0.8
+    let response = server.post("/api/files/guide.md/duplicate-check").await;
+    assert_eq!(response.status_code(), 200);
+    assert_eq!(
+        response.json::<serde_json::Value>(),
+        serde_json::json!([
+            {"path": "copy.md", "similarity": 1.0, "matching_sections": 1},
+            {"path": "edited.md", "similarity": 0.86, "matching_sections": 1},
+        ])
+    );
+
+    let body: serde_json::Value = server
+        .post("/api/files/guide.md/duplicate-check")
+        .json(&serde_json::json!({ "threshold": 0.95 }))
+        .await
+        .json();
+    assert_eq!(
+        body,
+        serde_json::json!([{"path": "copy.md", "similarity": 1.0, "matching_sections": 1}])
+    );
+
+    // Read-only
+    assert_eq!(
+        fs::read_to_string(temp_dir.path().join("copy.md")).unwrap(),
+        files[1].1
+    );
+}
+
+#[tokio::test]
+async fn test_api_duplicate_check_errors() {
+    let temp_dir = tempdir().expect("Failed to create temp dir");
+    fs::write(temp_dir.path().join("doc.md"), "# Doc").expect("Failed to write");
+    let server = create_lazy_server(&temp_dir);
+
+    let response = server.post("/api/files/missing.md/duplicate-check").await;
+    assert_eq!(response.status_code(), 404);
+
+    let response = server
+        .post("/api/files/doc.md/duplicate-check")
+        .json(&serde_json::json!({ "threshold": 1.5 }))
+        .await;
+    assert_eq!(response.status_code(), 400);
+
+    let body: serde_json::Value = server
+        .post("/api/files/doc.md/duplicate-check")
+        .await
+        .json();
+    assert_eq!(body, serde_json::json!([]));
+}
+
+#[tokio::test]
+async fn test_api_preview_with_variables() {
+    let temp_dir = tempdir().expect("Failed to create temp dir");
+    let template = "# {{product}} {{version}}\n\nInstall {{ product }} with `{{missing}}`.\n";
+    fs::write(temp_dir.path().join("release.md"), template).expect("Failed to write");
+    let server = create_lazy_server(&temp_dir);
+
+    let response = server
+        .post("/api/files/release.md/preview")
+        .json(&serde_json::json!({
+            "variables": { "version": "1.2.3", "product": "<Acme>" }
+        }))
+        .await;
+    assert_eq!(response.status_code(), 200);
+    let body: serde_json::Value = response.json();
+    assert_eq!(
+        body["html"],
+        "<h1>&lt;Acme&gt; 1.2.3</h1>\n<p>Install &lt;Acme&gt; with <code>{{missing}}</code>.</p>\n"
+    );
+
+    // Nothing is written, and without variables the placeholders stay
+    assert_eq!(
+        fs::read_to_string(temp_dir.path().join("release.md")).unwrap(),
+        template
+    );
+    let body: serde_json::Value = server.post("/api/files/release.md/preview").await.json();
+    assert!(body["html"]
+        .as_str()
+        .unwrap()
+        .starts_with("<h1>{{product}} {{version}}</h1>"));
+
+    let response = server
+        .post("/api/files/release.md/preview")
+        .json(&serde_json::json!({ "variables": { "version": 1 } }))
+        .await;
+    assert_eq!(response.status_code(), 400);
+
+    let response = server.post("/api/files/missing.md/preview").await;
+    assert_eq!(response.status_code(), 404);
+}
+
+// ===========================
+// Export API Tests
+// ===========================
+
+// A 1x1 transparent PNG
+const PIXEL_PNG: &[u8] = &[
+    0x89, 0x50, 0x4e, 0x47, 0x0d, 0x0a, 0x1a, 0x0a, 0x00, 0x00, 0x00, 0x0d, 0x49, 0x48, 0x44, 0x52,
+    0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x01, 0x08, 0x06, 0x00, 0x00, 0x00, 0x1f, 0x15, 0xc4,
+    0x89, 0x00, 0x00, 0x00, 0x0d, 0x49, 0x44, 0x41, 0x54, 0x78, 0x9c, 0x63, 0x00, 0x01, 0x00, 0x00,
+    0x05, 0x00, 0x01, 0x0d, 0x0a, 0x2d, 0xb4, 0x00, 0x00, 0x00, 0x00, 0x49, 0x45, 0x4e, 0x44, 0xae,
+    0x42, 0x60, 0x82,
+];
+
+#[tokio::test]
+async fn test_api_export_html_is_standalone_html5() {
+    let temp_dir = tempdir().expect("Failed to create temp dir");
+    fs::write(
+        temp_dir.path().join("doc.md"),
+        "# Release <Notes>\n\nSome **bold** text.\n",
+    )
+    .expect("Failed to write");
+    let server = create_lazy_server(&temp_dir);
+
+    let response = server.get("/api/files/doc.md/export/html").await;
+    assert_eq!(response.status_code(), 200);
+    assert_eq!(response.header("content-type"), "text/html; charset=utf-8");
+
+    let html = response.text();
+    assert!(
+        html.starts_with("<!DOCTYPE html>\n<html lang=\"en\">\n<head>\n<meta charset=\"utf-8\">")
+    );
+    assert!(html.contains("<title>Release &lt;Notes&gt;</title>"));
+    assert!(html.contains("<style>\nbody {"));
+    assert!(html.contains("<strong>bold</strong>"));
+    assert!(html.trim_end().ends_with("</body>\n</html>"));
+    for element in ["html", "head", "title", "style", "body", "main"] {
+        assert_eq!(
+            html.matches(&format!("<{element}")).count(),
+            html.matches(&format!("</{element}>")).count(),
+            "unbalanced <{element}>"
+        );
+    }
+    // Nothing is loaded from the server
+    assert!(!html.contains("<link"));
+    assert!(!html.contains("<script"));
+}
+
+#[tokio::test]
+async fn test_api_export_html_inlines_local_images() {
+    let temp_dir = tempdir().expect("Failed to create temp dir");
+    fs::create_dir_all(temp_dir.path().join("guide/img")).expect("Failed to create dir");
+    fs::write(temp_dir.path().join("guide/img/pixel.png"), PIXEL_PNG).expect("Failed to write");
+    fs::write(temp_dir.path().join("logo.svg"), "<svg/>").expect("Failed to write");
+    fs::write(
+        temp_dir.path().join("guide/intro.md"),
+        "![Pixel](img/pixel.png)\n\n\
+         <img src=\"/logo.svg\" alt=\"Logo\">\n\n\
+         ![Remote](https://example.com/remote.png)\n\n\
+         ![Missing](missing.png)\n",
+    )
+    .expect("Failed to write");
+    let server = create_lazy_server(&temp_dir);
+
+    let html = server
+        .get("/api/files/guide/intro.md/export/html")
+        .await
+        .text();
+
+    let pixel = format!(
+        "src=\"data:image/png;base64,{}\"",
+        STANDARD.encode(PIXEL_PNG)
+    );
+    assert!(html.contains(&pixel), "{html}");
+    assert!(html.contains("src=\"data:image/svg+xml;base64,PHN2Zy8+\""));
+    assert!(html.contains("src=\"https://example.com/remote.png\""));
+    assert!(html.contains("src=\"missing.png\""));
+    // Falls back to the file name without a heading
+    assert!(html.contains("<title>intro.md</title>"));
+}
+
+#[tokio::test]
+async fn test_api_export_html_custom_stylesheet() {
+    let temp_dir = tempdir().expect("Failed to create temp dir");
+    fs::write(temp_dir.path().join("doc.md"), "# Doc").expect("Failed to write");
+    let css_path = temp_dir.path().join("export.css");
+    fs::write(&css_path, "body { color: rebeccapurple; }\n").expect("Failed to write");
+    let config = RouterConfig {
+        export_css: Some(css_path.clone()),
+        ..RouterConfig::default()
+    };
+    let router = new_router_with_config(
+        temp_dir.path().to_path_buf(),
+        vec![temp_dir.path().join("doc.md")],
+        true,
+        config,
+    )
+    .expect("Failed to create router");
+    let server = TestServer::new(router).expect("Failed to create test server");
+
+    let html = server.get("/api/files/doc.md/export/html").await.text();
+    assert!(html.contains("<style>\nbody { color: rebeccapurple; }\n</style>"));
+
+    fs::remove_file(&css_path).expect("Failed to remove");
+    let response = server.get("/api/files/doc.md/export/html").await;
+    assert_eq!(response.status_code(), 500);
+
+    let response = server.get("/api/files/missing.md/export/html").await;
+    assert_eq!(response.status_code(), 404);
+}
+
+/// The names and contents of the entries of a ZIP archive, in archive order.
+fn zip_entries(archive: &[u8]) -> Vec<(String, Vec<u8>)> {
+    use std::io::Read;
+
+    let mut archive =
+        zip::ZipArchive::new(std::io::Cursor::new(archive)).expect("Invalid ZIP archive");
+    (0..archive.len())
+        .map(|i| {
+            let mut entry = archive.by_index(i).expect("Failed to read entry");
+            let mut contents = Vec::new();
+            entry
+                .read_to_end(&mut contents)
+                .expect("Failed to read entry");
+            (entry.name().to_string(), contents)
+        })
+        .collect()
+}
+
+#[tokio::test]
+async fn test_api_export_zip_without_images() {
+    let temp_dir = tempdir().expect("Failed to create temp dir");
+    let markdown = "# Plain\n\nNo pictures, only a [link](other.md).\n";
+    fs::write(temp_dir.path().join("doc.md"), markdown).expect("Failed to write");
+    let server = create_lazy_server(&temp_dir);
+
+    let response = server.get("/api/files/doc.md/export/zip").await;
+    assert_eq!(response.status_code(), 200);
+    assert_eq!(response.header("content-type"), "application/zip");
+    assert_eq!(
+        response.header("content-disposition"),
+        "attachment; filename=\"doc.zip\""
+    );
+    assert_eq!(
+        zip_entries(response.as_bytes()),
+        vec![("doc.md".to_string(), markdown.as_bytes().to_vec())]
+    );
+}
+
+#[tokio::test]
+async fn test_api_export_zip_with_one_image() {
+    let temp_dir = tempdir().expect("Failed to create temp dir");
+    fs::create_dir_all(temp_dir.path().join("images")).expect("Failed to create dir");
+    fs::create_dir_all(temp_dir.path().join("guide")).expect("Failed to create dir");
+    fs::write(temp_dir.path().join("images/logo.png"), PIXEL_PNG).expect("Failed to write");
+    fs::write(
+        temp_dir.path().join("guide/intro.md"),
+        "# Intro\n\n![Logo](../images/logo.png \"Our logo\")\n",
+    )
+    .expect("Failed to write");
+    let server = create_lazy_server(&temp_dir);
+
+    let response = server.get("/api/files/guide/intro.md/export/zip").await;
+    assert_eq!(response.status_code(), 200);
+    assert_eq!(
+        response.header("content-disposition"),
+        "attachment; filename=\"intro.zip\""
+    );
+    assert_eq!(
+        zip_entries(response.as_bytes()),
+        vec![
+            (
+                "intro.md".to_string(),
+                b"# Intro\n\n![Logo](images/logo.png \"Our logo\")\n".to_vec()
+            ),
+            ("images/logo.png".to_string(), PIXEL_PNG.to_vec()),
+        ]
+    );
+}
+
+#[tokio::test]
+async fn test_api_export_zip_with_three_images() {
+    let temp_dir = tempdir().expect("Failed to create temp dir");
+    for dir in ["a", "b"] {
+        fs::create_dir_all(temp_dir.path().join(dir)).expect("Failed to create dir");
+    }
+    fs::write(temp_dir.path().join("logo.svg"), "<svg/>").expect("Failed to write");
+    fs::write(temp_dir.path().join("a/pic.png"), PIXEL_PNG).expect("Failed to write");
+    fs::write(temp_dir.path().join("b/pic.png"), b"other").expect("Failed to write");
+    fs::write(
+        temp_dir.path().join("doc.md"),
+        "![First](a/pic.png)\n\n\
+         ![Again](a/pic.png) ![Second][second]\n\n\
+         <img src=\"/logo.svg\" alt=\"Logo\">\n\n\
+         ![Remote](https://example.com/remote.png) ![Missing](missing.png)\n\n\
+         [second]: <b/pic.png>\n",
+    )
+    .expect("Failed to write");
+    let server = create_lazy_server(&temp_dir);
+
+    let response = server.get("/api/files/doc.md/export/zip").await;
+    assert_eq!(response.status_code(), 200);
+    let entries = zip_entries(response.as_bytes());
+    assert_eq!(
+        String::from_utf8(entries[0].1.clone()).unwrap(),
+        "![First](images/pic.png)\n\n\
+         ![Again](images/pic.png) ![Second][second]\n\n\
+         <img src=\"images/logo.svg\" alt=\"Logo\">\n\n\
+         ![Remote](https://example.com/remote.png) ![Missing](missing.png)\n\n\
+         [second]: <images/pic-2.png>\n"
+    );
+    let images: Vec<(&str, &[u8])> = entries[1..]
+        .iter()
+        .map(|(name, contents)| (name.as_str(), contents.as_slice()))
+        .collect();
+    assert_eq!(
+        images,
+        vec![
+            ("images/pic.png", PIXEL_PNG),
+            ("images/logo.svg", b"<svg/>".as_slice()),
+            ("images/pic-2.png", b"other".as_slice()),
+        ]
+    );
+}
+
+fn create_pandoc_server(temp_dir: &TempDir, pandoc_path: std::path::PathBuf) -> TestServer {
+    let config = RouterConfig {
+        pandoc_path: Some(pandoc_path),
+        ..RouterConfig::default()
+    };
+    let router = new_router_with_config(
+        temp_dir.path().to_path_buf(),
+        vec![temp_dir.path().join("guide/doc.md")],
+        true,
+        config,
+    )
+    .expect("Failed to create router");
+    TestServer::new(router).expect("Failed to create test server")
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn test_api_export_pdf_runs_pandoc() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let temp_dir = tempdir().expect("Failed to create temp dir");
+    fs::create_dir_all(temp_dir.path().join("guide")).expect("Failed to create dir");
+    fs::write(temp_dir.path().join("guide/doc.md"), "# Doc\n").expect("Failed to write");
+    // Stands in for `pandoc --from=markdown --to=pdf -o <output> <input>`, recording the
+    // directory it ran in
+    let pandoc = temp_dir.path().join("pandoc");
+    fs::write(
+        &pandoc,
+        "#!/bin/sh\n{ printf '%%PDF-stub\\n'; cat \"$5\"; pwd; } > \"$4\"\n",
+    )
+    .expect("Failed to write");
+    fs::set_permissions(&pandoc, fs::Permissions::from_mode(0o755)).expect("Failed to chmod");
+    let server = create_pandoc_server(&temp_dir, pandoc);
+
+    let response = server.get("/api/files/guide/doc.md/export/pdf").await;
+    assert_eq!(response.status_code(), 200);
+    assert_eq!(response.header("content-type"), "application/pdf");
+    assert_eq!(
+        response.header("content-disposition"),
+        "inline; filename=\"doc.pdf\""
+    );
+    let guide_dir = temp_dir.path().join("guide").canonicalize().unwrap();
+    assert_eq!(
+        response.as_bytes().as_ref(),
+        format!("%PDF-stub\n# Doc\n{}\n", guide_dir.display()).as_bytes()
+    );
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn test_api_export_pdf_reports_pandoc_failure() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let temp_dir = tempdir().expect("Failed to create temp dir");
+    fs::create_dir_all(temp_dir.path().join("guide")).expect("Failed to create dir");
+    fs::write(temp_dir.path().join("guide/doc.md"), "# Doc\n").expect("Failed to write");
+    let pandoc = temp_dir.path().join("pandoc");
+    fs::write(
+        &pandoc,
+        "#!/bin/sh\necho 'pdflatex not found' >&2\nexit 47\n",
+    )
+    .expect("Failed to write");
+    fs::set_permissions(&pandoc, fs::Permissions::from_mode(0o755)).expect("Failed to chmod");
+    let server = create_pandoc_server(&temp_dir, pandoc);
+
+    let response = server.get("/api/files/guide/doc.md/export/pdf").await;
+    assert_eq!(response.status_code(), 500);
+    let error = response.json::<serde_json::Value>()["error"]
+        .as_str()
+        .unwrap()
+        .to_string();
+    assert!(error.contains("pdflatex not found"), "{error}");
+}
+
+#[tokio::test]
+async fn test_api_export_pdf_without_pandoc() {
+    let temp_dir = tempdir().expect("Failed to create temp dir");
+    fs::create_dir_all(temp_dir.path().join("guide")).expect("Failed to create dir");
+    fs::write(temp_dir.path().join("guide/doc.md"), "# Doc\n").expect("Failed to write");
+    let server = create_pandoc_server(&temp_dir, temp_dir.path().join("no-pandoc-here"));
+
+    let response = server.get("/api/files/guide/doc.md/export/pdf").await;
+    assert_eq!(response.status_code(), 501);
+    assert_eq!(
+        response.json::<serde_json::Value>(),
+        serde_json::json!({ "error": "pandoc not installed" })
+    );
+
+    let response = server.get("/api/files/missing.md/export/pdf").await;
+    assert_eq!(response.status_code(), 404);
+}
+
+// ===========================
+// Bulk File API Tests
+// ===========================
+
+#[tokio::test]
+async fn test_api_bulk_files_all_found() {
+    let (server, _temp_dir) = create_directory_server().await;
+
+    let response = server
+        .post("/api/files/bulk")
+        .json(&serde_json::json!({ "paths": ["test3.md", "test1.md"] }))
+        .await;
+    assert_eq!(response.status_code(), 200);
+    let body: serde_json::Value = response.json();
+    let files = body["files"].as_array().unwrap();
+    assert_eq!(files.len(), 2);
+    assert_eq!(files[0]["path"], "test3.md");
+    assert_eq!(files[0]["markdown"], TEST_FILE_3_CONTENT);
+    assert_eq!(files[1]["path"], "test1.md");
+    assert_eq!(files[1]["markdown"], TEST_FILE_1_CONTENT);
+    assert_eq!(
+        files[1]["hash"],
+        format!("{:x}", md5::compute(TEST_FILE_1_CONTENT))
+    );
+}
+
+#[tokio::test]
+async fn test_api_bulk_files_some_missing() {
+    let (server, _temp_dir) = create_directory_server().await;
+
+    let response = server
+        .post("/api/files/bulk")
+        .json(&serde_json::json!({ "paths": ["test1.md", "missing.md"] }))
+        .await;
+    assert_eq!(response.status_code(), 200);
+    let body: serde_json::Value = response.json();
+    assert_eq!(body["files"][0]["markdown"], TEST_FILE_1_CONTENT);
+    assert_eq!(
+        body["files"][1],
+        serde_json::json!({ "path": "missing.md", "error": "not found" })
+    );
+}
+
+#[tokio::test]
+async fn test_api_bulk_files_rejects_too_many_paths() {
+    let (server, _temp_dir) = create_directory_server().await;
+
+    let paths: Vec<_> = (0..51).map(|i| format!("file{i}.md")).collect();
+    let response = server
+        .post("/api/files/bulk")
+        .json(&serde_json::json!({ "paths": paths }))
+        .await;
+    assert_eq!(response.status_code(), 400);
+}
+
+fn create_bulk_delete_server(allow_bulk_delete: bool) -> (TestServer, TempDir) {
+    let temp_dir = tempdir().expect("Failed to create temp dir");
+    fs::create_dir(temp_dir.path().join("old")).expect("Failed to create dir");
+    for file in ["old/a.md", "old/b.md", "keep.md"] {
+        fs::write(temp_dir.path().join(file), "# Doc").expect("Failed to write");
+    }
+    fs::write(temp_dir.path().join(".mdindex"), "old\nkeep.md\n").expect("Failed to write");
+    fs::write(temp_dir.path().join("old/.mdindex"), "b.md\na.md\n").expect("Failed to write");
+
+    let base_dir = temp_dir.path().to_path_buf();
+    let tracked_files = scan_markdown_files(&base_dir)
+        .expect("Failed to scan")
+        .files;
+    let config = RouterConfig {
+        allow_bulk_delete,
+        ..RouterConfig::default()
+    };
+    let router = new_router_with_config(base_dir, tracked_files, true, config)
+        .expect("Failed to create router");
+    let server = TestServer::builder()
+        .http_transport()
+        .build(router)
+        .expect("Failed to create test server");
+    (server, temp_dir)
+}
+
+#[tokio::test]
+async fn test_api_bulk_delete_partial_success() {
+    let (server, temp_dir) = create_bulk_delete_server(true);
+    let mut websocket = connect_websocket(&server).await;
+
+    let response = server
+        .delete("/api/files")
+        .json(&serde_json::json!({ "paths": ["old/a.md", "old/missing.md", "old/b.md"] }))
+        .await;
+    assert_eq!(response.status_code(), 207);
+    let body: serde_json::Value = response.json();
+    assert_eq!(
+        body,
+        serde_json::json!([
+            {"path": "old/a.md", "status": 204},
+            {"path": "old/missing.md", "status": 404},
+            {"path": "old/b.md", "status": 204},
+        ])
+    );
+    assert!(!temp_dir.path().join("old/a.md").exists());
+    assert!(!temp_dir.path().join("old/b.md").exists());
+
+    let body: serde_json::Value = server.get("/api/files").await.json();
+    assert_eq!(
+        body["files"],
+        serde_json::json!([{"path": "keep.md", "directory": ""}])
+    );
+
+    // One reload for the whole batch, and nothing more once the watcher catches up
+    assert_eq!(receive_value(&mut websocket).await["type"], "Reload");
+    let extra = tokio::time::timeout(
+        Duration::from_secs(1),
+        websocket.receive_json::<serde_json::Value>(),
+    )
+    .await;
+    assert!(extra.is_err(), "Unexpected message: {extra:?}");
+}
+
+#[tokio::test]
+async fn test_api_bulk_delete_updates_neighbors() {
+    let (server, _temp_dir) = create_bulk_delete_server(true);
+    let neighbors = |path: &str| server.get(&format!("/api/files/{path}/neighbors"));
+
+    let body: serde_json::Value = neighbors("old/a.md").await.json();
+    assert_eq!(
+        body,
+        serde_json::json!({ "previous": "old/b.md", "next": "keep.md" })
+    );
+
+    let response = server
+        .delete("/api/files")
+        .json(&serde_json::json!({ "paths": ["old/a.md"] }))
+        .await;
+    assert_eq!(response.status_code(), 207);
+
+    let body: serde_json::Value = neighbors("old/b.md").await.json();
+    assert_eq!(
+        body,
+        serde_json::json!({ "previous": null, "next": "keep.md" })
+    );
+    let body: serde_json::Value = neighbors("keep.md").await.json();
+    assert_eq!(
+        body,
+        serde_json::json!({ "previous": "old/b.md", "next": null })
+    );
+    assert_eq!(neighbors("old/a.md").await.status_code(), 404);
+}
+
+#[tokio::test]
+async fn test_api_bulk_delete_requires_flag() {
+    let (server, temp_dir) = create_bulk_delete_server(false);
+
+    let response = server
+        .delete("/api/files")
+        .json(&serde_json::json!({ "paths": ["old/a.md"] }))
+        .await;
+    assert_eq!(response.status_code(), 403);
+    assert!(temp_dir.path().join("old/a.md").exists());
+}
+
+// ===========================
+// File Update API Tests
+// ===========================
+
+#[tokio::test]
+async fn test_api_update_file() {
+    let temp_dir = tempdir().expect("Failed to create temp dir");
+    let path = temp_dir.path().join("post.md");
+    fs::write(&path, "# Old\n").expect("Failed to write");
+    let server = create_lazy_server(&temp_dir);
+
+    let response = server
+        .put("/api/files/post.md")
+        .json(&serde_json::json!({ "markdown": "# New\n" }))
+        .await;
+    assert_eq!(response.status_code(), 200);
+    let body: serde_json::Value = response.json();
+    assert_eq!(body, serde_json::json!({ "markdown": "# New\n" }));
+    assert_eq!(fs::read_to_string(&path).unwrap(), "# New\n");
+    assert!(server.get("/post.md").await.text().contains("<h1>New</h1>"));
+
+    let response = server
+        .put("/api/files/missing.md")
+        .json(&serde_json::json!({ "markdown": "# Missing\n" }))
+        .await;
+    assert_eq!(response.status_code(), 404);
+}
+
+#[tokio::test]
+async fn test_api_update_file_idempotency_key() {
+    let temp_dir = tempdir().expect("Failed to create temp dir");
+    let path = temp_dir.path().join("post.md");
+    fs::write(&path, "# Old\n").expect("Failed to write");
+    let server = create_lazy_server(&temp_dir);
+
+    let put = |markdown: &str, key: &str| {
+        server
+            .put("/api/files/post.md")
+            .add_header("idempotency-key", key.to_string())
+            .json(&serde_json::json!({ "markdown": markdown }))
+    };
+
+    let first = put("# Saved\n", "save-1").await;
+    assert_eq!(first.status_code(), 200);
+    assert!(first.maybe_header("idempotent-replayed").is_none());
+
+    // Edited elsewhere before the client retries
+    fs::write(&path, "# Edited meanwhile\n").expect("Failed to write");
+
+    let retry = put("# Saved\n", "save-1").await;
+    assert_eq!(retry.status_code(), 200);
+    assert_eq!(retry.header("idempotent-replayed"), "true");
+    assert_eq!(retry.text(), first.text());
+    assert_eq!(fs::read_to_string(&path).unwrap(), "# Edited meanwhile\n");
+
+    let response = put("# Saved again\n", "save-2").await;
+    assert_eq!(response.status_code(), 200);
+    assert_eq!(fs::read_to_string(&path).unwrap(), "# Saved again\n");
+}
+
+#[tokio::test]
+async fn test_api_update_file_idempotency_key_reused_on_other_path() {
+    let temp_dir = tempdir().expect("Failed to create temp dir");
+    fs::write(temp_dir.path().join("a.md"), "# A\n").expect("Failed to write");
+    fs::write(temp_dir.path().join("b.md"), "# B\n").expect("Failed to write");
+    let server = create_lazy_server(&temp_dir);
+
+    let put = |path: &str| {
+        server
+            .put(&format!("/api/files/{path}"))
+            .add_header("idempotency-key", "save-1")
+            .json(&serde_json::json!({ "markdown": "# Saved\n" }))
+    };
+
+    assert_eq!(put("a.md").await.status_code(), 200);
+
+    let response = put("b.md").await;
+    assert_eq!(response.status_code(), 422);
+    assert!(response.maybe_header("idempotent-replayed").is_none());
+    assert_eq!(
+        fs::read_to_string(temp_dir.path().join("b.md")).unwrap(),
+        "# B\n"
+    );
+
+    // The same file under another resource is a different request too
+    let response = server
+        .put("/api/files/a.md/frontmatter")
+        .add_header("idempotency-key", "save-1")
+        .json(&serde_json::json!({ "frontmatter": { "title": "A" } }))
+        .await;
+    assert_eq!(response.status_code(), 422);
+}
+
+#[tokio::test]
+async fn test_api_update_file_idempotency_key_reused_with_other_body() {
+    let temp_dir = tempdir().expect("Failed to create temp dir");
+    let path = temp_dir.path().join("post.md");
+    fs::write(&path, "# Old\n").expect("Failed to write");
+    let server = create_lazy_server(&temp_dir);
+
+    let put = |markdown: &str| {
+        server
+            .put("/api/files/post.md")
+            .add_header("idempotency-key", "save-1")
+            .json(&serde_json::json!({ "markdown": markdown }))
+    };
+
+    assert_eq!(put("# Saved\n").await.status_code(), 200);
+
+    let response = put("# Something else\n").await;
+    assert_eq!(response.status_code(), 422);
+    assert_eq!(fs::read_to_string(&path).unwrap(), "# Saved\n");
+
+    // The original request still replays
+    let retry = put("# Saved\n").await;
+    assert_eq!(retry.status_code(), 200);
+    assert_eq!(retry.header("idempotent-replayed"), "true");
+}
+
+// ===========================
+// Frontmatter API Tests
+// ===========================
+
+#[tokio::test]
+async fn test_api_update_frontmatter_replaces_existing_block() {
+    let temp_dir = tempdir().expect("Failed to create temp dir");
+    let path = temp_dir.path().join("post.md");
+    fs::write(
+        &path,
+        "---\ntitle: Draft\nauthor: me\n---\n# Post\n\nBody\n",
+    )
+    .expect("Failed to write");
+    let server = create_lazy_server(&temp_dir);
+
+    let response = server
+        .put("/api/files/post.md/frontmatter")
+        .json(&serde_json::json!({ "frontmatter": { "title": "Published", "tags": ["a", "b"] } }))
+        .await;
+    assert_eq!(response.status_code(), 200);
+
+    // JSON objects arrive with their keys sorted
+    let expected = "---\ntags:\n- a\n- b\ntitle: Published\n---\n# Post\n\nBody\n";
+    let body: serde_json::Value = response.json();
+    assert_eq!(body["markdown"], expected);
+    assert_eq!(fs::read_to_string(&path).unwrap(), expected);
+}
+
+#[tokio::test]
+async fn test_api_update_frontmatter_adds_missing_block() {
+    let temp_dir = tempdir().expect("Failed to create temp dir");
+    let path = temp_dir.path().join("post.md");
+    fs::write(&path, "# Post\n").expect("Failed to write");
+    let server = create_lazy_server(&temp_dir);
+
+    let response = server
+        .put("/api/files/post.md/frontmatter")
+        .json(&serde_json::json!({ "frontmatter": { "title": "Post" } }))
+        .await;
+    assert_eq!(response.status_code(), 200);
+    assert_eq!(
+        fs::read_to_string(&path).unwrap(),
+        "---\ntitle: Post\n---\n# Post\n"
+    );
+
+    // The rendered page keeps the frontmatter out of the body
+    let response = server.get("/post.md").await;
+    assert!(!response.text().contains("title: Post"));
+
+    let response = server
+        .put("/api/files/missing.md/frontmatter")
+        .json(&serde_json::json!({ "frontmatter": { "title": "Missing" } }))
+        .await;
+    assert_eq!(response.status_code(), 404);
+}
+
+async fn patch_frontmatter(
+    server: &TestServer,
+    path: &str,
+    patch: serde_json::Value,
+) -> axum_test::TestResponse {
+    server
+        .patch(path)
+        .json(&patch)
+        .content_type("application/json-patch+json")
+        .await
+}
+
+#[tokio::test]
+async fn test_api_patch_frontmatter_add_replace_remove() {
+    let temp_dir = tempdir().expect("Failed to create temp dir");
+    let path = temp_dir.path().join("post.md");
+    fs::write(
+        &path,
+        "---\ntitle: Draft\ntags:\n- a\ndraft: true\n---\n# Post\n\nBody\n",
+    )
+    .expect("Failed to write");
+    let server = create_lazy_server(&temp_dir);
+
+    let response = patch_frontmatter(
+        &server,
+        "/api/files/post.md",
+        serde_json::json!([
+            {"op": "add", "path": "/tags/-", "value": "b"},
+            {"op": "add", "path": "/author", "value": "me"},
+            {"op": "replace", "path": "/title", "value": "Published"},
+            {"op": "remove", "path": "/draft"},
+        ]),
+    )
+    .await;
+    assert_eq!(response.status_code(), 200);
+    assert_eq!(
+        response.json::<serde_json::Value>(),
+        serde_json::json!({"author": "me", "tags": ["a", "b"], "title": "Published"})
+    );
+    assert_eq!(
+        fs::read_to_string(&path).unwrap(),
+        "---\nauthor: me\ntags:\n- a\n- b\ntitle: Published\n---\n# Post\n\nBody\n"
+    );
+
+    // Removing every field removes the block
+    let response = patch_frontmatter(
+        &server,
+        "/api/files/post.md",
+        serde_json::json!([
+            {"op": "remove", "path": "/author"},
+            {"op": "remove", "path": "/tags"},
+            {"op": "remove", "path": "/title"},
+        ]),
+    )
+    .await;
+    assert_eq!(response.json::<serde_json::Value>(), serde_json::json!({}));
+    assert_eq!(fs::read_to_string(&path).unwrap(), "# Post\n\nBody\n");
+
+    // A file without frontmatter starts from an empty mapping
+    let response = patch_frontmatter(
+        &server,
+        "/api/files/post.md",
+        serde_json::json!([{"op": "add", "path": "/title", "value": "New"}]),
+    )
+    .await;
+    assert_eq!(response.status_code(), 200);
+    assert_eq!(
+        fs::read_to_string(&path).unwrap(),
+        "---\ntitle: New\n---\n# Post\n\nBody\n"
+    );
+}
+
+#[tokio::test]
+async fn test_api_patch_frontmatter_test_operation() {
+    let temp_dir = tempdir().expect("Failed to create temp dir");
+    let path = temp_dir.path().join("post.md");
+    let original = "---\nversion: 3\nstatus: draft\n---\n# Post\n";
+    fs::write(&path, original).expect("Failed to write");
+    let server = create_lazy_server(&temp_dir);
+
+    // A failed test discards the operations before it too
+    let response = patch_frontmatter(
+        &server,
+        "/api/files/post.md",
+        serde_json::json!([
+            {"op": "replace", "path": "/status", "value": "published"},
+            {"op": "test", "path": "/version", "value": 2},
+        ]),
+    )
+    .await;
+    assert_eq!(response.status_code(), 422);
+    assert_eq!(fs::read_to_string(&path).unwrap(), original);
+
+    let response = patch_frontmatter(
+        &server,
+        "/api/files/post.md",
+        serde_json::json!([
+            {"op": "test", "path": "/version", "value": 3},
+            {"op": "replace", "path": "/version", "value": 4},
+        ]),
+    )
+    .await;
+    assert_eq!(response.status_code(), 200);
+    assert_eq!(
+        response.json::<serde_json::Value>(),
+        serde_json::json!({"status": "draft", "version": 4})
+    );
+}
+
+#[tokio::test]
+async fn test_api_patch_frontmatter_errors() {
+    let temp_dir = tempdir().expect("Failed to create temp dir");
+    let path = temp_dir.path().join("post.md");
+    let original = "---\ntitle: Post\n---\n# Post\n";
+    fs::write(&path, original).expect("Failed to write");
+    let server = create_lazy_server(&temp_dir);
+    let replace_title = serde_json::json!([{"op": "replace", "path": "/title", "value": "X"}]);
+
+    // Replacing the whole frontmatter with something other than a mapping
+    let response = patch_frontmatter(
+        &server,
+        "/api/files/post.md",
+        serde_json::json!([{"op": "replace", "path": "", "value": "text"}]),
+    )
+    .await;
+    assert_eq!(response.status_code(), 422);
+    // Removing a field that isn't there
+    let response = patch_frontmatter(
+        &server,
+        "/api/files/post.md",
+        serde_json::json!([{"op": "remove", "path": "/author"}]),
+    )
+    .await;
+    assert_eq!(response.status_code(), 422);
+    assert_eq!(fs::read_to_string(&path).unwrap(), original);
+
+    let response = server
+        .patch("/api/files/post.md")
+        .json(&replace_title)
+        .await;
+    assert_eq!(response.status_code(), 415);
+
+    let response = patch_frontmatter(
+        &server,
+        "/api/files/post.md",
+        serde_json::json!([{"op": "frobnicate", "path": "/title"}]),
+    )
+    .await;
+    assert_eq!(response.status_code(), 400);
+
+    let response = patch_frontmatter(&server, "/api/files/missing.md", replace_title).await;
+    assert_eq!(response.status_code(), 404);
+}
+
+// ===========================
+// Required Frontmatter Tests
+// ===========================
+
+/// Collects formatted log output so tests can assert on warnings.
+#[derive(Clone, Default)]
+struct LogBuffer(std::sync::Arc<std::sync::Mutex<Vec<u8>>>);
+
+impl std::io::Write for LogBuffer {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.lock().unwrap().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+impl<'a> tracing_subscriber::fmt::MakeWriter<'a> for LogBuffer {
+    type Writer = LogBuffer;
+
+    fn make_writer(&'a self) -> Self::Writer {
+        self.clone()
+    }
+}
+
+#[tokio::test]
+async fn test_require_frontmatter_warns_at_startup_and_in_validate() {
+    let temp_dir = tempdir().expect("Failed to create temp dir");
+    fs::write(
+        temp_dir.path().join("complete.md"),
+        "---\ntitle: Complete\n---\n# Complete\n",
+    )
+    .expect("Failed to write");
+    fs::write(temp_dir.path().join("untitled.md"), "# Untitled\n").expect("Failed to write");
+
+    let base_dir = temp_dir.path().to_path_buf();
+    let tracked_files = scan_markdown_files(&base_dir)
+        .expect("Failed to scan markdown files")
+        .files;
+    let config = RouterConfig {
+        required_frontmatter: vec!["title".to_string()],
+        ..RouterConfig::default()
+    };
+
+    let logs = LogBuffer::default();
+    let subscriber = tracing_subscriber::fmt()
+        .with_writer(logs.clone())
+        .with_ansi(false)
+        .finish();
+    let router = tracing::subscriber::with_default(subscriber, || {
+        new_router_with_config(base_dir, tracked_files, true, config)
+    })
+    .expect("Failed to create router");
+
+    let output = String::from_utf8(logs.0.lock().unwrap().clone()).unwrap();
+    assert!(output.contains("WARN"));
+    assert!(output.contains("untitled.md is missing required frontmatter: title"));
+    assert!(!output.contains("complete.md"));
+
+    let server = TestServer::new(router).expect("Failed to create test server");
+    let response = server
+        .post("/api/files/untitled.md/validate")
+        .json(&serde_json::json!({ "markdown": "# Untitled\n" }))
+        .await;
+    assert_eq!(response.status_code(), 200);
+    let body: serde_json::Value = response.json();
+    assert_eq!(body["valid"], false);
+    assert_eq!(
+        body["missing_required_fields"],
+        serde_json::json!(["title"])
+    );
+}
+
+// ===========================
+// Workspace API Tests
+// ===========================
+
+#[tokio::test]
+async fn test_api_workspace_directory_mode() {
+    let (server, temp_dir) = create_directory_server().await;
+
+    let response = server.get("/api/workspace").await;
+    assert_eq!(response.status_code(), 200);
+    let body: serde_json::Value = response.json();
+    assert_eq!(
+        body["base_dir"],
+        temp_dir
+            .path()
+            .canonicalize()
+            .unwrap()
+            .display()
+            .to_string()
+    );
+    assert_eq!(body["is_directory_mode"], true);
+    assert_eq!(body["file_count"], 3);
+    assert_eq!(body["server_version"], env!("CARGO_PKG_VERSION"));
+    assert_eq!(
+        body["features"],
+        serde_json::json!({ "lazy_load": false, "include_hidden": false, "snapshot": false })
+    );
+}
+
+#[tokio::test]
+async fn test_api_workspace_single_file_mode() {
+    let (server, _temp_file) = create_test_server("# Single").await;
+
+    let body: serde_json::Value = server.get("/api/workspace").await.json();
+    assert_eq!(body["is_directory_mode"], false);
+    assert_eq!(body["file_count"], 1);
+}
+
+#[tokio::test]
+async fn test_api_workspace_search_index() {
+    let temp_dir = tempdir().expect("Failed to create temp dir");
+    fs::write(
+        temp_dir.path().join("guide.md"),
+        "---\ntitle: User Guide\ntags: [intro, setup]\n---\n# Guide\n\nRead the **setup** _notes_ in [the docs](docs/setup.md).\n\n```sh\ncargo install mdserve\n```\n",
+    )
+    .expect("Failed to write");
+    fs::create_dir(temp_dir.path().join("docs")).expect("Failed to create dir");
+    fs::write(
+        temp_dir.path().join("docs/setup.md"),
+        "## Setup\n\n> Quoted *text*\n",
+    )
+    .expect("Failed to write");
+    let server = create_lazy_server(&temp_dir);
+
+    let response = server.get("/api/workspace/search-index").await;
+    assert_eq!(response.status_code(), 200);
+    assert_eq!(response.header("cache-control"), "max-age=60");
+    let body: serde_json::Value = response.json();
+    assert_eq!(
+        body,
+        serde_json::json!({
+            "version": 1,
+            "files": [
+                {
+                    "path": "docs/setup.md",
+                    "title": null,
+                    "content": "Setup Quoted text",
+                    "tags": [],
+                },
+                {
+                    "path": "guide.md",
+                    "title": "User Guide",
+                    "content": "Guide Read the setup notes in the docs. cargo install mdserve",
+                    "tags": ["intro", "setup"],
+                },
+            ],
+        })
+    );
+    for file in body["files"].as_array().unwrap() {
+        let content = file["content"].as_str().unwrap();
+        assert!(
+            !content.contains(['#', '*', '_', '[', ']', '`', '>']),
+            "{content}"
+        );
+    }
+
+    let response = server
+        .get("/api/workspace/search-index")
+        .add_header("accept-encoding", "gzip")
+        .await;
+    assert_eq!(response.status_code(), 200);
+    assert_eq!(response.header("content-encoding"), "gzip");
+}
+
+#[tokio::test]
+async fn test_api_workspace_graph() {
+    let temp_dir = tempdir().expect("Failed to create temp dir");
+    fs::create_dir(temp_dir.path().join("guide")).expect("Failed to create dir");
+    fs::write(
+        temp_dir.path().join("index.md"),
+        "---\ntags: [home, start]\n---\n# Home\n\nRead the [guide](guide/intro.md), then the [guide again](./guide/intro.md#setup).\n\nAlso [the FAQ](faq.md), [a missing page](missing.md) and [the web](https://example.com).\n",
+    )
+    .expect("Failed to write");
+    fs::write(
+        temp_dir.path().join("guide/intro.md"),
+        "# Intro\n\nBack [home](../index.md) or [here](intro.md).\n",
+    )
+    .expect("Failed to write");
+    fs::write(temp_dir.path().join("faq.md"), "# FAQ\n\nNo links.\n").expect("Failed to write");
+    let server = create_lazy_server(&temp_dir);
+
+    let response = server.get("/api/workspace/graph").await;
+    assert_eq!(response.status_code(), 200);
+    let body: serde_json::Value = response.json();
+    assert_eq!(
+        body["edges"],
+        serde_json::json!([
+            {"source": "guide/intro.md", "target": "index.md", "count": 1},
+            {"source": "index.md", "target": "faq.md", "count": 1},
+            {"source": "index.md", "target": "guide/intro.md", "count": 2},
+        ])
+    );
+    assert_eq!(
+        body["nodes"],
+        serde_json::json!([
+            {"id": "faq.md", "title": "FAQ", "word_count": 3, "tag_count": 0, "link_count": 0, "backlink_count": 1},
+            {"id": "guide/intro.md", "title": "Intro", "word_count": 5, "tag_count": 0, "link_count": 2, "backlink_count": 1},
+            {"id": "index.md", "title": "Home", "word_count": 17, "tag_count": 2, "link_count": 5, "backlink_count": 1},
+        ])
+    );
+
+    // Served from the cache for a while, even after a change
+    fs::write(
+        temp_dir.path().join("faq.md"),
+        "# FAQ\n\n[Home](index.md)\n",
+    )
+    .expect("Failed to write");
+    let cached: serde_json::Value = server.get("/api/workspace/graph").await.json();
+    assert_eq!(cached, body);
+}
+
+#[tokio::test]
+async fn test_api_workspace_timeline() {
+    let temp_dir = tempdir().expect("Failed to create temp dir");
+    // 2024-02-28 12:00, 2024-02-29 00:00 and 2024-03-04 (a Monday) 08:00 UTC
+    for (file, secs) in [
+        ("a.md", 1_709_121_600),
+        ("b.md", 1_709_164_800),
+        ("c.md", 1_709_539_200),
+    ] {
+        let path = temp_dir.path().join(file);
+        fs::write(&path, "# Doc").expect("Failed to write");
+        fs::File::options()
+            .write(true)
+            .open(&path)
+            .and_then(|f| f.set_modified(std::time::UNIX_EPOCH + Duration::from_secs(secs)))
+            .expect("Failed to set mtime");
+    }
+    let server = create_lazy_server(&temp_dir);
+
+    let timeline = |granularity: &'static str| {
+        let request = server
+            .get("/api/workspace/stats/timeline")
+            .add_query_param("granularity", granularity)
+            .add_query_param("start", 1_709_078_400)
+            .add_query_param("end", 1_709_600_000);
+        async move {
+            let response = request.await;
+            assert_eq!(response.status_code(), 200, "{granularity}");
+            let body: serde_json::Value = response.json();
+            body.as_array()
+                .unwrap()
+                .iter()
+                .map(|entry| {
+                    (
+                        entry["period"].as_str().unwrap().to_string(),
+                        entry["files_modified"].as_u64().unwrap(),
+                    )
+                })
+                .collect::<Vec<_>>()
+        }
+    };
+    let periods = |expected: &[(&str, u64)]| {
+        expected
+            .iter()
+            .map(|(period, count)| (period.to_string(), *count))
+            .collect::<Vec<_>>()
+    };
+
+    assert_eq!(
+        timeline("day").await,
+        periods(&[
+            ("2024-02-28", 1),
+            ("2024-02-29", 1),
+            ("2024-03-01", 0),
+            ("2024-03-02", 0),
+            ("2024-03-03", 0),
+            ("2024-03-04", 1),
+            ("2024-03-05", 0),
+        ])
+    );
+    assert_eq!(
+        timeline("week").await,
+        periods(&[("2024-02-26", 2), ("2024-03-04", 1)])
+    );
+    assert_eq!(
+        timeline("month").await,
+        periods(&[("2024-02", 2), ("2024-03", 1)])
+    );
+
+    // Without a range, from the oldest file to now
+    let response = server.get("/api/workspace/stats/timeline").await;
+    assert_eq!(response.status_code(), 200);
+    let body: serde_json::Value = response.json();
+    let days = body.as_array().unwrap();
+    assert_eq!(days[0]["period"], "2024-02-28");
+    assert_eq!(
+        days.iter()
+            .map(|day| day["files_modified"].as_u64().unwrap())
+            .sum::<u64>(),
+        3
+    );
+}
+
+#[tokio::test]
+async fn test_api_workspace_timeline_rejects_bad_queries() {
+    let temp_dir = tempdir().expect("Failed to create temp dir");
+    fs::write(temp_dir.path().join("doc.md"), "# Doc").expect("Failed to write");
+    let server = create_lazy_server(&temp_dir);
+
+    let response = server
+        .get("/api/workspace/stats/timeline")
+        .add_query_param("granularity", "year")
+        .await;
+    assert_eq!(response.status_code(), 400);
+
+    let response = server
+        .get("/api/workspace/stats/timeline")
+        .add_query_param("start", 2000)
+        .add_query_param("end", 1000)
+        .await;
+    assert_eq!(response.status_code(), 400);
+
+    // A day per period since 1970 is too many
+    let response = server
+        .get("/api/workspace/stats/timeline")
+        .add_query_param("start", 0)
+        .await;
+    assert_eq!(response.status_code(), 400);
+}
+
+#[tokio::test]
+async fn test_api_workspace_export() {
+    let temp_dir = tempdir().expect("Failed to create temp dir");
+    for dir in ["guide", "images"] {
+        fs::create_dir_all(temp_dir.path().join(dir)).expect("Failed to create dir");
+    }
+    fs::write(temp_dir.path().join("images/logo.png"), PIXEL_PNG).expect("Failed to write");
+    fs::write(temp_dir.path().join("guide/diagram.svg"), "<svg/>").expect("Failed to write");
+    fs::write(temp_dir.path().join("images/unused.png"), PIXEL_PNG).expect("Failed to write");
+    let index = "# Home\n\n![Logo](images/logo.png)\n";
+    let intro = "# Intro\n\n![Logo](../images/logo.png)\n\n<img src=\"diagram.svg\">\n";
+    fs::write(temp_dir.path().join("index.md"), index).expect("Failed to write");
+    fs::write(temp_dir.path().join("guide/intro.md"), intro).expect("Failed to write");
+    let server = create_lazy_server(&temp_dir);
+
+    let response = server.get("/api/workspace/export").await;
+    assert_eq!(response.status_code(), 200);
+    assert_eq!(response.header("content-type"), "application/zip");
+    let directory = temp_dir
+        .path()
+        .file_name()
+        .expect("No directory name")
+        .to_string_lossy();
+    assert_eq!(
+        response.header("content-disposition"),
+        format!("attachment; filename=\"{directory}-export.zip\"").as_str()
+    );
+    assert_eq!(
+        zip_entries(response.as_bytes()),
+        vec![
+            ("guide/intro.md".to_string(), intro.as_bytes().to_vec()),
+            ("index.md".to_string(), index.as_bytes().to_vec()),
+            ("guide/diagram.svg".to_string(), b"<svg/>".to_vec()),
+            ("images/logo.png".to_string(), PIXEL_PNG.to_vec()),
+        ]
+    );
+
+    let response = server
+        .get("/api/workspace/export")
+        .add_query_param("include_images", false)
+        .await;
+    let names: Vec<String> = zip_entries(response.as_bytes())
+        .into_iter()
+        .map(|(name, _)| name)
+        .collect();
+    assert_eq!(names, vec!["guide/intro.md", "index.md"]);
+}
+
+#[tokio::test]
+async fn test_api_workspace_export_too_large() {
+    let temp_dir = tempdir().expect("Failed to create temp dir");
+    fs::write(
+        temp_dir.path().join("doc.md"),
+        "# Doc\n\n![Logo](logo.png)\n",
+    )
+    .expect("Failed to write");
+    fs::write(temp_dir.path().join("logo.png"), [0; 64]).expect("Failed to write");
+    let config = RouterConfig {
+        max_export_size: 32,
+        ..RouterConfig::default()
+    };
+    let router = new_router_with_config(
+        temp_dir.path().to_path_buf(),
+        vec![temp_dir.path().join("doc.md")],
+        true,
+        config,
+    )
+    .expect("Failed to create router");
+    let server = TestServer::new(router).expect("Failed to create test server");
+
+    let response = server.get("/api/workspace/export").await;
+    assert_eq!(response.status_code(), 413);
+
+    let response = server
+        .get("/api/workspace/export")
+        .add_query_param("include_images", false)
+        .await;
+    assert_eq!(response.status_code(), 200);
+}
+
+fn create_tag_cloud_server() -> (TestServer, TempDir) {
+    let temp_dir = tempdir().expect("Failed to create temp dir");
+    for (name, tags) in [
+        ("intro.md", "[tutorial, rust]"),
+        ("guide.md", "tutorial, rust, async"),
+        ("advanced.md", "[rust, async]"),
+        ("faq.md", "faq"),
+        ("notes.md", "[tutorial, tutorial]"),
+    ] {
+        let content = format!("---\ntags: {tags}\n---\n# {name}\n");
+        fs::write(temp_dir.path().join(name), content).expect("Failed to write");
+    }
+    fs::write(temp_dir.path().join("untagged.md"), "# Untagged\n").expect("Failed to write");
+    let server = create_lazy_server(&temp_dir);
+    (server, temp_dir)
+}
+
+#[tokio::test]
+async fn test_api_workspace_tag_cloud() {
+    let (server, _temp_dir) = create_tag_cloud_server();
+
+    let response = server.get("/api/workspace/tag-cloud").await;
+    assert_eq!(response.status_code(), 200);
+    assert_eq!(
+        response.json::<serde_json::Value>(),
+        serde_json::json!([
+            {"tag": "rust", "count": 3, "files": ["advanced.md", "guide.md", "intro.md"]},
+            {"tag": "tutorial", "count": 3, "files": ["guide.md", "intro.md", "notes.md"]},
+            {"tag": "async", "count": 2, "files": ["advanced.md", "guide.md"]},
+            {"tag": "faq", "count": 1, "files": ["faq.md"]},
+        ])
+    );
+}
+
+#[tokio::test]
+async fn test_api_workspace_tag_cloud_filters() {
+    let (server, _temp_dir) = create_tag_cloud_server();
+
+    let response = server
+        .get("/api/workspace/tag-cloud")
+        .add_query_param("min_count", 2)
+        .add_query_param("include_files", false)
+        .await;
+    assert_eq!(response.status_code(), 200);
+    assert_eq!(
+        response.json::<serde_json::Value>(),
+        serde_json::json!([
+            {"tag": "rust", "count": 3},
+            {"tag": "tutorial", "count": 3},
+            {"tag": "async", "count": 2},
+        ])
+    );
+
+    let body: serde_json::Value = server
+        .get("/api/workspace/tag-cloud")
+        .add_query_param("min_count", 4)
+        .await
+        .json();
+    assert_eq!(body, serde_json::json!([]));
+}
+
+#[tokio::test]
+async fn test_api_workspace_tag_cloud_follows_edits() {
+    let (server, _temp_dir) = create_tag_cloud_server();
+    let counts = || {
+        let request = server
+            .get("/api/workspace/tag-cloud")
+            .add_query_param("include_files", false);
+        async move {
+            let body: serde_json::Value = request.await.json();
+            body.as_array()
+                .unwrap()
+                .iter()
+                .map(|tag| {
+                    (
+                        tag["tag"].as_str().unwrap().to_string(),
+                        tag["count"].as_u64().unwrap(),
+                    )
+                })
+                .collect::<Vec<_>>()
+        }
+    };
+    assert_eq!(counts().await[3], ("faq".to_string(), 1));
+
+    let response = server
+        .put("/api/files/faq.md/frontmatter")
+        .json(&serde_json::json!({ "frontmatter": {"tags": ["rust"]} }))
+        .await;
+    assert_eq!(response.status_code(), 200);
+
+    assert_eq!(
+        counts().await,
+        vec![
+            ("rust".to_string(), 4),
+            ("tutorial".to_string(), 3),
+            ("async".to_string(), 2),
+        ]
+    );
+}
+
+#[tokio::test]
+async fn test_api_workspace_sitemap() {
+    let temp_dir = tempdir().expect("Failed to create temp dir");
+    fs::create_dir(temp_dir.path().join("guide")).expect("Failed to create dir");
+    for file in ["index.md", "about.md", "guide/setup.md"] {
+        let path = temp_dir.path().join(file);
+        fs::write(&path, "# Doc\n").expect("Failed to write");
+        fs::File::options()
+            .write(true)
+            .open(&path)
+            // 2024-02-29 12:00 UTC
+            .and_then(|f| {
+                f.set_modified(std::time::UNIX_EPOCH + Duration::from_secs(1_709_208_000))
+            })
+            .expect("Failed to set mtime");
+    }
+    let tracked_files = ["index.md", "about.md", "guide/setup.md"]
+        .iter()
+        .map(|file| temp_dir.path().join(file))
+        .collect();
+    let config = RouterConfig {
+        public_url: Some("https://docs.example.com/".to_string()),
+        ..RouterConfig::default()
+    };
+    let router = new_router_with_config(temp_dir.path().to_path_buf(), tracked_files, true, config)
+        .expect("Failed to create router");
+    let server = TestServer::new(router).expect("Failed to create test server");
+
+    let response = server.get("/api/workspace/sitemap.xml").await;
+    assert_eq!(response.status_code(), 200);
+    assert_eq!(response.header("content-type"), "application/xml");
+    let xml = response.text();
+    assert!(xml.starts_with("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<urlset xmlns=\"http://www.sitemaps.org/schemas/sitemap/0.9\">\n"));
+    assert!(xml.ends_with("</urlset>\n"));
+    assert_eq!(xml.matches("<url>").count(), 3);
+    assert_eq!(xml.matches("</url>").count(), 3);
+    assert!(xml.contains(
+        "  <url>\n    <loc>https://docs.example.com/guide/setup.html</loc>\n    <lastmod>2024-02-29</lastmod>\n    <changefreq>weekly</changefreq>\n    <priority>0.8</priority>\n  </url>\n"
+    ));
+    assert!(xml.contains("<loc>https://docs.example.com/index.html</loc>"));
+    assert_eq!(xml.matches("<priority>1.0</priority>").count(), 2);
+}
+
+#[tokio::test]
+async fn test_api_workspace_sitemap_requires_public_url() {
+    let temp_dir = tempdir().expect("Failed to create temp dir");
+    fs::write(temp_dir.path().join("index.md"), "# Home\n").expect("Failed to write");
+    let server = create_lazy_server(&temp_dir);
+
+    let response = server.get("/api/workspace/sitemap.xml").await;
+    assert_eq!(response.status_code(), 501);
+}
+
+#[tokio::test]
+async fn test_api_workspace_duplicate_titles() {
+    let temp_dir = tempdir().expect("Failed to create temp dir");
+    for dir in ["guide", "tutorial"] {
+        fs::create_dir(temp_dir.path().join(dir)).expect("Failed to create dir");
+    }
+    fs::write(
+        temp_dir.path().join("tutorial/intro.md"),
+        "# Introduction\n\nStart here.\n",
+    )
+    .expect("Failed to write");
+    fs::write(
+        temp_dir.path().join("guide/intro.md"),
+        "---\ntitle: Guide\n---\n## Setup\n\n# Introduction\n",
+    )
+    .expect("Failed to write");
+    fs::write(temp_dir.path().join("overview.md"), "# Overview\n").expect("Failed to write");
+    let server = create_lazy_server(&temp_dir);
+
+    let response = server.get("/api/workspace/duplicate-titles").await;
+    assert_eq!(response.status_code(), 200);
+    assert_eq!(
+        response.json::<serde_json::Value>(),
+        serde_json::json!([
+            {"title": "Introduction", "files": ["guide/intro.md", "tutorial/intro.md"]},
+        ])
+    );
+    let stats: serde_json::Value = server.get("/api/stats").await.json();
+    assert_eq!(stats["title_duplicates"], 1);
+
+    // Retitling one of them resolves the clash
+    let response = server
+        .put("/api/files/guide/intro.md")
+        .json(&serde_json::json!({ "markdown": "# Guide introduction\n" }))
+        .await;
+    assert_eq!(response.status_code(), 200);
+
+    let body: serde_json::Value = server.get("/api/workspace/duplicate-titles").await.json();
+    assert_eq!(body, serde_json::json!([]));
+    let stats: serde_json::Value = server.get("/api/stats").await.json();
+    assert_eq!(stats["title_duplicates"], 0);
+}
+
+#[tokio::test]
+async fn test_api_workspace_broken_links() {
+    let temp_dir = tempdir().expect("Failed to create temp dir");
+    fs::create_dir(temp_dir.path().join("guide")).expect("Failed to create dir");
+    fs::write(
+        temp_dir.path().join("guide/setup.md"),
+        "# Setup\n\nSee [the intro](../index.md), [Installation](install.md) and [docs](https://example.com/missing.md).\n",
+    )
+    .expect("Failed to write");
+    fs::write(
+        temp_dir.path().join("index.md"),
+        "# Home\n\n[Setup](guide/setup.md) and [**old** notes](notes.md#top)\n",
+    )
+    .expect("Failed to write");
+    let server = create_lazy_server(&temp_dir);
+
+    let response = server.get("/api/workspace/broken-links").await;
+    assert_eq!(response.status_code(), 200);
+    assert_eq!(
+        response.json::<serde_json::Value>(),
+        serde_json::json!([
+            {"source": "guide/setup.md", "link": "install.md", "text": "Installation"},
+            {"source": "index.md", "link": "notes.md#top", "text": "old notes"},
+        ])
+    );
+    let stats: serde_json::Value = server.get("/api/stats").await.json();
+    assert_eq!(stats["broken_link_count"], 2);
+
+    server
+        .put("/api/files/guide/setup.md")
+        .json(&serde_json::json!({ "markdown": "# Setup\n\n[Home](/index.md)\n" }))
+        .await;
+    let body: serde_json::Value = server.get("/api/workspace/broken-links").await.json();
+    assert_eq!(
+        body,
+        serde_json::json!([{"source": "index.md", "link": "notes.md#top", "text": "old notes"}])
+    );
+    let stats: serde_json::Value = server.get("/api/stats").await.json();
+    assert_eq!(stats["broken_link_count"], 1);
+}
+
+fn create_orphan_check_server(temp_dir: &TempDir, config: RouterConfig) -> TestServer {
+    let base_dir = temp_dir.path().to_path_buf();
+    let tracked_files = scan_markdown_files(&base_dir)
+        .expect("Failed to scan")
+        .files;
+    let router = new_router_with_config(base_dir, tracked_files, true, config)
+        .expect("Failed to create router");
+    TestServer::new(router).expect("Failed to create test server")
+}
+
+#[tokio::test]
+async fn test_api_workspace_orphaned() {
+    let temp_dir = tempdir().expect("Failed to create temp dir");
+    fs::write(
+        temp_dir.path().join("guide.md"),
+        "# Guide\n\nContinue with [setup](setup.md).\n",
+    )
+    .expect("Failed to write");
+    fs::write(
+        temp_dir.path().join("setup.md"),
+        "# Setup\n\nBack to the [guide](./guide.md), or [setup](setup.md) again.\n",
+    )
+    .expect("Failed to write");
+    fs::write(
+        temp_dir.path().join("old.md"),
+        "# Old\n\nOutdated, see the [guide](guide.md).\n",
+    )
+    .expect("Failed to write");
+    fs::write(temp_dir.path().join("README.md"), "# Readme\n").expect("Failed to write");
+    let server = create_orphan_check_server(&temp_dir, RouterConfig::default());
+
+    let response = server.get("/api/workspace/orphaned").await;
+    assert_eq!(response.status_code(), 200);
+    assert_eq!(
+        response.json::<serde_json::Value>(),
+        serde_json::json!([{"path": "old.md", "word_count": 5}])
+    );
+    let stats: serde_json::Value = server.get("/api/stats").await.json();
+    assert_eq!(stats["orphaned_count"], 1);
+
+    let config = RouterConfig {
+        root_files: vec!["old.md".to_string()],
+        ..RouterConfig::default()
+    };
+    let server = create_orphan_check_server(&temp_dir, config);
+    let body: serde_json::Value = server.get("/api/workspace/orphaned").await.json();
+    assert_eq!(body, serde_json::json!([]));
+
+    let config = RouterConfig {
+        no_orphan_check: vec!["old*.md".to_string()],
+        ..RouterConfig::default()
+    };
+    let server = create_orphan_check_server(&temp_dir, config);
+    let body: serde_json::Value = server.get("/api/workspace/orphaned").await.json();
+    assert_eq!(
+        body,
+        serde_json::json!([{"path": "README.md", "word_count": 1}])
+    );
+}
+
+#[tokio::test]
+async fn test_api_workspace_verify() {
+    let temp_dir = tempdir().expect("Failed to create temp dir");
+    let file_path = temp_dir.path().join("doc.md");
+    fs::write(&file_path, "# Original").expect("Failed to write");
+    let server = create_unwatched_server(&temp_dir);
+
+    let response = server.post("/api/workspace/verify").await;
+    assert_eq!(response.status_code(), 200);
+    let clean = serde_json::json!({"missing": [], "diverged": [], "out_of_tree": []});
+    assert_eq!(response.json::<serde_json::Value>(), clean);
+
+    fs::write(&file_path, "# Changed behind the server's back").expect("Failed to write");
+    let body: serde_json::Value = server.post("/api/workspace/verify").await.json();
+    assert_eq!(
+        body,
+        serde_json::json!({"missing": [], "diverged": ["doc.md"], "out_of_tree": []})
+    );
+
+    fs::remove_file(&file_path).expect("Failed to remove");
+    let body: serde_json::Value = server.post("/api/workspace/verify").await.json();
+    assert_eq!(
+        body,
+        serde_json::json!({"missing": ["doc.md"], "diverged": [], "out_of_tree": []})
+    );
+
+    let response = server.get("/api/workspace/verify").await;
+    assert_eq!(response.status_code(), 405);
+}
+
+/// A ZIP archive of the given entries, for upload.
+fn zip_archive(entries: &[(&str, &[u8])]) -> Vec<u8> {
+    use std::io::Write;
+    use zip::{write::SimpleFileOptions, ZipWriter};
+
+    let mut writer = ZipWriter::new(std::io::Cursor::new(Vec::new()));
+    for (name, content) in entries {
+        writer
+            .start_file(*name, SimpleFileOptions::default())
+            .expect("Failed to start entry");
+        writer.write_all(content).expect("Failed to write entry");
+    }
+    writer.finish().expect("Failed to finish ZIP").into_inner()
+}
+
+fn import_form(archive: Vec<u8>) -> MultipartForm {
+    MultipartForm::new().add_part("file", Part::bytes(archive).file_name("import.zip"))
+}
+
+#[tokio::test]
+async fn test_api_workspace_import() {
+    let temp_dir = tempdir().expect("Failed to create temp dir");
+    fs::write(temp_dir.path().join("existing.md"), "# Existing").expect("Failed to write");
+    let config = RouterConfig {
+        poll_interval: Some(Duration::from_secs(3600)),
+        ..RouterConfig::default()
+    };
+    let router = new_router_with_config(
+        temp_dir.path().to_path_buf(),
+        vec![temp_dir.path().join("existing.md")],
+        true,
+        config,
+    )
+    .expect("Failed to create router");
+    let server = TestServer::builder()
+        .http_transport()
+        .build(router)
+        .expect("Failed to create test server");
+    let mut websocket = connect_websocket(&server).await;
+
+    let archive = zip_archive(&[
+        ("docs/intro.md", b"# Intro"),
+        ("docs/deep/ref.markdown", b"# Reference"),
+        ("docs/logo.png", b"png"),
+        ("binary.md", b"\xff\xfe"),
+        ("existing.md", b"# Replaced"),
+        ("../escape.md", b"# Escape"),
+    ]);
+    let response = server
+        .post("/api/workspace/import")
+        .multipart(import_form(archive))
+        .await;
+    assert_eq!(response.status_code(), 200);
+    assert_eq!(
+        response.json::<serde_json::Value>(),
+        serde_json::json!({
+            "imported": 2,
+            "skipped": ["binary.md", "existing.md"],
+            "errors": ["../escape.md: path leaves the directory"],
+        })
+    );
+
+    let message = tokio::time::timeout(
+        Duration::from_secs(WEBSOCKET_TIMEOUT_SECS),
+        websocket.receive_json::<ServerMessage>(),
+    )
+    .await
+    .expect("Timeout waiting for BulkReload");
+    assert_eq!(
+        message,
+        ServerMessage::BulkReload {
+            added: vec![
+                "docs/intro.md".to_string(),
+                "docs/deep/ref.markdown".to_string()
+            ],
+            removed: Vec::new(),
+            modified: Vec::new(),
+        }
+    );
+
+    let intro = fs::read_to_string(temp_dir.path().join("docs/intro.md")).expect("Not created");
+    assert_eq!(intro, "# Intro");
+    assert!(!temp_dir.path().join("docs/logo.png").exists());
+    let existing = fs::read_to_string(temp_dir.path().join("existing.md")).expect("Missing");
+    assert_eq!(existing, "# Existing");
+    let response = server.get("/api/files/docs/deep/ref.markdown").await;
+    assert_eq!(response.status_code(), 200);
+    assert_eq!(
+        response.json::<serde_json::Value>()["markdown"],
+        "# Reference"
+    );
+}
+
+#[tokio::test]
+async fn test_api_workspace_import_rejects_bad_uploads() {
+    let temp_dir = tempdir().expect("Failed to create temp dir");
+    fs::write(temp_dir.path().join("doc.md"), "# Doc").expect("Failed to write");
+    let config = RouterConfig {
+        max_import_size: 16,
+        poll_interval: Some(Duration::from_secs(3600)),
+        ..RouterConfig::default()
+    };
+    let router = new_router_with_config(
+        temp_dir.path().to_path_buf(),
+        vec![temp_dir.path().join("doc.md")],
+        true,
+        config,
+    )
+    .expect("Failed to create router");
+    let server = TestServer::new(router).expect("Failed to create test server");
+
+    let archive = zip_archive(&[("big.md", &[b'a'; 64])]);
+    let response = server
+        .post("/api/workspace/import")
+        .multipart(import_form(archive))
+        .await;
+    assert_eq!(response.status_code(), 413);
+    assert!(!temp_dir.path().join("big.md").exists());
+
+    let server = create_unwatched_server(&temp_dir);
+    let response = server
+        .post("/api/workspace/import")
+        .multipart(import_form(b"not a zip".to_vec()))
+        .await;
+    assert_eq!(response.status_code(), 400);
+
+    let form = MultipartForm::new().add_text("name", "import.zip");
+    let response = server.post("/api/workspace/import").multipart(form).await;
+    assert_eq!(response.status_code(), 400);
+
+    let (server, _temp_file) = create_test_server("# Single").await;
+    let archive = zip_archive(&[("new.md", b"# New")]);
+    let response = server
+        .post("/api/workspace/import")
+        .multipart(import_form(archive))
+        .await;
+    assert_eq!(response.status_code(), 400);
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn test_api_workspace_import_through_symlinked_directory() {
+    let temp_dir = tempdir().expect("Failed to create temp dir");
+    let outside_dir = tempdir().expect("Failed to create temp dir");
+    fs::write(temp_dir.path().join("doc.md"), "# Doc").expect("Failed to write");
+    std::os::unix::fs::symlink(outside_dir.path(), temp_dir.path().join("link"))
+        .expect("Failed to create symlink");
+    let server = create_unwatched_server(&temp_dir);
+
+    let archive = zip_archive(&[("link/new/x.md", b"# Escape")]);
+    let response = server
+        .post("/api/workspace/import")
+        .multipart(import_form(archive))
+        .await;
+    assert_eq!(response.status_code(), 200);
+    assert_eq!(
+        response.json::<serde_json::Value>(),
+        serde_json::json!({
+            "imported": 0,
+            "skipped": [],
+            "errors": ["link/new/x.md: File would be outside the directory"],
+        })
+    );
+    assert!(!outside_dir.path().join("new").exists());
+}
+
+#[tokio::test]
+async fn test_health_reports_server_info() {
+    let (server, _temp_dir) = create_directory_server().await;
+
+    let response = server.get("/__health").await;
+    assert_eq!(response.status_code(), 200);
+    let body: serde_json::Value = response.json();
+    assert_eq!(body["status"], "ready");
+    assert_eq!(body["file_count"], 3);
+    assert!(body["uptime_seconds"]
+        .as_u64()
+        .is_some_and(|uptime| uptime < 60));
+    assert_eq!(body["version"], env!("CARGO_PKG_VERSION"));
+}
+
+// ===========================
+// Request Tracing Tests
+// ===========================
+
+#[tokio::test]
+async fn test_requests_are_traced_with_level_by_status() {
+    let logs = LogBuffer::default();
+    let subscriber = tracing_subscriber::fmt()
+        .with_writer(logs.clone())
+        .with_ansi(false)
+        .with_max_level(tracing::Level::DEBUG)
+        .finish();
+    // The test runs on a single thread, so the subscriber sees the handlers too
+    let _guard = tracing::subscriber::set_default(subscriber);
+
+    let (server, _temp_dir) = create_directory_server().await;
+    server.get("/api/files").await;
+    server.get("/api/files/missing.md").await;
+
+    let output = String::from_utf8(logs.0.lock().unwrap().clone()).unwrap();
+    let line_for = |path: &str| {
+        output
+            .lines()
+            .find(|line| {
+                line.contains(&format!("http.path={path}")) && line.contains("duration_ms")
+            })
+            .unwrap_or_else(|| panic!("no trace for {path} in:\n{output}"))
+            .to_string()
+    };
+
+    let ok = line_for("/api/files");
+    assert!(ok.contains("DEBUG"), "{ok}");
+    assert!(ok.contains("http.method=GET"), "{ok}");
+    assert!(ok.contains("http.status_code=200"), "{ok}");
+
+    let not_found = line_for("/api/files/missing.md");
+    assert!(not_found.contains("WARN"), "{not_found}");
+    assert!(not_found.contains("http.status_code=404"), "{not_found}");
+}
+
+#[tokio::test]
+async fn test_request_id_is_echoed_or_generated() {
+    let (server, _temp_dir) = create_directory_server().await;
+
+    let response = server
+        .get("/api/files")
+        .add_header("x-request-id", "client-chosen-id")
+        .await;
+    assert_eq!(response.header("x-request-id"), "client-chosen-id");
+
+    let long_id = "a".repeat(200);
+    let response = server
+        .get("/api/files/missing.md")
+        .add_header("x-request-id", long_id.as_str())
+        .await;
+    assert_eq!(response.status_code(), 404);
+    assert_eq!(response.header("x-request-id"), "a".repeat(128).as_str());
+
+    let response = server.get("/").await;
+    let generated = response.header("x-request-id");
+    assert!(
+        uuid::Uuid::parse_str(generated.to_str().unwrap()).is_ok(),
+        "{generated:?}"
+    );
+    assert_ne!(server.get("/").await.header("x-request-id"), generated);
+}
+
+#[tokio::test]
+async fn test_request_id_is_recorded_in_trace_span() {
+    let logs = LogBuffer::default();
+    let subscriber = tracing_subscriber::fmt()
+        .with_writer(logs.clone())
+        .with_ansi(false)
+        .with_max_level(tracing::Level::DEBUG)
+        .finish();
+    let _guard = tracing::subscriber::set_default(subscriber);
+
+    let (server, _temp_dir) = create_directory_server().await;
+    server
+        .get("/api/files")
+        .add_header("x-request-id", "trace-me")
+        .await;
+
+    let output = String::from_utf8(logs.0.lock().unwrap().clone()).unwrap();
+    assert!(
+        output
+            .lines()
+            .any(|line| line.contains("request_id=trace-me") && line.contains("duration_ms")),
+        "{output}"
+    );
+}
+
+// ===========================
+// Metrics Tests
+// ===========================
+
+fn create_metrics_server(metrics: bool) -> (TestServer, TempDir) {
+    let temp_dir = tempdir().expect("Failed to create temp dir");
+    fs::write(temp_dir.path().join("doc.md"), "# Doc").expect("Failed to write");
+
+    let base_dir = temp_dir.path().to_path_buf();
+    let tracked_files = scan_markdown_files(&base_dir)
+        .expect("Failed to scan")
+        .files;
+    let config = RouterConfig {
+        metrics,
+        ..RouterConfig::default()
+    };
+    let router = new_router_with_config(base_dir, tracked_files, true, config)
+        .expect("Failed to create router");
+    let server = TestServer::new(router).expect("Failed to create test server");
+    (server, temp_dir)
+}
+
+/// The value of the first sample in a Prometheus text dump whose name and labels start
+/// with `prefix`.
+fn metric_value(dump: &str, prefix: &str) -> Option<f64> {
+    dump.lines()
+        .find(|line| line.starts_with(prefix))
+        .and_then(|line| line.rsplit(' ').next())
+        .and_then(|value| value.parse().ok())
+}
+
+#[tokio::test]
+async fn test_metrics_endpoint_counts_requests() {
+    let (server, _temp_dir) = create_metrics_server(true);
+    let request_total =
+        r#"mdserve_request_total{method="GET",path="/api/files/*path",status="200"}"#;
+
+    let before = server.get("/__metrics").await.text();
+    let before = metric_value(&before, request_total).unwrap_or(0.0);
+
+    for _ in 0..3 {
+        server.get("/api/files/doc.md").await.assert_status_ok();
+    }
+    server
+        .get("/api/files/missing.md")
+        .await
+        .assert_status_not_found();
+
+    let response = server.get("/__metrics").await;
+    assert_eq!(response.status_code(), 200);
+    assert!(response
+        .header("content-type")
+        .to_str()
+        .unwrap()
+        .starts_with("text/plain"));
+    let dump = response.text();
+
+    assert_eq!(metric_value(&dump, request_total), Some(before + 3.0));
+    assert!(metric_value(
+        &dump,
+        r#"mdserve_request_total{method="GET",path="/api/files/*path",status="404"}"#
+    )
+    .is_some_and(|count| count >= 1.0));
+    assert!(dump.contains("mdserve_request_duration_seconds_bucket{"));
+    assert!(metric_value(&dump, "mdserve_tracked_files_total").is_some());
+}
+
+#[tokio::test]
+async fn test_metrics_endpoint_disabled_by_default() {
+    let (server, _temp_dir) = create_metrics_server(false);
+
+    server.get("/__metrics").await.assert_status_not_found();
+}
+
+// ===========================
+// Connection Limit Tests
+// ===========================
+
+async fn websocket_count(server: &TestServer) -> u64 {
+    let stats: serde_json::Value = server.get("/api/stats").await.json();
+    stats["websocket_connections"].as_u64().unwrap()
+}
+
+#[tokio::test]
+async fn test_websocket_connection_limit() {
+    let temp_dir = tempdir().expect("Failed to create temp dir");
+    fs::write(temp_dir.path().join("doc.md"), "# Doc").expect("Failed to write");
+
+    let base_dir = temp_dir.path().to_path_buf();
+    let tracked_files = scan_markdown_files(&base_dir)
+        .expect("Failed to scan")
+        .files;
+    let config = RouterConfig {
+        max_ws_connections: 2,
+        ..RouterConfig::default()
+    };
+    let router = new_router_with_config(base_dir, tracked_files, true, config)
+        .expect("Failed to create router");
+    let server = TestServer::builder()
+        .http_transport()
+        .build(router)
+        .expect("Failed to create test server");
+
+    let first = connect_websocket(&server).await;
+    let _second = connect_websocket(&server).await;
+    assert_eq!(websocket_count(&server).await, 2);
+
+    let refused = server.get_websocket("/ws").await;
+    assert_eq!(refused.status_code(), 503);
+
+    let stats: serde_json::Value = server.get("/api/stats").await.json();
+    assert_eq!(
+        stats,
+        serde_json::json!({
+            "tracked_files": 1,
+            "websocket_connections": 2,
+            "max_websocket_connections": 2,
+            "broken_link_count": 0,
+            "orphaned_count": 1,
+            "title_duplicates": 0,
+        })
+    );
+
+    // Closing a connection frees its slot
+    first.close().await;
+    let freed = tokio::time::timeout(Duration::from_secs(WEBSOCKET_TIMEOUT_SECS), async {
+        while websocket_count(&server).await != 1 {
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+    })
+    .await;
+    assert!(freed.is_ok(), "Closed connection should be released");
+    let _third = connect_websocket(&server).await;
+}
+
+#[tokio::test]
+async fn test_websocket_outlives_request_timeout() {
+    let temp_dir = tempdir().expect("Failed to create temp dir");
+    let file_path = temp_dir.path().join("doc.md");
+    fs::write(&file_path, "# Doc").expect("Failed to write");
+
+    let config = RouterConfig {
+        request_timeout: Duration::from_millis(100),
+        ..RouterConfig::default()
+    };
+    let router = new_router_with_config(
+        temp_dir.path().to_path_buf(),
+        vec![file_path.clone()],
+        false,
+        config,
+    )
+    .expect("Failed to create router");
+    let server = TestServer::builder()
+        .http_transport()
+        .build(router)
+        .expect("Failed to create test server");
+    assert_eq!(server.get("/api/files").await.status_code(), 200);
+
+    let mut websocket = connect_websocket(&server).await;
+    tokio::time::sleep(Duration::from_millis(300)).await;
+    fs::write(&file_path, "# Changed").expect("Failed to write");
+
+    let message = tokio::time::timeout(
+        Duration::from_secs(WEBSOCKET_TIMEOUT_SECS),
+        websocket.receive_json::<ServerMessage>(),
+    )
+    .await
+    .expect("Timeout waiting for Reload after the request timeout");
+    assert_eq!(message, ServerMessage::Reload);
 }